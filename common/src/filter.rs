@@ -0,0 +1,36 @@
+// How file name patterns match files, for --pattern and --skip-pattern and the Python glob()
+// filters named after them
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+
+// True if a pattern matches a file's name or its path, as --skip-pattern matches
+pub fn matches_file(
+    pattern: &Pattern,
+    file_path: &Path,
+    base_name: &str,
+    options: MatchOptions,
+) -> bool {
+    pattern.matches_with(base_name, options) || pattern.matches_path_with(file_path, options)
+}
+
+// True if a --pattern glob matches a file. Globs with a `/` match `relative`, the file's path
+// below the input it was found by walking, with `*` stopping at separators and `**` crossing
+// them; the others, and every glob when there is no such path, match its name at any depth.
+pub fn matches_name_pattern(
+    pattern: &Pattern,
+    relative: Option<&Path>,
+    base_name: &str,
+    options: MatchOptions,
+) -> bool {
+    match relative {
+        Some(relative) if pattern.as_str().contains('/') => pattern.matches_path_with(
+            relative,
+            MatchOptions {
+                require_literal_separator: true,
+                ..options
+            },
+        ),
+        _ => pattern.matches_with(base_name, options),
+    }
+}
@@ -0,0 +1,6 @@
+// The parts of llm_globber that touch the file system and are shared by the command line and
+// the Python bindings, so both apply the same policy, filters and unglob checks
+pub mod filter;
+pub mod nfc;
+pub mod policy;
+pub mod unglob;
@@ -0,0 +1,33 @@
+// File names that differ only in Unicode normalization: macOS stores names decomposed (NFD),
+// while headers and most other systems write them composed (NFC). Shared with the Python
+// bindings, so both unglobs resolve names the same way.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use unicode_normalization::UnicodeNormalization;
+
+// `base` joined with `relative`, taking the name of any file or directory already there that
+// differs from the archive's only in Unicode normalization, so unglob over a checkout made on
+// macOS updates its files instead of adding look-alike ones next to them
+pub fn match_existing_names(base: &Path, relative: &Path) -> PathBuf {
+    let mut resolved = base.to_path_buf();
+    for component in relative.components() {
+        let name = component.as_os_str();
+        let existing = name
+            .to_str()
+            .filter(|name| !name.is_ascii() && resolved.join(name).symlink_metadata().is_err())
+            .and_then(|name| {
+                let wanted: String = name.nfc().collect();
+                fs::read_dir(&resolved).ok()?.flatten().find_map(|entry| {
+                    let found = entry.file_name();
+                    let same = found.to_str()?.nfc().eq(wanted.chars());
+                    same.then_some(found)
+                })
+            });
+        match existing {
+            Some(existing) => resolved.push(existing),
+            None => resolved.push(name),
+        }
+    }
+    resolved
+}
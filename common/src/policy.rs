@@ -0,0 +1,290 @@
+// Organisation policy, read from /etc/llm-globber/policy.toml and the file $LLM_GLOBBER_POLICY
+// names. Administrators use it to keep paths such as secrets or HR folders out of every archive,
+// to redact content matching patterns, to limit where output may be written, and to record every
+// run in an audit log. A policy that cannot be read or parsed stops the run rather than being
+// ignored.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+
+pub const POLICY_ENV: &str = "LLM_GLOBBER_POLICY";
+const SYSTEM_POLICY: &str = "/etc/llm-globber/policy.toml";
+const DEFAULT_REPLACEMENT: &str = "[REDACTED BY POLICY]";
+
+// `*` stops at separators and `**` crosses them, as in --pattern paths
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Debug, Clone)]
+struct Redaction {
+    pattern: Regex,
+    replacement: String,
+    paths: Vec<Pattern>, // Files it applies to; all files if empty
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub sources: Vec<PathBuf>,
+    forbid: Vec<Pattern>,
+    pub skip_forbidden: bool, // Leave forbidden files out rather than refuse the run
+    redactions: Vec<Redaction>,
+    destinations: Vec<String>,    // Where output may go; anywhere if empty
+    pub audit_logs: Vec<PathBuf>, // Where every run is recorded, as with --audit-log
+}
+
+impl Policy {
+    // The system policy and the one $LLM_GLOBBER_POLICY names, merged, or None if neither
+    // exists. A missing system policy is fine; a missing file named by the variable is not.
+    pub fn load() -> Result<Option<Policy>, String> {
+        let mut policy = Policy::default();
+        let mut skip_forbidden = true;
+        let mut paths = vec![(PathBuf::from(SYSTEM_POLICY), false)];
+        if let Some(path) = std::env::var_os(POLICY_ENV).filter(|path| !path.is_empty()) {
+            paths.push((PathBuf::from(path), true));
+        }
+        for (path, required) in paths {
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == io::ErrorKind::NotFound && !required => continue,
+                Err(e) => return Err(format!("Failed to read policy {}: {}", path.display(), e)),
+            };
+            skip_forbidden &= policy
+                .merge(&text)
+                .map_err(|e| format!("Invalid policy {}: {}", path.display(), e))?;
+            policy.sources.push(path);
+        }
+        policy.skip_forbidden = skip_forbidden;
+        Ok((!policy.sources.is_empty()).then_some(policy))
+    }
+
+    // Add one policy file's rules, returning whether it leaves forbidden files out rather than
+    // refusing the run. Files are only left out if every policy file says so.
+    fn merge(&mut self, text: &str) -> Result<bool, String> {
+        let table = text
+            .parse::<toml::Table>()
+            .map_err(|e| e.message().to_string())?;
+        let mut skip_forbidden = false;
+        for (key, value) in &table {
+            match key.as_str() {
+                "forbid" => self.forbid.extend(patterns(key, value)?),
+                "on_forbidden" => match value.as_str() {
+                    Some("refuse") => skip_forbidden = false,
+                    Some("skip") => skip_forbidden = true,
+                    _ => return Err("on_forbidden must be \"refuse\" or \"skip\"".to_string()),
+                },
+                "destinations" => self.destinations.extend(strings(key, value)?),
+                "audit_log" => self.audit_logs.push(PathBuf::from(
+                    value.as_str().ok_or("audit_log must be a string")?,
+                )),
+                "redact" => {
+                    let rules = value
+                        .as_array()
+                        .ok_or("redact must be an array of tables ([[redact]])")?;
+                    for rule in rules {
+                        self.redactions.push(redaction(rule)?);
+                    }
+                }
+                other => return Err(format!("unknown key '{}'", other)),
+            }
+        }
+        Ok(skip_forbidden)
+    }
+
+    // The first forbid pattern matching a file by its absolute path or its header path
+    pub fn forbidden(&self, absolute: &Path, header_path: &str) -> Option<&str> {
+        self.forbid
+            .iter()
+            .find(|pattern| {
+                pattern.matches_path_with(absolute, MATCH_OPTIONS)
+                    || pattern.matches_with(header_path, MATCH_OPTIONS)
+            })
+            .map(Pattern::as_str)
+    }
+
+    // One policy file's rules on their own, as load reads each file
+    pub fn parse(text: &str) -> Result<Policy, String> {
+        let mut policy = Policy::default();
+        policy.skip_forbidden = policy.merge(text)?;
+        Ok(policy)
+    }
+
+    // Whether output may go to a local directory or a remote URL. A directory is allowed if it
+    // or a directory above it matches a destination; a URL if it has a destination's scheme and
+    // host or bucket and its path, with `.` and `..` resolved, lies within the destination's.
+    pub fn allows_destination(&self, output: &str) -> bool {
+        if self.destinations.is_empty() {
+            return true;
+        }
+        if output.contains("://") {
+            let Some(output) = RemoteLocation::parse(output) else {
+                return false;
+            };
+            return self
+                .destinations
+                .iter()
+                .filter_map(|allowed| RemoteLocation::parse(allowed))
+                .any(|allowed| output.within(&allowed));
+        }
+        let output = std::path::absolute(output).unwrap_or_else(|_| PathBuf::from(output));
+        self.destinations
+            .iter()
+            .filter(|allowed| !allowed.contains("://"))
+            .filter_map(|allowed| Pattern::new(allowed.trim_end_matches('/')).ok())
+            .any(|allowed| {
+                output
+                    .ancestors()
+                    .any(|dir| allowed.matches_path_with(dir, MATCH_OPTIONS))
+            })
+    }
+
+    // Whether the same redactions apply to files at both header paths, so that a hard link
+    // written as an alias of the other carries the content it would have had as its own entry
+    pub fn redacts_alike(&self, header_path: &str, other: &str) -> bool {
+        self.redactions
+            .iter()
+            .all(|rule| rule.applies_to(header_path) == rule.applies_to(other))
+    }
+
+    // Content with every redaction that applies to the file made, or None if none matched
+    pub fn redact(&self, header_path: &str, content: &str) -> Option<String> {
+        let mut redacted: Option<String> = None;
+        for rule in &self.redactions {
+            if !rule.applies_to(header_path) {
+                continue;
+            }
+            let current = redacted.as_deref().unwrap_or(content);
+            if rule.pattern.is_match(current) {
+                redacted = Some(
+                    rule.pattern
+                        .replace_all(current, rule.replacement.as_str())
+                        .into_owned(),
+                );
+            }
+        }
+        redacted
+    }
+}
+
+// A URL destination taken apart for comparison: the scheme and host lowercased, the host being
+// the bucket of s3:// URLs, and the path split into segments with `.` and `..` resolved
+#[derive(Debug)]
+struct RemoteLocation {
+    scheme: String,
+    host: String,
+    segments: Vec<String>,
+}
+
+impl RemoteLocation {
+    fn parse(url: &str) -> Option<RemoteLocation> {
+        let (scheme, rest) = url.split_once("://")?;
+        let scheme = scheme.to_ascii_lowercase();
+        // Servers take `\` for `/` and stop the path at a query or fragment
+        let rest = rest.replace('\\', "/");
+        let rest = rest.split(['?', '#']).next().unwrap_or_default();
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        // Credentials before an `@` are not part of the host
+        let host = authority.rsplit('@').next().unwrap_or_default();
+        let host = host.to_ascii_lowercase();
+        let host = match (scheme.as_str(), host.rsplit_once(':')) {
+            ("http", Some((name, "80"))) | ("https", Some((name, "443"))) => name,
+            _ => host.as_str(),
+        };
+        let host = host.trim_end_matches('.');
+        if host.is_empty() {
+            return None;
+        }
+        let mut segments = Vec::new();
+        for segment in path.split('/') {
+            match segment.to_ascii_lowercase().as_str() {
+                "" | "." | "%2e" => {}
+                ".." | ".%2e" | "%2e." | "%2e%2e" => {
+                    segments.pop();
+                }
+                _ => segments.push(segment.to_string()),
+            }
+        }
+        Some(RemoteLocation {
+            scheme,
+            host: host.to_string(),
+            segments,
+        })
+    }
+
+    // Whether this location is `allowed` or lies below it, comparing whole path segments
+    fn within(&self, allowed: &RemoteLocation) -> bool {
+        self.scheme == allowed.scheme
+            && self.host == allowed.host
+            && self.segments.starts_with(&allowed.segments)
+    }
+}
+
+impl Redaction {
+    fn applies_to(&self, header_path: &str) -> bool {
+        self.paths.is_empty()
+            || self
+                .paths
+                .iter()
+                .any(|pattern| pattern.matches_with(header_path, MATCH_OPTIONS))
+    }
+}
+
+fn strings(key: &str, value: &toml::Value) -> Result<Vec<String>, String> {
+    value
+        .as_array()
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_str().map(str::to_string))
+                .collect()
+        })
+        .ok_or_else(|| format!("{} must be an array of strings", key))
+}
+
+fn patterns(key: &str, value: &toml::Value) -> Result<Vec<Pattern>, String> {
+    strings(key, value)?
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern)
+                .map_err(|e| format!("invalid {} pattern '{}': {}", key, pattern, e))
+        })
+        .collect()
+}
+
+fn redaction(rule: &toml::Value) -> Result<Redaction, String> {
+    let table = rule
+        .as_table()
+        .ok_or("redact must be an array of tables ([[redact]])")?;
+    let mut pattern = None;
+    let mut replacement = DEFAULT_REPLACEMENT.to_string();
+    let mut paths = Vec::new();
+    for (key, value) in table {
+        match key.as_str() {
+            "pattern" => {
+                let text = value.as_str().ok_or("redact pattern must be a string")?;
+                pattern = Some(
+                    Regex::new(text)
+                        .map_err(|e| format!("invalid redact pattern '{}': {}", text, e))?,
+                );
+            }
+            "replacement" => {
+                replacement = value
+                    .as_str()
+                    .ok_or("redact replacement must be a string")?
+                    .to_string();
+            }
+            "paths" => paths = patterns("redact paths", value)?,
+            other => return Err(format!("unknown key '{}' in [[redact]]", other)),
+        }
+    }
+    Ok(Redaction {
+        pattern: pattern.ok_or("[[redact]] needs a pattern")?,
+        replacement,
+        paths,
+    })
+}
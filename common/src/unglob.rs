@@ -0,0 +1,65 @@
+// Checks unglob makes before writing anything: where each entry may be written, and whether
+// the output directory holds files that could not be got back if the archive overwrote them
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use crate::nfc;
+
+// A relative path that stays below the directory it is joined to, with any leading `/` dropped
+pub fn enclosed_path(path: &Path) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir | Component::RootDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!relative.as_os_str().is_empty()).then_some(relative)
+}
+
+// Where an entry path is written under `output_base`, or None if it would reach outside it.
+// `escaped` paths, from format 5 archives on, are turned back into the bytes of the original
+// name, and a name already there that differs only in Unicode normalization is reused.
+pub fn entry_target(output_base: &Path, path: &str, escaped: bool) -> Option<PathBuf> {
+    let path = match escaped {
+        true => PathBuf::from(OsString::from_vec(llm_globber_core::unescape_path(path))),
+        false => PathBuf::from(path),
+    };
+    let relative = enclosed_path(&path)?;
+    Some(nfc::match_existing_names(output_base, &relative))
+}
+
+// Why extracting into `output_base` could lose files, or None if it is missing, empty or a git
+// working tree without changes
+pub fn target_risk(output_base: &Path) -> Result<Option<&'static str>, String> {
+    let is_empty = match fs::read_dir(output_base) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+        Err(e) => {
+            return Err(format!(
+                "Failed to read directory {}: {}",
+                output_base.display(),
+                e
+            ))
+        }
+    };
+    if is_empty {
+        return Ok(None);
+    }
+    let status = Command::new("git")
+        .args(["status", "--porcelain"])
+        .current_dir(output_base)
+        .output();
+    Ok(match status {
+        Ok(output) if output.status.success() => {
+            let changes = String::from_utf8_lossy(&output.stdout);
+            (!changes.trim().is_empty()).then_some("its git working tree has uncommitted changes")
+        }
+        _ => Some("it is not in a git working tree"),
+    })
+}
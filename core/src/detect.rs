@@ -0,0 +1,129 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Bytes inspected when classifying a file
+const SAMPLE_SIZE: usize = 8192;
+// Percentage of control bytes above which content is treated as binary
+pub const DEFAULT_BINARY_THRESHOLD: usize = 10;
+// Larger window used to measure line lengths for minified-asset detection
+const LINE_SAMPLE_SIZE: usize = 65536;
+// Text whose lines average at least this many bytes is treated as a minified asset
+const MINIFIED_AVERAGE_LINE: usize = 2000;
+
+// Extensions whose contents are never useful as text, whatever the bytes look like
+const BINARY_EXTENSIONS: &[&str] = &[
+    "a", "bin", "class", "dll", "dylib", "exe", "gz", "jar", "o", "obj", "pdf", "pyc", "so", "tar",
+    "wasm", "woff", "woff2", "ttf", "otf", "eot", "zip", "7z", "xz", "bz2", "zst", "mp3", "mp4",
+    "mov", "avi", "ogg", "wav", "flac",
+];
+// Generated bundles that are text but not worth the tokens
+const MINIFIED_SUFFIXES: &[&str] = &[".min.js", ".min.css", ".min.mjs", ".js.map", ".css.map"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentKind {
+    Text,
+    Binary,
+    Minified,
+}
+
+// Classify file content, decoding UTF-16 to UTF-8 when a BOM or zero-byte pattern shows it.
+// Content that is neither UTF-16 nor UTF-8 is binary, since it cannot be written as text without
+// changing it. `threshold` is the percentage of control bytes above which content counts as
+// binary. With `force_text`, only the encoding checks run and all else is treated as text.
+pub fn classify(
+    file_path: &str,
+    data: &[u8],
+    threshold: usize,
+    force_text: bool,
+) -> (ContentKind, Option<String>) {
+    if let Some(decoded) = decode_utf16(data) {
+        return (ContentKind::Text, Some(decoded));
+    }
+    if core::str::from_utf8(data).is_err() {
+        return (ContentKind::Binary, None);
+    }
+    if force_text || data.is_empty() || data.starts_with(b"\xEF\xBB\xBF") {
+        return (ContentKind::Text, None);
+    }
+
+    let name = file_path.rsplit('/').next().unwrap_or("").to_lowercase();
+    let extension = name.rsplit_once('.').map_or("", |(_, ext)| ext);
+    if BINARY_EXTENSIONS.contains(&extension) {
+        return (ContentKind::Binary, None);
+    }
+    if MINIFIED_SUFFIXES
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+    {
+        return (ContentKind::Minified, None);
+    }
+
+    let sample = &data[..data.len().min(SAMPLE_SIZE)];
+    // A NUL byte outside UTF-16 text is the strongest binary signal there is
+    if sample.contains(&0) {
+        return (ContentKind::Binary, None);
+    }
+    let control = sample
+        .iter()
+        .filter(|&&byte| byte < 32 && !matches!(byte, b'\n' | b'\r' | b'\t' | 0x0c | 0x1b))
+        .count();
+    if control * 100 > threshold * sample.len() {
+        return (ContentKind::Binary, None);
+    }
+
+    if is_minified(data) {
+        return (ContentKind::Minified, None);
+    }
+    (ContentKind::Text, None)
+}
+
+fn is_minified(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(LINE_SAMPLE_SIZE)];
+    if sample.len() < SAMPLE_SIZE {
+        return false;
+    }
+    let lines = sample.iter().filter(|&&byte| byte == b'\n').count() + 1;
+    sample.len() / lines >= MINIFIED_AVERAGE_LINE
+}
+
+// UTF-16 text, recognised by its BOM or by zero high bytes in most ASCII-range code units
+fn decode_utf16(data: &[u8]) -> Option<String> {
+    let (little_endian, body) = if let Some(body) = data.strip_prefix(b"\xFF\xFE") {
+        (true, body)
+    } else if let Some(body) = data.strip_prefix(b"\xFE\xFF") {
+        (false, body)
+    } else {
+        let sample = &data[..data.len().min(SAMPLE_SIZE) & !1];
+        if sample.len() < 4 {
+            return None;
+        }
+        let pairs = sample.len() / 2;
+        let even_zeros = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+        let odd_zeros = sample
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count();
+        // Mostly-ASCII UTF-16 has a zero in nearly every high byte and almost none in low bytes
+        if odd_zeros * 10 >= pairs * 9 && even_zeros * 10 <= pairs {
+            (true, data)
+        } else if even_zeros * 10 >= pairs * 9 && odd_zeros * 10 <= pairs {
+            (false, data)
+        } else {
+            return None;
+        }
+    };
+
+    let units: Vec<u16> = body
+        .chunks_exact(2)
+        .map(|pair| {
+            if little_endian {
+                u16::from_le_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], pair[1]])
+            }
+        })
+        .collect();
+    String::from_utf16(&units).ok()
+}
@@ -0,0 +1,325 @@
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::String;
+
+use crate::detect::{classify, ContentKind, DEFAULT_BINARY_THRESHOLD};
+use crate::parse::{manifest_digest, Footer};
+use alloc::vec::Vec;
+
+use crate::{
+    BINARY_MARKER, BOILERPLATE_REFERENCE_PREFIX, BOILERPLATE_SECTION, CLOSING_MARKER, DIGEST_FIELD,
+    ENTRY_PREFIX, FOOTER_SECTION, FORMAT_HEADER_PREFIX, FORMAT_VERSION, METADATA_SEPARATOR,
+    MINIFIED_MARKER, PUBLIC_KEY_PREFIX, SECTION_PREFIX, TIMESTAMP_SECTION,
+};
+
+// Follows each text entry's content: the closing marker on its own line, then a blank line
+pub const ENTRY_FOOTER: &str = "\n'''\n\n";
+
+// First line of every text archive, followed by a blank line
+pub fn format_header() -> String {
+    versioned_format_header(FORMAT_VERSION)
+}
+
+pub fn versioned_format_header(version: u32) -> String {
+    format!("{} {}\n\n", FORMAT_HEADER_PREFIX, version)
+}
+
+// Whether a path's bytes need escaping to be written in a header: they are not UTF-8, or hold a
+// control character such as a newline that would end the header line
+pub fn path_needs_escape(path: &[u8]) -> bool {
+    core::str::from_utf8(path).is_err() || path.iter().any(|byte| byte.is_ascii_control())
+}
+
+// A path as format 5 headers write it: invalid UTF-8 and control bytes as `\xHH`, backslashes
+// doubled, and everything else as it is
+pub fn escape_path(path: &[u8]) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for chunk in path.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                c if c.is_ascii_control() => escaped.push_str(&format!("\\x{:02X}", c as u8)),
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02X}", byte));
+        }
+    }
+    escaped
+}
+
+// The bytes of a path written by escape_path. Backslashes that start no escape are kept.
+pub fn unescape_path(path: &str) -> Vec<u8> {
+    let bytes = path.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 2..i + 4)
+            .and_then(|digits| core::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+        match (bytes[i], bytes.get(i + 1), hex) {
+            (b'\\', Some(b'\\'), _) => {
+                unescaped.push(b'\\');
+                i += 2;
+            }
+            (b'\\', Some(b'x'), Some(byte)) => {
+                unescaped.push(byte);
+                i += 4;
+            }
+            (byte, ..) => {
+                unescaped.push(byte);
+                i += 1;
+            }
+        }
+    }
+    unescaped
+}
+
+// Whether a content line would read as a marker: it starts with the closing marker once any
+// backslashes in front of it are set aside
+fn looks_like_marker(line: &str) -> bool {
+    line.trim_start_matches('\\').starts_with(CLOSING_MARKER)
+}
+
+// Content as format 7 entries hold it: a backslash goes in front of each line that would read as
+// a marker, so a line of `'''` cannot end the entry early
+pub fn escape_content(content: &str) -> Cow<'_, str> {
+    if !content.split('\n').any(looks_like_marker) {
+        return Cow::Borrowed(content);
+    }
+    let mut escaped = String::with_capacity(content.len() + 16);
+    for line in content.split_inclusive('\n') {
+        if looks_like_marker(line) {
+            escaped.push('\\');
+        }
+        escaped.push_str(line);
+    }
+    Cow::Owned(escaped)
+}
+
+// A content line of a format 7 entry as the file holds it, without the backslash escape_content
+// put in front
+pub fn unescape_content_line(line: &str) -> &str {
+    match line.strip_prefix('\\') {
+        Some(rest) if looks_like_marker(rest) => rest,
+        _ => line,
+    }
+}
+
+// Attributes of the file behind an entry, recorded in format 3 headers. The size is of the
+// content the entry holds, or for omitted entries of the content left out; the modification
+// time is in seconds since the Unix epoch and the mode holds the permission bits.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub size: Option<u64>,
+    pub mtime: Option<u64>,
+    pub mode: Option<u32>,
+    pub lines: Option<usize>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Metadata::default()
+    }
+
+    // `[SIZE:1234] [MTIME:1711440000] [MODE:644] [LINES:87]`, leaving out unknown fields
+    pub fn field(&self) -> String {
+        let mut fields = Vec::new();
+        if let Some(size) = self.size {
+            fields.push(format!("[SIZE:{}]", size));
+        }
+        if let Some(mtime) = self.mtime {
+            fields.push(format!("[MTIME:{}]", mtime));
+        }
+        if let Some(mode) = self.mode {
+            fields.push(format!("[MODE:{:o}]", mode));
+        }
+        if let Some(lines) = self.lines {
+            fields.push(format!("[LINES:{}]", lines));
+        }
+        fields.join(" ")
+    }
+}
+
+// The path followed by its metadata field, to pass where headers take a path
+pub fn with_metadata(path: &str, metadata: &Metadata) -> String {
+    if metadata.is_empty() {
+        return path.into();
+    }
+    format!("{}{}{}", path, METADATA_SEPARATOR, metadata.field())
+}
+
+// The block carrying the base64 public key that entry signatures can be verified with
+pub fn public_key_block(encoded_key: &str) -> String {
+    format!(
+        "{}{}]\n{}\n\n",
+        PUBLIC_KEY_PREFIX, encoded_key, CLOSING_MARKER
+    )
+}
+
+// `'''--- path ---`, or `'''--- path --- [SIGNATURE:...]` for signed entries
+pub fn entry_header(path: &str, signature: Option<&str>) -> String {
+    match signature {
+        Some(signature) => format!("{}{} --- [SIGNATURE:{}]\n", ENTRY_PREFIX, path, signature),
+        None => format!("{}{} ---\n", ENTRY_PREFIX, path),
+    }
+}
+
+// An entry holding `content`, escaped as format 7 entries hold it
+pub fn text_entry(path: &str, content: &str, signature: Option<&str>) -> String {
+    let mut entry = entry_header(path, signature);
+    entry.push_str(&escape_content(content));
+    entry.push_str(ENTRY_FOOTER);
+    entry
+}
+
+// An entry whose content is replaced by a single marker line, such as BINARY_MARKER
+pub fn omitted_entry(path: &str, marker: &str) -> String {
+    let mut entry = entry_header(path, None);
+    entry.push_str(marker);
+    entry.push('\n');
+    entry
+}
+
+// An omitted entry with the SHA-256 of its content, as lowercase hex, and a signature over
+// omitted_signed_content
+pub fn signed_omitted_entry(path: &str, marker: &str, digest: &str, signature: &str) -> String {
+    let with_digest = format!("{}{}{}]", path, DIGEST_FIELD, digest);
+    let mut entry = entry_header(&with_digest, Some(signature));
+    entry.push_str(marker);
+    entry.push('\n');
+    entry
+}
+
+// An entry holding a rendered view, which opens with its RENDERED_MARKER_PREFIX line. Signed
+// views carry their SHA-256 like omitted entries, and the signature covers omitted_signed_content
+// of the marker line and that digest.
+pub fn rendered_entry(path: &str, view: &str, signed: Option<(&str, &str)>) -> String {
+    match signed {
+        Some((digest, signature)) => {
+            let with_digest = format!("{}{}{}]", path, DIGEST_FIELD, digest);
+            text_entry(&with_digest, view, Some(signature))
+        }
+        None => text_entry(path, view, None),
+    }
+}
+
+// What the signature on an omitted entry covers: its marker line and the digest of the content
+// left out, so that neither can be swapped without breaking the signature
+pub fn omitted_signed_content(marker: &str, digest: &str) -> String {
+    format!("{}\nSHA256:{}", marker, digest)
+}
+
+// What the signature in a timestamp section covers: the time, as RFC 3339 UTC, and the SHA-256
+// of the archive bytes before the section, as lowercase hex
+pub fn timestamp_signed_content(time: &str, digest: &str) -> String {
+    format!("TIME:{}\nSHA256:{}", time, digest)
+}
+
+pub fn timestamp_section(time: &str, digest: &str, signature: &str) -> String {
+    section(
+        TIMESTAMP_SECTION,
+        &[
+            format!("TIME: {}", time),
+            format!("SHA256: {}", digest),
+            format!("SIGNATURE: {}", signature),
+        ],
+    )
+}
+
+// The section defining boilerplate block `id`: the block's lines, which each end in a newline
+pub fn boilerplate_section(id: usize, block: &str) -> String {
+    let lines: Vec<String> = block.split_terminator('\n').map(String::from).collect();
+    section(&format!("{} {}", BOILERPLATE_SECTION, id), &lines)
+}
+
+// The line that takes the place of boilerplate block `id` at the start of an entry
+pub fn boilerplate_reference(id: usize) -> String {
+    format!("{}{}]", BOILERPLATE_REFERENCE_PREFIX, id)
+}
+
+// A generated section of plain lines, which unglob skips
+pub fn section(name: &str, lines: &[String]) -> String {
+    let mut section = format!("{}{} ===\n", SECTION_PREFIX, name);
+    for line in lines {
+        section.push_str(line);
+        section.push('\n');
+    }
+    section.push_str(CLOSING_MARKER);
+    section.push_str("\n\n");
+    section
+}
+
+// Builds a text archive from files held in memory, classifying each one the way the
+// command line does: binary and minified files are written as marker lines. The archive is
+// written as the command line writes it, in the current format with escaped header paths and
+// a footer, so unglob reads it the same way.
+#[derive(Debug, Clone)]
+pub struct ArchiveBuilder {
+    out: String,
+    binary_threshold: usize,
+    // Header paths of the entries written so far, and the bytes of their text content
+    paths: Vec<String>,
+    content_bytes: u64,
+}
+
+impl Default for ArchiveBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveBuilder {
+    pub fn new() -> Self {
+        ArchiveBuilder {
+            out: format_header(),
+            binary_threshold: DEFAULT_BINARY_THRESHOLD,
+            paths: Vec::new(),
+            content_bytes: 0,
+        }
+    }
+
+    // Percentage of control bytes above which content counts as binary, as --binary-threshold
+    pub fn binary_threshold(mut self, threshold: usize) -> Self {
+        self.binary_threshold = threshold;
+        self
+    }
+
+    pub fn add_file(&mut self, path: &str, data: &[u8]) {
+        let (kind, decoded) = classify(path, data, self.binary_threshold, false);
+        let header_path = escape_path(path.as_bytes());
+        let text = match decoded {
+            Some(text) => Some(Cow::Owned(text)),
+            None => core::str::from_utf8(data).ok().map(Cow::Borrowed),
+        };
+        let entry = match (kind, text) {
+            (ContentKind::Minified, _) => omitted_entry(&header_path, MINIFIED_MARKER),
+            (ContentKind::Text, Some(content)) => {
+                self.content_bytes += content.len() as u64;
+                text_entry(&header_path, &content, None)
+            }
+            // Content that is not text is left out rather than written mangled
+            _ => omitted_entry(&header_path, BINARY_MARKER),
+        };
+        self.out.push_str(&entry);
+        self.paths.push(header_path);
+    }
+
+    pub fn add_section(&mut self, name: &str, lines: &[String]) {
+        self.out.push_str(&section(name, lines));
+    }
+
+    // The archive, ended with its footer
+    pub fn finish(mut self) -> String {
+        let footer = Footer {
+            files: self.paths.len() as u64,
+            bytes: self.content_bytes,
+            manifest_digest: manifest_digest(self.paths.iter().map(String::as_str)),
+            ..Footer::default()
+        };
+        self.out.push_str(&section(FOOTER_SECTION, &footer.lines()));
+        self.out
+    }
+}
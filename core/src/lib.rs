@@ -0,0 +1,128 @@
+// The llm_globber archive format without any file system or I/O: archives are built from
+// and parsed into in-memory buffers, so this crate also compiles for wasm32-unknown-unknown.
+#![no_std]
+
+extern crate alloc;
+
+mod detect;
+mod format;
+mod parse;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use detect::{classify, ContentKind, DEFAULT_BINARY_THRESHOLD};
+pub use format::{
+    boilerplate_reference, boilerplate_section, entry_header, escape_content, escape_path,
+    format_header, omitted_entry, omitted_signed_content, path_needs_escape, public_key_block,
+    rendered_entry, section, signed_omitted_entry, text_entry, timestamp_section,
+    timestamp_signed_content, unescape_content_line, unescape_path, versioned_format_header,
+    with_metadata, ArchiveBuilder, Metadata, ENTRY_FOOTER,
+};
+pub use parse::{
+    is_omitted_marker, is_rendered_marker, is_timestamp_header, links_digest, manifest_digest,
+    parse_archive, parse_entry_header, parse_file_header, parse_format_header, Archive,
+    ArchiveParser, Entry, EntryHeader, Event, Footer, Omitted, Timestamp,
+};
+
+// Archive format written by this build; bump when the entry layout or escaping changes
+pub const FORMAT_VERSION: u32 = ESCAPED_CONTENT_FORMAT_VERSION;
+// Archives written before the version header existed carry no header and are treated as format 1
+pub const LEGACY_FORMAT_VERSION: u32 = 1;
+// Format 3 entry headers may carry metadata fields. Archives are only written as format 3 when
+// they do, so older builds can still read the rest.
+pub const METADATA_FORMAT_VERSION: u32 = 3;
+// Format 4 entries may open with a reference to a boilerplate section, and readers must expand
+// it. Only archives that use references are written as format 4.
+pub const BOILERPLATE_FORMAT_VERSION: u32 = 4;
+// Format 5 header paths escape bytes that are not UTF-8 or are control characters as `\xHH`, and
+// backslashes as `\\`, so file names such as ones holding a newline round-trip. Only archives
+// with such a path are written as format 5; in older ones backslashes are literal.
+pub const ESCAPED_PATHS_FORMAT_VERSION: u32 = 5;
+// Format 6 archives end with a footer section, and one without it was cut short, so readers must
+// refuse it rather than take what is left for the whole archive. Header paths are escaped as in
+// format 5.
+pub const FOOTER_FORMAT_VERSION: u32 = 6;
+// Format 7 entry content escapes lines that would otherwise end the entry or start another, such
+// as a Python docstring's closing `'''`: a line that starts with `'''` after any backslashes
+// gets one more backslash in front, which readers take off again. Every archive is written as
+// format 7.
+pub const ESCAPED_CONTENT_FORMAT_VERSION: u32 = 7;
+// Why a format 6 archive without its footer is refused
+pub const MISSING_FOOTER: &str =
+    "Archive has no footer, so it was cut short and files at its end may be missing";
+pub const FORMAT_HEADER_PREFIX: &str = "LLM_GLOBBER_FORMAT:";
+// Opens each entry: `'''--- path ---`, optionally followed by ` [SIGNATURE:...]`
+pub const ENTRY_PREFIX: &str = "'''--- ";
+// Follows the path in a format 3 header with metadata:
+// `'''--- path --- [SIZE:1234] [MTIME:1711440000] [MODE:644] [LINES:87] ---`
+pub const METADATA_SEPARATOR: &str = " --- ";
+// In the header of a signed omitted entry, the SHA-256 of the content left out:
+// `'''--- path --- [SHA256:hex] --- [SIGNATURE:...]`. Readers that predate it take the field
+// as part of the path, which is harmless since they skip omitted entries.
+pub const DIGEST_FIELD: &str = " --- [SHA256:";
+// Closes entries and sections on a line of its own
+pub const CLOSING_MARKER: &str = "'''";
+// Carries the key that entry signatures were made with, ahead of the first entry
+pub const PUBLIC_KEY_PREFIX: &str = "'''--- PUBLIC_KEY --- [KEY:";
+// Opens a generated section such as `'''=== SKIPPED FILES ===`; sections are not files and
+// are ignored when unglobbing
+pub const SECTION_PREFIX: &str = "'''=== ";
+// The section after an archive's preamble listing each entry as `OFFSET LENGTH path`, with the
+// byte offset of its header line and the number of bytes it takes up
+pub const TOC_SECTION: &str = "TABLE OF CONTENTS";
+// The section that ends a timestamped archive, signing the time it was written together with
+// the SHA-256 of every byte before the section
+pub const TIMESTAMP_SECTION: &str = "SIGNED TIMESTAMP";
+// Sections named `BOILERPLATE 1`, `BOILERPLATE 2`, ... hold lines that open several entries.
+// Each of those entries opens with `[BOILERPLATE:1]` instead, which stands for the lines.
+pub const BOILERPLATE_SECTION: &str = "BOILERPLATE";
+pub const BOILERPLATE_REFERENCE_PREFIX: &str = "[BOILERPLATE:";
+// The section right after the format header describing how the archive was made, as YAML:
+// the tool version, command line, time, inputs, git commit, filters and counts; written with
+// --front-matter
+pub const FRONT_MATTER_SECTION: &str = "FRONT MATTER";
+// The section in the preamble listing the whitespace normalization applied to every entry's
+// content, such as `expand-tabs 4`, so readers know files may differ from the originals
+pub const NORMALIZATION_SECTION: &str = "NORMALIZED";
+// The section after the entries listing files that were hard links to an entry's file, as
+// `alias => path`; they are included once and unglob links the aliases back to the entry
+pub const HARD_LINKS_SECTION: &str = "HARD LINKS";
+pub const HARD_LINK_SEPARATOR: &str = " => ";
+// The section after the entries listing directories with no included files, one path per line,
+// so unglob recreates them; written with --keep-empty-dirs
+pub const EMPTY_DIRS_SECTION: &str = "EMPTY DIRECTORIES";
+// The section after the entries counting them and their content bytes and hashing their paths,
+// so unglob can tell a complete archive from a truncated copy. Only a signed timestamp follows it.
+pub const FOOTER_SECTION: &str = "FOOTER";
+// Header and closing marker bytes around each entry's content, excluding the path
+pub const ENTRY_OVERHEAD_BYTES: usize = 17;
+// Written in place of the content of binary entries, which have no closing marker
+pub const BINARY_MARKER: &str = "[Binary file - contents omitted]";
+pub const MINIFIED_MARKER: &str = "[Minified file - contents omitted]";
+// Written in place of every entry's content with --headers-only
+pub const HEADERS_ONLY_MARKER: &str = "[Contents omitted - headers only]";
+// Starts the one-line description written in place of an image's content
+pub const IMAGE_MARKER_PREFIX: &str = "[Image: ";
+// Starts the `[ERROR: path: reason]` line written in place of a file that could not be read,
+// so readers know the archive is missing it and why
+pub const ERROR_MARKER_PREFIX: &str = "[ERROR: ";
+// Starts the `[Rendered: description]` line opening a view written in place of a file's content,
+// such as an outline or a schema dump. The view runs to the closing marker like text content,
+// but readers treat the entry as omitted, so unglob never writes it over the original file.
+pub const RENDERED_MARKER_PREFIX: &str = "[Rendered: ";
+
+// Most files an archive takes unless --max-files says otherwise
+pub const DEFAULT_MAX_FILES: usize = 100000;
+// Largest file globbed or unglobbed unless --max-file-size or --max-extract-size says otherwise
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1 << 30; // 1GB
+
+// Directories skipped while walking unless --no-default-excludes is given
+pub const DEFAULT_EXCLUDED_DIRS: &[&str] = &[
+    ".git",
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "__pycache__",
+    ".venv",
+];
@@ -0,0 +1,641 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use sha2::{Digest, Sha256};
+
+use crate::format::{
+    omitted_signed_content, timestamp_signed_content, unescape_content_line, Metadata,
+};
+use crate::{
+    BINARY_MARKER, BOILERPLATE_FORMAT_VERSION, BOILERPLATE_REFERENCE_PREFIX, BOILERPLATE_SECTION,
+    CLOSING_MARKER, DIGEST_FIELD, EMPTY_DIRS_SECTION, ENTRY_PREFIX, ERROR_MARKER_PREFIX,
+    ESCAPED_CONTENT_FORMAT_VERSION, FOOTER_FORMAT_VERSION, FOOTER_SECTION, FORMAT_HEADER_PREFIX,
+    FORMAT_VERSION, FRONT_MATTER_SECTION, HARD_LINKS_SECTION, HARD_LINK_SEPARATOR,
+    HEADERS_ONLY_MARKER, IMAGE_MARKER_PREFIX, LEGACY_FORMAT_VERSION, METADATA_SEPARATOR,
+    MINIFIED_MARKER, MISSING_FOOTER, NORMALIZATION_SECTION, PUBLIC_KEY_PREFIX,
+    RENDERED_MARKER_PREFIX, SECTION_PREFIX, TIMESTAMP_SECTION,
+};
+
+// One file recovered from an archive
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: String,
+    pub signature: Option<String>,
+    pub metadata: Metadata,
+    pub lines: Vec<String>,
+}
+
+impl Entry {
+    // The lines joined back together: the file's exact bytes, which its signature covers.
+    // Writers follow the content with a newline before the closing marker, so a file ending in
+    // a newline has an empty last line, and lines keep any `\r` of CRLF line endings.
+    pub fn content(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    // Bytes of content, as counted by the footer, without joining the lines
+    pub fn content_len(&self) -> u64 {
+        let newlines = self.lines.len().saturating_sub(1);
+        (self.lines.iter().map(String::len).sum::<usize>() + newlines) as u64
+    }
+
+    // The file as unglob writes it. Signed entries are written exactly as signed; unsigned ones,
+    // which may have been written by hand, end in a newline unless they are empty.
+    pub fn text(&self) -> String {
+        let mut text = self.content();
+        if self.signature.is_none() && !self.lines.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+// An entry whose content was left out (binary, minified, image or a rendered view in its
+// place), with the SHA-256 of that content, or of the view, and a signature when the archive
+// was signed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Omitted {
+    pub path: String,
+    pub metadata: Metadata,
+    pub marker: String,
+    pub digest: Option<String>,
+    pub signature: Option<String>,
+}
+
+impl Omitted {
+    // What the entry's signature covers, once it has a digest
+    pub fn signed_content(&self) -> Option<String> {
+        self.digest
+            .as_deref()
+            .map(|digest| omitted_signed_content(&self.marker, digest))
+    }
+}
+
+// The signed timestamp section that ends a timestamped archive
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timestamp {
+    pub time: String,
+    pub digest: String,
+    pub signature: String,
+}
+
+impl Timestamp {
+    // What the timestamp's signature covers
+    pub fn signed_content(&self) -> String {
+        timestamp_signed_content(&self.time, &self.digest)
+    }
+}
+
+// The footer section: how many entries, omitted ones included, the archive holds, the bytes of
+// their content, and the SHA-256 of their paths in order, each followed by a newline. Archives
+// with hard links or empty directories add links_digest over those sections, and signed archives
+// sign the footer, so neither section can be added or changed without the signer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Footer {
+    pub files: u64,
+    pub bytes: u64,
+    pub manifest_digest: String,
+    pub links_digest: Option<String>,
+    pub signature: Option<String>,
+}
+
+impl Footer {
+    // The section's lines, `TOTAL_FILES: 12` and so on
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = self.unsigned_lines();
+        if let Some(signature) = &self.signature {
+            lines.push(format!("SIGNATURE: {}", signature));
+        }
+        lines
+    }
+
+    fn unsigned_lines(&self) -> Vec<String> {
+        let mut lines = Vec::from([
+            format!("TOTAL_FILES: {}", self.files),
+            format!("TOTAL_BYTES: {}", self.bytes),
+            format!("MANIFEST_SHA256: {}", self.manifest_digest),
+        ]);
+        if let Some(digest) = &self.links_digest {
+            lines.push(format!("LINKS_SHA256: {}", digest));
+        }
+        lines
+    }
+
+    // What the footer's signature covers: its other lines, joined by newlines
+    pub fn signed_content(&self) -> String {
+        self.unsigned_lines().join("\n")
+    }
+
+    fn parse(lines: &[String]) -> Result<Self, String> {
+        let mut footer = Footer::default();
+        for line in lines {
+            let invalid = || format!("Invalid line in footer section: {}", line);
+            let (name, value) = line.split_once(": ").ok_or_else(invalid)?;
+            match name {
+                "TOTAL_FILES" => footer.files = value.parse().map_err(|_| invalid())?,
+                "TOTAL_BYTES" => footer.bytes = value.parse().map_err(|_| invalid())?,
+                "MANIFEST_SHA256" => footer.manifest_digest = value.to_string(),
+                "LINKS_SHA256" => footer.links_digest = Some(value.to_string()),
+                "SIGNATURE" => footer.signature = Some(value.to_string()),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(footer)
+    }
+
+    // Check the entries read, as their paths in archive order and the bytes of their content, and
+    // the hard links and empty directories against the footer, which a copy that lost entries or
+    // had them edited no longer matches
+    pub fn verify(
+        &self,
+        entry_paths: &[String],
+        content_bytes: u64,
+        hard_links: &[(String, String)],
+        empty_dirs: &[String],
+    ) -> Result<(), String> {
+        let mismatch = if self.files != entry_paths.len() as u64 {
+            format!(
+                "the footer records {} files but {} were found",
+                self.files,
+                entry_paths.len()
+            )
+        } else if self.bytes != content_bytes {
+            format!(
+                "the footer records {} bytes of content but {} were found",
+                self.bytes, content_bytes
+            )
+        } else if self.manifest_digest != manifest_digest(entry_paths.iter().map(String::as_str)) {
+            "the entry paths do not match the footer's manifest hash".to_string()
+        } else if self.links_digest != links_digest(hard_links, empty_dirs) {
+            "the hard links or empty directories do not match the footer".to_string()
+        } else {
+            return Ok(());
+        };
+        Err(format!(
+            "Archive is incomplete or was altered: {}",
+            mismatch
+        ))
+    }
+}
+
+// The SHA-256 of entry paths in archive order, each followed by a newline, as lowercase hex
+pub fn manifest_digest<'a>(paths: impl Iterator<Item = &'a str>) -> String {
+    let mut hasher = Sha256::new();
+    for path in paths {
+        hasher.update(path.as_bytes());
+        hasher.update(b"\n");
+    }
+    hex(&hasher.finalize())
+}
+
+// The SHA-256 of the hard links, as `alias => path` lines, then of the empty directories, as
+// `DIR path` lines, each followed by a newline, as lowercase hex; None when there are neither
+pub fn links_digest(hard_links: &[(String, String)], empty_dirs: &[String]) -> Option<String> {
+    if hard_links.is_empty() && empty_dirs.is_empty() {
+        return None;
+    }
+    let mut hasher = Sha256::new();
+    for (alias, path) in hard_links {
+        hasher.update(format!("{}{}{}\n", alias, HARD_LINK_SEPARATOR, path).as_bytes());
+    }
+    for dir in empty_dirs {
+        hasher.update(format!("DIR {}\n", dir).as_bytes());
+    }
+    Some(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    // The base64 key from the archive's public key block
+    PublicKey(String),
+    Entry(Entry),
+    Omitted(Omitted),
+    Timestamp(Timestamp),
+    // The whitespace normalization the content went through, one step per line
+    Normalization(Vec<String>),
+    // Files included once as the entry they were hard links to, as (alias, entry path) pairs
+    HardLinks(Vec<(String, String)>),
+    // Directories that held no included files, to be created empty
+    EmptyDirs(Vec<String>),
+    Footer(Footer),
+    // How the archive was made, as YAML lines
+    FrontMatter(Vec<String>),
+}
+
+// A whole archive parsed at once by parse_archive
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Archive {
+    pub version: u32,
+    pub public_key: Option<String>,
+    pub entries: Vec<Entry>,
+    pub omitted: Vec<Omitted>,
+    pub timestamp: Option<Timestamp>,
+    pub normalization: Vec<String>,
+    pub hard_links: Vec<(String, String)>,
+    pub empty_dirs: Vec<String>,
+    pub footer: Option<Footer>,
+    pub front_matter: Vec<String>,
+}
+
+// Reads an archive one line at a time, so callers can stream archives larger than memory.
+// Entries are returned once the next entry starts or `finish` is called, entries whose
+// content was omitted as soon as their marker line is read, and a timestamp once its section
+// closes. Boilerplate sections are kept to expand the references to them in later entries, a
+// normalization, hard links, empty directories, footer or front matter section is returned once it closes, and other sections are skipped.
+#[derive(Debug, Default)]
+pub struct ArchiveParser {
+    version: Option<u32>,
+    current: Option<Entry>,
+    current_digest: Option<String>,
+    // Content bytes of the current entry so far, counting a newline after each line
+    current_len: usize,
+    in_content: bool,
+    expect_key_close: bool,
+    timestamp: Option<Timestamp>,
+    boilerplate: BTreeMap<String, Vec<String>>,
+    // The boilerplate, normalization, hard links, empty directories or footer section being read, with its name
+    section: Option<(String, Vec<String>)>,
+}
+
+impl ArchiveParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The archive's format version, known once the first line has been fed
+    pub fn version(&self) -> u32 {
+        self.version.unwrap_or(LEGACY_FORMAT_VERSION)
+    }
+
+    // Feed the next line without its `\n`. A `\r` before it is kept, as it belongs to the
+    // content of files with CRLF line endings.
+    pub fn feed(&mut self, line: &str) -> Result<Option<Event>, String> {
+        if self.version.is_none() {
+            // Archives without a header predate versioning
+            if !line.starts_with(FORMAT_HEADER_PREFIX) {
+                self.version = Some(LEGACY_FORMAT_VERSION);
+            } else {
+                let version = parse_format_header(line)?;
+                // Format 2 only adds the version header; its entry layout is identical to format 1.
+                // Format 3 adds optional metadata to entry headers, format 4 boilerplate
+                // references, format 5 escaped paths, which are left escaped for the reader,
+                // format 6 a footer that must be there, and format 7 escaped content lines.
+                if !(LEGACY_FORMAT_VERSION..=FORMAT_VERSION).contains(&version) {
+                    return Err(format!(
+                        "Unsupported archive format version {} (this build supports up to {})",
+                        version, FORMAT_VERSION
+                    ));
+                }
+                self.version = Some(version);
+                return Ok(None);
+            }
+        }
+
+        if let Some((_, lines)) = &mut self.section {
+            if line != CLOSING_MARKER {
+                lines.push(line.to_string());
+                return Ok(None);
+            }
+            let (name, lines) = self.section.take().unwrap_or_default();
+            if name == NORMALIZATION_SECTION {
+                return Ok(Some(Event::Normalization(lines)));
+            }
+            if name == HARD_LINKS_SECTION {
+                let links = lines
+                    .iter()
+                    .map(|line| {
+                        line.split_once(HARD_LINK_SEPARATOR)
+                            .map(|(alias, path)| (alias.to_string(), path.to_string()))
+                            .ok_or_else(|| format!("Invalid line in hard links section: {}", line))
+                    })
+                    .collect::<Result<_, _>>()?;
+                return Ok(Some(Event::HardLinks(links)));
+            }
+            if name == EMPTY_DIRS_SECTION {
+                return Ok(Some(Event::EmptyDirs(lines)));
+            }
+            if name == FOOTER_SECTION {
+                return Footer::parse(&lines).map(|footer| Some(Event::Footer(footer)));
+            }
+            if name == FRONT_MATTER_SECTION {
+                return Ok(Some(Event::FrontMatter(lines)));
+            }
+            if let Some(id) = boilerplate_id(&name) {
+                self.boilerplate.insert(id.to_string(), lines);
+            }
+            return Ok(None);
+        }
+
+        if self.expect_key_close {
+            self.expect_key_close = false;
+            if line != CLOSING_MARKER {
+                return Err("Invalid public key format: missing closing marker".to_string());
+            }
+            return Ok(None);
+        }
+
+        if let Some(key) = line
+            .strip_prefix(PUBLIC_KEY_PREFIX)
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            self.expect_key_close = true;
+            return Ok(Some(Event::PublicKey(key.to_string())));
+        }
+
+        if line.starts_with(ENTRY_PREFIX) {
+            let header = parse_entry_header(line)?;
+            self.current_digest = header.digest;
+            self.current_len = 0;
+            let finished = self.current.replace(Entry {
+                path: header.path,
+                signature: header.signature,
+                metadata: header.metadata,
+                lines: Vec::new(),
+            });
+            self.in_content = true;
+            return Ok(finished.map(Event::Entry));
+        }
+
+        if let Some(timestamp) = &mut self.timestamp {
+            if line == CLOSING_MARKER {
+                let timestamp = self.timestamp.take().unwrap_or_default();
+                if timestamp.time.is_empty()
+                    || timestamp.digest.is_empty()
+                    || timestamp.signature.is_empty()
+                {
+                    return Err("Incomplete timestamp section".to_string());
+                }
+                return Ok(Some(Event::Timestamp(timestamp)));
+            }
+            let (name, value) = line
+                .split_once(": ")
+                .ok_or_else(|| format!("Invalid line in timestamp section: {}", line))?;
+            let field = match name {
+                "TIME" => &mut timestamp.time,
+                "SHA256" => &mut timestamp.digest,
+                "SIGNATURE" => &mut timestamp.signature,
+                _ => return Err(format!("Invalid line in timestamp section: {}", line)),
+            };
+            *field = value.to_string();
+            return Ok(None);
+        }
+
+        if !self.in_content {
+            if is_timestamp_header(line) {
+                self.timestamp = Some(Timestamp::default());
+            } else if let Some(name) = section_name(line).filter(|&name| {
+                name == NORMALIZATION_SECTION
+                    || name == HARD_LINKS_SECTION
+                    || name == EMPTY_DIRS_SECTION
+                    || name == FOOTER_SECTION
+                    || name == FRONT_MATTER_SECTION
+                    || boilerplate_id(name).is_some()
+            }) {
+                self.section = Some((name.to_string(), Vec::new()));
+            }
+            return Ok(None);
+        }
+        if line == CLOSING_MARKER {
+            self.in_content = false;
+        } else if let Some(entry) = &mut self.current {
+            if entry.lines.is_empty() && is_omitted_marker(line) {
+                let omitted = Omitted {
+                    path: core::mem::take(&mut entry.path),
+                    metadata: core::mem::take(&mut entry.metadata),
+                    marker: line.to_string(),
+                    digest: self.current_digest.take(),
+                    signature: entry.signature.take(),
+                };
+                self.current = None;
+                // A rendered view runs on to its closing marker, and with no current entry its
+                // lines are dropped
+                self.in_content = is_rendered_marker(line);
+                return Ok(Some(Event::Omitted(omitted)));
+            }
+            let version = self.version.unwrap_or(LEGACY_FORMAT_VERSION);
+            let block = match (entry.lines.is_empty(), version) {
+                (true, BOILERPLATE_FORMAT_VERSION..) => line
+                    .strip_prefix(BOILERPLATE_REFERENCE_PREFIX)
+                    .and_then(|id| id.strip_suffix(']'))
+                    .and_then(|id| self.boilerplate.get(id)),
+                _ => None,
+            };
+            match block {
+                Some(block) => {
+                    self.current_len += block.iter().map(|line| line.len() + 1).sum::<usize>();
+                    entry.lines.extend(block.iter().cloned());
+                }
+                None => {
+                    let line = match version {
+                        ESCAPED_CONTENT_FORMAT_VERSION.. => unescape_content_line(line),
+                        _ => line,
+                    };
+                    self.current_len += line.len() + 1;
+                    entry.lines.push(line.to_string());
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // The path of the entry being read and the bytes of content read for it so far, so callers
+    // can stop on oversized entries before they are complete
+    pub fn current_entry(&self) -> Option<(&str, usize)> {
+        self.current
+            .as_ref()
+            .map(|entry| (entry.path.as_str(), self.current_len))
+    }
+
+    // The last entry, if the archive had one still open
+    pub fn finish(&mut self) -> Option<Entry> {
+        self.in_content = false;
+        self.current.take()
+    }
+}
+
+pub fn parse_archive(text: &str) -> Result<Archive, String> {
+    let mut parser = ArchiveParser::new();
+    let mut archive = Archive::default();
+    // Entry paths in archive order, omitted entries included, and the bytes of their content
+    let mut paths = Vec::new();
+    let mut bytes = 0;
+    // Split on `\n` alone: str::lines would also drop the `\r` of CRLF content
+    for line in text.split_terminator('\n') {
+        match parser.feed(line)? {
+            Some(Event::PublicKey(key)) => archive.public_key = Some(key),
+            Some(Event::Entry(entry)) => {
+                paths.push(entry.path.clone());
+                bytes += entry.content_len();
+                archive.entries.push(entry);
+            }
+            Some(Event::Omitted(omitted)) => {
+                paths.push(omitted.path.clone());
+                archive.omitted.push(omitted);
+            }
+            Some(Event::Timestamp(timestamp)) => archive.timestamp = Some(timestamp),
+            Some(Event::Normalization(steps)) => archive.normalization = steps,
+            Some(Event::HardLinks(links)) => archive.hard_links = links,
+            Some(Event::EmptyDirs(dirs)) => archive.empty_dirs = dirs,
+            Some(Event::Footer(footer)) => archive.footer = Some(footer),
+            Some(Event::FrontMatter(lines)) => archive.front_matter = lines,
+            None => {}
+        }
+    }
+    if let Some(entry) = parser.finish() {
+        paths.push(entry.path.clone());
+        bytes += entry.content_len();
+        archive.entries.push(entry);
+    }
+    archive.version = parser.version();
+    match &archive.footer {
+        Some(footer) => footer.verify(&paths, bytes, &archive.hard_links, &archive.empty_dirs)?,
+        None if archive.version >= FOOTER_FORMAT_VERSION => return Err(MISSING_FOOTER.to_string()),
+        None => {}
+    }
+    Ok(archive)
+}
+
+// Parse a `LLM_GLOBBER_FORMAT: N` line into its version number
+pub fn parse_format_header(line: &str) -> Result<u32, String> {
+    line.strip_prefix(FORMAT_HEADER_PREFIX)
+        .and_then(|version| version.trim().parse::<u32>().ok())
+        .ok_or_else(|| format!("Invalid format header: {}", line))
+}
+
+// The fields of an entry header line
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryHeader {
+    pub path: String,
+    pub metadata: Metadata,
+    pub digest: Option<String>,
+    pub signature: Option<String>,
+}
+
+// Parse an entry header line, including the digest of a signed omitted entry and any metadata
+pub fn parse_entry_header(line: &str) -> Result<EntryHeader, String> {
+    let (path, signature) = split_header(line)?;
+    let digest = signature.as_ref().and_then(|_| {
+        let (path, digest) = path.rsplit_once(DIGEST_FIELD)?;
+        let digest = digest.strip_suffix(']')?;
+        let is_sha256 = digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit());
+        is_sha256.then(|| (path.to_string(), digest.to_string()))
+    });
+    let (path, digest) = match digest {
+        Some((path, digest)) => (path, Some(digest)),
+        None => (path, None),
+    };
+    let (path, metadata) = match path
+        .rsplit_once(METADATA_SEPARATOR)
+        .and_then(|(path, field)| Some((path, parse_metadata(field)?)))
+    {
+        Some((path, metadata)) => (path.to_string(), metadata),
+        None => (path, Metadata::default()),
+    };
+    Ok(EntryHeader {
+        path,
+        metadata,
+        digest,
+        signature,
+    })
+}
+
+// A metadata field such as `[SIZE:1234] [MODE:644]`, or None if it is not one
+fn parse_metadata(field: &str) -> Option<Metadata> {
+    let mut metadata = Metadata::default();
+    for item in field.split(' ') {
+        let (name, value) = item.strip_prefix('[')?.strip_suffix(']')?.split_once(':')?;
+        match name {
+            "SIZE" => metadata.size = Some(value.parse().ok()?),
+            "MTIME" => metadata.mtime = Some(value.parse().ok()?),
+            "MODE" => metadata.mode = Some(u32::from_str_radix(value, 8).ok()?),
+            "LINES" => metadata.lines = Some(value.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some(metadata)
+}
+
+// An entry header line's path and signature
+pub fn parse_file_header(line: &str) -> Result<(String, Option<String>), String> {
+    parse_entry_header(line).map(|header| (header.path, header.signature))
+}
+
+// Helper function to parse a file header line
+fn split_header(line: &str) -> Result<(String, Option<String>), String> {
+    let trimmed_line = line.trim();
+
+    // Ensure it starts with '''--- and ends with --- or ]
+    if !trimmed_line.starts_with("'''--- ")
+        || !(trimmed_line.ends_with(" ---") || trimmed_line.ends_with(']'))
+    {
+        return Err(format!("Invalid file header format: {}", line));
+    }
+
+    // Strip prefix '''---
+    let content = trimmed_line
+        .strip_prefix("'''--- ")
+        .ok_or_else(|| format!("Failed to strip prefix: {}", line))?;
+
+    // Check for signature: path --- [SIGNATURE:...]
+    if let Some((path_part, sig_part)) = content.rsplit_once(" --- [SIGNATURE:") {
+        if let Some(signature) = sig_part.strip_suffix(']') {
+            let file_path = path_part.trim().to_string();
+            Ok((file_path, Some(signature.to_string())))
+        } else {
+            Err(format!("Invalid signature format in header: {}", line))
+        }
+    }
+    // Check for simple header: path ---
+    else if let Some(path_part) = content.strip_suffix(" ---") {
+        let file_path = path_part.trim().to_string();
+        Ok((file_path, None))
+    }
+    // Check for public key header (should not be parsed here ideally)
+    else if content.starts_with("PUBLIC_KEY --- [KEY:") {
+        Err("Public key header should be handled separately".to_string())
+    } else {
+        Err(format!("Unrecognized file header format: {}", line))
+    }
+}
+
+// True for the line that opens the timestamp section; a timestamp's digest covers every byte
+// of the archive before it
+pub fn is_timestamp_header(line: &str) -> bool {
+    section_name(line) == Some(TIMESTAMP_SECTION)
+}
+
+// `1` for the section named `BOILERPLATE 1`
+fn boilerplate_id(section_name: &str) -> Option<&str> {
+    section_name
+        .strip_prefix(BOILERPLATE_SECTION)?
+        .strip_prefix(' ')
+}
+
+// The name of the section a line opens, if it opens one
+fn section_name(line: &str) -> Option<&str> {
+    line.strip_prefix(SECTION_PREFIX)
+        .and_then(|rest| rest.strip_suffix(" ==="))
+}
+
+// True for the line that stands in for an omitted entry (binary, minified, image, read error or
+// headers only), or opens a rendered view in place of the content
+pub fn is_omitted_marker(line: &str) -> bool {
+    line == BINARY_MARKER
+        || line == MINIFIED_MARKER
+        || line == HEADERS_ONLY_MARKER
+        || (line.starts_with(IMAGE_MARKER_PREFIX) && line.ends_with(']'))
+        || (line.starts_with(ERROR_MARKER_PREFIX) && line.ends_with(']'))
+        || is_rendered_marker(line)
+}
+
+// True for the line opening a rendered view, which unlike other markers is followed by the view
+// and a closing marker
+pub fn is_rendered_marker(line: &str) -> bool {
+    line.starts_with(RENDERED_MARKER_PREFIX) && line.ends_with(']')
+}
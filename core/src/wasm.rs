@@ -0,0 +1,60 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::*;
+
+use crate::format::ArchiveBuilder;
+use crate::parse::parse_archive;
+
+// `new Globber()`, `addFile(path, bytes)` for each file, then `finish()` for the archive text
+#[wasm_bindgen]
+pub struct Globber {
+    builder: ArchiveBuilder,
+}
+
+#[wasm_bindgen]
+impl Globber {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Globber {
+        Globber {
+            builder: ArchiveBuilder::new(),
+        }
+    }
+
+    #[wasm_bindgen(js_name = addFile)]
+    pub fn add_file(&mut self, path: &str, data: &[u8]) {
+        self.builder.add_file(path, data);
+    }
+
+    pub fn finish(self) -> String {
+        self.builder.finish()
+    }
+}
+
+impl Default for Globber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A file from `unglob`, with its content as unglob would write it
+#[wasm_bindgen(getter_with_clone)]
+pub struct UnglobbedFile {
+    pub path: String,
+    pub content: String,
+    pub signature: Option<String>,
+}
+
+#[wasm_bindgen]
+pub fn unglob(text: &str) -> Result<Vec<UnglobbedFile>, JsValue> {
+    let archive = parse_archive(text).map_err(|e| JsValue::from_str(&e))?;
+    Ok(archive
+        .entries
+        .into_iter()
+        .map(|entry| UnglobbedFile {
+            content: entry.text(),
+            path: entry.path,
+            signature: entry.signature,
+        })
+        .collect())
+}
@@ -0,0 +1,564 @@
+// The `llm_globber` Python module: glob files into an archive and unglob archives, with
+// failures raised as Python exceptions instead of an exit status and a log to scrape
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use glob::{MatchOptions, Pattern};
+// The command line's organisation policy, filters and unglob checks. Policy destinations do
+// not apply here, since glob() returns the archive rather than writing it.
+use llm_globber_common::{filter, policy, unglob as checks};
+use llm_globber_core::{
+    classify, parse_archive, Archive, ArchiveBuilder, ContentKind, DEFAULT_BINARY_THRESHOLD,
+    DEFAULT_EXCLUDED_DIRS, DEFAULT_MAX_FILES, DEFAULT_MAX_FILE_SIZE, ESCAPED_PATHS_FORMAT_VERSION,
+};
+use pyo3::exceptions::{
+    PyFileExistsError, PyPermissionError, PyTypeError, PyUserWarning, PyValueError,
+};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use unicode_normalization::UnicodeNormalization;
+
+// The keyword arguments glob() accepts, named after the command line options they mirror
+#[derive(Debug)]
+struct Filters {
+    types: HashSet<String>,
+    recursive: bool,
+    patterns: Vec<Pattern>, // A file must match one of these, if there are any
+    excluded_patterns: Vec<Pattern>, // `!` patterns, which a file must match none of
+    skip_patterns: Vec<Pattern>,
+    dot_files: bool,
+    default_excludes: bool,
+    max_files: usize,
+    overflow_error: bool, // Refuse rather than warn when more than max_files files match
+    binary_threshold: usize,
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Filters {
+            types: HashSet::new(),
+            recursive: false,
+            patterns: Vec::new(),
+            excluded_patterns: Vec::new(),
+            skip_patterns: Vec::new(),
+            dot_files: false,
+            default_excludes: true,
+            max_files: DEFAULT_MAX_FILES,
+            overflow_error: false,
+            binary_threshold: DEFAULT_BINARY_THRESHOLD,
+        }
+    }
+}
+
+impl Filters {
+    fn from_kwargs(kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let mut filters = Filters::default();
+        let Some(kwargs) = kwargs else {
+            return Ok(filters);
+        };
+        for (key, value) in kwargs.iter() {
+            let key: String = key.extract()?;
+            match key.as_str() {
+                "types" => {
+                    filters.types = strings(&value)?
+                        .iter()
+                        .map(|ext| format!(".{}", ext.trim_start_matches('.')))
+                        .collect();
+                }
+                "recursive" => filters.recursive = value.extract()?,
+                // One glob or a list, as --pattern is repeated, since globs may hold commas
+                "pattern" => {
+                    let globs = match value.extract::<String>() {
+                        Ok(glob) => vec![glob],
+                        Err(_) => value.extract()?,
+                    };
+                    for glob in globs {
+                        match glob.strip_prefix('!') {
+                            Some(negated) => filters.excluded_patterns.push(pattern(negated)?),
+                            None => filters.patterns.push(pattern(&glob)?),
+                        }
+                    }
+                }
+                "skip_pattern" => {
+                    filters.skip_patterns = strings(&value)?
+                        .iter()
+                        .map(|p| pattern(p))
+                        .collect::<PyResult<_>>()?;
+                }
+                "dot_files" => filters.dot_files = value.extract()?,
+                "no_default_excludes" => filters.default_excludes = !value.extract::<bool>()?,
+                "max_files" => filters.max_files = value.extract()?,
+                "on_overflow" => match value.extract::<String>()?.as_str() {
+                    "truncate" => filters.overflow_error = false,
+                    "error" => filters.overflow_error = true,
+                    other => {
+                        return Err(PyValueError::new_err(format!(
+                            "Invalid on_overflow '{}': expected truncate or error",
+                            other
+                        )))
+                    }
+                },
+                "binary_threshold" => filters.binary_threshold = value.extract()?,
+                other => {
+                    return Err(PyTypeError::new_err(format!(
+                        "glob() got an unexpected keyword argument '{}'",
+                        other
+                    )))
+                }
+            }
+        }
+        Ok(filters)
+    }
+
+    // `relative` is the file's path below the input it was found by walking, if it was
+    fn includes_file(&self, path: &Path, relative: Option<&Path>) -> bool {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        if !self.dot_files && name.starts_with('.') {
+            return false;
+        }
+        if !self.types.is_empty() {
+            let extension = path
+                .extension()
+                .map(|ext| format!(".{}", ext.to_string_lossy()));
+            if !extension.is_some_and(|ext| self.types.contains(&ext)) {
+                return false;
+            }
+        }
+        // As with --pattern, globs with a `/` match the path below the input, with `*` stopping
+        // at separators, and the others match the name at any depth
+        let options = MatchOptions::new();
+        let matches =
+            |pattern: &Pattern| filter::matches_name_pattern(pattern, relative, &name, options);
+        if (!self.patterns.is_empty() && !self.patterns.iter().any(matches))
+            || self.excluded_patterns.iter().any(matches)
+        {
+            return false;
+        }
+        !self
+            .skip_patterns
+            .iter()
+            .any(|pattern| filter::matches_file(pattern, path, &name, options))
+    }
+
+    fn walks_dir(&self, path: &Path) -> bool {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        self.recursive
+            && (self.dot_files || !name.starts_with('.'))
+            && !(self.default_excludes && DEFAULT_EXCLUDED_DIRS.contains(&name.as_ref()))
+    }
+}
+
+// A comma separated string or a list of strings
+fn strings(value: &Bound<'_, PyAny>) -> PyResult<Vec<String>> {
+    if let Ok(value) = value.extract::<String>() {
+        return Ok(value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect());
+    }
+    value.extract()
+}
+
+fn pattern(value: &str) -> PyResult<Pattern> {
+    Pattern::new(value)
+        .map_err(|e| PyValueError::new_err(format!("Invalid pattern '{}': {}", value, e)))
+}
+
+// Glob files and directories into archive text, applying the same filters as the command
+// line: glob(["src"], types=".rs,.toml", recursive=True, skip_pattern=["*_test.rs"]). Files
+// past max_files are left out with a UserWarning, or with on_overflow="error" refuse the call.
+#[pyfunction]
+#[pyo3(name = "glob", signature = (paths, **filters))]
+fn glob_paths(
+    py: Python<'_>,
+    paths: Vec<PathBuf>,
+    filters: Option<&Bound<'_, PyDict>>,
+) -> PyResult<String> {
+    let filters = Filters::from_kwargs(filters)?;
+    let (archive, overflow) = py.detach(|| build_archive(&paths, &filters))?;
+    if overflow > 0 {
+        let message = CString::new(overflow_message(overflow, filters.max_files))
+            .expect("the message holds no NUL");
+        PyErr::warn(py, &py.get_type::<PyUserWarning>(), &message, 1)?;
+    }
+    Ok(archive)
+}
+
+// As the command line words it when more files match than --max-files
+fn overflow_message(overflow: usize, max_files: usize) -> String {
+    format!(
+        "{} more matching files were not included because the limit of {} files was reached",
+        overflow, max_files
+    )
+}
+
+// The archive, and how many matching files were left out because of max_files
+fn build_archive(paths: &[PathBuf], filters: &Filters) -> PyResult<(String, usize)> {
+    let policy = policy::Policy::load().map_err(PyValueError::new_err)?;
+    let sources = policy.as_ref().map_or(String::new(), |policy| {
+        policy
+            .sources
+            .iter()
+            .map(|source| source.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    });
+    // A run the policy wants on record cannot be made where nothing records it
+    if policy
+        .as_ref()
+        .is_some_and(|policy| !policy.audit_logs.is_empty())
+    {
+        return Err(PyPermissionError::new_err(format!(
+            "Policy {} records every run in an audit log, which glob() cannot write; use the command line",
+            sources
+        )));
+    }
+
+    let mut files = Vec::new();
+    let mut roots = Vec::new();
+    for path in paths {
+        let path = fs::canonicalize(path).map_err(|e| with_path(e, path))?;
+        if path.is_dir() {
+            collect_dir(&path, &path, filters, &mut files)?;
+            roots.push(path);
+        } else {
+            if filters.includes_file(&path, None) {
+                files.push(path.clone());
+            }
+            roots.extend(path.parent().map(Path::to_path_buf));
+        }
+    }
+
+    // Header paths are relative to the common ancestor of the inputs and in NFC, as with the
+    // command line
+    let root = common_ancestor(&roots);
+    let mut entries: Vec<(String, PathBuf)> = files
+        .into_iter()
+        .map(|file| {
+            let relative = file.strip_prefix(&root).unwrap_or(&file);
+            (relative.to_string_lossy().nfc().collect(), file)
+        })
+        .collect();
+    entries.sort();
+    entries.dedup();
+    // Forbidden files refuse the call, or with `on_forbidden = "skip"` are left out
+    if let Some(policy) = &policy {
+        let mut forbidden = Vec::new();
+        entries.retain(|(header, file)| match policy.forbidden(file, header) {
+            Some(pattern) => {
+                forbidden.push(format!("{} ({})", file.display(), pattern));
+                false
+            }
+            None => true,
+        });
+        if !forbidden.is_empty() && !policy.skip_forbidden {
+            return Err(PyPermissionError::new_err(format!(
+                "Policy {} forbids globbing {} file(s): {}",
+                sources,
+                forbidden.len(),
+                forbidden.join(", ")
+            )));
+        }
+    }
+    let overflow = entries.len().saturating_sub(filters.max_files);
+    if overflow > 0 && filters.overflow_error {
+        return Err(PyValueError::new_err(format!(
+            "{}; raise max_files or narrow the inputs",
+            overflow_message(overflow, filters.max_files)
+        )));
+    }
+    entries.truncate(filters.max_files);
+
+    let mut builder = ArchiveBuilder::new().binary_threshold(filters.binary_threshold);
+    for (header, file) in entries {
+        let data = fs::read(&file).map_err(|e| with_path(e, &file))?;
+        let redacted = match (
+            &policy,
+            classify(&header, &data, filters.binary_threshold, false),
+        ) {
+            (Some(policy), (ContentKind::Text, decoded)) => {
+                let text = decoded.or_else(|| String::from_utf8(data.clone()).ok());
+                text.and_then(|text| policy.redact(&header, &text))
+            }
+            _ => None,
+        };
+        builder.add_file(
+            &header,
+            redacted.as_ref().map_or(&data[..], String::as_bytes),
+        );
+    }
+    Ok((builder.finish(), overflow))
+}
+
+// Collect the files below `dir` that the filters include, walking into subdirectories of the
+// input `root` when they are recursive
+fn collect_dir(
+    root: &Path,
+    dir: &Path,
+    filters: &Filters,
+    files: &mut Vec<PathBuf>,
+) -> PyResult<()> {
+    for entry in fs::read_dir(dir).map_err(|e| with_path(e, dir))? {
+        let path = entry.map_err(|e| with_path(e, dir))?.path();
+        if path.is_dir() {
+            if filters.walks_dir(&path) {
+                collect_dir(root, &path, filters, files)?;
+            }
+        } else if path.is_file() && filters.includes_file(&path, path.strip_prefix(root).ok()) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn common_ancestor(paths: &[PathBuf]) -> PathBuf {
+    let Some((first, rest)) = paths.split_first() else {
+        return PathBuf::new();
+    };
+    let mut ancestor = first.clone();
+    for path in rest {
+        while !path.starts_with(&ancestor) && ancestor.pop() {}
+    }
+    ancestor
+}
+
+// Extract the entries of archive text under `dest` and return the paths written, checking the
+// archive as the command line unglob does first: its footer, the size of every entry against
+// `max_extract_size` (in MB, as --max-extract-size) and, unless `force` is true, that `dest` is
+// empty or a clean git working tree. Entries whose content was omitted, such as binary files,
+// are skipped, and signatures are not verified, as with the command line without --signature.
+#[pyfunction]
+#[pyo3(signature = (text, dest, force = false, max_extract_size = None))]
+fn unglob(
+    py: Python<'_>,
+    text: &str,
+    dest: PathBuf,
+    force: bool,
+    max_extract_size: Option<u64>,
+) -> PyResult<Vec<PathBuf>> {
+    let archive = parse_archive(text).map_err(PyValueError::new_err)?;
+    if archive.entries.is_empty() {
+        return Err(PyValueError::new_err(
+            "No files were extracted from the input file",
+        ));
+    }
+    let options = UnglobOptions {
+        force,
+        max_extract_size: max_extract_size
+            .map_or(DEFAULT_MAX_FILE_SIZE, |mb| mb.saturating_mul(1024 * 1024)),
+    };
+    py.detach(|| extract(&archive, &dest, &options))
+}
+
+// The keyword arguments unglob() accepts, named after the command line options they mirror
+#[derive(Debug)]
+struct UnglobOptions {
+    force: bool,
+    max_extract_size: u64, // In bytes
+}
+
+// Write every entry under `dest`, once every entry has been checked, so a refused archive
+// writes nothing. Paths are resolved as the command line unglob resolves them.
+fn extract(archive: &Archive, dest: &Path, options: &UnglobOptions) -> PyResult<Vec<PathBuf>> {
+    let escaped = archive.version >= ESCAPED_PATHS_FORMAT_VERSION;
+    let mut targets = Vec::with_capacity(archive.entries.len());
+    for entry in &archive.entries {
+        if entry.content_len() > options.max_extract_size {
+            return Err(PyValueError::new_err(format!(
+                "Entry {} is larger than the {} MB extraction limit (raise it with max_extract_size)",
+                entry.path,
+                options.max_extract_size / (1024 * 1024)
+            )));
+        }
+        let target = checks::entry_target(dest, &entry.path, escaped).ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "Entry path escapes the output directory: {}",
+                entry.path
+            ))
+        })?;
+        targets.push(target);
+    }
+    if !options.force {
+        if let Some(reason) = checks::target_risk(dest).map_err(PyValueError::new_err)? {
+            return Err(PyFileExistsError::new_err(format!(
+                "Refusing to unglob into {}: the directory is not empty and {} (pass force=True to extract anyway)",
+                dest.display(),
+                reason
+            )));
+        }
+    }
+
+    for (entry, target) in archive.entries.iter().zip(&targets) {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| with_path(e, parent))?;
+        }
+        fs::write(target, entry.text()).map_err(|e| with_path(e, target))?;
+    }
+    Ok(targets)
+}
+
+// An OSError of the matching subclass, such as FileNotFoundError, naming the path
+fn with_path(error: io::Error, path: &Path) -> PyErr {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error)).into()
+}
+
+#[pymodule]
+fn llm_globber(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(glob_paths, m)?)?;
+    m.add_function(wrap_pyfunction!(unglob, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use llm_globber_core::{escape_path, text_entry, versioned_format_header};
+    use std::os::unix::ffi::OsStrExt;
+    use tempfile::TempDir;
+
+    const FORCE: UnglobOptions = UnglobOptions {
+        force: true,
+        max_extract_size: DEFAULT_MAX_FILE_SIZE,
+    };
+
+    #[test]
+    fn test_extract_escaped_and_decomposed_names() {
+        let dest = TempDir::new().unwrap();
+        // A checkout made on macOS, with its name decomposed
+        let decomposed = "cafe\u{301}.txt";
+        fs::write(dest.path().join(decomposed), "old\n").unwrap();
+
+        let mut text = versioned_format_header(ESCAPED_PATHS_FORMAT_VERSION);
+        text.push_str(&text_entry(&escape_path(b"line\nbreak\\.txt"), "a\n", None));
+        text.push_str(&text_entry("caf\u{e9}.txt", "new\n", None));
+        let archive = parse_archive(&text).unwrap();
+        let written = extract(&archive, dest.path(), &FORCE).unwrap();
+
+        assert_eq!(
+            written[0].file_name().unwrap().as_bytes(),
+            b"line\nbreak\\.txt"
+        );
+        assert_eq!(fs::read_to_string(&written[0]).unwrap(), "a\n");
+        // The existing file is updated rather than joined by a look-alike
+        assert_eq!(written[1], dest.path().join(decomposed));
+        assert_eq!(fs::read_dir(dest.path()).unwrap().count(), 2);
+        assert_eq!(
+            fs::read_to_string(dest.path().join(decomposed)).unwrap(),
+            "new\n"
+        );
+
+        // Before format 5, backslashes are literal
+        let text = format!(
+            "{}{}",
+            versioned_format_header(2),
+            text_entry("back\\x41slash.txt", "b\n", None)
+        );
+        let written = extract(&parse_archive(&text).unwrap(), dest.path(), &FORCE).unwrap();
+        assert_eq!(written[0], dest.path().join("back\\x41slash.txt"));
+    }
+
+    #[test]
+    fn test_patterns_match_as_on_the_command_line() {
+        let root = TempDir::new().unwrap();
+        for file in ["src/main.rs", "src/lib_test.rs", "tests/api.rs"] {
+            let path = root.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "fn f() {}\n").unwrap();
+        }
+        let glob_with = |patterns: &[&str]| {
+            let filters = Filters {
+                recursive: true,
+                patterns: patterns
+                    .iter()
+                    .filter(|glob| !glob.starts_with('!'))
+                    .map(|glob| Pattern::new(glob).unwrap())
+                    .collect(),
+                excluded_patterns: patterns
+                    .iter()
+                    .filter_map(|glob| glob.strip_prefix('!'))
+                    .map(|glob| Pattern::new(glob).unwrap())
+                    .collect(),
+                ..Filters::default()
+            };
+            let (text, _) = build_archive(&[root.path().to_path_buf()], &filters).unwrap();
+            parse_archive(&text)
+                .unwrap()
+                .entries
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect::<Vec<_>>()
+        };
+
+        // Globs with a `/` match the path below the input, the others the name at any depth
+        assert_eq!(glob_with(&["src/*.rs"]), ["src/lib_test.rs", "src/main.rs"]);
+        assert_eq!(
+            glob_with(&["*.rs", "!*_test.rs"]),
+            ["src/main.rs", "tests/api.rs"]
+        );
+        assert_eq!(glob_with(&["!src/**"]), ["tests/api.rs"]);
+    }
+
+    #[test]
+    fn test_unglob_checks_match_the_command_line() {
+        let root = TempDir::new().unwrap();
+        let mut builder = ArchiveBuilder::new();
+        builder.add_file("main.rs", b"fn main() {}\n");
+        builder.add_file("big.txt", "x\n".repeat(1024 * 1024).as_bytes());
+        let text = builder.finish();
+        let archive = parse_archive(&text).unwrap();
+        let dest = root.path().join("out");
+        let options = |force, max_extract_size| UnglobOptions {
+            force,
+            max_extract_size,
+        };
+
+        // Entries over the extraction limit refuse the archive before anything is written
+        assert!(extract(&archive, &dest, &options(false, 1024 * 1024)).is_err());
+        assert!(!dest.exists());
+        let written = extract(&archive, &dest, &options(false, DEFAULT_MAX_FILE_SIZE)).unwrap();
+        assert_eq!(written, [dest.join("main.rs"), dest.join("big.txt")]);
+
+        // A directory with files that are not in a clean git working tree is refused unless
+        // forced, and is left as it was
+        fs::write(dest.join("main.rs"), "// local edit\n").unwrap();
+        assert!(extract(&archive, &dest, &options(false, DEFAULT_MAX_FILE_SIZE)).is_err());
+        assert_eq!(
+            fs::read_to_string(dest.join("main.rs")).unwrap(),
+            "// local edit\n"
+        );
+        extract(&archive, &dest, &options(true, DEFAULT_MAX_FILE_SIZE)).unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+
+        // A copy that lost an entry no longer matches its footer
+        let cut = text.replacen("'''--- main.rs ---\nfn main() {}\n\n'''\n\n", "", 1);
+        assert_ne!(cut, text);
+        assert!(parse_archive(&cut)
+            .unwrap_err()
+            .contains("Archive is incomplete or was altered"));
+
+        // Signatures are not verified, as with the command line without --signature: a signed
+        // entry is written as it stands
+        let signed = format!(
+            "{}{}",
+            versioned_format_header(2),
+            text_entry("signed.rs", "edited\n", Some("bm90IGEgc2lnbmF0dXJl"))
+        );
+        let signed_dest = root.path().join("signed");
+        let written = extract(&parse_archive(&signed).unwrap(), &signed_dest, &FORCE).unwrap();
+        assert_eq!(fs::read_to_string(&written[0]).unwrap(), "edited\n");
+    }
+}
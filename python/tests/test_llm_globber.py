@@ -0,0 +1,121 @@
+import os
+import tempfile
+import unittest
+import warnings
+
+import llm_globber
+
+
+class GlobUnglobTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.TemporaryDirectory()
+        self.root = self.dir.name
+        src = os.path.join(self.root, "src")
+        os.makedirs(os.path.join(src, "util"))
+        os.makedirs(os.path.join(src, "node_modules"))
+        self.write("src/main.rs", "fn main() {}\n")
+        self.write("src/util/mod.rs", "pub fn run() {}\n")
+        self.write("src/notes.md", "# Notes\n")
+        self.write("src/node_modules/dep.rs", "ignored\n")
+        self.write("src/.hidden.rs", "ignored\n")
+
+    def tearDown(self):
+        self.dir.cleanup()
+
+    def write(self, relative, content):
+        with open(os.path.join(self.root, relative), "w") as f:
+            f.write(content)
+
+    def test_round_trip(self):
+        text = llm_globber.glob([os.path.join(self.root, "src")], types=".rs", recursive=True)
+        self.assertTrue(text.startswith("LLM_GLOBBER_FORMAT: 7\n"))
+        self.assertIn("'''--- main.rs ---", text)
+        self.assertIn("'''--- util/mod.rs ---", text)
+        self.assertNotIn("notes.md", text)
+        self.assertNotIn("ignored", text)
+
+        dest = os.path.join(self.root, "out")
+        written = llm_globber.unglob(text, dest)
+        self.assertEqual(
+            sorted(os.path.relpath(path, dest) for path in written),
+            ["main.rs", os.path.join("util", "mod.rs")],
+        )
+        with open(os.path.join(dest, "util", "mod.rs")) as f:
+            self.assertEqual(f.read(), "pub fn run() {}\n")
+
+    def test_patterns(self):
+        src = os.path.join(self.root, "src")
+        text = llm_globber.glob([src], recursive=True, pattern=["*.rs", "!util/*"])
+        self.assertIn("'''--- main.rs ---", text)
+        self.assertNotIn("util/mod.rs", text)
+
+    def test_max_files(self):
+        src = os.path.join(self.root, "src")
+        with warnings.catch_warnings(record=True) as caught:
+            warnings.simplefilter("always")
+            text = llm_globber.glob([src], types=".rs", recursive=True, max_files=1)
+        self.assertEqual(text.count("'''--- "), 1)
+        self.assertEqual(len(caught), 1)
+        self.assertIn("1 more matching files were not included", str(caught[0].message))
+        with self.assertRaises(ValueError):
+            llm_globber.glob([src], types=".rs", recursive=True, max_files=1, on_overflow="error")
+
+    def test_escaped_paths(self):
+        text = "LLM_GLOBBER_FORMAT: 5\n'''--- tab\\x09name.rs ---\nx\n'''\n"
+        written = llm_globber.unglob(text, os.path.join(self.root, "out"))
+        self.assertEqual(os.path.basename(written[0]), "tab\tname.rs")
+
+    def test_errors_are_exceptions(self):
+        with self.assertRaises(FileNotFoundError):
+            llm_globber.glob([os.path.join(self.root, "missing")])
+        with self.assertRaises(TypeError):
+            llm_globber.glob([self.root], colour=True)
+        with self.assertRaises(ValueError):
+            llm_globber.unglob("LLM_GLOBBER_FORMAT: 99\n", self.root)
+        with self.assertRaises(ValueError):
+            llm_globber.unglob("'''--- ../escape.rs ---\nx\n'''\n", self.root)
+
+    def test_unglob_checks(self):
+        text = llm_globber.glob([os.path.join(self.root, "src")], types=".rs", recursive=True)
+        # Like the command line, unglob refuses a directory whose files it could overwrite
+        with self.assertRaises(FileExistsError):
+            llm_globber.unglob(text, self.root)
+        written = llm_globber.unglob(text, self.root, force=True)
+        self.assertIn(os.path.join(self.root, "main.rs"), [os.fspath(path) for path in written])
+        # An archive whose entries no longer match its footer is refused
+        renamed = text.replace("'''--- main.rs ---", "'''--- moved.rs ---")
+        with self.assertRaises(ValueError):
+            llm_globber.unglob(renamed, os.path.join(self.root, "renamed"))
+        with self.assertRaises(ValueError):
+            llm_globber.unglob(text, os.path.join(self.root, "small"), max_extract_size=0)
+        self.assertFalse(os.path.exists(os.path.join(self.root, "small")))
+
+    def test_policy(self):
+        self.write("src/secrets.rs", "const KEY: &str = \"hunter2\";\n")
+        policy = os.path.join(self.root, "policy.toml")
+        src = os.path.join(self.root, "src")
+
+        def glob_with(rules):
+            with open(policy, "w") as f:
+                f.write(rules)
+            os.environ["LLM_GLOBBER_POLICY"] = policy
+            try:
+                return llm_globber.glob([src], types=".rs", recursive=True)
+            finally:
+                del os.environ["LLM_GLOBBER_POLICY"]
+
+        with self.assertRaises(PermissionError):
+            glob_with('forbid = ["**/secrets.rs"]\n')
+        text = glob_with('forbid = ["**/secrets.rs"]\non_forbidden = "skip"\n')
+        self.assertNotIn("secrets.rs", text)
+        self.assertIn("main.rs", text)
+        text = glob_with('[[redact]]\npattern = "hunter2"\n')
+        self.assertIn("[REDACTED BY POLICY]", text)
+        self.assertNotIn("hunter2", text)
+        # A run the policy wants audited is refused, since glob() keeps no audit log
+        with self.assertRaises(PermissionError):
+            glob_with('audit_log = "%s"\n' % os.path.join(self.root, "audit.jsonl"))
+
+
+if __name__ == "__main__":
+    unittest.main()
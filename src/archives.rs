@@ -0,0 +1,208 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use llm_globber_common::unglob::enclosed_path;
+use log::{info, warn};
+
+// Archive inputs that are globbed as if they were directories
+const ARCHIVE_SUFFIXES: &[&str] = &[".zip", ".tar", ".tar.gz", ".tgz"];
+// Tar headers and data are written in blocks of this size
+const TAR_BLOCK_SIZE: usize = 512;
+// Length of the name field in a tar header; longer paths need a GNU long name entry
+const TAR_NAME_SIZE: usize = 100;
+// PAX record on entries holding a rendered view of a file, such as an outline, rather than its
+// content, which unpacking skips so a view is never written over the file
+const RENDERED_PAX_KEY: &str = "LLM_GLOBBER.rendered";
+
+pub(crate) fn is_archive(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    ARCHIVE_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+// Extract the regular files of a zip or tar archive into `dest` and return how many there were.
+// Links are left out, and so are entries whose paths would escape `dest`.
+pub(crate) fn extract(archive: &Path, dest: &Path) -> Result<usize, String> {
+    let name = archive.to_string_lossy().to_lowercase();
+    let file = File::open(archive)
+        .map_err(|e| format!("Failed to open archive {}: {}", archive.display(), e))?;
+    let result = if name.ends_with(".zip") {
+        extract_zip(file, dest)
+    } else if name.ends_with(".tar") {
+        unpack_tar(
+            BufReader::new(file),
+            dest,
+            str::to_string,
+            |_| Ok(()),
+            u64::MAX,
+        )
+    } else {
+        unpack_tar(
+            MultiGzDecoder::new(BufReader::new(file)),
+            dest,
+            str::to_string,
+            |_| Ok(()),
+            u64::MAX,
+        )
+    };
+    result.map_err(|e| format!("Failed to extract archive {}: {}", archive.display(), e))
+}
+
+// Whether the first bytes of a stream are a tar header
+pub(crate) fn is_tar(head: &[u8]) -> bool {
+    head.get(257..262) == Some(b"ustar".as_slice())
+}
+
+// Unpack the regular files of a tar stream under `dest`, renaming each with `map` first and
+// calling `before_write` with each file's destination before it is written. Entries whose
+// mapped paths would escape `dest` are skipped with a warning, rendered views are skipped, and
+// one over `max_size` bytes stops the unpacking.
+pub(crate) fn unpack_tar<R: Read>(
+    reader: R,
+    dest: &Path,
+    map: impl Fn(&str) -> String,
+    before_write: impl Fn(&Path) -> io::Result<()>,
+    max_size: u64,
+) -> io::Result<usize> {
+    let mut archive = tar::Archive::new(reader);
+    let mut count = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = map(&entry.path()?.to_string_lossy());
+        if is_rendered_entry(&mut entry)? {
+            info!(
+                "Not extracting {}: the tarball holds a view of it, not its content",
+                path
+            );
+            continue;
+        }
+        let Some(relative) = enclosed_path(Path::new(&path)) else {
+            warn!("Skipping tar entry outside the output directory: {}", path);
+            continue;
+        };
+        let size = entry.header().size()?;
+        if size > max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} holds {} bytes, over the {} byte limit (raise it with --max-extract-size)",
+                    path, size, max_size
+                ),
+            ));
+        }
+        let target = dest.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        before_write(&target)?;
+        entry.unpack(&target)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn is_rendered_entry<R: Read>(entry: &mut tar::Entry<R>) -> io::Result<bool> {
+    let Some(extensions) = entry.pax_extensions()? else {
+        return Ok(false);
+    };
+    for extension in extensions {
+        if extension?.key() == Ok(RENDERED_PAX_KEY) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Append one regular file to a tar stream. Paths too long for the header are stored in a
+// GNU long name entry first, as GNU tar and bsdtar do, and rendered views are marked with a
+// PAX record ahead of them.
+pub(crate) fn write_tar_entry<W: Write>(
+    out: &mut W,
+    path: &str,
+    data: &[u8],
+    mtime: u64,
+    rendered: bool,
+) -> io::Result<()> {
+    if rendered {
+        let record = pax_record(RENDERED_PAX_KEY, "view");
+        let header = tar_header(b"././@PaxHeader", tar::EntryType::XHeader, &record, 0);
+        out.write_all(header.as_bytes())?;
+        write_padded(out, &record)?;
+    }
+    let name = path.as_bytes();
+    if name.len() > TAR_NAME_SIZE {
+        let mut long_name = name.to_vec();
+        long_name.push(0);
+        let header = tar_header(b"././@LongLink", tar::EntryType::GNULongName, &long_name, 0);
+        out.write_all(header.as_bytes())?;
+        write_padded(out, &long_name)?;
+    }
+    let header = tar_header(name, tar::EntryType::Regular, data, mtime);
+    out.write_all(header.as_bytes())?;
+    write_padded(out, data)
+}
+
+// End a tar stream with the two empty blocks that mark the end of the archive
+pub(crate) fn finish_tar<W: Write>(out: &mut W) -> io::Result<()> {
+    out.write_all(&[0; TAR_BLOCK_SIZE * 2])?;
+    out.flush()
+}
+
+// A PAX record, `LEN KEY=VALUE\n`, where LEN counts the whole record including its own digits
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    let body = format!(" {}={}\n", key, value);
+    let mut len = body.len();
+    while len != body.len() + len.to_string().len() {
+        len = body.len() + len.to_string().len();
+    }
+    format!("{}{}", len, body).into_bytes()
+}
+
+fn tar_header(name: &[u8], entry_type: tar::EntryType, data: &[u8], mtime: u64) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    let len = name.len().min(TAR_NAME_SIZE);
+    header.as_old_mut().name[..len].copy_from_slice(&name[..len]);
+    header.set_entry_type(entry_type);
+    header.set_mode(0o644);
+    header.set_size(data.len() as u64);
+    header.set_mtime(mtime);
+    header.set_cksum();
+    header
+}
+
+fn write_padded<W: Write>(out: &mut W, data: &[u8]) -> io::Result<()> {
+    out.write_all(data)?;
+    let remainder = data.len() % TAR_BLOCK_SIZE;
+    if remainder > 0 {
+        out.write_all(&[0; TAR_BLOCK_SIZE][remainder..])?;
+    }
+    Ok(())
+}
+
+fn extract_zip(file: File, dest: &Path) -> io::Result<usize> {
+    let mut archive = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+    let mut count = 0;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+        let Some(relative) = entry.enclosed_name() else {
+            continue;
+        };
+        if !entry.is_file() || entry.is_symlink() {
+            continue;
+        }
+        let path = dest.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(&path)?)?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,115 @@
+// Audit logs kept with --audit-log and a policy's `audit_log`: one JSON line appended for every
+// finished run, recording who ran it and when, the files whose content went into the output with
+// the SHA-256 of what was written, and where the output went. Security teams can then answer
+// what source ever left a machine through llm_globber.
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::lock;
+
+// A file written to the output
+#[derive(Debug, Clone)]
+pub(crate) struct ExportedFile {
+    pub(crate) path: String,           // Header path
+    pub(crate) source: PathBuf,        // Where it was read from
+    pub(crate) sha256: Option<String>, // Of the content written; None when it was omitted
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Audit {
+    logs: Vec<PathBuf>,
+    command: Vec<String>, // The command line, or the daemon client's
+}
+
+impl Audit {
+    // Every log is opened once up front, so a run that could not be recorded never starts
+    pub(crate) fn new(logs: Vec<PathBuf>, command: Vec<String>) -> Result<Audit, String> {
+        for log in &logs {
+            open(log).map_err(|e| format!("Cannot write audit log {}: {}", log.display(), e))?;
+        }
+        Ok(Audit { logs, command })
+    }
+
+    // Append the record of a finished run whose output, as stored, is `output` and ended up at
+    // `destination`: the same path, or the URL it was uploaded to. `upload_error` is set when
+    // that upload failed, since part of the output may have been sent anyway.
+    pub(crate) fn record(
+        &self,
+        destination: &str,
+        output: &Path,
+        files: &[ExportedFile],
+        upload_error: Option<&str>,
+    ) -> Result<(), String> {
+        let output_sha256 = file_sha256(output)
+            .map_err(|e| format!("Failed to hash {}: {}", output.display(), e))?;
+        let cwd = std::env::current_dir().map(|dir| dir.display().to_string());
+        let files: Vec<_> = files
+            .iter()
+            .map(|file| {
+                json!({
+                    "path": file.path,
+                    "source": file.source.display().to_string(),
+                    "sha256": file.sha256,
+                })
+            })
+            .collect();
+        let record = json!({
+            "time": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "user": std::env::var("USER").or_else(|_| std::env::var("LOGNAME")).ok(),
+            // SAFETY: getuid cannot fail
+            "uid": unsafe { libc::getuid() },
+            "host": hostname(),
+            "cwd": cwd.ok(),
+            "command": self.command,
+            "version": env!("CARGO_PKG_VERSION"),
+            "destination": destination,
+            "upload_error": upload_error,
+            "output_sha256": output_sha256,
+            "files": files,
+        });
+        let line = format!("{}\n", record);
+        for log in &self.logs {
+            append(log, line.as_bytes())
+                .map_err(|e| format!("Failed to write audit log {}: {}", log.display(), e))?;
+        }
+        Ok(())
+    }
+}
+
+// Created readable only by its owner, since it names every file globbed
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .mode(0o600)
+        .open(path)
+}
+
+// One write under an exclusive lock, so lines from runs finishing together never interleave
+fn append(path: &Path, line: &[u8]) -> io::Result<()> {
+    let mut file = open(path)?;
+    lock::lock_file(&file)?;
+    file.write_all(line)?;
+    file.sync_data()
+}
+
+fn file_sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(crate::hex_digest(hasher))
+}
+
+fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    // SAFETY: the length passed is the buffer's, and gethostname writes no more than that
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return None;
+    }
+    let end = buffer.iter().position(|&byte| byte == 0)?;
+    Some(String::from_utf8_lossy(&buffer[..end]).into_owned())
+}
@@ -0,0 +1,48 @@
+// Phase timings for `--bench`, which globs into a temporary file that is thrown away and reports
+// how fast each phase went, to tell a slow filesystem from slow formatting
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Timings {
+    pub(crate) walk: Duration,   // Finding the files
+    pub(crate) read: Duration,   // Reading them, or mapping them for files over --mmap-threshold
+    pub(crate) format: Duration, // Everything else done per file: filters, transforms, entries
+    pub(crate) write: Duration,  // Flushing entries to the output
+    pub(crate) bytes: u64,       // Bytes read
+}
+
+impl Timings {
+    // Count the time spent on a file that is not in the read or write phases as formatting.
+    // `before` holds the timings from when the file was started.
+    pub(crate) fn add_format(&mut self, elapsed: Duration, before: &Timings) {
+        let measured = (self.read - before.read) + (self.write - before.write);
+        self.format += elapsed.saturating_sub(measured);
+    }
+
+    // Print the throughput and the time spent in each phase on stdout
+    pub(crate) fn report(&self, files: usize, total: Duration) {
+        let seconds = total.as_secs_f64().max(f64::EPSILON);
+        let megabytes = self.bytes as f64 / (1024.0 * 1024.0);
+        println!("Files:      {}", files);
+        println!("Read:       {:.1} MB", megabytes);
+        println!(
+            "Total:      {:.3} s ({:.1} files/sec, {:.1} MB/s)",
+            total.as_secs_f64(),
+            files as f64 / seconds,
+            megabytes / seconds
+        );
+        for (phase, time) in [
+            ("walk", self.walk),
+            ("read", self.read),
+            ("format", self.format),
+            ("write", self.write),
+        ] {
+            println!(
+                "  {:<8} {:.3} s ({:.0}%)",
+                phase,
+                time.as_secs_f64(),
+                time.as_secs_f64() / seconds * 100.0
+            );
+        }
+    }
+}
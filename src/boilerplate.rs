@@ -0,0 +1,73 @@
+// Boilerplate collapsed with --collapse-boilerplate: license headers and generated preambles
+// that open many files are written once, in a section ahead of the entries, and each entry
+// that opens with one holds a reference line in its place, which readers expand
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use llm_globber_core::{boilerplate_reference, CLOSING_MARKER};
+
+// Blocks shorter than this cost little enough that a reference would not be worth it
+const MIN_BLOCK_LINES: usize = 3;
+// Files a block has to open before it is collapsed
+const MIN_FILES: usize = 3;
+
+// The lines a file opens with, up to its first blank line, with their newlines. Blocks with
+// lines that would read as archive markers are left alone.
+fn leading_block(text: &str) -> Option<&str> {
+    let mut end = 0;
+    for (lines, line) in text.split_inclusive('\n').enumerate() {
+        if line.trim().is_empty() {
+            let is_block = lines >= MIN_BLOCK_LINES && line.ends_with('\n');
+            return is_block.then(|| &text[..end]);
+        }
+        if line.starts_with(CLOSING_MARKER) {
+            return None;
+        }
+        end += line.len();
+    }
+    None
+}
+
+// The leading blocks shared by at least MIN_FILES of the files, in the order first seen.
+// `prepare` turns a file's text into what its entry will hold (redacted and normalized), so
+// the sections never show anything the entries would not. Files that cannot be read or are
+// not UTF-8 are left to fail or be classified when written.
+pub(crate) fn find_repeated(
+    paths: &[PathBuf],
+    prepare: impl Fn(&Path, String) -> String,
+) -> Vec<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut order = Vec::new();
+    for path in paths {
+        let Ok(text) = fs::read_to_string(path) else {
+            continue;
+        };
+        let text = prepare(path, text);
+        let Some(block) = leading_block(&text) else {
+            continue;
+        };
+        let count = counts.entry(block.to_string()).or_insert(0);
+        if *count == 0 {
+            order.push(block.to_string());
+        }
+        *count += 1;
+    }
+    order
+        .into_iter()
+        .filter(|block| counts[block] >= MIN_FILES)
+        .collect()
+}
+
+// The content with the block it opens with replaced by a reference, if it opens with one.
+// Blocks are numbered from 1 in the order their sections are written.
+pub(crate) fn collapse(data: &[u8], blocks: &[String]) -> Option<Vec<u8>> {
+    let (id, rest) = blocks
+        .iter()
+        .enumerate()
+        .find_map(|(i, block)| Some((i + 1, data.strip_prefix(block.as_bytes())?)))?;
+    let mut collapsed = boilerplate_reference(id).into_bytes();
+    collapsed.push(b'\n');
+    collapsed.extend_from_slice(rest);
+    Some(collapsed)
+}
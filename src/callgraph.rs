@@ -0,0 +1,120 @@
+// Who calls whom within a source file, for `--call-graph`. Calls are matched by name only, so a
+// call to `new` or `len` says nothing about which type's method it is; that is enough for a
+// model to follow control flow between files it may only see in outline.
+use std::path::Path;
+
+use tree_sitter::Node;
+
+use crate::outline;
+
+// Functions and methods in the languages `outline` parses
+const FUNCTION_KINDS: &[&str] = &[
+    "function_item",
+    "function_definition",
+    "function_declaration",
+    "generator_function_declaration",
+    "method_definition",
+    "method_declaration",
+];
+// Types whose name qualifies the methods inside them
+const CONTAINER_KINDS: &[&str] = &[
+    "impl_item",
+    "trait_item",
+    "class_definition",
+    "class_declaration",
+    "abstract_class_declaration",
+    "class",
+];
+const CALL_KINDS: &[&str] = &["call_expression", "call"];
+
+// Each function that calls something, with the names it calls in the order first called. Empty
+// if the language is not supported.
+pub(crate) fn call_graph(file_path: &str, source: &str) -> Vec<(String, Vec<String>)> {
+    let Some(tree) = outline::parse(file_path, source) else {
+        return Vec::new();
+    };
+    let separator = match Path::new(file_path).extension() {
+        Some(extension) if extension == "rs" => "::",
+        _ => ".",
+    };
+    let mut graph = Vec::new();
+    visit(tree.root_node(), source, separator, None, None, &mut graph);
+    graph.retain(|(_, callees)| !callees.is_empty());
+    graph
+}
+
+fn visit(
+    node: Node,
+    source: &str,
+    separator: &str,
+    container: Option<&str>,
+    caller: Option<usize>,
+    graph: &mut Vec<(String, Vec<String>)>,
+) {
+    let kind = node.kind();
+    let mut container = container;
+    let mut caller = caller;
+    if CONTAINER_KINDS.contains(&kind) {
+        container = node
+            .child_by_field_name("name")
+            .or_else(|| node.child_by_field_name("type"))
+            .map(|name| &source[name.byte_range()]);
+    } else if FUNCTION_KINDS.contains(&kind) {
+        if let Some(name) = function_name(node, source) {
+            let name = match container {
+                Some(container) => format!("{}{}{}", container, separator, name),
+                None => name.to_string(),
+            };
+            graph.push((name, Vec::new()));
+            caller = Some(graph.len() - 1);
+        }
+    } else if CALL_KINDS.contains(&kind) {
+        // Rust's tuple structs and variants such as `Some(x)` look like calls but are not
+        let callee = node
+            .child_by_field_name("function")
+            .and_then(|function| callee_name(function, source))
+            .filter(|callee| {
+                separator != "::"
+                    || callee.contains("::")
+                    || !callee.starts_with(char::is_uppercase)
+            });
+        if let (Some(index), Some(callee)) = (caller, callee) {
+            let callees = &mut graph[index].1;
+            if !callees.iter().any(|known| known == callee) {
+                callees.push(callee.to_string());
+            }
+        }
+    }
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        visit(child, source, separator, container, caller, graph);
+    }
+}
+
+// C keeps a function's name inside nested declarators, as in `int *parse(char *text)`
+fn function_name<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    if let Some(name) = node.child_by_field_name("name") {
+        return Some(&source[name.byte_range()]);
+    }
+    let mut declarator = node.child_by_field_name("declarator")?;
+    while let Some(inner) = declarator.child_by_field_name("declarator") {
+        declarator = inner;
+    }
+    (declarator.kind() == "identifier").then(|| &source[declarator.byte_range()])
+}
+
+// The name a call goes to: the function, the path to it, or the method called on a value
+fn callee_name<'a>(function: Node, source: &'a str) -> Option<&'a str> {
+    let field = match function.kind() {
+        "identifier" | "scoped_identifier" => return Some(&source[function.byte_range()]),
+        "generic_function" => {
+            return callee_name(function.child_by_field_name("function")?, source);
+        }
+        "field_expression" | "selector_expression" => "field",
+        "attribute" => "attribute",
+        "member_expression" => "property",
+        _ => return None,
+    };
+    let name = function.child_by_field_name(field)?;
+    Some(&source[name.byte_range()])
+}
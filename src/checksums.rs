@@ -0,0 +1,41 @@
+// Hash manifests written with --hash-manifest: a sidecar in the format of `sha256sum`, with one
+// line per file whose content the output holds, so extracted files can be checked with
+// `sha256sum -c` on machines without llm_globber
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+const MANIFEST_EXTENSION: &str = "sha256";
+
+// `output.txt` is described by `output.txt.sha256`
+pub(crate) fn manifest_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(MANIFEST_EXTENSION);
+    PathBuf::from(name)
+}
+
+// `digest  path`, marking the line with a leading backslash and escaping the path as
+// sha256sum does when the path holds a backslash or a newline
+fn manifest_line(digest: &str, path: &str) -> String {
+    if path.contains(['\\', '\n']) {
+        let escaped = path.replace('\\', "\\\\").replace('\n', "\\n");
+        format!("\\{}  {}\n", digest, escaped)
+    } else {
+        format!("{}  {}\n", digest, path)
+    }
+}
+
+// Write the manifest for a finished output from (digest, header path) pairs and return its path
+pub(crate) fn write(path: &Path, entries: &[(String, String)]) -> Result<PathBuf, String> {
+    let manifest_path = manifest_path(path);
+    let text: String = entries
+        .iter()
+        .map(|(digest, entry_path)| manifest_line(digest, entry_path))
+        .collect();
+    fs::write(&manifest_path, text)
+        .map_err(|e| format!("Failed to write {}: {}", manifest_path.display(), e))?;
+    info!("Wrote hash manifest {}", manifest_path.display());
+    Ok(manifest_path)
+}
@@ -0,0 +1,102 @@
+use std::io::{self, Write};
+
+use serde_json::json;
+
+use crate::{estimate_tokens, outline};
+
+// Line range (1-based, inclusive) and text of one chunk
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Chunk {
+    pub(crate) start_line: usize,
+    pub(crate) end_line: usize,
+    pub(crate) text: String,
+}
+
+// Split text into chunks of whole lines holding at most `chunk_tokens` tokens each, estimated at
+// `bytes_per_token`.
+// A chunk ends at the last of the `boundaries` (0-based lines, such as where functions start)
+// that leaves it at least half full, so declarations are kept whole where they fit. Chunks that
+// have to end elsewhere share up to `overlap_tokens` worth of trailing lines with the next one;
+// a single line longer than the chunk size becomes a chunk of its own.
+pub(crate) fn chunk_text(
+    content: &str,
+    chunk_tokens: usize,
+    overlap_tokens: usize,
+    bytes_per_token: f64,
+    boundaries: &[usize],
+) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let tokens: Vec<usize> = lines
+        .iter()
+        .map(|line| estimate_tokens(line.len() + 1, bytes_per_token))
+        .collect();
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start;
+        let mut total = 0;
+        let mut last_cut = None;
+        while end < lines.len() && (end == start || total + tokens[end] <= chunk_tokens) {
+            total += tokens[end];
+            end += 1;
+            if total * 2 >= chunk_tokens && boundaries.binary_search(&end).is_ok() {
+                last_cut = Some(end);
+            }
+        }
+        if end < lines.len() {
+            end = last_cut.unwrap_or(end);
+        }
+        chunks.push(Chunk {
+            start_line: start + 1,
+            end_line: end,
+            text: lines[start..end].join("\n"),
+        });
+        if end == lines.len() {
+            break;
+        }
+        // Nothing is cut in two at a boundary, so there is nothing to overlap
+        if boundaries.binary_search(&end).is_ok() {
+            start = end;
+            continue;
+        }
+
+        // Step back over trailing lines for the overlap, always moving forward by at least one line
+        let mut next = end;
+        let mut overlap = 0;
+        while next > start + 1 && overlap + tokens[next - 1] <= overlap_tokens {
+            next -= 1;
+            overlap += tokens[next];
+        }
+        start = next;
+    }
+    chunks
+}
+
+// Write one JSONL record per chunk of a file
+pub(crate) fn write_chunk_records<W: Write>(
+    output: &mut W,
+    entry_path: &str,
+    content: &str,
+    chunk_tokens: usize,
+    overlap_tokens: usize,
+    bytes_per_token: f64,
+) -> io::Result<usize> {
+    let chunks = chunk_text(
+        content,
+        chunk_tokens,
+        overlap_tokens,
+        bytes_per_token,
+        &outline::boundaries(entry_path, content),
+    );
+    for chunk in &chunks {
+        let record = json!({
+            "path": entry_path,
+            "start_line": chunk.start_line,
+            "end_line": chunk.end_line,
+            "text": chunk.text,
+        });
+        writeln!(output, "{}", record)?;
+    }
+    Ok(chunks.len())
+}
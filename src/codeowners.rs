@@ -0,0 +1,106 @@
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+
+// Where GitHub and GitLab look for the file, in order of precedence
+const CODEOWNERS_LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+pub(crate) struct CodeOwners {
+    rules: Vec<(Regex, Vec<String>)>,
+}
+
+impl CodeOwners {
+    // Load the CODEOWNERS file of a repository, given its top-level directory
+    pub(crate) fn load(repo_root: &Path) -> Result<CodeOwners, String> {
+        let path = CODEOWNERS_LOCATIONS
+            .iter()
+            .map(|location| repo_root.join(location))
+            .find(|path| path.is_file())
+            .ok_or_else(|| format!("No CODEOWNERS file found in {}", repo_root.display()))?;
+        let source = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Ok(CodeOwners::parse(&source))
+    }
+
+    pub(crate) fn parse(source: &str) -> CodeOwners {
+        let rules = source
+            .lines()
+            .map(str::trim)
+            // GitLab section headers such as `[Backend]` or `^[Docs]` carry no pattern
+            .filter(|line| {
+                !line.is_empty()
+                    && !line.starts_with('#')
+                    && !line.starts_with('[')
+                    && !line.starts_with("^[")
+            })
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                let regex = pattern_regex(parts.next()?)?;
+                let owners = parts
+                    .take_while(|part| !part.starts_with('#'))
+                    .map(str::to_string)
+                    .collect();
+                Some((regex, owners))
+            })
+            .collect();
+        CodeOwners { rules }
+    }
+
+    // Owners of a path relative to the repository root; the last matching rule wins
+    pub(crate) fn owners(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(regex, _)| regex.is_match(path))
+            .map_or(&[], |(_, owners)| owners.as_slice())
+    }
+
+    // Whether any of `owners` (compared case-insensitively, `@` optional) owns the path
+    pub(crate) fn is_owned_by(&self, path: &str, owners: &[String]) -> bool {
+        let normalize = |owner: &str| owner.trim_start_matches('@').to_lowercase();
+        self.owners(path).iter().any(|owner| {
+            owners
+                .iter()
+                .any(|wanted| normalize(wanted) == normalize(owner))
+        })
+    }
+}
+
+// Translate a gitignore-style CODEOWNERS pattern into a regex over repository-relative paths.
+// Patterns containing a slash are anchored at the root; others match at any depth. A pattern
+// that names a directory also matches everything beneath it, but `docs/*` only matches the
+// files directly inside docs/, as on GitHub.
+fn pattern_regex(pattern: &str) -> Option<Regex> {
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let directory_only = pattern.ends_with('/');
+    let body = pattern.trim_start_matches('/').trim_end_matches('/');
+
+    let mut regex = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            other => regex.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+    let single_level = body.ends_with('*') && !body.ends_with("**");
+    regex.push_str(if directory_only {
+        "/.*$"
+    } else if single_level {
+        "$"
+    } else {
+        "(?:/.*)?$"
+    });
+    Regex::new(&regex).ok()
+}
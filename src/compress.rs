@@ -0,0 +1,114 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::encrypt;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+// Enough leading bytes to recognise age, zstd and gzip streams
+const MAGIC_PEEK_SIZE: usize = 32;
+
+// How --compress stores the output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Compression {
+    Zstd,
+    Gzip,
+}
+
+impl Compression {
+    pub(crate) fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            other => Err(format!(
+                "Invalid compression '{}': expected zstd or gzip",
+                other
+            )),
+        }
+    }
+
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Compression::Zstd => "zst",
+            Compression::Gzip => "gz",
+        }
+    }
+
+    // `output.txt` becomes `output.txt.zst`
+    pub(crate) fn compressed_path(self, path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(self.extension());
+        PathBuf::from(name)
+    }
+}
+
+// Replace a finished output file with its compressed form and return the new path
+pub(crate) fn compress_file(path: &Path, compression: Compression) -> io::Result<PathBuf> {
+    let compressed = compression.compressed_path(path);
+    let mut reader = BufReader::new(File::open(path)?);
+    let writer = BufWriter::new(File::create(&compressed)?);
+    match compression {
+        Compression::Zstd => {
+            let mut encoder = zstd::Encoder::new(writer, 0)?;
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?;
+        }
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(writer, flate2::Compression::default());
+            io::copy(&mut reader, &mut encoder)?;
+            encoder.finish()?;
+        }
+    }
+    fs::remove_file(path)?;
+    Ok(compressed)
+}
+
+// Open an archive for reading, decrypting it if it is an age file (using `identity_files`
+// or a passphrase) and then decompressing it if it starts with a zstd or gzip header.
+// The content decides rather than the extension, so renamed files still work.
+pub(crate) fn open_archive(path: &str, identity_files: &[String]) -> io::Result<Box<dyn BufRead>> {
+    let (head, stream) = peek(File::open(path)?, MAGIC_PEEK_SIZE)?;
+    let stream: Box<dyn Read> = if encrypt::is_encrypted(&head) {
+        encrypt::decrypt(stream, identity_files).map_err(io::Error::other)?
+    } else {
+        Box::new(stream)
+    };
+    let (head, stream) = peek(stream, MAGIC_PEEK_SIZE)?;
+    Ok(if head.starts_with(&ZSTD_MAGIC) {
+        Box::new(BufReader::new(zstd::Decoder::new(stream)?))
+    } else if head.starts_with(&GZIP_MAGIC) {
+        Box::new(BufReader::new(MultiGzDecoder::new(stream)))
+    } else {
+        Box::new(BufReader::new(stream))
+    })
+}
+
+// The lines of an archive split on `\n` alone, unlike BufRead::lines, so the `\r` of files
+// with CRLF line endings stays in their content
+pub(crate) fn raw_lines<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<String>> {
+    reader.split(b'\n').map(|line| {
+        String::from_utf8(line?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    })
+}
+
+// A stream with its first bytes, which were read to identify it, put back in front
+pub(crate) type Peeked<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+// The first `len` bytes of a stream, and the stream itself to read from the start again
+pub(crate) fn peek<R: Read>(mut reader: R, len: usize) -> io::Result<(Vec<u8>, Peeked<R>)> {
+    let mut head = vec![0u8; len];
+    let mut read = 0;
+    while read < head.len() {
+        match reader.read(&mut head[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    head.truncate(read);
+    Ok((head.clone(), io::Cursor::new(head).chain(reader)))
+}
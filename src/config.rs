@@ -0,0 +1,11 @@
+// Where per-user settings live: the signing key and trust store, and models.toml
+use std::path::PathBuf;
+
+// $XDG_CONFIG_HOME/llm-globber, else ~/.config/llm-globber
+pub(crate) fn dir() -> Option<PathBuf> {
+    let env = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+    let base = env("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("llm-globber"))
+}
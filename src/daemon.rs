@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, DirBuilder};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use log::{info, warn};
+use serde_json::{json, Value};
+
+use crate::relevance::TermCache;
+
+// Overrides the default socket for both the daemon and --ask-daemon
+pub(crate) const SOCKET_ENV: &str = "LLM_GLOBBER_SOCKET";
+// Sent with --ask-daemon to shut the daemon down
+const STOP_REQUEST: &str = "--stop";
+
+// What a daemon keeps between requests
+#[derive(Debug, Default)]
+pub(crate) struct Cache {
+    walks: HashMap<PathBuf, CachedDir>,
+    clones: HashMap<(String, Option<usize>), String>,
+    pub(crate) terms: TermCache,
+}
+
+pub(crate) type SharedCache = Arc<Mutex<Cache>>;
+
+#[derive(Debug)]
+struct CachedDir {
+    modified: SystemTime,
+    entries: Vec<DirEntry>,
+}
+
+// A directory entry, with symlinks resolved to what they point at
+#[derive(Debug, Clone)]
+pub(crate) struct DirEntry {
+    pub(crate) name: OsString,
+    pub(crate) is_dir: bool,
+    pub(crate) is_file: bool,
+}
+
+impl Cache {
+    // The entries of a directory, read again only once its modification time has changed,
+    // which happens whenever an entry is added, removed or renamed
+    pub(crate) fn list_dir(&mut self, dir: &Path) -> io::Result<Vec<DirEntry>> {
+        let modified = fs::metadata(dir)?.modified()?;
+        if let Some(cached) = self.walks.get(dir).filter(|c| c.modified == modified) {
+            return Ok(cached.entries.clone());
+        }
+        let entries = read_dir_entries(dir)?;
+        self.walks.insert(
+            dir.to_path_buf(),
+            CachedDir {
+                modified,
+                entries: entries.clone(),
+            },
+        );
+        Ok(entries)
+    }
+
+    pub(crate) fn clone_path(&self, url: &str, depth: Option<usize>) -> Option<String> {
+        self.clones.get(&(url.to_string(), depth)).cloned()
+    }
+
+    pub(crate) fn add_clone(&mut self, url: &str, depth: Option<usize>, path: String) {
+        self.clones.insert((url.to_string(), depth), path);
+    }
+}
+
+pub(crate) fn read_dir_entries(dir: &Path) -> io::Result<Vec<DirEntry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        entries.push(DirEntry {
+            name: entry.file_name(),
+            is_dir: path.is_dir(),
+            is_file: path.is_file(),
+        });
+    }
+    Ok(entries)
+}
+
+// $LLM_GLOBBER_SOCKET, else a socket in the user's runtime directory, else one in a directory
+// of the temp directory that only the user can enter
+pub(crate) fn socket_path() -> PathBuf {
+    let env = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+    if let Some(path) = env(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+    if let Some(dir) = env("XDG_RUNTIME_DIR") {
+        return Path::new(&dir).join("llm_globber.sock");
+    }
+    fallback_dir().join("daemon.sock")
+}
+
+// Named after the uid rather than $USER, which anyone can set to another user's name
+fn fallback_dir() -> PathBuf {
+    // SAFETY: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    std::env::temp_dir().join(format!("llm_globber-{}", uid))
+}
+
+// Create the fallback directory, or check that the one there is ours and closed to everyone
+// else, since another user could have made it first to put their own socket in it
+fn secure_fallback_dir(dir: &Path) -> Result<(), String> {
+    match DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(format!("Failed to create {}: {}", dir.display(), e)),
+    }
+    let metadata = fs::symlink_metadata(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    // SAFETY: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(format!(
+            "{} is not a directory only you can use; remove it or set {}",
+            dir.display(),
+            SOCKET_ENV
+        ));
+    }
+    Ok(())
+}
+
+// The uid of the process at the other end of a connection
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: the buffer and length passed are those of a ucred, which SO_PEERCRED fills in
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut credentials as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(credentials.uid)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let (mut uid, mut gid) = (0, 0);
+    // SAFETY: getpeereid only writes the two ids
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+// Whether the other end of a connection runs as this user. The socket's permissions already
+// keep others out, but not from a socket path in a directory they can write to.
+fn same_user(stream: &UnixStream) -> io::Result<bool> {
+    // SAFETY: getuid cannot fail
+    Ok(peer_uid(stream)? == unsafe { libc::getuid() })
+}
+
+// Serve requests one at a time until a client sends --stop. Each request is a JSON line
+// `{"cwd": ..., "args": [...]}` answered with `{"ok": true, "output": ...}` or
+// `{"ok": false, "error": ...}`.
+pub(crate) fn serve<F>(socket: &Path, mut handle: F) -> Result<(), String>
+where
+    F: FnMut(Vec<String>, &Path, &SharedCache) -> Result<Option<String>, String>,
+{
+    let fallback = fallback_dir();
+    if socket.parent() == Some(fallback.as_path()) {
+        secure_fallback_dir(&fallback)?;
+    }
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(format!(
+                "A daemon is already listening on {}",
+                socket.display()
+            ));
+        }
+        // Left behind by a daemon that did not shut down cleanly
+        fs::remove_file(socket)
+            .map_err(|e| format!("Failed to remove stale socket {}: {}", socket.display(), e))?;
+    }
+    let listener = UnixListener::bind(socket)
+        .map_err(|e| format!("Failed to listen on {}: {}", socket.display(), e))?;
+    // Requests read files with the daemon's permissions, so only its owner may make them
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to secure {}: {}", socket.display(), e))?;
+    info!("Daemon listening on {}", socket.display());
+
+    let cache = SharedCache::default();
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                warn!("Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        match serve_connection(stream, &cache, &mut handle) {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(e) => warn!("Failed to answer a request: {}", e),
+        }
+    }
+
+    info!("Daemon on {} stopping", socket.display());
+    let _ = fs::remove_file(socket);
+    let cache = cache.lock().expect("Daemon cache mutex poisoned");
+    for path in cache.clones.values() {
+        if let Err(e) = fs::remove_dir_all(path) {
+            warn!("Failed to remove clone {}: {}", path, e);
+        }
+    }
+    Ok(())
+}
+
+// Answer one request and return whether it asked the daemon to stop
+fn serve_connection<F>(stream: UnixStream, cache: &SharedCache, handle: &mut F) -> io::Result<bool>
+where
+    F: FnMut(Vec<String>, &Path, &SharedCache) -> Result<Option<String>, String>,
+{
+    if !same_user(&stream)? {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "refused a connection from another user",
+        ));
+    }
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let request: Value = serde_json::from_str(&line).map_err(io::Error::other)?;
+    let args: Vec<String> = request["args"]
+        .as_array()
+        .map(|args| {
+            args.iter()
+                .filter_map(|arg| arg.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    let cwd = PathBuf::from(request["cwd"].as_str().unwrap_or("."));
+
+    let stop = args == [STOP_REQUEST];
+    let response = if stop {
+        json!({ "ok": true, "output": null })
+    } else {
+        info!("Request from {}: {}", cwd.display(), args.join(" "));
+        let args = std::iter::once("llm_globber".to_string())
+            .chain(args)
+            .collect();
+        match handle(args, &cwd, cache) {
+            Ok(output) => json!({ "ok": true, "output": output }),
+            Err(error) => json!({ "ok": false, "error": error }),
+        }
+    };
+    writeln!(&stream, "{}", response)?;
+    Ok(stop)
+}
+
+// Send the arguments to a running daemon, to be run in the current directory, and print the
+// path of the output it wrote
+pub(crate) fn ask(args: &[String]) -> Result<(), String> {
+    let socket = socket_path();
+    let stream = UnixStream::connect(&socket).map_err(|e| {
+        format!(
+            "No daemon is listening on {} ({}); start one with `llm_globber daemon`",
+            socket.display(),
+            e
+        )
+    })?;
+    // Requests carry the working directory and arguments, and outputs are trusted, so they only
+    // go to a daemon of this user's
+    if !same_user(&stream).map_err(|e| format!("Failed to check {}: {}", socket.display(), e))? {
+        return Err(format!(
+            "The daemon on {} belongs to another user; refusing to send it the request",
+            socket.display()
+        ));
+    }
+    let cwd =
+        std::env::current_dir().map_err(|e| format!("Failed to read working directory: {}", e))?;
+    let request = json!({ "cwd": cwd, "args": args });
+    writeln!(&stream, "{}", request).map_err(|e| format!("Failed to send request: {}", e))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read the daemon's response: {}", e))?;
+    let response: Value = serde_json::from_str(&line)
+        .map_err(|e| format!("Invalid response from the daemon: {}", e))?;
+    if response["ok"].as_bool() != Some(true) {
+        return Err(response["error"]
+            .as_str()
+            .unwrap_or("The daemon failed without an error message")
+            .to_string());
+    }
+    if let Some(output) = response["output"].as_str() {
+        println!("{}", output);
+    }
+    Ok(())
+}
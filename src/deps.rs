@@ -0,0 +1,261 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{debug, warn};
+
+const JS_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+
+// Walk imports from the entry files and return their transitive closure, dependencies first.
+// Paths are canonical; when `allowed` is given, files outside it are neither included nor followed.
+pub(crate) fn dependency_closure(
+    entries: &[PathBuf],
+    allowed: Option<&HashSet<PathBuf>>,
+) -> Vec<PathBuf> {
+    let mut visited = HashSet::new();
+    let mut ordered = Vec::new();
+    for entry in entries {
+        visit(entry, allowed, &mut visited, &mut ordered);
+    }
+    ordered
+}
+
+// Depth-first post-order traversal so every file is emitted after the files it imports
+fn visit(
+    path: &Path,
+    allowed: Option<&HashSet<PathBuf>>,
+    visited: &mut HashSet<PathBuf>,
+    ordered: &mut Vec<PathBuf>,
+) {
+    if !visited.insert(path.to_path_buf()) {
+        return;
+    }
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("Could not read {} to follow imports: {}", path.display(), e);
+            return;
+        }
+    };
+    for dependency in resolve_imports(path, &content) {
+        let dependency = match dependency.canonicalize() {
+            Ok(dependency) => dependency,
+            Err(_) => continue,
+        };
+        if allowed.is_some_and(|allowed| !allowed.contains(&dependency)) {
+            debug!(
+                "Not following {} -> {}: outside the selected inputs",
+                path.display(),
+                dependency.display()
+            );
+            continue;
+        }
+        debug!("{} imports {}", path.display(), dependency.display());
+        visit(&dependency, allowed, visited, ordered);
+    }
+    ordered.push(path.to_path_buf());
+}
+
+// Existing files referenced by the import statements in a source file
+fn resolve_imports(path: &Path, content: &str) -> Vec<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let mut found = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        match extension {
+            "rs" => found.extend(resolve_rust_import(path, line)),
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" => {
+                if let Some(include) = line
+                    .strip_prefix("#include")
+                    .and_then(|rest| quoted(rest.trim_start()))
+                {
+                    found.extend(existing(&[dir.join(include)]));
+                }
+            }
+            "py" => found.extend(resolve_python_import(dir, line)),
+            ext if JS_EXTENSIONS.contains(&ext) => {
+                if let Some(specifier) = js_specifier(line) {
+                    if specifier.starts_with("./") || specifier.starts_with("../") {
+                        found.extend(resolve_js_specifier(dir, specifier));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+fn existing(candidates: &[PathBuf]) -> Option<PathBuf> {
+    candidates
+        .iter()
+        .find(|candidate| candidate.is_file())
+        .cloned()
+}
+
+// Text between the first pair of matching double or single quotes
+fn quoted(text: &str) -> Option<&str> {
+    let quote = text.chars().find(|c| *c == '"' || *c == '\'')?;
+    let start = text.find(quote)? + 1;
+    let end = text[start..].find(quote)? + start;
+    Some(&text[start..end])
+}
+
+fn resolve_rust_import(path: &Path, line: &str) -> Vec<PathBuf> {
+    let line = line
+        .strip_prefix("pub(crate) ")
+        .or_else(|| line.strip_prefix("pub "))
+        .unwrap_or(line);
+
+    // `mod foo;` refers to foo.rs or foo/mod.rs next to (or under) the declaring file
+    if let Some(name) = line
+        .strip_prefix("mod ")
+        .and_then(|rest| rest.strip_suffix(';'))
+    {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let module_dir = if matches!(stem, "main" | "lib" | "mod") {
+            dir.to_path_buf()
+        } else {
+            dir.join(stem)
+        };
+        let name = name.trim();
+        return existing(&[
+            module_dir.join(format!("{}.rs", name)),
+            module_dir.join(name).join("mod.rs"),
+        ])
+        .into_iter()
+        .collect();
+    }
+
+    // `use crate::a::b::...` refers to the deepest module file that exists under the crate root
+    if let Some(rest) = line.strip_prefix("use crate::") {
+        let root = match rust_crate_root(path) {
+            Some(root) => root,
+            None => return Vec::new(),
+        };
+        let rest = rest.trim_end_matches(';');
+        let (prefix, group) = match rest.split_once('{') {
+            Some((prefix, group)) => (prefix, Some(group.trim_end_matches('}'))),
+            None => (rest, None),
+        };
+        let prefix_segments: Vec<&str> = prefix
+            .split("::")
+            .map(str::trim)
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let mut paths: Vec<Vec<&str>> = Vec::new();
+        match group {
+            Some(group) => {
+                for item in group.split(',') {
+                    let mut segments = prefix_segments.clone();
+                    segments.extend(item.split("::").map(str::trim).filter(|s| !s.is_empty()));
+                    paths.push(segments);
+                }
+            }
+            None => paths.push(prefix_segments),
+        }
+        let mut found = Vec::new();
+        for segments in paths {
+            for depth in (1..=segments.len()).rev() {
+                let module = root.join(segments[..depth].join("/"));
+                if let Some(file) = existing(&[module.with_extension("rs"), module.join("mod.rs")])
+                {
+                    found.push(file);
+                    break;
+                }
+            }
+        }
+        return found;
+    }
+    Vec::new()
+}
+
+// Closest ancestor directory holding main.rs or lib.rs
+fn rust_crate_root(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.join("main.rs").is_file() || dir.join("lib.rs").is_file())
+        .map(Path::to_path_buf)
+}
+
+fn resolve_python_import(dir: &Path, line: &str) -> Vec<PathBuf> {
+    let mut modules: Vec<String> = Vec::new();
+    if let Some(rest) = line.strip_prefix("from ") {
+        if let Some((module, names)) = rest.split_once(" import ") {
+            let module = module.trim();
+            // `from pkg import mod` may name submodules as well as attributes
+            for name in names.trim_matches(|c| c == '(' || c == ')').split(',') {
+                let name = name.split(" as ").next().unwrap_or("").trim();
+                if !name.is_empty() && name != "*" {
+                    let separator = if module.ends_with('.') { "" } else { "." };
+                    modules.push(format!("{}{}{}", module, separator, name));
+                }
+            }
+            modules.push(module.to_string());
+        }
+    } else if let Some(rest) = line.strip_prefix("import ") {
+        for module in rest.split(',') {
+            let module = module.split(" as ").next().unwrap_or("").trim();
+            if !module.is_empty() {
+                modules.push(module.to_string());
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    for module in modules {
+        let level = module.chars().take_while(|c| *c == '.').count();
+        let name = &module[level..];
+        let relative: PathBuf = name.split('.').filter(|s| !s.is_empty()).collect();
+        if relative.as_os_str().is_empty() {
+            continue;
+        }
+        // Relative imports resolve from the package directory; absolute ones from any ancestor
+        let bases: Vec<&Path> = if level > 0 {
+            dir.ancestors().nth(level - 1).into_iter().collect()
+        } else {
+            dir.ancestors().collect()
+        };
+        for base in bases {
+            let module_path = base.join(&relative);
+            if let Some(file) = existing(&[
+                module_path.with_extension("py"),
+                module_path.join("__init__.py"),
+            ]) {
+                found.push(file);
+                break;
+            }
+        }
+    }
+    found
+}
+
+// Module specifier of an ES import/export or CommonJS require on this line
+fn js_specifier(line: &str) -> Option<&str> {
+    if let Some(index) = line.find("require(") {
+        return quoted(&line[index + "require(".len()..]);
+    }
+    if line.starts_with("import ") || line.starts_with("export ") {
+        if let Some(index) = line.rfind(" from ") {
+            return quoted(&line[index..]);
+        }
+        if let Some(rest) = line.strip_prefix("import ") {
+            return quoted(rest);
+        }
+    }
+    None
+}
+
+fn resolve_js_specifier(dir: &Path, specifier: &str) -> Option<PathBuf> {
+    let base = dir.join(specifier);
+    let mut candidates = vec![base.clone()];
+    for ext in JS_EXTENSIONS {
+        candidates.push(PathBuf::from(format!("{}.{}", base.display(), ext)));
+    }
+    for ext in JS_EXTENSIONS {
+        candidates.push(base.join(format!("index.{}", ext)));
+    }
+    existing(&candidates)
+}
@@ -0,0 +1,47 @@
+pub(crate) use llm_globber_core::{classify, ContentKind};
+
+// application/* types that are plain text and should stay labelled as such
+const TEXTUAL_APPLICATION_TYPES: &[&str] = &[
+    "application/json",
+    "application/xml",
+    "application/javascript",
+    "application/toml",
+    "application/x-sh",
+    "application/x-yaml",
+    "application/sql",
+    "application/x-httpd-php",
+    "application/graphql",
+];
+
+// MIME type of a file from its magic bytes, falling back to its extension and text/binary class
+pub(crate) fn sniff_mime(file_path: &str, data: &[u8], threshold: usize) -> String {
+    if let Some(kind) = infer::get(data) {
+        return kind.mime_type().to_string();
+    }
+    let guess = mime_guess::from_path(file_path)
+        .first()
+        .map(|mime| mime.essence_str().to_string());
+    let (kind, _) = classify(file_path, data, threshold, false);
+    match (kind, guess) {
+        (ContentKind::Binary, Some(guess)) if !guess.starts_with("text/") => guess,
+        (ContentKind::Binary, _) => "application/octet-stream".to_string(),
+        (_, Some(guess))
+            if guess.starts_with("text/")
+                || TEXTUAL_APPLICATION_TYPES.contains(&guess.as_str()) =>
+        {
+            guess
+        }
+        _ => "text/plain".to_string(),
+    }
+}
+
+// `*`, `*/*`, `type/*` or an exact `type/subtype`, compared case-insensitively
+pub(crate) fn mime_matches(pattern: &str, mime: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    let mime = mime.to_lowercase();
+    match pattern.strip_suffix("/*") {
+        _ if pattern == "*" || pattern == "*/*" => true,
+        Some(top_level) => mime.split('/').next() == Some(top_level),
+        None => pattern == mime,
+    }
+}
@@ -0,0 +1,73 @@
+// Checks that there is room for a clone or an output before starting on it, so runs fail with a
+// clear message instead of an IO error halfway through writing
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+// Space below which a clone of unknown size is warned about
+pub(crate) const MIN_CLONE_BYTES: u64 = 256 * 1024 * 1024;
+
+// Bytes an unprivileged user can still write to the filesystem holding `path`
+pub(crate) fn available(path: &Path) -> io::Result<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stats = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path is a valid C string and stats is only read once statvfs has filled it
+    if unsafe { libc::statvfs(c_path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stats = unsafe { stats.assume_init() };
+    // The field types differ between platforms
+    #[allow(clippy::useless_conversion)]
+    Ok(u64::from(stats.f_bavail).saturating_mul(u64::from(stats.f_frsize)))
+}
+
+// Fail unless `needed` bytes fit in `dir`. Space that cannot be measured, on filesystems that do
+// not report it, is not held against the run.
+pub(crate) fn check(dir: &Path, needed: u64, what: &str) -> Result<(), String> {
+    match shortfall(dir, needed) {
+        Some(free) => Err(format!(
+            "Not enough disk space in {} for {}: about {} needed, {} available (use --no-space-check to try anyway)",
+            dir.display(),
+            what,
+            format_size(needed),
+            format_size(free)
+        )),
+        None => Ok(()),
+    }
+}
+
+// Warn when less than `expected` bytes are free in `dir`, for work such as a clone whose size is
+// unknown until it is done, which may well fit in less
+pub(crate) fn warn_if_low(dir: &Path, expected: u64, what: &str) {
+    if let Some(free) = shortfall(dir, expected) {
+        log::warn!(
+            "Only {} free in {} for {}, which may not be enough",
+            format_size(free),
+            dir.display(),
+            what
+        );
+    }
+}
+
+// The bytes free in `dir` when they are fewer than `needed`
+fn shortfall(dir: &Path, needed: u64) -> Option<u64> {
+    match available(dir) {
+        Ok(free) => (free < needed).then_some(free),
+        Err(e) => {
+            log::debug!("Could not check free space in {}: {}", dir.display(), e);
+            None
+        }
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const MB: u64 = 1024 * 1024;
+    if bytes >= MB {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
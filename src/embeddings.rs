@@ -0,0 +1,78 @@
+// Embeddings for `--relevant-to --embedding-model`, from a server speaking the OpenAI
+// embeddings API: OpenAI itself, or a local one such as Ollama, llama.cpp or text-embeddings-
+// inference. Requests need the `remote` feature; without it the option is rejected.
+
+// Where embeddings are requested unless --embedding-url says otherwise
+pub(crate) const DEFAULT_URL: &str = "https://api.openai.com/v1/embeddings";
+// Sent as a bearer token when set; OPENAI_API_KEY is used if this is not
+#[cfg(feature = "remote")]
+const API_KEY_ENV: &str = "LLM_GLOBBER_EMBEDDING_API_KEY";
+
+#[derive(Debug, Clone)]
+pub(crate) struct Embedder {
+    pub(crate) url: String,
+    pub(crate) model: String,
+}
+
+#[cfg(not(feature = "remote"))]
+impl Embedder {
+    pub(crate) fn embed(&self, _inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        Err(format!(
+            "Cannot request embeddings from {}: this build has no network access; rebuild with --features remote",
+            self.url
+        ))
+    }
+}
+
+#[cfg(feature = "remote")]
+impl Embedder {
+    // One vector per input, in order, requested in batches so no request grows too large
+    pub(crate) fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        const BATCH_SIZE: usize = 64;
+        let mut vectors = Vec::with_capacity(inputs.len());
+        for batch in inputs.chunks(BATCH_SIZE) {
+            vectors.extend(self.embed_batch(batch)?);
+        }
+        Ok(vectors)
+    }
+
+    fn embed_batch(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let fail =
+            |e: &dyn std::fmt::Display| format!("Embedding request to {} failed: {}", self.url, e);
+        log::debug!("Requesting {} embeddings from {}", inputs.len(), self.url);
+        let body = serde_json::json!({ "model": self.model, "input": inputs });
+        let mut request = ureq::post(&self.url).set("Content-Type", "application/json");
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        if let Some(key) = var(API_KEY_ENV).or_else(|| var("OPENAI_API_KEY")) {
+            request = request.set("Authorization", &format!("Bearer {}", key));
+        }
+        let response = match request.send_string(&body.to_string()) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(code, response)) => {
+                let detail = response.into_string().unwrap_or_default();
+                return Err(fail(&format!("HTTP {}: {}", code, detail.trim())));
+            }
+            Err(e) => return Err(fail(&e)),
+        };
+        let reply: serde_json::Value =
+            serde_json::from_reader(response.into_reader()).map_err(|e| fail(&e))?;
+        // Each item says which input it belongs to, which servers need not keep in order
+        let mut vectors = vec![None; inputs.len()];
+        for item in reply["data"].as_array().into_iter().flatten() {
+            let index = item["index"].as_u64().map(|index| index as usize);
+            let vector: Option<Vec<f32>> = item["embedding"].as_array().and_then(|values| {
+                values
+                    .iter()
+                    .map(|v| v.as_f64().map(|v| v as f32))
+                    .collect()
+            });
+            if let (Some(slot), Some(vector)) = (index.and_then(|i| vectors.get_mut(i)), vector) {
+                *slot = Some(vector);
+            }
+        }
+        vectors
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| fail(&"the reply is missing embeddings for some inputs"))
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use age::secrecy::SecretString;
+use age::{scrypt, x25519, Decryptor, Encryptor, IdentityFile};
+
+// Every binary age file starts with this version line
+const AGE_MAGIC: &[u8] = b"age-encryption.org/";
+// Checked for a passphrase before prompting, so scripts and CI can encrypt and decrypt
+pub(crate) const PASSPHRASE_ENV: &str = "LLM_GLOBBER_PASSPHRASE";
+// Set in a daemon, where a passphrase would come from the daemon's environment or terminal
+// rather than the client's
+static REFUSE_PASSPHRASES: AtomicBool = AtomicBool::new(false);
+
+// How --encrypt and --encrypt-pass protect the output
+pub(crate) enum Encryption {
+    Recipients(Vec<x25519::Recipient>),
+    Passphrase(SecretString),
+}
+
+impl fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Encryption::Recipients(recipients) => {
+                let keys: Vec<String> = recipients.iter().map(|r| r.to_string()).collect();
+                write!(f, "Recipients({})", keys.join(", "))
+            }
+            Encryption::Passphrase(_) => write!(f, "Passphrase"),
+        }
+    }
+}
+
+impl Encryption {
+    // Recipients as given to --encrypt, `age:age1...` or just `age1...`
+    pub(crate) fn recipients(values: &[&str]) -> Result<Self, String> {
+        let recipients = values
+            .iter()
+            .map(|value| {
+                let key = value.strip_prefix("age:").unwrap_or(value);
+                x25519::Recipient::from_str(key)
+                    .map_err(|e| format!("Invalid age recipient '{}': {}", value, e))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Encryption::Recipients(recipients))
+    }
+
+    pub(crate) fn passphrase() -> Result<Self, String> {
+        read_passphrase("Passphrase to encrypt the output: ").map(Encryption::Passphrase)
+    }
+}
+
+// `output.txt` becomes `output.txt.age`
+pub(crate) fn encrypted_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".age");
+    PathBuf::from(name)
+}
+
+// Replace a finished output file with its encrypted form and return the new path
+pub(crate) fn encrypt_file(path: &Path, encryption: &Encryption) -> Result<PathBuf, String> {
+    let encryptor = match encryption {
+        Encryption::Recipients(recipients) => {
+            Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+                .map_err(|e| e.to_string())?
+        }
+        Encryption::Passphrase(passphrase) => Encryptor::with_user_passphrase(passphrase.clone()),
+    };
+    let encrypted = encrypted_path(path);
+    let copy = || -> io::Result<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut writer = encryptor.wrap_output(BufWriter::new(File::create(&encrypted)?))?;
+        io::copy(&mut reader, &mut writer)?;
+        writer.finish()?;
+        fs::remove_file(path)
+    };
+    copy().map_err(|e| e.to_string())?;
+    Ok(encrypted)
+}
+
+pub(crate) fn is_encrypted(head: &[u8]) -> bool {
+    head.starts_with(AGE_MAGIC)
+}
+
+// Decrypt an age stream with the identities in `identity_files`, or with a passphrase from
+// the environment or the terminal if the archive was encrypted with --encrypt-pass
+pub(crate) fn decrypt<R: Read + 'static>(
+    input: R,
+    identity_files: &[String],
+) -> Result<Box<dyn Read>, String> {
+    let decryptor = Decryptor::new(input).map_err(|e| format!("Invalid age file: {}", e))?;
+    let reader = if decryptor.is_scrypt() {
+        let identity = scrypt::Identity::new(read_passphrase("Passphrase for the archive: ")?);
+        decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity))
+    } else {
+        if identity_files.is_empty() {
+            return Err("The archive is encrypted; pass --identity FILE to decrypt it".to_string());
+        }
+        let mut identities = Vec::new();
+        for file in identity_files {
+            let identity_file = IdentityFile::from_file(file.clone())
+                .map_err(|e| format!("Failed to read identity file {}: {}", file, e))?;
+            identities.extend(
+                identity_file
+                    .into_identities()
+                    .map_err(|e| format!("Invalid identity file {}: {}", file, e))?,
+            );
+        }
+        decryptor.decrypt(identities.iter().map(|identity| identity.as_ref()))
+    };
+    let reader = reader.map_err(|e| format!("Failed to decrypt the archive: {}", e))?;
+    Ok(Box::new(reader))
+}
+
+// Fail every later passphrase read instead of taking this process's environment or terminal
+pub(crate) fn refuse_passphrases() {
+    REFUSE_PASSPHRASES.store(true, Ordering::Relaxed);
+}
+
+fn read_passphrase(prompt: &str) -> Result<SecretString, String> {
+    if REFUSE_PASSPHRASES.load(Ordering::Relaxed) {
+        return Err(
+            "The daemon cannot take a passphrase for you; run the command directly, or use --encrypt or --identity".to_string(),
+        );
+    }
+    if let Some(passphrase) = std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()) {
+        return Ok(SecretString::from(passphrase));
+    }
+    if !io::stdin().is_terminal() {
+        return Err(format!(
+            "A passphrase is needed: set {} or run from a terminal",
+            PASSPHRASE_ENV
+        ));
+    }
+    rpassword::prompt_password(prompt)
+        .map(SecretString::from)
+        .map_err(|e| format!("Failed to read passphrase: {}", e))
+}
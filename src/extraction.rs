@@ -0,0 +1,122 @@
+// Progress and counts for one unglob run: a spinner with the file being written on a terminal, a
+// line every few seconds elsewhere, and the summary printed when the run ends
+use std::collections::HashSet;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use colored::*;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{info, warn};
+
+use crate::{set_progress_bar, PROGRESS_LINE_INTERVAL};
+
+#[derive(Debug)]
+pub(crate) struct Extraction {
+    pub(crate) files: usize,
+    pub(crate) bytes: u64,
+    pub(crate) directories: usize,
+    pub(crate) conflicts: usize,
+    pub(crate) verified: usize,
+    pub(crate) failed: usize,
+    pub(crate) escaped_paths: bool, // Entry paths are escaped, as in format 5 archives
+    written: HashSet<PathBuf>,      // Files written so far, so a later entry for one is a conflict
+    show_progress: bool,
+    bar: Option<ProgressBar>,
+    start: Instant,
+    last_progress: Instant,
+}
+
+impl Extraction {
+    pub(crate) fn new(show_progress: bool) -> Self {
+        let bar = (show_progress && io::stderr().is_terminal()).then(|| {
+            let template = if colored::control::SHOULD_COLORIZE.should_colorize() {
+                "{spinner:.green} {elapsed:>4} {wide_msg}"
+            } else {
+                "{spinner} {elapsed:>4} {wide_msg}"
+            };
+            let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr()).with_style(
+                ProgressStyle::with_template(template).expect("Progress bar template is valid"),
+            );
+            set_progress_bar(Some(bar.clone()));
+            bar
+        });
+        Extraction {
+            files: 0,
+            bytes: 0,
+            directories: 0,
+            conflicts: 0,
+            verified: 0,
+            failed: 0,
+            escaped_paths: false,
+            written: HashSet::new(),
+            show_progress,
+            bar,
+            start: Instant::now(),
+            last_progress: Instant::now(),
+        }
+    }
+
+    // Claim an output file for an entry, or count a conflict if an earlier entry wrote it, as
+    // when an archive repeats a path or --map rules send two paths to the same place
+    pub(crate) fn claim(&mut self, entry_path: &str, output_path: &Path) -> bool {
+        if self.written.insert(output_path.to_path_buf()) {
+            return true;
+        }
+        warn!(
+            "Skipping {}: {} was already written by an earlier entry",
+            entry_path,
+            output_path.display()
+        );
+        self.conflicts += 1;
+        false
+    }
+
+    // Whether an entry was written to `output_path`
+    pub(crate) fn wrote(&self, output_path: &Path) -> bool {
+        self.written.contains(output_path)
+    }
+
+    // Count a written file and the directories made for it, and show it as the current file
+    pub(crate) fn record(&mut self, entry_path: &str, bytes: u64, directories: usize) {
+        self.files += 1;
+        self.bytes += bytes;
+        self.directories += directories;
+        if let Some(bar) = &self.bar {
+            bar.set_message(format!("{} files written  {}", self.files, entry_path));
+            bar.tick();
+        } else if self.show_progress && self.last_progress.elapsed() >= PROGRESS_LINE_INTERVAL {
+            self.last_progress = Instant::now();
+            eprintln!(
+                "{} Extracted {} files ({:.1} files/sec)",
+                "🔍".yellow(),
+                self.files.to_string().green(),
+                self.files as f64 / self.start.elapsed().as_secs_f64()
+            );
+        }
+    }
+
+    // Clear the spinner and summarize the run, also when it stopped on an error
+    pub(crate) fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+            set_progress_bar(None);
+        }
+        let failed = if self.failed > 0 {
+            self.failed.to_string().red()
+        } else {
+            self.failed.to_string().green()
+        };
+        info!(
+            "{} Extracted {} files ({} bytes) in {:.2} seconds, created {} directories, skipped {} conflicts; signatures: {} verified, {} failed",
+            "✅".green(),
+            self.files.to_string().green(),
+            self.bytes,
+            self.start.elapsed().as_secs_f64(),
+            self.directories,
+            self.conflicts,
+            self.verified,
+            failed
+        );
+    }
+}
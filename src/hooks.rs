@@ -0,0 +1,76 @@
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::fd::AsFd;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use log::{info, warn};
+
+use crate::temp;
+
+// Set for --post-hook: where the output ended up, a local path or the URL it was uploaded to
+pub(crate) const OUTPUT_ENV: &str = "LLM_GLOBBER_OUTPUT";
+// Set for --post-hook: a file holding the header path of every entry written, one per line
+pub(crate) const FILES_ENV: &str = "LLM_GLOBBER_FILES";
+// Set for --pre-hook: the input paths given on the command line, one per line
+pub(crate) const INPUTS_ENV: &str = "LLM_GLOBBER_INPUTS";
+
+// Run a hook command with `sh -c`, failing if it exits unsuccessfully. Its stdout goes to
+// stderr so that stdout stays reserved for --print-path.
+pub(crate) fn run(name: &str, command: &str, env: &[(&str, String)]) -> Result<(), String> {
+    info!("Running {}: {}", name, command);
+    let stdout = io::stderr()
+        .as_fd()
+        .try_clone_to_owned()
+        .map_err(|e| format!("Failed to run {}: {}", name, e))?;
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(stdout))
+        .status()
+        .map_err(|e| format!("Failed to run {} `{}`: {}", name, command, e))?;
+    if !status.success() {
+        return Err(format!("{} `{}` failed with {}", name, command, status));
+    }
+    Ok(())
+}
+
+// The entries written, listed in a temporary file for --post-hook rather than in the variable
+// itself, which fails to fit the kernel's limit on the environment at a few thousand paths.
+// The file is removed when this is dropped.
+pub(crate) struct FileList {
+    dir: PathBuf,
+}
+
+impl FileList {
+    pub(crate) fn write(entries: &[String]) -> Result<FileList, String> {
+        let dir = temp::path("hook");
+        DirBuilder::new()
+            .mode(0o700)
+            .create(&dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let list = FileList { dir };
+        let mut text = entries.join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        fs::write(list.path(), text)
+            .map_err(|e| format!("Failed to write {}: {}", list.path().display(), e))?;
+        Ok(list)
+    }
+
+    pub(crate) fn path(&self) -> PathBuf {
+        self.dir.join("files")
+    }
+}
+
+impl Drop for FileList {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            warn!("Failed to remove {}: {}", self.dir.display(), e);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use imagesize::ImageType;
+use llm_globber_core::IMAGE_MARKER_PREFIX;
+
+// One-line description of an image, e.g. `[Image: logo.png, 512x512 PNG, 34 KB]`,
+// or None if the data is not an image format we can read dimensions from
+pub(crate) fn image_placeholder(file_path: &str, data: &[u8]) -> Option<String> {
+    let image_type = imagesize::image_type(data).ok()?;
+    let size = imagesize::blob_size(data).ok()?;
+    let name = Path::new(file_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(file_path);
+    Some(format!(
+        "{}{}, {}x{} {}, {}]",
+        IMAGE_MARKER_PREFIX,
+        name,
+        size.width,
+        size.height,
+        format_name(image_type),
+        human_size(data.len())
+    ))
+}
+
+fn format_name(image_type: ImageType) -> String {
+    match image_type {
+        ImageType::Heif(_) => "HEIF".to_string(),
+        other => format!("{:?}", other).to_uppercase(),
+    }
+}
+
+fn human_size(bytes: usize) -> String {
+    const KB: usize = 1024;
+    const MB: usize = 1024 * 1024;
+    if bytes < KB {
+        format!("{} bytes", bytes)
+    } else if bytes < MB {
+        format!("{} KB", (bytes + KB / 2) / KB)
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB as f64)
+    }
+}
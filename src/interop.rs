@@ -0,0 +1,381 @@
+// The layouts other "repository in one file" tools write, so that `--unglob` and `convert` read
+// them and `--format` and `convert --to` write them: repomix's XML and markdown styles, and
+// files-to-prompt's default, `--cxml` and `--markdown` output. Only paths and contents carry
+// over; summaries, directory trees and other extras these tools add are left out.
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+use llm_globber_core::{
+    escape_path, format_header, parse_archive, text_entry, Entry, Footer,
+    ESCAPED_PATHS_FORMAT_VERSION, FOOTER_SECTION,
+};
+use log::{info, warn};
+
+use crate::compress::open_archive;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Layout {
+    RepomixXml,
+    RepomixMarkdown,
+    FilesToPrompt,
+    FilesToPromptCxml,
+    FilesToPromptMarkdown,
+}
+
+const LAYOUTS: &[(&str, Layout)] = &[
+    ("repomix-xml", Layout::RepomixXml),
+    ("repomix-markdown", Layout::RepomixMarkdown),
+    ("files-to-prompt", Layout::FilesToPrompt),
+    ("files-to-prompt-cxml", Layout::FilesToPromptCxml),
+    ("files-to-prompt-markdown", Layout::FilesToPromptMarkdown),
+];
+
+impl Layout {
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        LAYOUTS
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, layout)| *layout)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        LAYOUTS
+            .iter()
+            .find(|(_, layout)| *layout == self)
+            .map_or("", |(name, _)| name)
+    }
+
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Layout::RepomixXml | Layout::FilesToPromptCxml => "xml",
+            Layout::RepomixMarkdown | Layout::FilesToPromptMarkdown => "md",
+            Layout::FilesToPrompt => "txt",
+        }
+    }
+
+    // What comes before the first file
+    pub(crate) fn header(self) -> &'static str {
+        match self {
+            Layout::RepomixXml => {
+                "<files>\nThis section contains the contents of the repository's files.\n\n"
+            }
+            Layout::RepomixMarkdown => "# Files\n\n",
+            Layout::FilesToPromptCxml => "<documents>\n",
+            Layout::FilesToPrompt | Layout::FilesToPromptMarkdown => "",
+        }
+    }
+
+    // One file, the `index`th counting from 1. Readers take the lines between the opening and
+    // closing lines as the content, so it is followed by a newline before the closing line.
+    pub(crate) fn entry(self, index: usize, path: &str, content: &str) -> String {
+        match self {
+            Layout::RepomixXml => {
+                format!("<file path=\"{}\">\n{}\n</file>\n\n", escape_xml(path), content)
+            }
+            Layout::RepomixMarkdown => {
+                let fence = fence(content);
+                format!(
+                    "## File: {}\n{}{}\n{}\n{}\n\n",
+                    path,
+                    fence,
+                    language(path),
+                    content,
+                    fence
+                )
+            }
+            Layout::FilesToPrompt => format!("{}\n---\n{}\n\n---\n", path, content),
+            Layout::FilesToPromptCxml => format!(
+                "<document index=\"{}\">\n<source>{}</source>\n<document_content>\n{}\n</document_content>\n</document>\n",
+                index, path, content
+            ),
+            Layout::FilesToPromptMarkdown => {
+                let fence = fence(content);
+                format!("{}\n{}{}\n{}\n{}\n", path, fence, language(path), content, fence)
+            }
+        }
+    }
+
+    // What comes after the last file
+    pub(crate) fn footer(self) -> &'static str {
+        match self {
+            Layout::RepomixXml => "</files>\n",
+            Layout::FilesToPromptCxml => "</documents>\n",
+            _ => "",
+        }
+    }
+}
+
+// The names `--format` and `convert --to` accept, for error messages
+pub(crate) fn layout_names() -> String {
+    LAYOUTS
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Which layout a file is in, or None for an llm_globber archive or anything unrecognised.
+// files-to-prompt output is told apart by how it starts, repomix output by its file lines,
+// which follow a summary and directory tree.
+pub(crate) fn detect(text: &str) -> Option<Layout> {
+    let lines = split_lines(text);
+    let first = lines.iter().position(|line| !line.is_empty())?;
+    let (opening, next) = (lines[first], lines.get(first + 1).copied().unwrap_or(""));
+    if opening.starts_with("'''") || opening.starts_with(llm_globber_core::FORMAT_HEADER_PREFIX) {
+        return None;
+    }
+    if opening == "<documents>" {
+        return Some(Layout::FilesToPromptCxml);
+    }
+    if next == "---" {
+        return Some(Layout::FilesToPrompt);
+    }
+    if next.starts_with("```") && !opening.starts_with('#') {
+        return Some(Layout::FilesToPromptMarkdown);
+    }
+    if lines.iter().any(|line| xml_path(line).is_some()) {
+        return Some(Layout::RepomixXml);
+    }
+    if lines.iter().any(|line| line.starts_with("## File: ")) {
+        return Some(Layout::RepomixMarkdown);
+    }
+    None
+}
+
+// The files in text written in `layout`, as entries with no signature or metadata
+pub(crate) fn read(layout: Layout, text: &str) -> Result<Vec<Entry>, String> {
+    let lines = split_lines(text);
+    let mut entries = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let opened = match layout {
+            Layout::RepomixXml => xml_path(lines[i]).map(|path| (path, i + 1, None)),
+            Layout::RepomixMarkdown => lines[i]
+                .strip_prefix("## File: ")
+                .filter(|_| is_fence(lines.get(i + 1)))
+                .map(|path| (path.to_string(), i + 2, Some(lines[i + 1]))),
+            Layout::FilesToPrompt => (!lines[i].is_empty() && lines.get(i + 1) == Some(&"---"))
+                .then(|| (lines[i].to_string(), i + 2, None)),
+            Layout::FilesToPromptCxml => lines[i]
+                .strip_prefix("<source>")
+                .and_then(|rest| rest.strip_suffix("</source>"))
+                .filter(|_| lines.get(i + 1) == Some(&"<document_content>"))
+                .map(|path| (path.to_string(), i + 2, None)),
+            Layout::FilesToPromptMarkdown => (!lines[i].is_empty() && is_fence(lines.get(i + 1)))
+                .then(|| (lines[i].to_string(), i + 2, Some(lines[i + 1]))),
+        };
+        let Some((path, start, fence)) = opened else {
+            i += 1;
+            continue;
+        };
+        let end = (start..lines.len())
+            .find(|&j| closes(layout, &lines, j, fence))
+            .ok_or_else(|| format!("The {} entry for {} is not closed", layout.name(), path))?;
+        // files-to-prompt prints a blank line after each file's content
+        let content_end = match layout {
+            Layout::FilesToPrompt => end - 1,
+            _ => end,
+        };
+        entries.push(Entry {
+            path,
+            signature: None,
+            metadata: Default::default(),
+            lines: lines[start..content_end]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+        });
+        i = end + 1;
+    }
+    if entries.is_empty() {
+        return Err(format!("No files found in the {} layout", layout.name()));
+    }
+    Ok(entries)
+}
+
+// Whether line `j` closes the entry being read. A line that merely looks like the closing line
+// counts only where the next file or the end of the file follows it.
+fn closes(layout: Layout, lines: &[&str], j: usize, fence: Option<&str>) -> bool {
+    let line = lines[j];
+    let rest_is_empty = || lines[j + 1..].iter().all(|line| line.is_empty());
+    match layout {
+        Layout::RepomixXml => {
+            line == "</file>"
+                && lines[j + 1..]
+                    .iter()
+                    .find(|line| !line.is_empty())
+                    .is_none_or(|line| *line == "</files>" || xml_path(line).is_some())
+        }
+        Layout::RepomixMarkdown => {
+            Some(line) == fence.map(|fence| fence.trim_end_matches(|c: char| c != '`'))
+                && lines[j + 1..]
+                    .iter()
+                    .find(|line| !line.is_empty())
+                    .is_none_or(|line| line.starts_with("## File: ") || line.starts_with('#'))
+        }
+        Layout::FilesToPrompt => {
+            line == "---"
+                && j > 0
+                && lines[j - 1].is_empty()
+                && (rest_is_empty() || lines.get(j + 2) == Some(&"---"))
+        }
+        Layout::FilesToPromptCxml => {
+            line == "</document_content>" && lines.get(j + 1) == Some(&"</document>")
+        }
+        Layout::FilesToPromptMarkdown => {
+            Some(line) == fence.map(|fence| fence.trim_end_matches(|c: char| c != '`'))
+                && (rest_is_empty() || is_fence(lines.get(j + 2)))
+        }
+    }
+}
+
+// The path of a repomix `<file path="...">` line
+fn xml_path(line: &str) -> Option<String> {
+    let path = line.strip_prefix("<file path=\"")?.strip_suffix("\">")?;
+    Some(unescape_xml(path))
+}
+
+fn is_fence(line: Option<&&str>) -> bool {
+    line.is_some_and(|line| line.starts_with("```"))
+}
+
+// Split on `\n` alone, keeping any `\r` of CRLF content, without the empty line after a final
+// newline
+fn split_lines(text: &str) -> Vec<&str> {
+    text.split_terminator('\n').collect()
+}
+
+// A run of backticks longer than any that starts a line of the content, so none closes it early
+fn fence(content: &str) -> String {
+    let longest = content
+        .lines()
+        .map(|line| line.chars().take_while(|&c| c == '`').count())
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+// The fence's language tag: the extension, which markdown renderers mostly understand
+fn language(path: &str) -> String {
+    Path::new(path)
+        .extension()
+        .map_or(String::new(), |extension| {
+            extension.to_string_lossy().to_lowercase()
+        })
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Also the entities repomix's templates escape paths with
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&#x3D;", "=")
+        .replace("&#x60;", "`")
+        .replace("&amp;", "&")
+}
+
+// `llm_globber convert`: read an archive in any layout this module or llm_globber knows and write
+// it in `to`, a layout name or `text`, to `output` or standard output
+pub(crate) fn run_convert(
+    input: &str,
+    to: &str,
+    output: Option<&str>,
+    identity_files: &[String],
+) -> Result<(), String> {
+    let target = match to {
+        "text" => None,
+        name => Some(Layout::parse(name).ok_or_else(|| {
+            format!(
+                "Invalid --to format '{}': expected text, {}",
+                name,
+                layout_names()
+            )
+        })?),
+    };
+    let mut text = String::new();
+    open_archive(input, identity_files)
+        .and_then(|mut reader| reader.read_to_string(&mut text))
+        .map_err(|e| format!("Failed to read {}: {}", input, e))?;
+    // Whether the paths are already escaped, as in archives from format 5 on
+    let (entries, escaped) = match detect(&text) {
+        Some(layout) => {
+            info!("Reading {} as {}", input, layout.name());
+            (read(layout, &text)?, false)
+        }
+        None => {
+            let archive = parse_archive(&text)?;
+            if !archive.omitted.is_empty() {
+                warn!(
+                    "Leaving out {} entries whose contents the archive omits",
+                    archive.omitted.len()
+                );
+            }
+            let escaped = archive.version >= ESCAPED_PATHS_FORMAT_VERSION;
+            (archive.entries, escaped)
+        }
+    };
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?,
+        )),
+        None => Box::new(io::stdout().lock()),
+    };
+    let written = match target {
+        Some(layout) => write_layout(&mut writer, layout, &entries),
+        None => write_text(&mut writer, &entries, escaped),
+    };
+    written
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Failed to write converted archive: {}", e))?;
+    info!("Converted {} files to {}", entries.len(), to);
+    Ok(())
+}
+
+fn write_layout<W: Write>(writer: &mut W, layout: Layout, entries: &[Entry]) -> io::Result<()> {
+    writer.write_all(layout.header().as_bytes())?;
+    for (index, entry) in entries.iter().enumerate() {
+        writer.write_all(
+            layout
+                .entry(index + 1, &entry.path, &entry.content())
+                .as_bytes(),
+        )?;
+    }
+    writer.write_all(layout.footer().as_bytes())
+}
+
+// An llm_globber archive of the entries in the current format, with escaped paths and the footer
+// unglob checks
+fn write_text<W: Write>(writer: &mut W, entries: &[Entry], escaped: bool) -> io::Result<()> {
+    writer.write_all(format_header().as_bytes())?;
+    let paths: Vec<String> = entries
+        .iter()
+        .map(|entry| match escaped {
+            true => entry.path.clone(),
+            false => escape_path(entry.path.as_bytes()),
+        })
+        .collect();
+    let mut bytes = 0;
+    for (entry, path) in entries.iter().zip(&paths) {
+        let content = entry.text();
+        bytes += content.len() as u64;
+        writer.write_all(text_entry(path, &content, None).as_bytes())?;
+    }
+    let footer = Footer {
+        files: entries.len() as u64,
+        bytes,
+        manifest_digest: crate::manifest_digest(paths.iter().map(String::as_str)),
+        ..Footer::default()
+    };
+    writer.write_all(llm_globber_core::section(FOOTER_SECTION, &footer.lines()).as_bytes())
+}
@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::{Component, Path};
+
+use base64::{engine::general_purpose, Engine};
+
+use llm_globber_core::{
+    is_omitted_marker, is_rendered_marker, parse_file_header, parse_format_header, ArchiveParser,
+    Event, FOOTER_FORMAT_VERSION, FOOTER_SECTION, FORMAT_HEADER_PREFIX, FORMAT_VERSION,
+    SECTION_PREFIX,
+};
+
+use crate::compress::open_archive;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct LintIssue {
+    pub(crate) line: usize,
+    pub(crate) severity: Severity,
+    pub(crate) message: String,
+}
+
+// Tracks where the linter is within the archive while scanning line by line
+enum LintState {
+    Outside,
+    PublicKey {
+        line: usize,
+    },
+    Entry {
+        path: String,
+        line: usize,
+        first_line: bool,
+    },
+    // Generated sections such as SKIPPED FILES hold free-form lines up to their closing marker
+    Section {
+        name: String,
+        line: usize,
+    },
+}
+
+// Validate an archive on disk and return every issue found, ordered by line number
+pub(crate) fn lint_archive(
+    filename: &str,
+    identity_files: &[String],
+) -> Result<Vec<LintIssue>, String> {
+    let reader = open_archive(filename, identity_files)
+        .map_err(|e| format!("Failed to open input file: {}: {}", filename, e))?;
+    let mut lines = Vec::new();
+    for line_result in reader.lines() {
+        lines.push(line_result.map_err(|e| format!("Error reading line: {}", e))?);
+    }
+    Ok(lint_lines(&lines))
+}
+
+pub(crate) fn lint_lines(lines: &[String]) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let mut state = LintState::Outside;
+    let mut seen_paths: HashMap<String, usize> = HashMap::new();
+    let mut public_key_line: Option<usize> = None;
+    let mut entry_count = 0;
+    let mut version = None;
+    let mut has_footer = false;
+    // The entry whose closing marker was the line before, and that line
+    let mut just_closed: Option<(String, usize)> = None;
+
+    let mut report = |line: usize, severity: Severity, message: String| {
+        issues.push(LintIssue {
+            line,
+            severity,
+            message,
+        });
+    };
+
+    for (index, line) in lines.iter().enumerate() {
+        let line_no = index + 1;
+        let closed = just_closed.take();
+
+        if let LintState::PublicKey { line: key_line } = state {
+            if line != "'''" {
+                report(
+                    line_no,
+                    Severity::Error,
+                    format!(
+                        "missing closing marker for PUBLIC_KEY block on line {}",
+                        key_line
+                    ),
+                );
+            }
+            state = LintState::Outside;
+            if line == "'''" {
+                continue;
+            }
+        }
+
+        if line.starts_with(FORMAT_HEADER_PREFIX) && matches!(state, LintState::Outside) {
+            if index != 0 {
+                report(
+                    line_no,
+                    Severity::Error,
+                    "format header must be the first line of the archive".to_string(),
+                );
+            }
+            match parse_format_header(line) {
+                Ok(version) if version > FORMAT_VERSION => report(
+                    line_no,
+                    Severity::Error,
+                    format!(
+                        "unsupported format version {} (this build supports up to {})",
+                        version, FORMAT_VERSION
+                    ),
+                ),
+                Ok(found) => version = Some(found),
+                Err(e) => report(line_no, Severity::Error, e),
+            }
+            continue;
+        }
+
+        if line.starts_with("'''--- PUBLIC_KEY ---") {
+            if let LintState::Entry { path, line, .. } = &state {
+                report(
+                    line_no,
+                    Severity::Error,
+                    format!("entry '{}' (line {}) has no closing marker", path, line),
+                );
+            }
+            if let Some(first) = public_key_line {
+                report(
+                    line_no,
+                    Severity::Error,
+                    format!("duplicate PUBLIC_KEY block (first on line {})", first),
+                );
+            } else if entry_count > 0 {
+                report(
+                    line_no,
+                    Severity::Warning,
+                    "PUBLIC_KEY block appears after file entries".to_string(),
+                );
+            }
+            if let Some(message) = check_public_key(line) {
+                report(line_no, Severity::Error, message);
+            }
+            public_key_line.get_or_insert(line_no);
+            state = LintState::PublicKey { line: line_no };
+            continue;
+        }
+
+        if line.starts_with(SECTION_PREFIX) && !matches!(state, LintState::Section { .. }) {
+            if let LintState::Entry { path, line, .. } = &state {
+                report(
+                    line_no,
+                    Severity::Error,
+                    format!("entry '{}' (line {}) has no closing marker", path, line),
+                );
+            }
+            let name = line[SECTION_PREFIX.len()..]
+                .trim_end_matches("===")
+                .trim()
+                .to_string();
+            has_footer |= name == FOOTER_SECTION;
+            state = LintState::Section {
+                name,
+                line: line_no,
+            };
+            continue;
+        }
+
+        if line.starts_with("'''---") {
+            if let LintState::Entry { path, line, .. } = &state {
+                report(
+                    line_no,
+                    Severity::Error,
+                    format!("entry '{}' (line {}) has no closing marker", path, line),
+                );
+            }
+
+            match parse_file_header(line) {
+                Ok((path, signature)) => {
+                    entry_count += 1;
+                    for (severity, message) in check_entry_path(&path) {
+                        report(line_no, severity, message);
+                    }
+                    if let Some(first) = seen_paths.get(&path) {
+                        report(
+                            line_no,
+                            Severity::Error,
+                            format!("duplicate path '{}' (first on line {})", path, first),
+                        );
+                    } else {
+                        seen_paths.insert(path.clone(), line_no);
+                    }
+                    match (&signature, public_key_line) {
+                        (Some(sig), key_line) => {
+                            if let Some(message) = check_signature(sig) {
+                                report(line_no, Severity::Error, message);
+                            }
+                            if key_line.is_none() {
+                                report(
+                                    line_no,
+                                    Severity::Error,
+                                    format!(
+                                        "entry '{}' is signed but no PUBLIC_KEY block precedes it",
+                                        path
+                                    ),
+                                );
+                            }
+                        }
+                        (None, Some(_)) => {
+                            report(
+                                line_no,
+                                Severity::Warning,
+                                format!("entry '{}' has no signature in a signed archive", path),
+                            );
+                        }
+                        (None, None) => {}
+                    }
+                    state = LintState::Entry {
+                        path,
+                        line: line_no,
+                        first_line: true,
+                    };
+                }
+                Err(e) => {
+                    report(line_no, Severity::Error, format!("malformed header: {}", e));
+                    state = LintState::Outside;
+                }
+            }
+            continue;
+        }
+
+        match &mut state {
+            LintState::Entry {
+                first_line, path, ..
+            } => {
+                if line == "'''" {
+                    just_closed = Some((std::mem::take(path), line_no));
+                    state = LintState::Outside;
+                } else if *first_line && is_rendered_marker(line) {
+                    // Rendered views run on to a closing marker like text content
+                    *first_line = false;
+                } else if *first_line && is_omitted_marker(line) {
+                    // Binary entries are written without a closing marker
+                    state = LintState::Outside;
+                } else {
+                    *first_line = false;
+                }
+            }
+            LintState::Outside => {
+                if let Some((path, closing)) = closed.filter(|_| !line.trim().is_empty()) {
+                    // Writers follow the closing marker with a blank line, so text right after
+                    // it is the rest of the entry, cut short by a content line of `'''`
+                    report(
+                        closing,
+                        Severity::Error,
+                        format!(
+                            "entry '{}' is cut short by a content line that reads as its closing marker; archives from format 7 escape such lines",
+                            path
+                        ),
+                    );
+                } else if line == "'''" {
+                    report(
+                        line_no,
+                        Severity::Warning,
+                        "closing marker outside of any entry".to_string(),
+                    );
+                } else if !line.trim().is_empty() {
+                    report(
+                        line_no,
+                        Severity::Warning,
+                        "text outside of any entry".to_string(),
+                    );
+                }
+            }
+            LintState::Section { .. } => {
+                if line == "'''" {
+                    state = LintState::Outside;
+                }
+            }
+            LintState::PublicKey { .. } => {}
+        }
+    }
+
+    match state {
+        LintState::Entry { path, line, .. } => report(
+            lines.len(),
+            Severity::Error,
+            format!(
+                "entry '{}' (line {}) has no closing marker before end of file",
+                path, line
+            ),
+        ),
+        LintState::PublicKey { line } => report(
+            lines.len(),
+            Severity::Error,
+            format!(
+                "missing closing marker for PUBLIC_KEY block on line {}",
+                line
+            ),
+        ),
+        LintState::Section { name, line } => report(
+            lines.len(),
+            Severity::Error,
+            format!(
+                "section '{}' (line {}) has no closing marker before end of file",
+                name, line
+            ),
+        ),
+        LintState::Outside => {}
+    }
+
+    if version.is_some_and(|version| version >= FOOTER_FORMAT_VERSION) && !has_footer {
+        report(
+            lines.len().max(1),
+            Severity::Error,
+            "archive has no footer, so it was cut short".to_string(),
+        );
+    }
+
+    if entry_count == 0 {
+        report(
+            lines.len().max(1),
+            Severity::Error,
+            "archive contains no file entries".to_string(),
+        );
+    }
+
+    // Whatever the parser unglob reads archives with refuses, lint fails too, unless the line is
+    // already reported
+    if let Some((line, message)) = check_as_unglob(lines) {
+        if !issues
+            .iter()
+            .any(|issue| issue.line == line && issue.severity == Severity::Error)
+        {
+            issues.push(LintIssue {
+                line,
+                severity: Severity::Error,
+                message,
+            });
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+// Read the archive with the parser unglob uses and check its entries against the footer as unglob
+// does, returning the line of the first problem and what it is
+fn check_as_unglob(lines: &[String]) -> Option<(usize, String)> {
+    let mut parser = ArchiveParser::new();
+    let mut footer = None;
+    let mut entry_paths = Vec::new();
+    let mut content_bytes = 0;
+    let mut hard_links = Vec::new();
+    let mut empty_dirs = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        match parser.feed(line) {
+            Ok(Some(Event::Entry(entry))) => {
+                content_bytes += entry.content_len();
+                entry_paths.push(entry.path);
+            }
+            Ok(Some(Event::Omitted(omitted))) => entry_paths.push(omitted.path),
+            Ok(Some(Event::HardLinks(links))) => hard_links = links,
+            Ok(Some(Event::EmptyDirs(dirs))) => empty_dirs = dirs,
+            Ok(Some(Event::Footer(found))) => footer = Some((index + 1, found)),
+            Ok(_) => {}
+            Err(e) => return Some((index + 1, e)),
+        }
+    }
+    if let Some(entry) = parser.finish() {
+        content_bytes += entry.content_len();
+        entry_paths.push(entry.path);
+    }
+    let (line, footer) = footer?;
+    footer
+        .verify(&entry_paths, content_bytes, &hard_links, &empty_dirs)
+        .err()
+        .map(|e| (line, e))
+}
+
+// Returns a description of what is wrong with the PUBLIC_KEY header, if anything
+fn check_public_key(line: &str) -> Option<String> {
+    let encoded = match line
+        .strip_prefix("'''--- PUBLIC_KEY --- [KEY:")
+        .and_then(|rest| rest.strip_suffix(']'))
+    {
+        Some(encoded) => encoded,
+        None => return Some(format!("malformed PUBLIC_KEY header: {}", line)),
+    };
+    match general_purpose::STANDARD.decode(encoded) {
+        Ok(bytes) if bytes.len() != ed25519_dalek::PUBLIC_KEY_LENGTH => {
+            Some(format!("invalid public key length: {} bytes", bytes.len()))
+        }
+        Ok(bytes) => ed25519_dalek::PublicKey::from_bytes(&bytes)
+            .err()
+            .map(|e| format!("invalid public key: {}", e)),
+        Err(e) => Some(format!("public key is not valid base64: {}", e)),
+    }
+}
+
+fn check_signature(signature: &str) -> Option<String> {
+    match general_purpose::STANDARD.decode(signature) {
+        Ok(bytes) if bytes.len() != ed25519_dalek::SIGNATURE_LENGTH => {
+            Some(format!("invalid signature length: {} bytes", bytes.len()))
+        }
+        Ok(_) => None,
+        Err(e) => Some(format!("signature is not valid base64: {}", e)),
+    }
+}
+
+// Flag entry paths that would land outside the extraction directory on unglob
+fn check_entry_path(path: &str) -> Vec<(Severity, String)> {
+    let mut problems = Vec::new();
+    if path.is_empty() {
+        problems.push((Severity::Error, "entry has an empty path".to_string()));
+        return problems;
+    }
+    let path_ref = Path::new(path);
+    if path_ref.is_absolute() {
+        problems.push((
+            Severity::Warning,
+            format!("entry '{}' uses an absolute path", path),
+        ));
+    }
+    if path_ref
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        problems.push((
+            Severity::Error,
+            format!("entry '{}' contains a '..' traversal component", path),
+        ));
+    }
+    problems
+}
+
+// Entry point for `llm_globber lint FILE`: prints issues in `file:line: severity: message` form
+pub(crate) fn run_lint(filename: &str, identity_files: &[String]) -> Result<(), String> {
+    let issues = lint_archive(filename, identity_files)?;
+    let mut errors = 0;
+    let mut warnings = 0;
+    for issue in &issues {
+        let label = match issue.severity {
+            Severity::Error => {
+                errors += 1;
+                "error"
+            }
+            Severity::Warning => {
+                warnings += 1;
+                "warning"
+            }
+        };
+        println!("{}:{}: {}: {}", filename, issue.line, label, issue.message);
+    }
+    println!("{}: {} error(s), {} warning(s)", filename, errors, warnings);
+    if errors > 0 {
+        return Err(format!("Lint failed: {} error(s) in {}", errors, filename));
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use llm_globber_core::{ArchiveParser, Entry, Event, Metadata};
+
+use crate::compress::{open_archive, raw_lines};
+
+// One row of `llm_globber ls`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Listing {
+    pub(crate) path: String,
+    pub(crate) metadata: Metadata,
+    pub(crate) omitted: bool,
+}
+
+impl Listing {
+    // Entries without recorded metadata still have a size and line count from their content
+    fn from_entry(entry: Entry) -> Self {
+        let content = entry.content();
+        let mut metadata = entry.metadata;
+        metadata.size.get_or_insert(content.len() as u64);
+        metadata.lines.get_or_insert(
+            content.matches('\n').count()
+                + usize::from(!content.is_empty() && !content.ends_with('\n')),
+        );
+        Listing {
+            path: entry.path,
+            metadata,
+            omitted: false,
+        }
+    }
+
+    // `-rwxr-xr-x  1234  2025-03-26 14:02  87  path`, with `-` for anything not recorded
+    fn format(&self) -> String {
+        let mode = self.metadata.mode.map_or("-".to_string(), |mode| {
+            let bits: String = (0..9)
+                .map(|i| match (mode >> (8 - i)) & 1 {
+                    0 => '-',
+                    _ => ['r', 'w', 'x'][i % 3],
+                })
+                .collect();
+            format!("-{}", bits)
+        });
+        let size = self
+            .metadata
+            .size
+            .map_or("-".to_string(), |size| size.to_string());
+        let mtime = self
+            .metadata
+            .mtime
+            .and_then(|mtime| chrono::DateTime::from_timestamp(mtime as i64, 0))
+            .map_or("-".to_string(), |time| {
+                time.format("%Y-%m-%d %H:%M").to_string()
+            });
+        let lines = self
+            .metadata
+            .lines
+            .map_or("-".to_string(), |lines| lines.to_string());
+        let omitted = if self.omitted {
+            " (content omitted)"
+        } else {
+            ""
+        };
+        format!(
+            "{:<10}  {:>10}  {:<16}  {:>7}  {}{}",
+            mode, size, mtime, lines, self.path, omitted
+        )
+    }
+}
+
+// The front matter of an archive and its entries with their metadata, in archive order
+pub(crate) fn list_archive(
+    filename: &str,
+    identity_files: &[String],
+) -> Result<(Vec<String>, Vec<Listing>), String> {
+    let reader = open_archive(filename, identity_files)
+        .map_err(|e| format!("Failed to open input file: {}: {}", filename, e))?;
+    let mut parser = ArchiveParser::new();
+    let mut front_matter = Vec::new();
+    let mut listings = Vec::new();
+    for line_result in raw_lines(reader) {
+        let line = line_result.map_err(|e| format!("Error reading line: {}", e))?;
+        match parser.feed(&line)? {
+            Some(Event::Entry(entry)) => listings.push(Listing::from_entry(entry)),
+            Some(Event::Omitted(omitted)) => listings.push(Listing {
+                path: omitted.path,
+                metadata: omitted.metadata,
+                omitted: true,
+            }),
+            Some(Event::FrontMatter(lines)) => front_matter = lines,
+            _ => {}
+        }
+    }
+    listings.extend(parser.finish().map(Listing::from_entry));
+    Ok((front_matter, listings))
+}
+
+// The front matter of an archive, read up to its first entry, or nothing if it has none
+pub(crate) fn read_front_matter(
+    filename: &str,
+    identity_files: &[String],
+) -> Result<Vec<String>, String> {
+    let reader = open_archive(filename, identity_files)
+        .map_err(|e| format!("Failed to open input file: {}: {}", filename, e))?;
+    let mut parser = ArchiveParser::new();
+    for line_result in raw_lines(reader) {
+        let line = line_result.map_err(|e| format!("Error reading line: {}", e))?;
+        match parser.feed(&line)? {
+            Some(Event::FrontMatter(lines)) => return Ok(lines),
+            Some(Event::Entry(_) | Event::Omitted(_)) => break,
+            _ => {}
+        }
+        // The front matter opens the archive, so an open entry means there is none
+        if parser.current_entry().is_some() {
+            break;
+        }
+    }
+    Ok(Vec::new())
+}
+
+// Print front matter as a YAML document
+pub(crate) fn print_front_matter(lines: &[String]) {
+    if !lines.is_empty() {
+        println!("---\n{}\n---", lines.join("\n"));
+    }
+}
+
+// Entry point for `llm_globber ls FILE`
+pub(crate) fn run_ls(filename: &str, identity_files: &[String]) -> Result<(), String> {
+    let (front_matter, listings) = list_archive(filename, identity_files)?;
+    print_front_matter(&front_matter);
+    for listing in listings {
+        println!("{}", listing.format());
+    }
+    Ok(())
+}
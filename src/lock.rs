@@ -0,0 +1,49 @@
+// An advisory lock on the output directory for the length of a run, so runs writing there at the
+// same time, such as one started by a file watcher and one by hand, take turns instead of
+// interleaving their writes and renames
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use log::warn;
+
+// Held until dropped, when closing the directory releases the lock
+#[derive(Debug)]
+pub(crate) struct OutputLock {
+    _dir: File,
+}
+
+// Lock `dir`, waiting for a run that holds it to finish
+pub(crate) fn lock(dir: &Path) -> io::Result<OutputLock> {
+    let file = File::open(dir)?;
+    if !flock(&file, libc::LOCK_EX | libc::LOCK_NB)? {
+        warn!(
+            "Waiting for another run writing to {} to finish",
+            dir.display()
+        );
+        flock(&file, libc::LOCK_EX)?;
+    }
+    Ok(OutputLock { _dir: file })
+}
+
+// Lock an open file, such as a log appended to by runs at the same time, until it is closed
+pub(crate) fn lock_file(file: &File) -> io::Result<()> {
+    flock(file, libc::LOCK_EX).map(|_| ())
+}
+
+// Take the lock, or return false if it is held and `operation` asks not to wait
+fn flock(file: &File, operation: libc::c_int) -> io::Result<bool> {
+    loop {
+        // SAFETY: the descriptor stays open for as long as `file` is borrowed
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(true);
+        }
+        let error = io::Error::last_os_error();
+        match error.kind() {
+            io::ErrorKind::Interrupted => continue,
+            io::ErrorKind::WouldBlock => return Ok(false),
+            _ => return Err(error),
+        }
+    }
+}
@@ -1,29 +1,116 @@
+use std::ffi::OsString;
 use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
-use std::os::unix::fs::PermissionsExt;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
 use std::str;
 use std::sync::{Arc, Mutex};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches};
 use colored::*;
-use std::collections::HashSet;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use base64::{engine::general_purpose, Engine};
-use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
-use glob::{glob, Pattern};
-use log::{debug, error, info, warn, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use detect::ContentKind;
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use extraction::Extraction;
+use glob::{glob, glob_with, MatchOptions, Pattern};
+use llm_globber_common::{filter, policy, unglob};
+use llm_globber_core::{
+    manifest_digest, ArchiveParser, Entry, Event, Footer, Omitted, Timestamp, BINARY_MARKER,
+    DEFAULT_BINARY_THRESHOLD, DEFAULT_EXCLUDED_DIRS, DEFAULT_MAX_FILES, DEFAULT_MAX_FILE_SIZE,
+    ENTRY_OVERHEAD_BYTES, HEADERS_ONLY_MARKER, MINIFIED_MARKER,
+};
+use log::{debug, error, info, trace, warn, LevelFilter, Log, Metadata, Record, SetLoggerError};
 use memmap2::MmapOptions;
-use rand::rngs::OsRng;
-
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
+
+mod archives;
+mod audit;
+mod bench;
+mod boilerplate;
+mod callgraph;
+mod checksums;
+mod chunks;
+mod codeowners;
+mod compress;
+mod config;
+mod daemon;
+mod deps;
+mod detect;
+mod diskspace;
+mod embeddings;
+mod encrypt;
+mod extraction;
+mod hooks;
+mod images;
+mod interop;
+mod lint;
+mod listing;
+mod lock;
+mod manifest;
+mod models;
+mod naming;
+mod notebook;
+mod outline;
+mod output;
+mod patch;
+mod plain;
+mod plan;
+mod plugins;
+mod relevance;
+mod remote;
+mod resume;
+mod sample;
+mod search;
+mod sigfile;
+mod sqlite;
+mod sshkey;
+mod table;
+mod temp;
+mod testfiles;
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
-
-const MAX_FILES: usize = 100000;
-const IO_BUFFER_SIZE: usize = 1 << 18; // 256KB
-const DEFAULT_MAX_FILE_SIZE: u64 = 1 << 30; // 1GB
+mod toc;
+mod todos;
+mod trace;
+mod transforms;
+mod trust;
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod uring;
+mod vendored;
+mod whitespace;
+
+// Unglob reads this much of its input to tell a --format tar tarball from a text archive
+const TAR_PEEK_SIZE: usize = 512;
+// Output file names: the -n name and the run's unix timestamp unless --name-template is given
+const DEFAULT_NAME_TEMPLATE: &str = "{name}_{ts}";
+// Wait before retrying a failed read with --retries, unless --retry-delay says otherwise
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(100);
+// Longest the doubling wait between retries grows to, unless --retry-delay starts above it
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+// How often progress is reported when stderr is not a terminal and lines cannot be overwritten
+const PROGRESS_LINE_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_IO_BUFFER_SIZE: usize = 1 << 18; // 256KB
+                                               // Files at least this large are memory-mapped instead of read into a buffer
+const DEFAULT_MMAP_THRESHOLD: u64 = 1 << 20; // 1MB
+const DEFAULT_TOP_K: usize = 20;
+// Mirror tree in the output directory that --backup=tree copies overwritten files to
+const BACKUP_DIR: &str = ".llm-globber-backup";
+const DEFAULT_CHUNK_TOKENS: usize = 512;
+const DEFAULT_CHUNK_OVERLAP: usize = 64;
+// Rough bytes-per-token ratio for source text in common LLM tokenizers, unless --model says
+// otherwise
+const BYTES_PER_TOKEN: f64 = 4.0;
+// Ends content cut short by --over-budget truncate
+const TRUNCATED_MARKER: &str = "[... truncated to fit the budget]\n";
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 #[allow(dead_code)]
@@ -63,7 +150,7 @@ fn log_signature_debug_info(context: &str, file_path: &str, data: &[u8]) {
     }
     debug!("Content hash (first 1000 bytes): {}", hash_value);
 
-    // Log content samples at different positions
+    // Content dumps are only shown at -vvv
     let samples = [
         (0, std::cmp::min(50, data.len())),
         (
@@ -76,7 +163,7 @@ fn log_signature_debug_info(context: &str, file_path: &str, data: &[u8]) {
     for (i, (start, end)) in samples.iter().enumerate() {
         if *start < *end {
             let sample = String::from_utf8_lossy(&data[*start..*end]);
-            debug!(
+            trace!(
                 "Content sample {} (bytes {}-{}): {:?}",
                 i + 1,
                 start,
@@ -88,28 +175,60 @@ fn log_signature_debug_info(context: &str, file_path: &str, data: &[u8]) {
 
     // Log exact bytes being processed (for small files)
     if data.len() < 500 {
-        debug!(
+        trace!(
             "Full content being {}: {:?}",
             context.to_lowercase(),
             String::from_utf8_lossy(data)
         );
-        debug!("Raw bytes: {:?}", data);
+        trace!("Raw bytes: {:?}", data);
+    }
+}
+
+// How log records are written by --log-format
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LogFormat {
+    Text, // `[time] LEVEL: message`
+    Json, // One JSON object per line with timestamp, level, target and message
+}
+
+impl LogFormat {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "Invalid log format '{}': expected text or json",
+                other
+            )),
+        }
     }
 }
 
 static GLOBAL_LOGGER: GlobalLogger = GlobalLogger {
     level: Mutex::new(LogLevel::Warn), // Default to Warn
     quiet_mode: Mutex::new(false),
+    format: Mutex::new(LogFormat::Text),
+    log_file: Mutex::new(None),
+    progress_bar: Mutex::new(None),
 };
 
 struct GlobalLogger {
     level: Mutex<LogLevel>,
     quiet_mode: Mutex<bool>,
+    format: Mutex<LogFormat>,
+    log_file: Mutex<Option<File>>, // --log-file, written even in quiet mode
+    progress_bar: Mutex<Option<ProgressBar>>, // Hidden while a record is printed under it
 }
 
 impl Log for GlobalLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        if *self.quiet_mode.lock().expect("Quiet mode mutex poisoned") {
+        if *self.quiet_mode.lock().expect("Quiet mode mutex poisoned")
+            && self
+                .log_file
+                .lock()
+                .expect("Log file mutex poisoned")
+                .is_none()
+        {
             return false;
         }
         metadata.level()
@@ -120,29 +239,111 @@ impl Log for GlobalLogger {
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
+            let now = chrono::Local::now();
+            let format = *self.format.lock().expect("Log format mutex poisoned");
+            let mut log_file = self.log_file.lock().expect("Log file mutex poisoned");
+            if let Some(file) = log_file.as_mut() {
+                // A failing log file must not abort the run it is recording
+                let _ = writeln!(file, "{}", format_log_record(record, &now, format));
+            }
             if *self.quiet_mode.lock().expect("Quiet mode mutex poisoned") {
                 return;
             }
-            let level_str = match record.level() {
-                log::Level::Error => "ERROR".red(),
-                log::Level::Warn => "WARN".yellow(),
-                log::Level::Info => "INFO".green(),
-                log::Level::Debug => "DEBUG".blue(),
-                log::Level::Trace => "TRACE".cyan(),
+            // With a log file the requested format goes there and stderr stays readable
+            let line = if format == LogFormat::Json && log_file.is_none() {
+                format_log_record(record, &now, format)
+            } else {
+                let level_str = match record.level() {
+                    log::Level::Error => "ERROR".red(),
+                    log::Level::Warn => "WARN".yellow(),
+                    log::Level::Info => "INFO".green(),
+                    log::Level::Debug => "DEBUG".blue(),
+                    log::Level::Trace => "TRACE".cyan(),
+                };
+                format!(
+                    "[{}] {}: {}",
+                    now.format("%Y-%m-%d %H:%M:%S").to_string().dimmed(),
+                    level_str,
+                    record.args()
+                )
             };
-            eprintln!(
-                "[{}] {}: {}",
-                chrono::Local::now()
-                    .format("%Y-%m-%d %H:%M:%S")
-                    .to_string()
-                    .dimmed(),
-                level_str,
-                record.args()
-            );
+            match self
+                .progress_bar
+                .lock()
+                .expect("Progress bar mutex poisoned")
+                .as_ref()
+            {
+                Some(bar) => bar.suspend(|| eprintln!("{}", line)),
+                None => eprintln!("{}", line),
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self
+            .log_file
+            .lock()
+            .expect("Log file mutex poisoned")
+            .as_mut()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+// A log record without colors, for the log file or JSON on stderr
+fn format_log_record(
+    record: &Record,
+    now: &chrono::DateTime<chrono::Local>,
+    format: LogFormat,
+) -> String {
+    let message = strip_ansi(&record.args().to_string());
+    match format {
+        LogFormat::Text => format!(
+            "[{}] {}: {}",
+            now.format("%Y-%m-%d %H:%M:%S"),
+            record.level(),
+            message
+        ),
+        LogFormat::Json => serde_json::json!({
+            "timestamp": now.to_rfc3339(),
+            "level": record.level().to_string(),
+            "target": record.target(),
+            "message": message,
+        })
+        .to_string(),
+    }
+}
+
+// Remove the color escape sequences that messages pick up from `colored` on a terminal
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip `ESC [ parameters final-byte`
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
         }
     }
+    out
+}
 
-    fn flush(&self) {}
+// ANSI colors are only used on a terminal, and never with --no-color or a non-empty NO_COLOR
+// (https://no-color.org). CLICOLOR_FORCE turns them back on for a pipe.
+fn use_color(no_color: bool) -> bool {
+    let env = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+    if no_color || env("NO_COLOR").is_some() {
+        return false;
+    }
+    env("CLICOLOR_FORCE").is_some_and(|value| value != "0") || io::stderr().is_terminal()
 }
 
 fn init_logger() -> Result<(), SetLoggerError> {
@@ -166,14 +367,198 @@ fn set_quiet_mode(quiet: bool) {
         .expect("Quiet mode mutex poisoned") = quiet;
 }
 
+fn set_log_format(format: LogFormat) {
+    *GLOBAL_LOGGER
+        .format
+        .lock()
+        .expect("Log format mutex poisoned") = format;
+}
+
+fn set_progress_bar(bar: Option<ProgressBar>) {
+    *GLOBAL_LOGGER
+        .progress_bar
+        .lock()
+        .expect("Progress bar mutex poisoned") = bar;
+}
+
+// Append log records to `path` as well as stderr
+fn set_log_file(path: &str) -> Result<(), String> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open log file {}: {}", path, e))?;
+    *GLOBAL_LOGGER
+        .log_file
+        .lock()
+        .expect("Log file mutex poisoned") = Some(file);
+    Ok(())
+}
+
+// Put the logger back to its defaults and the given level, so that the logging options of
+// one daemon request do not carry over to the next
+fn reset_logger(level: LogLevel) {
+    set_log_level(level);
+    set_quiet_mode(false);
+    set_log_format(LogFormat::Text);
+    *GLOBAL_LOGGER
+        .log_file
+        .lock()
+        .expect("Log file mutex poisoned") = None;
+}
+
+// What to do when more files match than --max-files allows
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OverflowPolicy {
+    Truncate, // Keep the first files found and report how many were dropped
+    Error,    // Refuse to write a partial dump
+}
+
+impl OverflowPolicy {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "truncate" => Ok(OverflowPolicy::Truncate),
+            "error" => Ok(OverflowPolicy::Error),
+            other => Err(format!(
+                "Invalid overflow policy '{}': expected truncate or error",
+                other
+            )),
+        }
+    }
+}
+
+// What to do with a file that would push the output past --max-total-size
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OverBudgetPolicy {
+    Skip,     // Leave the file out
+    Outline,  // Include its outline instead, if that fits; otherwise leave it out
+    Truncate, // Include its outline if that fits, otherwise as many whole lines as fit
+}
+
+impl OverBudgetPolicy {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "skip" => Ok(OverBudgetPolicy::Skip),
+            "outline" => Ok(OverBudgetPolicy::Outline),
+            "truncate" => Ok(OverBudgetPolicy::Truncate),
+            other => Err(format!(
+                "Invalid over-budget policy '{}': expected skip, outline or truncate",
+                other
+            )),
+        }
+    }
+}
+
+// Which files to keep by whether they are tests, with --no-tests and --only-tests
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TestFiles {
+    Keep, // Tests and everything else
+    Skip, // Everything but tests
+    Only, // Tests alone
+}
+
+// Where unglob copies a file before overwriting it, with --backup
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BackupMode {
+    Suffix, // Next to the file, as FILE.orig
+    Tree,   // At the same relative path under BACKUP_DIR in the output directory
+}
+
+impl BackupMode {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "orig" => Ok(BackupMode::Suffix),
+            "tree" => Ok(BackupMode::Tree),
+            other => Err(format!(
+                "Invalid --backup mode '{}': expected orig or tree",
+                other
+            )),
+        }
+    }
+}
+
+// Order in which collected files are written to the output
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortOrder {
+    Path,
+    Size,
+    Mtime,
+    Ext,
+    None,
+}
+
+impl SortOrder {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "path" => Ok(SortOrder::Path),
+            "size" => Ok(SortOrder::Size),
+            "mtime" => Ok(SortOrder::Mtime),
+            "ext" => Ok(SortOrder::Ext),
+            "none" => Ok(SortOrder::None),
+            other => Err(format!(
+                "Invalid sort order '{}': expected path, size, mtime, ext, or none",
+                other
+            )),
+        }
+    }
+}
+
+// Layout of the generated output file
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Text,                     // Marker-delimited archive that can be unglobbed
+    Chunks,                   // JSONL records of overlapping line chunks for vector databases
+    Tar,   // Standard tarball of the selected files, gzipped unless --compress says otherwise
+    Plain, // File contents one after another with no markers
+    Interop(interop::Layout), // The layout another tool such as repomix writes
+}
+
+impl OutputFormat {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(OutputFormat::Text),
+            "chunks" => Ok(OutputFormat::Chunks),
+            "tar" => Ok(OutputFormat::Tar),
+            "plain" => Ok(OutputFormat::Plain),
+            other => interop::Layout::parse(other)
+                .map(OutputFormat::Interop)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid output format '{}': expected text, chunks, tar, plain, {}",
+                        other,
+                        interop::layout_names()
+                    )
+                }),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Chunks => "jsonl",
+            OutputFormat::Tar => "tar",
+            OutputFormat::Plain => "txt",
+            OutputFormat::Interop(layout) => layout.extension(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct FileEntry {
+    path: PathBuf,
+}
+
+// A repository given with --git, cloned to a temporary directory if it was a URL
+#[derive(Debug, Clone)]
+struct GitRepo {
     path: String,
+    name: String,
+    branch: String,
 }
 
 type ExtHashEntry = String; // In Rust, String directly is used, HashMap manages ownership
 
-// We can't derive Clone because BufWriter<File> and Keypair don't implement Clone
+// We can't derive Clone because OutputFile and Keypair don't implement Clone
 #[derive(Debug)]
 struct ScrapeConfig {
     // Keeping repo_paths for API compatibility but marking with #[allow(dead_code)]
@@ -185,31 +570,137 @@ struct ScrapeConfig {
     file_type_hash: HashSet<ExtHashEntry>, // Use HashSet for efficient extension lookups
     filter_files: bool,
     recursive: bool,
-    name_pattern: String,
+    name_patterns: Vec<Pattern>, // --pattern globs; files must match one of them, if any
+    excluded_name_patterns: Vec<Pattern>, // `!`-prefixed --pattern globs; files may match none
+    pattern_basename: bool, // Match --pattern globs against file names only, as before paths were supported
+    pattern_roots: Vec<PathBuf>, // The inputs walked, which --pattern paths are relative to
+    ignore_case: bool, // Match -t extensions and name, skip, priority and force-text patterns in any case
     skip_patterns: Vec<Pattern>,
     verbose: bool,
     quiet: bool,
     debug_mode: bool,
     no_dot_files: bool,
     max_file_size: u64,
-    output_file: Option<BufWriter<File>>, // Using BufWriter for efficiency
-    output_mutex: Arc<Mutex<()>>,         // Using a simple Mutex for output synchronization
+    mmap_threshold: Option<u64>, // Smallest file that is memory-mapped, None to never map
+    io_buffer_size: usize,       // Buffer for reading input files and writing the output
+    fsync: bool,                 // Sync the output to disk at each checkpoint
+    prefetched: HashMap<PathBuf, Vec<u8>>, // Contents read ahead of time with io_uring
+    space_check: bool,           // Refuse clones and outputs that will not fit on disk
+    compress: Option<compress::Compression>, // Compress the finished output file
+    encrypt: Option<encrypt::Encryption>, // Encrypt the finished output file with age
+    identity_files: Vec<String>, // age identities for reading encrypted archives
+    output_file: Option<output::OutputFile>, // Buffered, counting the bytes written
+    output_mutex: Arc<Mutex<()>>, // Using a simple Mutex for output synchronization
     abort_on_error: bool,
     show_progress: bool,
+    last_progress: Option<Instant>, // When progress was last printed as a full line
     processed_files: usize,
-    failed_files: usize,
+    failed_files: Vec<(String, String)>, // Files that could not be read, with the error
     start_time: Instant,
-    git_repo_path: Option<String>,
+    git_repos: Vec<GitRepo>,
     unglob_mode: bool,
     unglob_input_file: String,
     use_signature: bool,
-    keypair: Option<Keypair>,
+    signer: Option<sshkey::Signer>,
     public_key: Option<PublicKey>,
-    temp_git_path: Option<String>, // Path to temporary git clone that needs cleanup
+    require_trusted_key: bool, // Refuse archives signed by keys missing from the trust store
+    verify_key: Option<PublicKey>, // --key-file when unglobbing: the only key archives may use
+    detached_sig: bool,        // Also sign the finished output in a .sig file
+    timestamp: bool,           // End signed archives with a signed timestamp section
+    temp_dirs: Vec<String>,    // Temporary git clones and extracted archives that need cleanup
+    relative_root: Option<PathBuf>, // Headers record paths relative to this directory
+    source_prefixes: Vec<(PathBuf, String)>, // Header prefix for files under each source root
+    path_maps: Vec<(String, String)>, // Unglob prefix rewrites, applied first match wins
+    backup: Option<BackupMode>, // Copy files unglob overwrites out of the way first
+    as_patch: bool,            // Print unglob's changes as a unified diff instead of writing them
+    force: bool,               // Unglob into a non-empty directory that git cannot restore
+    max_extract_size: u64,     // Largest file unglob writes, in bytes
+    sort_order: SortOrder,
+    priority_patterns: Vec<Pattern>, // Matching files are emitted first, in pattern order
+    priority_weights: Vec<(Pattern, u32)>, // Files are emitted by their highest matching weight
+    outline: bool,                   // Emit only declarations for supported source languages
+    render_notebooks: bool,          // Flatten .ipynb files into markdown and code cells
+    table_sample: Option<usize>,     // Keep only the first/last N data rows of CSV/TSV files
+    sqlite_schema: bool,             // Dump the schema of SQLite databases instead of omitting them
+    default_excludes: bool,          // Skip DEFAULT_EXCLUDED_DIRS while walking directories
+    max_files: usize,
+    on_overflow: OverflowPolicy,
+    overflow_files: usize, // Matching files dropped because max_files was reached
+    seen_files: HashSet<PathBuf>, // Canonical paths of the files added, so overlapping inputs add each once
+    linked_files: HashMap<(u64, u64), PathBuf>, // Path added for each (device, inode) with several links
+    hard_links: Vec<(PathBuf, PathBuf)>, // Files left out as hard links to an added file, with its path
+    keep_empty_dirs: bool,               // Record directories left without included files
+    skip_empty: bool,                    // Leave out zero-byte files
+    test_files: TestFiles,               // Whether to keep tests, leave them out or keep only them
+    include_vendored: bool,              // Keep vendor directories and others' copyrighted files
+    project_holders: HashMap<PathBuf, Vec<String>>, // Copyright holders in each input's license
+    skipped_empty: usize,                // Zero-byte files left out with --skip-empty
+    walked_dirs: Vec<PathBuf>, // Directories walked below the inputs, with --keep-empty-dirs
+    max_total_size: Option<u64>, // Budget in bytes for all entries written in one run
+    budget_tokens: Option<usize>, // The budget as given with --budget-tokens, for messages
+    bytes_per_token: f64,      // How tokens are estimated from bytes, set by --model
+    over_budget: OverBudgetPolicy,
+    output_bytes: u64, // Approximate bytes written so far, counted against max_total_size
+    budget_dropped: Vec<String>, // Entry paths left out to stay within max_total_size
+    retries: u32,      // Extra attempts at reading a file after a transient error
+    retry_delay: Duration, // Wait before the first retry, doubled after each one
+    retried_files: Vec<(String, u32)>, // Files read after this many retries
+    binary_threshold: usize, // Percentage of control bytes above which content is binary
+    force_text_patterns: Vec<Pattern>, // Files always treated as text
+    mime_include: Vec<String>, // Sniffed MIME types to keep, e.g. `text/*`
+    mime_exclude: Vec<String>, // Sniffed MIME types to drop
+    output_format: OutputFormat,
+    chunk_tokens: usize, // Maximum estimated tokens per chunk with OutputFormat::Chunks
+    chunk_overlap: usize, // Estimated tokens shared between consecutive chunks
+    file_comments: bool, // Open each file with a comment naming it in OutputFormat::Plain
+    skip_log: bool,      // Append a SKIPPED FILES section to the archive
+    skipped_files: Vec<(PathBuf, String)>, // Path and reason for every file left out
+    trace_context: Option<usize>, // Excerpt files selected by --from-trace to N lines around references
+    trace_lines: HashMap<PathBuf, Vec<usize>>, // Referenced line numbers by canonical path
+    todos: bool,                  // Append a TODOS section to the archive
+    call_graph: bool,             // Append a CALL GRAPH section to the archive
+    call_graph_lines: Vec<String>, // Callers and callees found so far, one caller per line
+    front_matter: Option<Vec<String>>, // The command line and filters for --front-matter
+    prepend: Vec<(String, Vec<String>)>, // --prepend files as (path, lines), before the first entry
+    append: Vec<(String, Vec<String>)>, // --append files as (path, lines), after the last entry
+    todo_items: Vec<String>,      // `path:line: text` for each TODO/FIXME/HACK comment written
+    dependencies: bool,           // Start the archive with a DEPENDENCIES section from manifests
+    git_log: Option<usize>,       // Append the last N commits of the --git repository
+    name_template: String,        // Output file name, see naming::expand for the placeholders
+    remote_output: Option<String>, // s3:// or http(s):// destination the output is uploaded to
+    cache: Option<daemon::SharedCache>, // Walks, clones and relevance terms kept by a daemon
+    plugins: Vec<plugins::Plugin>, // WASM filters each text file is run through, in order
+    policy: Option<policy::Policy>, // Organisation policy on paths, redaction and destinations
+    audit: Option<audit::Audit>,  // Audit logs each finished run is recorded in
+    exported: Vec<audit::ExportedFile>, // The files written, for the audit logs
+    transforms: Vec<transforms::Transform>, // Commands matching files are piped through
+    written_entries: Vec<String>, // Header paths of the entries written, for --post-hook
+    written_content_bytes: u64,   // Bytes of text content in the entries written, for the footer
+    written_links: Vec<(String, String)>, // Hard links section as written, for the footer
+    written_empty_dirs: Vec<String>, // Empty directories section as written, for the footer
+    resume: bool,                 // Keep state next to the output to resume an interrupted run
+    resume_state: Option<resume::State>, // State of the interrupted run being resumed
+    bench: bool,                  // Glob into a temporary file and report phase timings
+    timings: bench::Timings,
+    hash_manifest: bool, // Write a sha256sum style manifest next to the output
+    metadata: bool,      // Record size, mtime, mode and line count in entry headers
+    toc: bool,           // Insert a table of contents after the preamble
+    headers_only: bool,  // Write entry headers with a marker in place of all content
+    collapse_boilerplate: bool, // Write blocks that open many files once and reference them
+    normalization: whitespace::Normalization, // Whitespace changes made to every text file
+    boilerplate: Vec<String>, // The blocks found, numbered from 1 in this order
+    toc_offset: u64,     // Where the preamble ends and the table of contents goes
+    toc_entries: Vec<toc::TocEntry>, // Where each entry was written, before the table is inserted
+    content_hashes: Vec<(String, String)>, // SHA-256 and header path of each entry with content
 }
 
 // Implement a custom clone method that doesn't clone the non-cloneable fields
 impl ScrapeConfig {
+    // Whether unsigned archives and entries are refused rather than extracted with a warning
+    fn strict_signatures(&self) -> bool {
+        self.require_trusted_key || self.verify_key.is_some()
+    }
+
     fn clone_for_verification(&self, new_public_key: Option<PublicKey>) -> Self {
         ScrapeConfig {
             repo_paths: self.repo_paths.clone(),
@@ -219,27 +710,128 @@ impl ScrapeConfig {
             file_type_hash: self.file_type_hash.clone(), // HashSet implements Clone
             filter_files: self.filter_files,
             recursive: self.recursive,
-            name_pattern: self.name_pattern.clone(),
+            name_patterns: self.name_patterns.clone(),
+            excluded_name_patterns: self.excluded_name_patterns.clone(),
+            pattern_basename: self.pattern_basename,
+            pattern_roots: self.pattern_roots.clone(),
+            ignore_case: self.ignore_case,
             skip_patterns: self.skip_patterns.clone(),
             verbose: self.verbose,
             quiet: self.quiet,
             debug_mode: self.debug_mode,
             no_dot_files: self.no_dot_files,
             max_file_size: self.max_file_size,
+            mmap_threshold: self.mmap_threshold,
+            io_buffer_size: self.io_buffer_size,
+            fsync: self.fsync,
+            prefetched: self.prefetched.clone(),
+            space_check: self.space_check,
+            compress: self.compress,
+            encrypt: None, // Verification never writes an output file
+            identity_files: self.identity_files.clone(),
             output_file: None, // Don't clone the file handle
             output_mutex: Arc::clone(&self.output_mutex),
             abort_on_error: self.abort_on_error,
             show_progress: self.show_progress,
+            last_progress: self.last_progress,
             processed_files: self.processed_files,
-            failed_files: self.failed_files,
+            failed_files: self.failed_files.clone(),
             start_time: self.start_time,
-            git_repo_path: self.git_repo_path.clone(),
+            git_repos: self.git_repos.clone(),
             unglob_mode: self.unglob_mode,
             unglob_input_file: self.unglob_input_file.clone(),
             use_signature: self.use_signature,
-            keypair: None, // Don't clone the keypair
+            signer: None, // Don't clone the signer
             public_key: new_public_key,
-            temp_git_path: self.temp_git_path.clone(),
+            require_trusted_key: self.require_trusted_key,
+            verify_key: self.verify_key,
+            detached_sig: self.detached_sig,
+            timestamp: self.timestamp,
+            temp_dirs: self.temp_dirs.clone(),
+            relative_root: self.relative_root.clone(),
+            source_prefixes: self.source_prefixes.clone(),
+            path_maps: self.path_maps.clone(),
+            backup: self.backup,
+            as_patch: self.as_patch,
+            force: self.force,
+            max_extract_size: self.max_extract_size,
+            sort_order: self.sort_order,
+            priority_patterns: self.priority_patterns.clone(),
+            priority_weights: self.priority_weights.clone(),
+            outline: self.outline,
+            render_notebooks: self.render_notebooks,
+            table_sample: self.table_sample,
+            sqlite_schema: self.sqlite_schema,
+            default_excludes: self.default_excludes,
+            max_files: self.max_files,
+            on_overflow: self.on_overflow,
+            overflow_files: self.overflow_files,
+            seen_files: self.seen_files.clone(),
+            linked_files: self.linked_files.clone(),
+            hard_links: self.hard_links.clone(),
+            keep_empty_dirs: self.keep_empty_dirs,
+            skip_empty: self.skip_empty,
+            test_files: self.test_files,
+            include_vendored: self.include_vendored,
+            project_holders: self.project_holders.clone(),
+            skipped_empty: self.skipped_empty,
+            walked_dirs: self.walked_dirs.clone(),
+            max_total_size: self.max_total_size,
+            budget_tokens: self.budget_tokens,
+            bytes_per_token: self.bytes_per_token,
+            over_budget: self.over_budget,
+            output_bytes: self.output_bytes,
+            budget_dropped: self.budget_dropped.clone(),
+            retries: self.retries,
+            retry_delay: self.retry_delay,
+            retried_files: self.retried_files.clone(),
+            binary_threshold: self.binary_threshold,
+            force_text_patterns: self.force_text_patterns.clone(),
+            mime_include: self.mime_include.clone(),
+            mime_exclude: self.mime_exclude.clone(),
+            output_format: self.output_format,
+            chunk_tokens: self.chunk_tokens,
+            chunk_overlap: self.chunk_overlap,
+            file_comments: self.file_comments,
+            skip_log: self.skip_log,
+            skipped_files: self.skipped_files.clone(),
+            trace_context: self.trace_context,
+            trace_lines: self.trace_lines.clone(),
+            todos: self.todos,
+            call_graph: self.call_graph,
+            call_graph_lines: self.call_graph_lines.clone(),
+            front_matter: self.front_matter.clone(),
+            prepend: self.prepend.clone(),
+            append: self.append.clone(),
+            todo_items: self.todo_items.clone(),
+            dependencies: self.dependencies,
+            git_log: self.git_log,
+            name_template: self.name_template.clone(),
+            remote_output: self.remote_output.clone(),
+            cache: self.cache.clone(),
+            plugins: Vec::new(), // Only used while globbing
+            policy: self.policy.clone(),
+            audit: self.audit.clone(),
+            exported: self.exported.clone(),
+            transforms: Vec::new(),
+            written_entries: self.written_entries.clone(),
+            written_content_bytes: self.written_content_bytes,
+            written_links: self.written_links.clone(),
+            written_empty_dirs: self.written_empty_dirs.clone(),
+            resume: self.resume,
+            resume_state: self.resume_state.clone(),
+            bench: self.bench,
+            timings: self.timings.clone(),
+            hash_manifest: self.hash_manifest,
+            metadata: self.metadata,
+            toc: self.toc,
+            headers_only: self.headers_only,
+            collapse_boilerplate: self.collapse_boilerplate,
+            normalization: self.normalization.clone(),
+            boilerplate: self.boilerplate.clone(),
+            toc_offset: self.toc_offset,
+            toc_entries: self.toc_entries.clone(),
+            content_hashes: self.content_hashes.clone(),
         }
     }
 }
@@ -254,29 +846,234 @@ impl Default for ScrapeConfig {
             file_type_hash: HashSet::new(), // Initialize as empty HashSet
             filter_files: true,
             recursive: false,
-            name_pattern: String::new(),
+            name_patterns: Vec::new(),
+            excluded_name_patterns: Vec::new(),
+            pattern_basename: false,
+            pattern_roots: Vec::new(),
+            ignore_case: false,
             skip_patterns: Vec::new(),
             verbose: false,
             quiet: false,
             debug_mode: false,
             no_dot_files: true,
             max_file_size: DEFAULT_MAX_FILE_SIZE,
+            mmap_threshold: Some(DEFAULT_MMAP_THRESHOLD),
+            io_buffer_size: DEFAULT_IO_BUFFER_SIZE,
+            fsync: false,
+            prefetched: HashMap::new(),
+            space_check: true,
+            compress: None,
+            encrypt: None,
+            identity_files: Vec::new(),
             output_file: None,
             output_mutex: Arc::new(Mutex::new(())),
             abort_on_error: false,
             show_progress: false,
+            last_progress: None,
             processed_files: 0,
-            failed_files: 0,
+            failed_files: Vec::new(),
             start_time: Instant::now(),
-            git_repo_path: None,
+            git_repos: Vec::new(),
             unglob_mode: false,
             unglob_input_file: String::new(),
             use_signature: false,
-            keypair: None,
+            signer: None,
             public_key: None,
-            temp_git_path: None,
+            require_trusted_key: false,
+            verify_key: None,
+            detached_sig: false,
+            timestamp: false,
+            temp_dirs: Vec::new(),
+            relative_root: None,
+            source_prefixes: Vec::new(),
+            path_maps: Vec::new(),
+            backup: None,
+            as_patch: false,
+            force: false,
+            max_extract_size: DEFAULT_MAX_FILE_SIZE,
+            sort_order: SortOrder::Path,
+            priority_patterns: Vec::new(),
+            priority_weights: Vec::new(),
+            outline: false,
+            render_notebooks: false,
+            table_sample: None,
+            sqlite_schema: false,
+            default_excludes: true,
+            max_files: DEFAULT_MAX_FILES,
+            on_overflow: OverflowPolicy::Truncate,
+            overflow_files: 0,
+            seen_files: HashSet::new(),
+            linked_files: HashMap::new(),
+            hard_links: Vec::new(),
+            keep_empty_dirs: false,
+            skip_empty: false,
+            test_files: TestFiles::Keep,
+            include_vendored: false,
+            project_holders: HashMap::new(),
+            skipped_empty: 0,
+            walked_dirs: Vec::new(),
+            max_total_size: None,
+            budget_tokens: None,
+            bytes_per_token: BYTES_PER_TOKEN,
+            over_budget: OverBudgetPolicy::Skip,
+            output_bytes: 0,
+            budget_dropped: Vec::new(),
+            retries: 0,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            retried_files: Vec::new(),
+            binary_threshold: DEFAULT_BINARY_THRESHOLD,
+            force_text_patterns: Vec::new(),
+            mime_include: Vec::new(),
+            mime_exclude: Vec::new(),
+            output_format: OutputFormat::Text,
+            chunk_tokens: DEFAULT_CHUNK_TOKENS,
+            chunk_overlap: DEFAULT_CHUNK_OVERLAP,
+            file_comments: false,
+            skip_log: false,
+            skipped_files: Vec::new(),
+            trace_context: None,
+            trace_lines: HashMap::new(),
+            todos: false,
+            call_graph: false,
+            call_graph_lines: Vec::new(),
+            front_matter: None,
+            prepend: Vec::new(),
+            append: Vec::new(),
+            todo_items: Vec::new(),
+            dependencies: false,
+            git_log: None,
+            name_template: DEFAULT_NAME_TEMPLATE.to_string(),
+            remote_output: None,
+            cache: None,
+            plugins: Vec::new(),
+            policy: None,
+            audit: None,
+            exported: Vec::new(),
+            transforms: Vec::new(),
+            written_entries: Vec::new(),
+            written_content_bytes: 0,
+            written_links: Vec::new(),
+            written_empty_dirs: Vec::new(),
+            resume: false,
+            resume_state: None,
+            bench: false,
+            timings: bench::Timings::default(),
+            hash_manifest: false,
+            metadata: false,
+            toc: false,
+            headers_only: false,
+            collapse_boilerplate: false,
+            normalization: whitespace::Normalization::default(),
+            boilerplate: Vec::new(),
+            toc_offset: 0,
+            toc_entries: Vec::new(),
+            content_hashes: Vec::new(),
+        }
+    }
+}
+
+// Check the output destination and every file against the organisation policy before anything
+// is read or written. Forbidden files refuse the run, or with `on_forbidden = "skip"` are left out.
+fn enforce_policy(config: &mut ScrapeConfig) -> Result<(), String> {
+    let Some(policy) = config.policy.clone() else {
+        return Ok(());
+    };
+    let sources = policy
+        .sources
+        .iter()
+        .map(|source| source.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let destination = config
+        .remote_output
+        .clone()
+        .unwrap_or_else(|| config.output_path.clone());
+    if !config.bench && !policy.allows_destination(&destination) {
+        return Err(format!(
+            "Policy {} does not allow writing output to {}",
+            sources, destination
+        ));
+    }
+
+    // A hard link redacted differently from the file it links to becomes an entry of its own,
+    // so its content is redacted by its own name
+    let (linked, separate): (Vec<_>, Vec<_>) = std::mem::take(&mut config.hard_links)
+        .into_iter()
+        .partition(|(alias, target)| {
+            policy.redacts_alike(&header_path(config, alias), &header_path(config, target))
+        });
+    config.hard_links = linked;
+    if !separate.is_empty() {
+        config
+            .file_entries
+            .extend(separate.into_iter().map(|(path, _)| FileEntry { path }));
+        sort_file_entries(config);
+    }
+
+    // Aliases of hard links are checked by their own names too, as unglob writes them there
+    let mut forbidden = HashMap::new();
+    let paths = config
+        .file_entries
+        .iter()
+        .map(|entry| &entry.path)
+        .chain(config.hard_links.iter().map(|(alias, _)| alias));
+    for path in paths {
+        let absolute = canonical_file_path(path).unwrap_or_else(|_| path.clone());
+        if let Some(pattern) = policy.forbidden(&absolute, &header_path(config, path)) {
+            forbidden.insert(path.clone(), pattern.to_string());
         }
     }
+    if forbidden.is_empty() {
+        return Ok(());
+    }
+    if !policy.skip_forbidden {
+        let mut paths: Vec<&PathBuf> = forbidden.keys().collect();
+        paths.sort();
+        return Err(format!(
+            "Policy {} forbids globbing {} file(s): {}",
+            sources,
+            paths.len(),
+            paths
+                .iter()
+                .map(|path| format!("{} ({})", path.display(), forbidden[*path]))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    config
+        .file_entries
+        .retain(|entry| !forbidden.contains_key(&entry.path));
+    config
+        .hard_links
+        .retain(|(alias, path)| !forbidden.contains_key(alias) && !forbidden.contains_key(path));
+    for (path, pattern) in forbidden {
+        warn!(
+            "Leaving out {}: forbidden by policy ({})",
+            path.display(),
+            pattern
+        );
+        record_skip(config, &path, format!("forbidden by policy ({})", pattern));
+    }
+    Ok(())
+}
+
+// Roughly how many bytes the output takes: every entry's header and content, or the budget when
+// that is smaller. Omitted binary content is counted too, so this errs on the large side.
+fn estimated_output_size(config: &ScrapeConfig) -> u64 {
+    let size: u64 = config
+        .file_entries
+        .iter()
+        .map(|entry| {
+            let content = match config.headers_only {
+                true => HEADERS_ONLY_MARKER.len() as u64,
+                false => get_file_size(&entry.path).unwrap_or(0),
+            };
+            (ENTRY_OVERHEAD_BYTES + entry.path.as_os_str().len()) as u64 + content
+        })
+        .sum();
+    config
+        .max_total_size
+        .map_or(size, |budget| size.min(budget))
 }
 
 fn run_scraper(config: &mut ScrapeConfig) -> Result<String, String> {
@@ -287,6 +1084,38 @@ fn run_scraper(config: &mut ScrapeConfig) -> Result<String, String> {
 
     config.start_time = Instant::now();
 
+    // Interrupted runs are picked up from a state file next to their output
+    if config.resume
+        && (config.output_format != OutputFormat::Text
+            || config.remote_output.is_some()
+            || !config.source_prefixes.is_empty())
+    {
+        return Err(
+            "--resume needs text output to a local directory from file and directory inputs"
+                .to_string(),
+        );
+    }
+
+    if let Some(state) = &config.resume_state {
+        let signer = config.public_key.as_ref().map(trust::encode_key);
+        state.check(signer.as_deref(), &resume_options(config))?;
+    }
+
+    enforce_policy(config)?;
+
+    // Remote outputs are built in a temporary directory and uploaded once finished
+    if config.remote_output.is_some() {
+        let staging = temp::path("upload");
+        config.output_path = staging.to_string_lossy().to_string();
+        config.temp_dirs.push(config.output_path.clone());
+    }
+    // Benchmarks do the writing too, but into a file that is removed with its directory
+    if config.bench {
+        let scratch = temp::path("bench");
+        config.output_path = scratch.to_string_lossy().to_string();
+        config.temp_dirs.push(config.output_path.clone());
+    }
+
     let output_path = PathBuf::from(&config.output_path);
     if !output_path.exists() {
         fs::create_dir_all(&output_path).map_err(|e| {
@@ -297,14 +1126,27 @@ fn run_scraper(config: &mut ScrapeConfig) -> Result<String, String> {
         })?;
         info!("Created output directory: {}", config.output_path);
     }
+    // Held until the output is finished, compressed, encrypted and uploaded
+    let _output_lock = lock::lock(&output_path).map_err(|e| {
+        format!(
+            "Could not lock output directory: {}: {}",
+            config.output_path, e
+        )
+    })?;
+
+    if config.space_check {
+        diskspace::check(&output_path, estimated_output_size(config), "the output")?;
+    }
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let output_file_name = format!("{}_{}.txt", config.output_filename, timestamp);
-    let output_file_path = output_path.join(output_file_name);
-    let output_file = File::create(&output_file_path).map_err(|e| {
+    let output_file_path = match &config.resume_state {
+        Some(state) => state.output.clone(),
+        None => output_file_path(config, &output_path)?,
+    };
+    let output_file = match &config.resume_state {
+        Some(state) => resume::reopen(state),
+        None => File::create(&output_file_path),
+    }
+    .map_err(|e| {
         format!(
             "Error creating output file: {}: {}",
             output_file_path.display(),
@@ -314,46 +1156,277 @@ fn run_scraper(config: &mut ScrapeConfig) -> Result<String, String> {
 
     set_secure_file_permissions(&output_file_path)?;
 
-    config.output_file = Some(BufWriter::with_capacity(IO_BUFFER_SIZE, output_file));
+    let output_file = output::OutputFile::at_end(output_file, config.io_buffer_size)
+        .map_err(|e| format!("Error writing output file: {}", e))?;
+    config.output_file = Some(output_file);
 
-    // Write public key at the start of the file if signature is enabled
-    if config.use_signature {
-        if let Some(public_key) = &config.public_key {
-            let encoded_pubkey = general_purpose::STANDARD.encode(public_key.to_bytes());
-            if let Some(output_file) = &mut config.output_file {
-                writeln!(
-                    output_file,
-                    "'''--- PUBLIC_KEY --- [KEY:{}]",
-                    encoded_pubkey
-                )
-                .map_err(|e| format!("Error writing public key to output file: {}", e))?;
-                writeln!(output_file, "'''\n")
-                    .map_err(|e| format!("Error writing public key to output file: {}", e))?;
-            }
-            info!("Added public key to output file");
-        }
+    if config.resume_state.is_none() {
+        write_preamble(config)?;
     }
 
-    let mut files_processed = 0;
     // Create a copy of the paths to avoid borrowing issues
-    let file_paths: Vec<String> = config
+    let file_paths: Vec<PathBuf> = config
         .file_entries
         .iter()
         .map(|entry| entry.path.clone())
         .collect();
 
-    for (i, file_path) in file_paths.iter().enumerate() {
-        if process_file(config, file_path).is_ok() {
-            files_processed += 1;
-            config.processed_files = files_processed;
-        } else {
-            config.failed_files += 1;
+    let state_path = resume::state_path(&output_path, &config.output_filename);
+    let mut journal = None;
+    let mut done = 0;
+    if config.resume {
+        let fail =
+            |e: io::Error| format!("Error writing resume state {}: {}", state_path.display(), e);
+        let state = match config.resume_state.take() {
+            Some(state) => {
+                let (entries, bytes) = resume::written_entries(&state)
+                    .map_err(|e| format!("Failed to read {}: {}", output_file_path.display(), e))?;
+                config.written_entries = entries;
+                config.written_content_bytes = bytes;
+                state
+            }
+            None => resume::State {
+                output: output_file_path.clone(),
+                relative_root: config.relative_root.clone(),
+                start: current_output_offset(config),
+                signer: config.public_key.as_ref().map(trust::encode_key),
+                options: resume_options(config),
+                files: file_paths.clone(),
+                hard_links: config.hard_links.clone(),
+                walked_dirs: config.walked_dirs.clone(),
+                offsets: Vec::new(),
+            },
+        };
+        done = state.offsets.len();
+        journal = Some(resume::Journal::create(&state_path, &state).map_err(fail)?);
+    }
+
+    let mut files_processed = done;
+    let remaining = &file_paths[done..];
+    let progress_bar = start_progress_bar(config, remaining);
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    let (mut uring_reader, mut prefetched_until) = (uring::Reader::new(), 0);
+    for (i, file_path) in remaining.iter().enumerate() {
+        if let Some((bar, _)) = &progress_bar {
+            bar.set_message(progress_message(config, file_path));
+        }
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        if i >= prefetched_until {
+            let read_start = Instant::now();
+            let max_size = config.mmap_threshold.unwrap_or(u64::MAX);
+            let (contents, covered) = uring_reader.read_batch(&remaining[i..], max_size);
+            config.timings.read += read_start.elapsed();
+            // Whatever the last batch read for files that were then filtered out is dropped
+            config.prefetched = contents;
+            prefetched_until = i + covered;
+        }
+        let dropped_before = config.budget_dropped.len();
+        let timings_before = config.timings.clone();
+        let file_start = Instant::now();
+        let result = process_file(config, file_path);
+        config
+            .timings
+            .add_format(file_start.elapsed(), &timings_before);
+        match result {
+            Ok(()) => {
+                if config.budget_dropped.len() == dropped_before {
+                    files_processed += 1;
+                    config.processed_files = files_processed;
+                }
+            }
+            Err(e) => {
+                config
+                    .failed_files
+                    .push((file_path.display().to_string(), e.to_string()));
+                warn!("Failed to process {}: {}", file_path.display(), e);
+                if config.abort_on_error {
+                    if let Some((bar, _)) = &progress_bar {
+                        bar.finish_and_clear();
+                        set_progress_bar(None);
+                    }
+                    // Leave no partial output behind
+                    config.output_file = None;
+                    let _ = fs::remove_file(&output_file_path);
+                    if journal.is_some() {
+                        resume::remove(&state_path);
+                    }
+                    return Err(format!(
+                        "Aborting after failing to process {}: {}",
+                        file_path.display(),
+                        e
+                    ));
+                }
+                write_error_entry(config, file_path, &e.to_string())
+                    .map_err(|e| format!("Error writing error entry to output file: {}", e))?;
+            }
+        }
+
+        if let Some(journal) = &mut journal {
+            // The offset is only recorded once everything before it is written
+            checkpoint_output(config)
+                .and_then(|_| journal.record(current_output_offset(config)))
+                .map_err(|e| {
+                    format!("Error writing resume state {}: {}", state_path.display(), e)
+                })?;
+        }
+
+        match &progress_bar {
+            Some((bar, sizes)) => bar.inc(sizes[i]),
+            None if i % 10 == 0 => print_progress(config),
+            None => {}
+        }
+    }
+    if let Some((bar, _)) = progress_bar {
+        bar.finish_and_clear();
+        set_progress_bar(None);
+    }
+
+    if let (Some(count), OutputFormat::Text) = (config.git_log, config.output_format) {
+        let repos = config.git_repos.clone();
+        let mut lines = Vec::new();
+        for repo in &repos {
+            let commits = get_git_log(&repo.path, count)?;
+            // With several repositories, each one's commits are listed under its name
+            if repos.len() > 1 {
+                lines.push(format!("{} ({}):", repo.name, repo.branch));
+                lines.extend(commits.iter().map(|commit| format!("  {}", commit)));
+            } else {
+                lines.extend(commits);
+            }
+        }
+        if !repos.is_empty() {
+            write_section(config, "GIT LOG", &lines)
+                .map_err(|e| format!("Error writing git log to output file: {}", e))?;
+        }
+    }
+
+    if config.todos && config.output_format == OutputFormat::Text {
+        let items = std::mem::take(&mut config.todo_items);
+        write_section(config, "TODOS", &items)
+            .map_err(|e| format!("Error writing TODOs to output file: {}", e))?;
+    }
+
+    if config.call_graph && config.output_format == OutputFormat::Text {
+        let lines = std::mem::take(&mut config.call_graph_lines);
+        write_section(config, "CALL GRAPH", &lines)
+            .map_err(|e| format!("Error writing call graph to output file: {}", e))?;
+    }
+
+    if !config.hard_links.is_empty() && config.output_format == OutputFormat::Text {
+        // Aliases of files that were later left out, by sampling or a plan, have nothing to name
+        let included: HashSet<&Path> = config
+            .file_entries
+            .iter()
+            .map(|entry| entry.path.as_path())
+            .collect();
+        let links: Vec<(String, String)> = config
+            .hard_links
+            .iter()
+            .filter(|(_, target)| included.contains(target.as_path()))
+            .map(|(alias, target)| (header_path(config, alias), header_path(config, target)))
+            .collect();
+        let lines: Vec<String> = links
+            .iter()
+            .map(|(alias, target)| {
+                format!(
+                    "{}{}{}",
+                    alias,
+                    llm_globber_core::HARD_LINK_SEPARATOR,
+                    target
+                )
+            })
+            .collect();
+        config.written_links = links;
+        if !lines.is_empty() {
+            info!("Included {} hard-linked file(s) once", lines.len());
+            write_section(config, llm_globber_core::HARD_LINKS_SECTION, &lines)
+                .map_err(|e| format!("Error writing hard links to output file: {}", e))?;
+        }
+    }
+
+    if config.keep_empty_dirs && config.output_format == OutputFormat::Text {
+        let lines = empty_dirs(config);
+        if !lines.is_empty() {
+            info!(
+                "Recorded {} empty director{}",
+                lines.len(),
+                if lines.len() == 1 { "y" } else { "ies" }
+            );
+            write_section(config, llm_globber_core::EMPTY_DIRS_SECTION, &lines)
+                .map_err(|e| format!("Error writing empty directories to output file: {}", e))?;
         }
+        config.written_empty_dirs = lines;
+    }
+
+    if config.skip_log && config.output_format == OutputFormat::Text {
+        let lines: Vec<String> = config
+            .skipped_files
+            .iter()
+            .map(|(path, reason)| format!("{}: {}", header_path(config, path), reason))
+            .collect();
+        write_section(config, "SKIPPED FILES", &lines)
+            .map_err(|e| format!("Error writing skip log to output file: {}", e))?;
+    }
+
+    if config.toc && config.output_format == OutputFormat::Text {
+        insert_toc(config, &output_file_path)?;
+    }
 
-        if i % 10 == 0 {
-            print_progress(&config);
+    if config.output_format == OutputFormat::Text {
+        for (path, lines) in std::mem::take(&mut config.append) {
+            write_section(config, &format!("{}{}", APPEND_SECTION, path), &lines)
+                .map_err(|e| format!("Error writing {} to output file: {}", path, e))?;
         }
+        write_footer(config)?;
+    }
+    if let (OutputFormat::Interop(layout), Some(output_file)) =
+        (config.output_format, &mut config.output_file)
+    {
+        output_file
+            .write_all(layout.footer().as_bytes())
+            .map_err(|e| {
+                format!(
+                    "Error writing {} footer to output file: {}",
+                    layout.name(),
+                    e
+                )
+            })?;
+    }
+
+    if config.timestamp && config.output_format == OutputFormat::Text {
+        write_timestamp(config, &output_file_path)?;
+    }
+
+    // The output is complete, so there is nothing left to resume
+    if journal.is_some() {
+        resume::remove(&state_path);
+    }
+
+    if config.bench {
+        let write_start = Instant::now();
+        checkpoint_output(config).map_err(|e| format!("Error writing output file: {}", e))?;
+        config.timings.write += write_start.elapsed();
+        config.output_file = None;
+        config.timings.report(
+            files_processed,
+            config.timings.walk + config.start_time.elapsed(),
+        );
+        return Ok(output_file_path.display().to_string());
+    }
+
+    if let (OutputFormat::Tar, Some(output_file)) = (config.output_format, &mut config.output_file)
+    {
+        archives::finish_tar(output_file)
+            .map_err(|e| format!("Error finishing tar output file: {}", e))?;
     }
+    // Entries are buffered until now, and the steps below read the output from disk
+    checkpoint_output(config).map_err(|e| {
+        format!(
+            "Error writing output file: {}: {}",
+            output_file_path.display(),
+            e
+        )
+    })?;
 
     if files_processed == 0 {
         fs::remove_file(&output_file_path).map_err(|e| {
@@ -368,15 +1441,89 @@ fn run_scraper(config: &mut ScrapeConfig) -> Result<String, String> {
 
     let elapsed = config.start_time.elapsed().as_secs_f64();
 
-    let output_file_path_str = output_file_path.display().to_string();
+    let mut output_file_path_str = output_file_path.display().to_string();
 
-    if !output_file_path_str.contains("basic_test") {
-        info!("Cleaning up file...");
-        if let Err(e) = clean_up_text(&output_file_path_str, 2) {
-            error!("Error cleaning up file: {}: {}", output_file_path_str, e);
-        }
+    if let Some(compression) = config.compress {
+        let compressed = compress::compress_file(&output_file_path, compression).map_err(|e| {
+            format!(
+                "Error compressing output file: {}: {}",
+                output_file_path_str, e
+            )
+        })?;
+        set_secure_file_permissions(&compressed)?;
+        output_file_path_str = compressed.display().to_string();
+    }
+    if let Some(encryption) = &config.encrypt {
+        let encrypted = encrypt::encrypt_file(Path::new(&output_file_path_str), encryption)
+            .map_err(|e| {
+                format!(
+                    "Error encrypting output file: {}: {}",
+                    output_file_path_str, e
+                )
+            })?;
+        set_secure_file_permissions(&encrypted)?;
+        output_file_path_str = encrypted.display().to_string();
+    }
+    // Signs the bytes as stored, after compression and encryption
+    let detached_sig = match (&config.signer, config.detached_sig) {
+        (Some(signer), true) => Some(sigfile::write(Path::new(&output_file_path_str), signer)?),
+        _ => None,
+    };
+    let hash_manifest = if config.hash_manifest {
+        Some(checksums::write(
+            Path::new(&output_file_path_str),
+            &config.content_hashes,
+        )?)
     } else {
-        info!("Skipping cleanup for basic test file");
+        None
+    };
+    let stored_path = output_file_path_str.clone();
+    // A failed upload may still have sent some of the output, so it is audited before failing
+    let mut upload_error = None;
+    if let Some(url) = &config.remote_output {
+        match remote::upload(Path::new(&output_file_path_str), url) {
+            Ok(uploaded) => {
+                output_file_path_str = uploaded;
+                let sidecars = [
+                    (
+                        &detached_sig,
+                        sigfile::sig_path(Path::new(&output_file_path_str)),
+                    ),
+                    (
+                        &hash_manifest,
+                        checksums::manifest_path(Path::new(&output_file_path_str)),
+                    ),
+                ];
+                upload_error = sidecars
+                    .iter()
+                    .filter_map(|(path, url)| path.as_ref().map(|path| (path, url)))
+                    .try_for_each(|(path, url)| {
+                        remote::upload(path, &url.to_string_lossy()).map(|_| ())
+                    })
+                    .err();
+            }
+            Err(e) => {
+                output_file_path_str = url.clone();
+                upload_error = Some(e);
+            }
+        }
+    }
+    // Benchmark output never leaves the temporary directory it is written to
+    if let (Some(audit), false) = (&config.audit, config.bench) {
+        let destination = match &config.remote_output {
+            Some(_) => output_file_path_str.clone(),
+            None => fs::canonicalize(&stored_path)
+                .map_or(stored_path.clone(), |path| path.display().to_string()),
+        };
+        audit.record(
+            &destination,
+            Path::new(&stored_path),
+            &config.exported,
+            upload_error.as_deref(),
+        )?;
+    }
+    if let Some(e) = upload_error {
+        return Err(e);
     }
 
     if !config.quiet {
@@ -391,47 +1538,573 @@ fn run_scraper(config: &mut ScrapeConfig) -> Result<String, String> {
         output_file_path_str.cyan()
     );
 
-    if config.failed_files > 0 {
+    if !config.budget_dropped.is_empty() {
         warn!(
-            "{} Failed to process {} files",
+            "{} Left out {} files to stay within the {}",
             "❗".yellow(),
-            config.failed_files.to_string().red()
+            config.budget_dropped.len().to_string().red(),
+            budget_description(config)
         );
+        for path in &config.budget_dropped {
+            warn!("  dropped: {}", path);
+        }
     }
 
-    Ok(output_file_path_str)
-}
-
-fn clean_up_text(filename: &str, max_consecutive_newlines: usize) -> io::Result<()> {
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
+    if config.skipped_empty > 0 {
+        info!("Skipped {} empty files", config.skipped_empty);
+    }
 
-    let temp_filename = format!("{}.tmp", filename);
-    let temp_file = File::create(&temp_filename)?;
-    // Handle the error manually instead of using ?
-    if let Err(e) = set_secure_file_permissions(&PathBuf::from(&temp_filename)) {
-        return Err(io::Error::new(io::ErrorKind::PermissionDenied, e));
+    if !config.retried_files.is_empty() {
+        warn!(
+            "{} Read {} files only after retrying",
+            "❗".yellow(),
+            config.retried_files.len().to_string().yellow()
+        );
+        for (path, retries) in &config.retried_files {
+            warn!("  retried {} time(s): {}", retries, path);
+        }
     }
-    let mut writer = BufWriter::new(temp_file);
 
-    let mut consecutive_newlines = 0;
-    for line_result in reader.lines() {
-        let line = line_result?;
-        if line.trim().is_empty() {
-            consecutive_newlines += 1;
-            if consecutive_newlines <= max_consecutive_newlines {
-                writeln!(writer)?;
-            }
-        } else {
-            consecutive_newlines = 0;
-            writeln!(writer, "{}", line)?;
+    if !config.failed_files.is_empty() {
+        warn!(
+            "{} Failed to process {} files",
+            "❗".yellow(),
+            config.failed_files.len().to_string().red()
+        );
+        for (path, reason) in &config.failed_files {
+            warn!("  failed: {}: {}", path, reason);
         }
     }
 
-    fs::rename(temp_filename, filename)?;
+    Ok(output_file_path_str)
+}
+
+// Sections holding --prepend and --append files, named after the file: `PREPEND: NOTES.md`
+const PREPEND_SECTION: &str = "PREPEND: ";
+const APPEND_SECTION: &str = "APPEND: ";
+
+// The files given to --prepend or --append, as their lines. They become sections rather than
+// entries, so they are not counted in the footer or listed in the table of contents, and unglob
+// leaves them out.
+fn user_sections(matches: &ArgMatches, id: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut sections = Vec::new();
+    for path in matches.values_of(id).into_iter().flatten() {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read --{} file {}: {}", id, path, e))?;
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        // Such a line would end the section early or start an entry of its own
+        if let Some(line) = lines.iter().find(|line| {
+            *line == llm_globber_core::CLOSING_MARKER
+                || line.starts_with(llm_globber_core::ENTRY_PREFIX)
+                || line.starts_with(llm_globber_core::SECTION_PREFIX)
+        }) {
+            return Err(format!(
+                "--{} file {} has a line that would break the archive: {}",
+                id, path, line
+            ));
+        }
+        sections.push((path.to_string(), lines));
+    }
+    Ok(sections)
+}
+
+// Dependencies declared by the manifests among the inputs and above the header root
+fn dependency_summary(config: &ScrapeConfig) -> Vec<String> {
+    let files: Vec<PathBuf> = config
+        .file_entries
+        .iter()
+        .filter_map(|entry| canonical_file_path(&entry.path).ok())
+        .collect();
+    let root = config.relative_root.as_deref();
+    let mut lines = Vec::new();
+    for path in manifest::find_manifests(&files, root) {
+        // Manifests above the root are shown as ../Cargo.toml and so on
+        let display = match (root, path.parent()) {
+            (Some(root), _) if path.starts_with(root) => path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .display()
+                .to_string(),
+            (Some(root), Some(dir)) if root.starts_with(dir) => format!(
+                "{}{}",
+                "../".repeat(
+                    root.strip_prefix(dir)
+                        .map_or(0, |rest| rest.components().count())
+                ),
+                path.file_name().and_then(|s| s.to_str()).unwrap_or("")
+            ),
+            _ => path.display().to_string(),
+        };
+        match manifest::summary_lines(&path, &display) {
+            Ok(summary) => lines.extend(summary),
+            Err(e) => warn!("Skipping manifest in dependency summary: {}", e),
+        }
+    }
+    if lines.is_empty() {
+        info!("No dependency manifests found");
+    }
+    lines
+}
+
+// Digest of the options that shape the entries and the sections after them, which a resumed run
+// must share with the interrupted one for its entries to match those already written
+fn resume_options(config: &ScrapeConfig) -> String {
+    let options = format!(
+        "metadata={} headers_only={} collapse_boilerplate={} normalization={:?} outline={} \
+         notebooks={} table_sample={:?} sqlite_schema={} trace_context={:?} transforms={:?} \
+         timestamp={} keep_empty_dirs={} append={:?}",
+        config.metadata,
+        config.headers_only,
+        config.collapse_boilerplate,
+        config.normalization,
+        config.outline,
+        config.render_notebooks,
+        config.table_sample,
+        config.sqlite_schema,
+        config.trace_context,
+        config.transforms,
+        config.timestamp,
+        config.keep_empty_dirs,
+        config.append,
+    );
+    sha256_hex(options.as_bytes())
+}
+
+// Length of the output written so far, buffered bytes included
+fn current_output_offset(config: &ScrapeConfig) -> u64 {
+    config
+        .output_file
+        .as_ref()
+        .map_or(0, output::OutputFile::position)
+}
+
+// Write out everything buffered for the output, and with --fsync make sure it reached the disk.
+// Entries are only flushed here, not one by one, which matters when there are many small files.
+fn checkpoint_output(config: &mut ScrapeConfig) -> io::Result<()> {
+    if let Some(output_file) = &mut config.output_file {
+        output_file.flush()?;
+        if config.fsync {
+            output_file.get_ref().sync_data()?;
+        }
+    }
+    Ok(())
+}
+
+// Everything ahead of the entries: the format header, public key and leading sections
+fn write_preamble(config: &mut ScrapeConfig) -> Result<(), String> {
+    // Blocks are found before anything is written, since their sections come before the entries
+    if config.collapse_boilerplate
+        && !config.headers_only
+        && config.output_format == OutputFormat::Text
+    {
+        let paths: Vec<PathBuf> = config
+            .file_entries
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect();
+        // Blocks are taken from the content as written, after policy redaction
+        let config_ref = &*config;
+        let blocks = boilerplate::find_repeated(&paths, |path, text| {
+            let entry_path = header_path(config_ref, path);
+            let text = match &config_ref.policy {
+                Some(policy) => policy.redact(&entry_path, &text).unwrap_or(text),
+                None => text,
+            };
+            config_ref.normalization.apply(&text).unwrap_or(text)
+        });
+        config.boilerplate = blocks;
+        info!(
+            "Found {} repeated boilerplate block(s)",
+            config.boilerplate.len()
+        );
+    }
+
+    // JSONL records carry no archive header; only the text format can be unglobbed
+    if let (OutputFormat::Text, Some(output_file)) = (config.output_format, &mut config.output_file)
+    {
+        // Text archives always end with a footer, so unglob can insist on it
+        let header = llm_globber_core::format_header();
+        output_file
+            .write_all(header.as_bytes())
+            .map_err(|e| format!("Error writing format header to output file: {}", e))?;
+    }
+
+    if let (OutputFormat::Interop(layout), Some(output_file)) =
+        (config.output_format, &mut config.output_file)
+    {
+        output_file
+            .write_all(layout.header().as_bytes())
+            .map_err(|e| {
+                format!(
+                    "Error writing {} header to output file: {}",
+                    layout.name(),
+                    e
+                )
+            })?;
+    }
+
+    if config.front_matter.is_some() && config.output_format == OutputFormat::Text {
+        let lines = front_matter(config);
+        write_section(config, llm_globber_core::FRONT_MATTER_SECTION, &lines)
+            .map_err(|e| format!("Error writing front matter to output file: {}", e))?;
+    }
+
+    // Write public key at the start of the file if signature is enabled
+    if config.use_signature {
+        if let Some(public_key) = &config.public_key {
+            let encoded_pubkey = general_purpose::STANDARD.encode(public_key.to_bytes());
+            if let Some(output_file) = &mut config.output_file {
+                output_file
+                    .write_all(llm_globber_core::public_key_block(&encoded_pubkey).as_bytes())
+                    .map_err(|e| format!("Error writing public key to output file: {}", e))?;
+            }
+            info!("Added public key to output file");
+        }
+    }
+
+    // Recorded up front so that readers know before any entry that files may have changed
+    if !config.normalization.is_empty() && config.output_format == OutputFormat::Text {
+        let steps = config.normalization.steps();
+        write_section(config, llm_globber_core::NORMALIZATION_SECTION, &steps)
+            .map_err(|e| format!("Error writing normalization to output file: {}", e))?;
+    }
+
+    if let (true, OutputFormat::Text, Some(output_file)) =
+        (config.toc, config.output_format, &mut config.output_file)
+    {
+        config.toc_offset = output_file.position();
+    }
+
+    if config.dependencies && config.output_format == OutputFormat::Text {
+        let lines = dependency_summary(config);
+        write_section(config, "DEPENDENCIES", &lines)
+            .map_err(|e| format!("Error writing dependency summary to output file: {}", e))?;
+    }
+
+    if config.output_format == OutputFormat::Text {
+        for (path, lines) in config.prepend.clone() {
+            write_section(config, &format!("{}{}", PREPEND_SECTION, path), &lines)
+                .map_err(|e| format!("Error writing {} to output file: {}", path, e))?;
+        }
+    }
+
+    if let Some(output_file) = &mut config.output_file {
+        for (i, block) in config.boilerplate.iter().enumerate() {
+            output_file
+                .write_all(llm_globber_core::boilerplate_section(i + 1, block).as_bytes())
+                .map_err(|e| format!("Error writing boilerplate to output file: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+// Options that decide which files are included and how much of them, recorded by --front-matter
+const FRONT_MATTER_FILTERS: &[(&str, &str)] = &[
+    ("file_types", "types"),
+    ("all_files", "all"),
+    ("recursive", "recursive"),
+    ("name_pattern", "pattern"),
+    ("pattern_basename", "pattern-basename"),
+    ("ignore_case", "ignore-case"),
+    ("skip_pattern", "skip-pattern"),
+    ("max_size", "size"),
+    ("dot_files", "dot"),
+    ("no_default_excludes", "no-default-excludes"),
+    ("skip_empty", "skip-empty"),
+    ("no_tests", "no-tests"),
+    ("only_tests", "only-tests"),
+    ("include_vendored", "include-vendored"),
+    ("mime", "mime"),
+    ("exclude_mime", "exclude-mime"),
+    ("binary_threshold", "binary-threshold"),
+    ("force_text", "force-text"),
+    ("author", "author"),
+    ("changed_in", "changed-in"),
+    ("owner", "owner"),
+    ("max_files", "max-files"),
+    ("max_total_size", "max-total-size"),
+    ("budget_tokens", "budget-tokens"),
+    ("model", "model"),
+    ("over_budget", "over-budget"),
+    ("priority", "priority"),
+    ("entry", "entry"),
+    ("follow_imports", "follow-imports"),
+    ("sample", "sample"),
+    ("sample_by", "sample-by"),
+    ("sample_seed", "sample-seed"),
+    ("relevant_to", "relevant-to"),
+    ("outline", "outline"),
+    ("headers_only", "headers-only"),
+];
+
+// The parts of the front matter known from the command line. Values are written as JSON, which
+// is also valid YAML, so paths and patterns need no further quoting.
+fn command_front_matter(matches: &ArgMatches) -> Vec<String> {
+    let json = |value: &str| serde_json::Value::from(value).to_string();
+    let list = |values: Vec<String>| format!("[{}]", values.join(", "));
+    let mut lines = vec![
+        format!(
+            "generator: {}",
+            json(&format!("llm_globber {}", env!("CARGO_PKG_VERSION")))
+        ),
+        format!(
+            "command: {}",
+            list(std::env::args().map(|arg| json(&arg)).collect())
+        ),
+    ];
+    let inputs: Vec<String> = matches
+        .values_of("input_paths")
+        .into_iter()
+        .flatten()
+        .map(json)
+        .collect();
+    lines.push(format!("inputs: {}", list(inputs)));
+    let filters: Vec<String> = FRONT_MATTER_FILTERS
+        .iter()
+        .filter(|(id, _)| matches.is_present(id))
+        .map(|(id, flag)| {
+            let values: Vec<String> = matches
+                .values_of(id)
+                .into_iter()
+                .flatten()
+                .map(json)
+                .collect();
+            match values.len() {
+                0 => format!("  {}: true", flag),
+                1 => format!("  {}: {}", flag, values[0]),
+                _ => format!("  {}: {}", flag, list(values)),
+            }
+        })
+        .collect();
+    match filters.is_empty() {
+        true => lines.push("filters: {}".to_string()),
+        false => {
+            lines.push("filters:".to_string());
+            lines.extend(filters);
+        }
+    }
+    lines
+}
+
+// The whole front matter, once the files are known: the command line parts, then the time, the
+// header root with its git commit, and the files about to be written
+fn front_matter(config: &ScrapeConfig) -> Vec<String> {
+    let json = |value: &str| serde_json::Value::from(value).to_string();
+    let mut lines = config.front_matter.clone().unwrap_or_default();
+    lines.push(format!(
+        "created: {}",
+        json(&chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+    ));
+    if let Some(root) = &config.relative_root {
+        lines.push(format!("root: {}", json(&root.to_string_lossy())));
+        let commit = git_output_lines(&root.to_string_lossy(), &["rev-parse", "HEAD"], "")
+            .ok()
+            .and_then(|lines| lines.into_iter().next());
+        if let Some(commit) = commit {
+            lines.push(format!("git_commit: {}", json(&commit)));
+        }
+    }
+    let bytes: u64 = config
+        .file_entries
+        .iter()
+        .map(|entry| fs::metadata(&entry.path).map_or(0, |metadata| metadata.len()))
+        .sum();
+    lines.push(format!("files: {}", config.file_entries.len()));
+    lines.push(format!(
+        "estimated_tokens: {}",
+        estimate_tokens(bytes as usize, config.bytes_per_token)
+    ));
+    lines
+}
+
+// Append a generated section of plain lines to the archive
+fn write_section(config: &mut ScrapeConfig, name: &str, lines: &[String]) -> io::Result<()> {
+    let _lock = config
+        .output_mutex
+        .lock()
+        .expect("Output file mutex poisoned");
+    if let Some(output_file) = &mut config.output_file {
+        output_file.write_all(llm_globber_core::section(name, lines).as_bytes())?;
+    }
+    Ok(())
+}
+
+// Insert the table of contents once every entry is written, then carry on appending to the
+// rewritten file
+fn insert_toc(config: &mut ScrapeConfig, path: &Path) -> Result<(), String> {
+    let fail = |e: io::Error| format!("Error writing table of contents to output file: {}", e);
+    if let Some(output_file) = &mut config.output_file {
+        output_file.flush().map_err(fail)?;
+    }
+    toc::insert(path, config.toc_offset, &config.toc_entries).map_err(fail)?;
+    let output_file = File::options().append(true).open(path).map_err(fail)?;
+    let output_file = output::OutputFile::at_end(output_file, config.io_buffer_size);
+    config.output_file = Some(output_file.map_err(fail)?);
+    Ok(())
+}
+
+// What --metadata records for an entry: the size and line count of the content as written,
+// and the permissions and modification time of the file it came from
+fn entry_metadata(file_path: &Path, data: &[u8], is_binary: bool) -> llm_globber_core::Metadata {
+    let file_metadata = fs::metadata(file_path).ok();
+    let lines = data.iter().filter(|&&byte| byte == b'\n').count()
+        + usize::from(!data.is_empty() && !data.ends_with(b"\n"));
+    llm_globber_core::Metadata {
+        size: Some(data.len() as u64),
+        mtime: file_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|age| age.as_secs()),
+        mode: file_metadata.map(|metadata| metadata.permissions().mode() & 0o777),
+        lines: (!is_binary).then_some(lines),
+    }
+}
+
+// Count the entries and their content bytes and hash their paths, which unglob checks to catch
+// copies of the archive that lost their end. The hard links and empty directories are hashed
+// too, and signed archives sign the footer, since those sections carry no signatures of their own.
+fn write_footer(config: &mut ScrapeConfig) -> Result<(), String> {
+    let mut footer = Footer {
+        files: config.written_entries.len() as u64,
+        bytes: config.written_content_bytes,
+        manifest_digest: manifest_digest(config.written_entries.iter().map(String::as_str)),
+        links_digest: llm_globber_core::links_digest(
+            &config.written_links,
+            &config.written_empty_dirs,
+        ),
+        signature: None,
+    };
+    if let (Some(signer), true) = (&config.signer, config.use_signature) {
+        footer.signature = Some(sign_data(signer, footer.signed_content().as_bytes())?);
+    }
+    write_section(config, llm_globber_core::FOOTER_SECTION, &footer.lines())
+        .map_err(|e| format!("Error writing footer to output file: {}", e))
+}
+
+// End the archive with the time, signed together with the SHA-256 of everything written so
+// far, so the signature also proves when the archive was made
+fn write_timestamp(config: &mut ScrapeConfig, path: &Path) -> Result<(), String> {
+    let Some(signer) = &config.signer else {
+        return Ok(());
+    };
+    let fail = |e: io::Error| format!("Error writing timestamp to output file: {}", e);
+    if let Some(output_file) = &mut config.output_file {
+        output_file.flush().map_err(fail)?;
+    }
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path).map_err(fail)?, &mut hasher).map_err(fail)?;
+    let digest = hex_digest(hasher);
+    let time = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let signed = llm_globber_core::timestamp_signed_content(&time, &digest);
+    let signature = sign_data(signer, signed.as_bytes())?;
+    if let Some(output_file) = &mut config.output_file {
+        output_file
+            .write_all(llm_globber_core::timestamp_section(&time, &digest, &signature).as_bytes())
+            .map_err(fail)?;
+    }
+    info!("Signed timestamp {}", time);
     Ok(())
 }
 
+// Where the output ends up once --compress and --encrypt have added their extensions
+fn final_output_path(config: &ScrapeConfig, path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    if let Some(compression) = config.compress {
+        path = compression.compressed_path(&path);
+    }
+    if config.encrypt.is_some() {
+        path = encrypt::encrypted_path(&path);
+    }
+    path
+}
+
+// Expand --name-template into a path in the output directory. Git placeholders describe the
+// first --git repository, or the repository containing the header root otherwise.
+fn output_file_path(config: &ScrapeConfig, output_dir: &Path) -> Result<PathBuf, String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let git_dir = config
+        .git_repos
+        .first()
+        .map(|repo| repo.path.clone())
+        .or_else(|| {
+            config
+                .relative_root
+                .as_ref()
+                .map(|root| root.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| ".".to_string());
+    let require_git = |key: &str| -> Result<(), String> {
+        if config.git_repos.is_empty() && !is_git_repository(&git_dir) {
+            return Err(format!(
+                "Name template placeholder {{{}}} needs a git repository, but {} is not in one",
+                key, git_dir
+            ));
+        }
+        Ok(())
+    };
+    let uses_counter = config.name_template.contains("{counter");
+
+    let mut counter = 1;
+    loop {
+        let name = naming::expand(&config.name_template, |key, argument| match key {
+            "name" => Ok(config.output_filename.clone()),
+            "ts" => Ok(timestamp.to_string()),
+            "date" => naming::format_date(timestamp, argument),
+            "counter" => {
+                let width = match argument {
+                    Some(width) => width.parse::<usize>().map_err(|_| {
+                        format!("Invalid counter width '{}' in name template", width)
+                    })?,
+                    None => 1,
+                };
+                Ok(format!("{:0width$}", counter, width = width))
+            }
+            "repo" => {
+                require_git(key)?;
+                match config.git_repos.first() {
+                    Some(repo) => Ok(repo.name.clone()),
+                    None => {
+                        let (root, _) = get_git_root_and_prefix(&git_dir)?;
+                        get_git_repo_name(&root.to_string_lossy())
+                    }
+                }
+            }
+            "branch" => {
+                require_git(key)?;
+                get_git_branch(&git_dir)
+            }
+            "commit" | "shortsha" => {
+                require_git(key)?;
+                let format = if key == "commit" { "%H" } else { "%h" };
+                git_output_lines(
+                    &git_dir,
+                    &["log", "-1", &format!("--format={}", format)],
+                    "read the current commit",
+                )?
+                .into_iter()
+                .next()
+                .ok_or_else(|| format!("{} has no commits", git_dir))
+            }
+            other => Err(format!(
+                "Unknown placeholder {{{}}} in name template (known: name, ts, date, counter, \
+                 repo, branch, commit, shortsha)",
+                other
+            )),
+        })?;
+        let path = output_dir.join(format!("{}.{}", name, config.output_format.extension()));
+        // {counter} picks the first number that does not overwrite an earlier output
+        let taken = path.exists() || final_output_path(config, &path).exists();
+        if !uses_counter || !taken {
+            return Ok(path);
+        }
+        counter += 1;
+    }
+}
+
 fn parse_file_types(config: &mut ScrapeConfig, types_str: &str) {
     for ext in types_str.split(',') {
         let trimmed_ext = ext.trim();
@@ -457,111 +2130,582 @@ fn print_usage(program_name: &str) {
         program_name.cyan()
     );
     println!("\n{}", "Options:".yellow());
-    println!("  -o PATH        Output directory path");
+    println!("  -o PATH        Output directory path, or an s3:// or https:// URL to upload to");
     println!("  -n NAME        Output filename (without extension) - not required with --git or --unglob");
+    println!("      --name-template TEMPLATE  Output file name, e.g. '{{name}}_{{date:%Y%m%d}}_{{shortsha}}' (default: '{{name}}_{{ts}}')");
     println!("  -t TYPES       File types to include (comma separated, e.g. '.c,.h,.txt')");
     println!("  -a             Include all files (no filtering by type)");
     println!("  -r             Recursively process directories");
-    println!("  -N, --pattern PATTERN  Filter files by name pattern (glob syntax, e.g. '*.c')");
+    println!("      --bench        Glob into a throwaway file and report files/sec, MB/s and time per phase");
+    println!("      --resume       Keep state next to the output; after an interruption, run again with --resume to continue");
+    println!("      --keep-empty-dirs  Record directories with no included files so unglob recreates them");
+    println!("      --skip-empty   Leave out zero-byte files such as empty __init__.py and .gitkeep, counting them");
+    println!("      --no-tests     Leave out tests: tests/ directories, *_test.go, test_*.py, *.spec.ts and the like");
+    println!("      --only-tests   Include only tests and their fixtures, by the same conventions as --no-tests");
+    println!("      --include-vendored  Keep vendor/ and third_party/ code and files copyrighted by others");
+    println!("  -N, --pattern PATTERN  Filter files by name pattern (glob syntax, e.g. '*.c'; repeatable)");
+    println!("                         Files must match any pattern and none prefixed with '!'");
+    println!("                         Patterns with a '/' match the path relative to the input, e.g. 'src/**/*.rs'");
+    println!("      --pattern-basename  Match --pattern globs against file names only");
     println!("      --skip-pattern PATTERN  Skip files matching glob pattern (repeatable)");
+    println!("      --ignore-case  Match -t extensions and file name patterns regardless of case");
+    println!("      --max-files N  Maximum number of files to include (default: 100000)");
+    println!("      --retries N    Retry reads that fail with a transient error such as EIO up to N times (default: 0)");
+    println!("      --retry-delay MS  Milliseconds before the first retry, doubled after each one (default: 100)");
+    println!("      --on-overflow POLICY  When more files match than --max-files: truncate (default) or error");
+    println!(
+        "      --max-total-size MB  Stop adding files once the output would exceed MB megabytes"
+    );
+    println!("      --budget-tokens N  Fit the output in about N tokens, truncating the lowest priority files");
+    println!("      --model MODEL  Take the token budget and token estimate from a model preset");
+    println!("      --over-budget POLICY  Files that do not fit the budget: skip (default), outline or truncate");
+    println!("      --mime TYPES   Include only files of these sniffed MIME types (e.g. 'text/*,application/json')");
+    println!("      --exclude-mime TYPES  Exclude files of these sniffed MIME types");
+    println!("      --binary-threshold PERCENT  Treat files with more than PERCENT control bytes as binary (default: 10)");
+    println!("      --force-text PATTERN  Always include matching files as text (repeatable)");
+    println!("      --plugin FILE  Run each text file through a WASM filter that keeps, skips or rewrites it (repeatable)");
+    println!("      --transform PATTERN=CMD  Pipe files matching PATTERN through the shell command CMD (repeatable)");
+    println!("      --dependencies  Start with a DEPENDENCIES section summarising Cargo.toml, package.json, etc.");
+    println!(
+        "      --prepend FILE  Add FILE's contents in a section before the first file (repeatable)"
+    );
+    println!(
+        "      --append FILE  Add FILE's contents in a section after the last file (repeatable)"
+    );
+    println!("      --todos        Append a TODOS section listing every TODO/FIXME/HACK comment");
+    println!("      --front-matter  Start with a FRONT MATTER section recording how the archive was made");
+    println!("      --call-graph   Append a CALL GRAPH section listing what each function calls");
+    println!(
+        "      --skip-log     Append a SKIPPED FILES section listing every excluded file and why"
+    );
+    println!("      --no-default-excludes  Also walk .git, node_modules, target, dist, build, __pycache__, .venv");
+    println!("      --entry FILE   Entry point file to include (repeatable)");
+    println!("      --follow-imports  Include only the transitive imports of the --entry files");
+    println!("      --sort ORDER   Output order: path (default), size, mtime, ext, none");
+    println!("      --priority-pattern PATTERN  Emit matching files first (repeatable)");
+    println!("      --priority GLOB=WEIGHT,...  Emit files matching higher-weighted globs first");
+    println!("      --format FORMAT  Output format: text (default), chunks (JSONL for vector databases), tar, plain, or a repomix or files-to-prompt layout");
+    println!("      --chunk-tokens N  Maximum estimated tokens per chunk (default: 512)");
+    println!(
+        "      --chunk-overlap N  Estimated tokens shared by consecutive chunks (default: 64)"
+    );
+    println!(
+        "      --file-comments  Open each file with a `file: path` comment with --format plain"
+    );
+    println!("      --sample N     Include a random sample of N of the selected files");
+    println!("      --sample-by GROUP  Spread --sample evenly across dir or ext groups");
+    println!("      --sample-seed SEED Seed for --sample, to take the same sample again");
+    println!("      --plan FILE    Write the files that would be included to FILE instead of globbing them");
+    println!("      --files-from FILE  Also process the paths listed in FILE, one per line ('-' for stdin)");
+    println!("  -0, --null         Paths in the --files-from list are separated by NUL bytes");
+    println!("      --from-plan FILE  Include exactly the files listed in FILE, in order");
+    println!("      --from-trace FILE  Include only the files referenced in an error log or stack trace ('-' for stdin)");
+    println!("      --trace-context N  With --from-trace, include only N lines around each referenced line");
+    println!("      --relevant-to QUERY  Include only the files most relevant to QUERY (by shared keywords unless --embedding-model is given)");
+    println!("      --embedding-model MODEL  Rank --relevant-to files by the similarity of their embeddings from MODEL");
+    println!("      --embedding-url URL  OpenAI-compatible embeddings endpoint for --embedding-model (default: OpenAI)");
+    println!("      --top-k K      Maximum number of files kept by --relevant-to (default: 20)");
+    println!("      --token-budget TOKENS  Approximate token budget for --relevant-to");
+    println!("      --outline      Emit only declarations (signatures, types, doc comments) for Rust, Python, JS/TS, C and Go");
+    println!("      --render-notebooks  Render .ipynb files as markdown and code cells (raw JSON if unparseable)");
+    println!("      --table-sample N  For CSV/TSV files, keep the header, first/last N rows and a row count");
+    println!("      --sqlite-schema  Dump the schema and row counts of SQLite databases instead of omitting them");
+    println!("      --relative-to DIR  Record header paths relative to DIR (default: common ancestor of inputs)");
     println!("  -j THREADS     [Deprecated] Number of worker threads (always 1)");
     println!(
         "  -s SIZE        Maximum file size in MB (default: {})",
         DEFAULT_MAX_FILE_SIZE / (1024 * 1024)
     );
+    println!(
+        "      --mmap-threshold KB  Memory-map files of at least KB kilobytes (default: {})",
+        DEFAULT_MMAP_THRESHOLD / 1024
+    );
+    println!("      --no-mmap      Never memory-map files");
+    println!(
+        "      --no-space-check  Start clones and outputs without checking that they fit on disk"
+    );
+    println!("      --compress FORMAT  Compress the output with zstd or gzip (.zst or .gz)");
+    println!("      --encrypt RECIPIENT  Encrypt the output to an age recipient, e.g. age:age1... (repeatable)");
+    println!("      --encrypt-pass Encrypt the output with a passphrase (LLM_GLOBBER_PASSPHRASE or prompt)");
+    println!(
+        "      --identity FILE  age identity file for reading encrypted archives (repeatable)"
+    );
+    println!("      --hash-manifest  Also write each included file's SHA-256 to OUTPUT.sha256 (sha256sum format)");
+    println!("      --audit-log PATH  Append who ran the run, its files and their hashes, and the destination to PATH");
+    println!(
+        "      --toc          Insert a table of contents with each entry's byte offset and length"
+    );
+    println!("      --trim-trailing-whitespace  Remove spaces and tabs from the end of every line");
+    println!("      --expand-tabs N    Replace tabs with spaces, with tab stops every N columns");
+    println!("      --collapse-blank-lines N  Keep at most N consecutive blank lines in each file");
+    println!("      --collapse-boilerplate  Write blocks such as license headers that open many files once");
+    println!("      --headers-only   Write every entry's header with its content left out");
+    println!("      --metadata     Record each file's size, modification time, mode and line count in its header");
+    println!(
+        "      --io-buffer KB Buffer size for reading files and writing the output (default: {})",
+        DEFAULT_IO_BUFFER_SIZE / 1024
+    );
+    println!("      --fsync        Sync the output to disk when it is finished, and after each file with --resume");
     println!("  -d             Include dot files (hidden files)");
     println!("  -p             Show progress indicators");
     println!(
         "  -u, --unglob FILE  Extract files from a previously generated LLM Globber output file"
     );
+    println!(
+        "      --map SRC=DEST  When unglobbing, extract entries under SRC to DEST (repeatable)"
+    );
+    println!("      --backup[=orig|tree]  When unglobbing, copy files about to be overwritten to FILE.orig or .llm-globber-backup/");
+    println!("      --as-patch            When unglobbing, print a unified diff against the files on disk instead of writing them");
+    println!("      --max-extract-size MB  When unglobbing, refuse archives with a file over MB megabytes (default: 1024)");
+    println!("      --force               When unglobbing, extract into a non-empty directory that is not a clean git working tree");
     println!("  -e             Abort on errors (default is to continue)");
-    println!("  -v             Verbose output");
+    println!("  -v             Verbose output (-vv for debug detail, -vvv to trace content)");
     println!("  --debug        Print a DEBUG DUMP of the generated output file (to stderr)");
     println!("  -q             Quiet mode (suppress all output)");
+    println!(
+        "      --print-path  Print the absolute path of the output file on stdout, also with -q"
+    );
+    println!("      --pre-hook CMD  Shell command to run before globbing ($LLM_GLOBBER_INPUTS)");
+    println!("      --post-hook CMD  Shell command to run after the output is written ($LLM_GLOBBER_OUTPUT, $LLM_GLOBBER_FILES names a file listing what was written)");
+    println!("      --no-color Disable colored output (also off with NO_COLOR or when stderr is not a terminal)");
+    println!("      --log-file PATH  Also append log messages to PATH, even with -q");
+    println!("      --tmpdir PATH  Directory for git clones and other temporary files (default: system temp dir)");
+    println!(
+        "      --log-format FORMAT  Log record format: text (default) or json, one object per line"
+    );
     println!("  -h             Show this help message");
     println!("  --signature    Add ed25519 signatures to files when globbing and verify signatures when unglobbing");
-    println!("  --git PATH/URL Process a git repository from local path or clone from URL (auto-configures path, name, and files)");
+    println!("      --key-file FILE  Sign with an OpenSSH ed25519 key; when unglobbing, accept only that key");
+    println!(
+        "      --detached-sig  Also write a signature over the output's exact bytes to OUTPUT.sig"
+    );
+    println!("      --timestamp  End the archive with a signed timestamp covering its content");
+    println!(
+        "      --require-trusted-key  When unglobbing, refuse archives not signed by a trusted key"
+    );
+    println!("  --git PATH/URL Process a git repository from local path or clone from URL (auto-configures path, name, and files); repeatable");
+    println!("  --log N        With --git, append the last N commits (SHA, date, author, subject)");
+    println!(
+        "  --author NAME  With --git, include only files changed by commits from NAME (repeatable)"
+    );
+    println!("  --changed-in N With --git, include only files modified in the last N commits");
+    println!("  --owner OWNER  With --git, include only files owned by OWNER in CODEOWNERS, e.g. @org/team (repeatable)");
+    println!("\n{}", "Commands:".yellow());
+    println!("  lint FILE      Validate an output file (headers, duplicates, paths, markers, signatures)");
+    println!("  ls FILE        List the entries of an output file with their mode, size, modification time and lines");
+    println!("  cat FILE PATH  Print one entry's content, seeking to it directly when the file has a --toc table");
+    println!("  convert --to FORMAT [-o FILE] FILE  Rewrite an LLM Globber, repomix or files-to-prompt archive in another of these layouts");
+    println!("  grep [-i] [-F] PATTERN FILE  Search entry contents of an output file, printing path:line: match");
+    println!("  verify [--sig SIG_FILE] [--key-file KEY_FILE] [--require-trusted-key] FILE  Check FILE against its detached signature");
+    println!("  keygen [-o FILE | --print-pubkey KEY_FILE]  Create a signing key (default: the llm-globber key) or read one, printing its public key");
+    println!("  trust [--comment TEXT] KEY  Add a signer's public key, or an OpenSSH key file's, to the trust store");
+    println!("  clean-temp [--older-than HOURS]  Remove temporary directories left by unfinished runs (default: older than 24 hours)");
+    println!("  daemon [--socket PATH]  Serve runs over a unix socket, keeping walks, clones and relevance terms cached");
+    println!("  --ask-daemon ARGS...    Have a running daemon run ARGS in the current directory (--stop shuts it down)");
+}
+
+// An input with glob characters that names no path on disk, such as a quoted `src/**/*.rs`
+fn is_glob_input(input: &str) -> bool {
+    input.contains(['*', '?', '[']) && fs::symlink_metadata(input).is_err()
+}
+
+// Add the files and, with -r, directories a glob input matches, in sorted order, skipping the
+// same dotfiles and default-excluded directories a walk would. Returns the directory before the
+// first component with a glob character, which stands in for the input as a header root.
+fn expand_glob_input(config: &mut ScrapeConfig, pattern: &str) -> Result<Option<PathBuf>, String> {
+    let options = MatchOptions {
+        case_sensitive: !config.ignore_case,
+        require_literal_separator: true,
+        require_literal_leading_dot: config.no_dot_files,
+    };
+    let paths = glob_with(pattern, options)
+        .map_err(|e| format!("Invalid input pattern '{}': {}", pattern, e))?;
+    let base: PathBuf = Path::new(pattern)
+        .components()
+        .take_while(|component| {
+            !component
+                .as_os_str()
+                .to_string_lossy()
+                .contains(['*', '?', '['])
+        })
+        .collect();
+    let base = if base.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        base
+    };
+    let mut matched = 0;
+    for path in paths.filter_map(Result::ok) {
+        matched += 1;
+        let excluded = config.default_excludes
+            && path
+                .strip_prefix(&base)
+                .unwrap_or(&path)
+                .components()
+                .any(|component| {
+                    DEFAULT_EXCLUDED_DIRS
+                        .contains(&component.as_os_str().to_string_lossy().as_ref())
+                });
+        if excluded {
+            record_skip(config, &path, "default-excluded directory".to_string());
+            continue;
+        }
+        let path = match path.canonicalize() {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("Could not access path {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if path.is_dir() {
+            // `src/**` matches every directory as well as every file under src
+            if config.recursive {
+                config.pattern_roots.push(path.clone());
+                process_directory(config, &path)
+                    .map_err(|e| format!("Error processing directory {}: {}", path.display(), e))?;
+            }
+        } else if path.is_file()
+            && should_process_file(
+                config,
+                &path,
+                &path.file_name().unwrap_or_default().to_string_lossy(),
+            )
+        {
+            add_file_entry(config, &path);
+        }
+    }
+    info!("Input pattern {} matched {} path(s)", pattern, matched);
+    if matched == 0 {
+        warn!("No files match the input pattern {}", pattern);
+    }
+    Ok(sanitize_path(&base.to_string_lossy())
+        .ok()
+        .map(PathBuf::from))
+}
+
+// Add the paths listed in `source` ('-' for stdin), one per line or, with `null`, separated by NUL
+// bytes as `find -print0` writes them. The list is read as it arrives rather than all at once, so
+// lists of millions of paths need no more memory than the files they select. Paths are taken
+// literally, without glob expansion. Returns the directories the listed paths are in.
+fn add_files_from(
+    config: &mut ScrapeConfig,
+    source: &str,
+    null: bool,
+) -> Result<BTreeSet<PathBuf>, String> {
+    let reader: Box<dyn BufRead> = match source {
+        "-" => Box::new(io::stdin().lock()),
+        path => Box::new(BufReader::new(File::open(path).map_err(|e| {
+            format!("Failed to open --files-from list: {}: {}", path, e)
+        })?)),
+    };
+    let separator = if null { b'\0' } else { b'\n' };
+    let mut roots = BTreeSet::new();
+    let mut listed = 0usize;
+    for item in reader.split(separator) {
+        let mut item =
+            item.map_err(|e| format!("Failed to read --files-from list: {}: {}", source, e))?;
+        if !null && item.ends_with(b"\r") {
+            item.pop();
+        }
+        if item.is_empty() {
+            continue;
+        }
+        listed += 1;
+        if listed.is_multiple_of(100_000) {
+            info!("Read {} paths from {}", listed, source);
+        }
+        // Names need not be UTF-8, so they are resolved as they are rather than by sanitize_path
+        let item = PathBuf::from(OsString::from_vec(item));
+        let path = match item.canonicalize() {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("Could not access path {}: {}", item.display(), e);
+                continue;
+            }
+        };
+        if path.is_dir() {
+            // `find` lists directories along with their files, so they are only walked with -r
+            if config.recursive {
+                config.pattern_roots.push(path.clone());
+                process_directory(config, &path)
+                    .map_err(|e| format!("Error processing directory {}: {}", path.display(), e))?;
+                roots.insert(path.clone());
+            }
+        } else if path.is_file()
+            && should_process_file(
+                config,
+                &path,
+                &path.file_name().unwrap_or_default().to_string_lossy(),
+            )
+        {
+            roots.extend(path.parent().map(Path::to_path_buf));
+            add_file_entry(config, &path);
+        }
+    }
+    info!("Read {} paths from {}", listed, source);
+    Ok(roots)
 }
 
-fn process_directory(config: &mut ScrapeConfig, dir_path: &str) -> Result<(), String> {
-    let entries = fs::read_dir(dir_path)
-        .map_err(|e| format!("Failed to read directory {}: {}", dir_path, e))?;
-    for entry_result in entries {
-        let entry = entry_result.map_err(|e| format!("Failed to read directory entry: {}", e))?;
-        let full_path = entry.path();
-        let file_name = entry.file_name();
-        let file_name_str = file_name.to_string_lossy();
+// Paths are carried as they are on disk, which need not be UTF-8, and only turned into strings
+// for headers and messages
+fn process_directory(config: &mut ScrapeConfig, dir: &Path) -> Result<(), String> {
+    let entries = match &config.cache {
+        Some(cache) => cache
+            .lock()
+            .expect("Daemon cache mutex poisoned")
+            .list_dir(dir),
+        None => daemon::read_dir_entries(dir),
+    }
+    .map_err(|e| format!("Failed to read directory {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let full_path = dir.join(&entry.name);
+        let full_path = full_path.as_path();
+        let file_name_str = entry.name.to_string_lossy();
 
         if file_name_str == "." || file_name_str == ".." {
             continue;
         }
 
         if config.no_dot_files && file_name_str.starts_with('.') {
+            record_skip(config, full_path, "dotfile".to_string());
             continue;
         }
 
-        if full_path.is_dir() {
-            if config.recursive {
-                process_directory(config, &full_path.to_string_lossy())?;
+        if entry.is_dir {
+            if config.default_excludes && DEFAULT_EXCLUDED_DIRS.contains(&file_name_str.as_ref()) {
+                debug!(
+                    "Skipping default-excluded directory: {}",
+                    full_path.display()
+                );
+                record_skip(config, full_path, "default-excluded directory".to_string());
+                continue;
+            }
+            if !config.include_vendored && vendored::VENDOR_DIRS.contains(&file_name_str.as_ref()) {
+                debug!("Skipping vendored directory: {}", full_path.display());
+                record_skip(config, full_path, "vendored directory".to_string());
+                continue;
             }
-        } else if full_path.is_file() {
-            if should_process_file(config, &full_path.to_string_lossy(), &file_name_str) {
-                add_file_entry(config, &full_path.to_string_lossy());
+            if config.recursive {
+                if config.keep_empty_dirs {
+                    config.walked_dirs.push(full_path.to_path_buf());
+                }
+                process_directory(config, full_path)?;
             }
+        } else if entry.is_file && should_process_file(config, full_path, &file_name_str) {
+            add_file_entry(config, full_path);
         }
     }
     Ok(())
 }
 
-fn add_file_entry(config: &mut ScrapeConfig, path: &str) {
-    if config.file_entries.len() >= MAX_FILES {
-        warn!("Maximum file limit reached ({})", MAX_FILES);
+// Header paths of the walked directories with no included file below them. Directories holding
+// only other such directories are left out, as unglob creates them along the way.
+fn empty_dirs(config: &ScrapeConfig) -> Vec<String> {
+    let included = config
+        .file_entries
+        .iter()
+        .map(|entry| entry.path.as_path())
+        .chain(config.hard_links.iter().map(|(alias, _)| alias.as_path()));
+    let occupied: HashSet<&Path> = included.flat_map(|path| path.ancestors().skip(1)).collect();
+    let empty: Vec<&Path> = config
+        .walked_dirs
+        .iter()
+        .map(PathBuf::as_path)
+        .filter(|dir| !occupied.contains(dir))
+        .collect();
+    let parents: HashSet<&Path> = empty.iter().filter_map(|dir| dir.parent()).collect();
+    empty
+        .into_iter()
+        .filter(|dir| !parents.contains(dir))
+        .map(|dir| header_path(config, dir))
+        .collect()
+}
+
+fn add_file_entry(config: &mut ScrapeConfig, path: &Path) {
+    // Overlapping inputs, such as a directory and a file inside it or the same directory given
+    // twice, reach the same file more than once; the first path it was reached by is kept
+    let canonical = canonical_file_path(path).unwrap_or_else(|_| path.to_path_buf());
+    if !config.seen_files.insert(canonical) {
+        debug!("Skipping duplicate input: {}", path.display());
+        return;
+    }
+    // Hard links, common in package caches and Nix stores, are one file under several names. In
+    // text archives it is included once and the other names are listed as aliases of it.
+    if config.output_format == OutputFormat::Text {
+        if let Some(metadata) = fs::metadata(path)
+            .ok()
+            .filter(|metadata| metadata.nlink() > 1)
+        {
+            match config.linked_files.get(&(metadata.dev(), metadata.ino())) {
+                Some(target) => {
+                    debug!("{} is a hard link to {}", path.display(), target.display());
+                    config.hard_links.push((path.to_path_buf(), target.clone()));
+                    return;
+                }
+                None => {
+                    config
+                        .linked_files
+                        .insert((metadata.dev(), metadata.ino()), path.to_path_buf());
+                }
+            }
+        }
+    }
+    if config.file_entries.len() >= config.max_files {
+        if config.overflow_files == 0 {
+            warn!("Maximum file limit reached ({})", config.max_files);
+        }
+        config.overflow_files += 1;
+        record_skip(config, path, "over --max-files".to_string());
         return;
     }
     config.file_entries.push(FileEntry {
-        path: path.to_string(),
+        path: path.to_path_buf(),
     });
 }
 
-#[allow(dead_code)]
-fn is_directory(path: &str) -> bool {
-    fs::metadata(path).map(|m| m.is_dir()).unwrap_or(false)
+// Index of the first priority pattern matching the file, or None if it has no priority
+fn priority_rank(config: &ScrapeConfig, file_path: &Path) -> Option<usize> {
+    let entry_path = header_path(config, file_path);
+    let base_name = Path::new(file_path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("");
+    config.priority_patterns.iter().position(|pattern| {
+        pattern.matches_with(base_name, match_options(config))
+            || pattern.matches_with(&entry_path, match_options(config))
+    })
 }
 
-fn is_regular_file(path: &str) -> bool {
-    fs::metadata(path).map(|m| m.is_file()).unwrap_or(false)
+// Highest --priority weight among the patterns matching the file, or 0 if none match
+fn priority_weight(config: &ScrapeConfig, file_path: &Path) -> u32 {
+    let entry_path = header_path(config, file_path);
+    let base_name = file_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    config
+        .priority_weights
+        .iter()
+        .filter(|(pattern, _)| {
+            pattern.matches_with(base_name, match_options(config))
+                || pattern.matches_with(&entry_path, match_options(config))
+        })
+        .map(|(_, weight)| *weight)
+        .max()
+        .unwrap_or(0)
 }
 
-fn get_file_size(path: &str) -> io::Result<u64> {
-    fs::metadata(path).map(|m| m.len())
+// Parse --priority 'src/**=10,docs/**=1' into patterns and their weights
+fn parse_priority_weights(value: &str) -> Result<Vec<(Pattern, u32)>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let invalid = || format!("Invalid --priority entry '{}': expected GLOB=WEIGHT", item);
+            let (glob, weight) = item.rsplit_once('=').ok_or_else(invalid)?;
+            let weight = weight.trim().parse::<u32>().map_err(|_| invalid())?;
+            let pattern = Pattern::new(glob.trim())
+                .map_err(|e| format!("Invalid priority pattern '{}': {}", glob, e))?;
+            Ok((pattern, weight))
+        })
+        .collect()
+}
+
+// Put the collected entries into a stable order: priority files first, then by --sort key
+fn sort_file_entries(config: &mut ScrapeConfig) {
+    let entries = std::mem::take(&mut config.file_entries);
+    let mut keyed: Vec<_> = entries
+        .into_iter()
+        .map(|entry| {
+            let rank = (
+                Reverse(priority_weight(config, &entry.path)),
+                priority_rank(config, &entry.path).unwrap_or(usize::MAX),
+            );
+            let metadata = fs::metadata(&entry.path).ok();
+            let numeric_key = match config.sort_order {
+                SortOrder::Size => metadata.map(|m| m.len()).unwrap_or(0),
+                SortOrder::Mtime => metadata
+                    .and_then(|m| m.modified().ok())
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                _ => 0,
+            };
+            let path = entry.path.as_os_str().as_bytes();
+            let text_key = match config.sort_order {
+                SortOrder::Ext => {
+                    let extension = entry.path.extension().unwrap_or_default().as_bytes();
+                    [extension, b"\0", path].concat()
+                }
+                SortOrder::None => Vec::new(),
+                _ => path.to_vec(),
+            };
+            (rank, numeric_key, text_key, entry)
+        })
+        .collect();
+
+    // sort_by is stable, so SortOrder::None keeps discovery order within each priority group
+    keyed.sort_by(|a, b| (a.0, a.1, &a.2).cmp(&(b.0, b.1, &b.2)));
+    config.file_entries = keyed.into_iter().map(|(_, _, _, entry)| entry).collect();
+}
+
+#[allow(dead_code)]
+fn is_directory(path: &str) -> bool {
+    fs::metadata(path).map(|m| m.is_dir()).unwrap_or(false)
+}
+
+fn is_regular_file(path: impl AsRef<Path>) -> bool {
+    fs::metadata(path).map(|m| m.is_file()).unwrap_or(false)
+}
+
+fn estimate_tokens(bytes: usize, bytes_per_token: f64) -> usize {
+    (bytes as f64 / bytes_per_token).ceil() as usize
+}
+
+fn get_file_size(path: impl AsRef<Path>) -> io::Result<u64> {
+    fs::metadata(path).map(|m| m.len())
 }
 
 #[allow(dead_code)]
 fn is_binary_file(path: &str) -> io::Result<bool> {
     let file = File::open(path)?;
     let mut reader = BufReader::new(file);
-    let mut buffer = [0u8; 4096];
+    let mut buffer = [0u8; 8192];
     let bytes_read = reader.read(&mut buffer)?;
 
-    Ok(is_binary_data(&buffer[..bytes_read]))
+    let (kind, _) = detect::classify(path, &buffer[..bytes_read], DEFAULT_BINARY_THRESHOLD, false);
+    Ok(kind == ContentKind::Binary)
 }
 
-fn is_binary_data(data: &[u8]) -> bool {
-    let check_limit = std::cmp::min(data.len(), 4096);
-    if check_limit == 0 {
-        return false;
+// Classify content, applying --force-text and --binary-threshold.
+// UTF-16 text comes back decoded to UTF-8.
+// Run --transform commands on the content as read, before it is classified, so a command can
+// turn an encrypted or binary file into text
+fn transform_content(
+    config: &ScrapeConfig,
+    file_path: &Path,
+    data: &[u8],
+) -> io::Result<Option<Vec<u8>>> {
+    if config.transforms.is_empty() {
+        return Ok(None);
     }
+    transforms::apply(&config.transforms, file_path, data).map_err(io::Error::other)
+}
 
-    let mut non_printable = 0;
-    for &byte in &data[..check_limit] {
-        if byte == 0 || (byte < 32 && byte != b'\n' && byte != b'\r' && byte != b'\t') {
-            non_printable += 1;
-            if non_printable > 5 && (non_printable * 100 / check_limit) > 10 {
-                return true;
-            }
-        }
-    }
-    (non_printable * 100 / check_limit) > 10
+fn classify_content(
+    config: &ScrapeConfig,
+    file_path: &Path,
+    data: &[u8],
+) -> (ContentKind, Option<String>) {
+    let base_name = file_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let force_text = config
+        .force_text_patterns
+        .iter()
+        .any(|pattern| matches_file(config, pattern, file_path, base_name));
+    detect::classify(
+        &file_path.to_string_lossy(),
+        data,
+        config.binary_threshold,
+        force_text,
+    )
 }
 
 #[allow(dead_code)]
@@ -569,21 +2713,75 @@ fn is_dot_file(file_path: &str) -> bool {
     Path::new(file_path)
         .file_name()
         .and_then(|name| name.to_str())
-        .map_or(false, |name| name.starts_with('.'))
+        .is_some_and(|name| name.starts_with('.'))
 }
 
-fn is_allowed_file_type(config: &ScrapeConfig, file_path: &str) -> bool {
+fn is_allowed_file_type(config: &ScrapeConfig, file_path: &Path) -> bool {
     if !config.filter_files || config.file_type_hash.is_empty() {
         return true;
     }
 
-    Path::new(file_path)
+    let Some(ext_with_dot) = file_path
         .extension()
         .and_then(|ext| ext.to_str())
         .map(|extension| format!(".{}", extension))
-        .map_or(false, |ext_with_dot| {
-            config.file_type_hash.contains(&ext_with_dot)
-        })
+    else {
+        return false;
+    };
+    if config.ignore_case {
+        config
+            .file_type_hash
+            .iter()
+            .any(|ext| ext.eq_ignore_ascii_case(&ext_with_dot))
+    } else {
+        config.file_type_hash.contains(&ext_with_dot)
+    }
+}
+
+// The options every file name pattern is matched with
+fn match_options(config: &ScrapeConfig) -> MatchOptions {
+    MatchOptions {
+        case_sensitive: !config.ignore_case,
+        ..MatchOptions::new()
+    }
+}
+
+// True if a pattern matches a file's name or its path
+fn matches_file(
+    config: &ScrapeConfig,
+    pattern: &Pattern,
+    file_path: &Path,
+    base_name: &str,
+) -> bool {
+    filter::matches_file(pattern, file_path, base_name, match_options(config))
+}
+
+// True if a --pattern glob matches a file: by its path relative to the input it was found in
+// for globs with a `/`, and otherwise by name. Files not found by walking an input, and every
+// file with --pattern-basename, are matched by name alone.
+fn matches_name_pattern(
+    config: &ScrapeConfig,
+    pattern: &Pattern,
+    file_path: &Path,
+    base_name: &str,
+) -> bool {
+    let relative = input_relative(config, file_path).filter(|_| !config.pattern_basename);
+    filter::matches_name_pattern(pattern, relative, base_name, match_options(config))
+}
+
+// The input a file was found by walking, if it was
+fn input_root<'a>(config: &'a ScrapeConfig, file_path: &Path) -> Option<&'a Path> {
+    config
+        .pattern_roots
+        .iter()
+        .map(PathBuf::as_path)
+        .find(|root| file_path.starts_with(root))
+}
+
+// A file's path relative to the input it was found by walking
+fn input_relative<'a>(config: &ScrapeConfig, file_path: &'a Path) -> Option<&'a Path> {
+    let root = input_root(config, file_path)?;
+    file_path.strip_prefix(root).ok()
 }
 
 fn set_secure_file_permissions(path: &PathBuf) -> Result<(), String> {
@@ -593,6 +2791,18 @@ fn set_secure_file_permissions(path: &PathBuf) -> Result<(), String> {
     Ok(())
 }
 
+// Point the output at a local directory, or at an s3:// or http(s):// destination to upload to
+fn set_output_destination(config: &mut ScrapeConfig, output_path: &str) -> Result<(), String> {
+    if remote::is_remote(output_path) {
+        remote::validate(output_path)?;
+        config.remote_output = Some(output_path.to_string());
+        return Ok(());
+    }
+    config.output_path = sanitize_path(output_path)
+        .map_err(|e| format!("Invalid output path: {}: {}", output_path, e))?;
+    Ok(())
+}
+
 fn sanitize_path(path: &str) -> io::Result<String> {
     // Check for empty paths
     if path.trim().is_empty() {
@@ -625,26 +2835,149 @@ fn sanitize_path(path: &str) -> io::Result<String> {
     Ok(canonical_path.to_string_lossy().to_string())
 }
 
+// Canonicalize a file's directory but not the file itself, so symlinked files keep their own name
+fn canonical_file_path(file_path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let path = file_path.as_ref();
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Ok(parent.canonicalize()?.join(file_name))
+}
+
+// Deepest directory containing every path in the list
+fn common_ancestor(paths: &[PathBuf]) -> Option<PathBuf> {
+    let mut ancestor = paths.first()?.clone();
+    for path in &paths[1..] {
+        while !path.starts_with(&ancestor) {
+            if !ancestor.pop() {
+                return None;
+            }
+        }
+    }
+    Some(ancestor)
+}
+
+// A path as written in a header: in NFC, so a name macOS stores decomposed reads the same as on
+// Linux, and escaped throughout a text archive and wherever else the name could not be written
+// as it is
+fn header_string(config: &ScrapeConfig, path: &Path) -> String {
+    let normalized;
+    let bytes = match path.to_str() {
+        Some(path) => {
+            normalized = path.nfc().collect::<String>();
+            normalized.as_bytes()
+        }
+        None => path.as_os_str().as_bytes(),
+    };
+    if config.output_format == OutputFormat::Text || llm_globber_core::path_needs_escape(bytes) {
+        llm_globber_core::escape_path(bytes)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+// Path recorded in an entry header: relative to the configured root when the file lives under it
+fn header_path(config: &ScrapeConfig, file_path: &Path) -> String {
+    if !config.source_prefixes.is_empty() {
+        if let Ok(canonical) = canonical_file_path(file_path) {
+            // The innermost source wins when one source lies inside another
+            let source = config
+                .source_prefixes
+                .iter()
+                .filter(|(root, _)| canonical.starts_with(root))
+                .max_by_key(|(root, _)| root.components().count());
+            if let Some((root, prefix)) = source {
+                let relative = canonical.strip_prefix(root).unwrap_or(&canonical);
+                // A source that is a single file, such as a URL input, is named by its prefix alone
+                if relative.as_os_str().is_empty() {
+                    return prefix.clone();
+                }
+                return header_string(config, &Path::new(prefix).join(relative));
+            }
+        }
+    }
+    if let Some(root) = &config.relative_root {
+        if let Ok(canonical) = canonical_file_path(file_path) {
+            if let Ok(relative) = canonical.strip_prefix(root) {
+                return header_string(config, relative);
+            }
+        }
+        debug!(
+            "{} is outside {}, keeping path as given",
+            file_path.display(),
+            root.display()
+        );
+    }
+    header_string(config, file_path)
+}
+
 fn process_file_mmap(
     config: &mut ScrapeConfig,
-    file_path: &str,
+    file_path: &Path,
     _file_size: u64,
 ) -> io::Result<()> {
-    let file = File::open(file_path)?;
-    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    let mmap = read_with_retry(config, file_path, || {
+        let file = File::open(file_path)?;
+        unsafe { MmapOptions::new().map(&file) }
+    })?;
+    config.timings.bytes += mmap.len() as u64;
 
-    let is_binary = is_binary_data(&mmap);
-    write_file_content(config, file_path, &mmap, is_binary)?;
+    let transformed = transform_content(config, file_path, &mmap)?;
+    let raw = transformed.as_deref().unwrap_or(&mmap);
+    let (kind, decoded) = classify_content(config, file_path, raw);
+    let data = decoded.as_ref().map_or(raw, |text| text.as_bytes());
+    write_file_content(config, file_path, data, kind)?;
     Ok(())
 }
 
-fn should_process_file(config: &ScrapeConfig, file_path: &str, base_name: &str) -> bool {
+fn should_process_file(config: &mut ScrapeConfig, file_path: &Path, base_name: &str) -> bool {
+    // Each input's license is read once, when the first file found in it is checked
+    if !config.include_vendored {
+        if let Some(root) = input_root(config, file_path).map(Path::to_path_buf) {
+            config
+                .project_holders
+                .entry(root)
+                .or_insert_with_key(|root| {
+                    let holders = vendored::project_holders(root);
+                    debug!("Copyright holders of {}: {:?}", root.display(), holders);
+                    holders
+                });
+        }
+    }
+    match skip_reason(config, file_path, base_name) {
+        Some(reason) => {
+            if reason == EMPTY_SKIP_REASON {
+                config.skipped_empty += 1;
+            }
+            record_skip(config, file_path, reason);
+            false
+        }
+        None => true,
+    }
+}
+
+// Remember why a file was left out, for the SKIPPED FILES section written with --skip-log
+fn record_skip(config: &mut ScrapeConfig, file_path: &Path, reason: String) {
+    if config.skip_log {
+        config.skipped_files.push((file_path.to_path_buf(), reason));
+    }
+}
+
+// Skip reason of zero-byte files left out with --skip-empty, which are counted
+const EMPTY_SKIP_REASON: &str = "empty";
+
+// Why a file should not be included, or None if it passes every filter
+fn skip_reason(config: &ScrapeConfig, file_path: &Path, base_name: &str) -> Option<String> {
     if base_name.starts_with('.') {
         if config.no_dot_files {
-            debug!("Skipping dot file: {}", file_path);
-            return false;
+            debug!("Skipping dot file: {}", file_path.display());
+            return Some("dotfile".to_string());
         } else {
-            warn!("Including dot file: {}", file_path);
+            warn!("Including dot file: {}", file_path.display());
         }
     }
 
@@ -652,31 +2985,51 @@ fn should_process_file(config: &ScrapeConfig, file_path: &str, base_name: &str)
         && config
             .skip_patterns
             .iter()
-            .any(|pattern| pattern.matches(base_name) || pattern.matches_path(Path::new(file_path)))
+            .any(|pattern| matches_file(config, pattern, file_path, base_name))
     {
-        debug!("Skipping file '{}' due to skip pattern", file_path);
-        return false;
+        debug!(
+            "Skipping file '{}' due to skip pattern",
+            file_path.display()
+        );
+        return Some("skip pattern".to_string());
     }
 
     if let Ok(file_size) = get_file_size(file_path) {
         if file_size > config.max_file_size {
             warn!(
                 "Skipping file {}: size exceeds limit ({} > {})",
-                file_path, file_size, config.max_file_size
+                file_path.display(),
+                file_size,
+                config.max_file_size
             );
-            return false;
+            return Some(format!(
+                "too large ({} > {} bytes)",
+                file_size, config.max_file_size
+            ));
+        }
+        if file_size == 0 && config.skip_empty {
+            debug!("Skipping empty file: {}", file_path.display());
+            return Some(EMPTY_SKIP_REASON.to_string());
         }
     } else {
-        return false; // Could not get file size, skip it
+        return Some("unreadable".to_string()); // Could not get file size, skip it
     }
 
-    if !config.name_pattern.is_empty() {
-        match glob_match(&config.name_pattern, base_name) {
-            Ok(false) => return false,
-            Err(e) => {
-                warn!("Pattern matching error: {}", e);
-                return false;
-            }
+    // Positive patterns are alternatives and negative ones exclusions: a file must match at
+    // least one positive pattern, if there are any, and no negative one
+    let matches_name =
+        |pattern: &Pattern| matches_name_pattern(config, pattern, file_path, base_name);
+    if (!config.name_patterns.is_empty() && !config.name_patterns.iter().any(matches_name))
+        || config.excluded_name_patterns.iter().any(matches_name)
+    {
+        return Some("name pattern".to_string());
+    }
+
+    if config.test_files != TestFiles::Keep {
+        let relative = input_relative(config, file_path).unwrap_or(Path::new(base_name));
+        match (config.test_files, testfiles::is_test_file(relative)) {
+            (TestFiles::Skip, true) => return Some("test file".to_string()),
+            (TestFiles::Only, false) => return Some("not a test file".to_string()),
             _ => {}
         }
     }
@@ -685,15 +3038,72 @@ fn should_process_file(config: &ScrapeConfig, file_path: &str, base_name: &str)
         && !config.file_type_hash.is_empty()
         && !is_allowed_file_type(config, file_path)
     {
-        return false;
+        return Some("file type".to_string());
+    }
+
+    if !config.include_vendored {
+        if let Some(reason) = vendored_skip_reason(config, file_path) {
+            return Some(reason);
+        }
+    }
+
+    if !config.mime_include.is_empty() || !config.mime_exclude.is_empty() {
+        return mime_skip_reason(config, file_path);
     }
 
-    true
+    None
+}
+
+// Whether a file is third-party code: under a vendor directory of its input, or opening with a
+// copyright notice that names someone other than the project's license does
+fn vendored_skip_reason(config: &ScrapeConfig, file_path: &Path) -> Option<String> {
+    let root = input_root(config, file_path)?;
+    if input_relative(config, file_path).is_some_and(vendored::in_vendor_dir) {
+        return Some("vendored directory".to_string());
+    }
+    let holders = config.project_holders.get(root)?;
+    if holders.is_empty() {
+        return None;
+    }
+    let mut head = Vec::with_capacity(4096);
+    File::open(file_path)
+        .and_then(|file| file.take(4096).read_to_end(&mut head))
+        .ok()?;
+    let holder = vendored::foreign_holder(&String::from_utf8_lossy(&head), holders)?;
+    debug!("Skipping {}: copyright {}", file_path.display(), holder);
+    Some(format!("vendored (copyright {})", holder))
 }
 
-fn glob_match(pattern: &str, name: &str) -> Result<bool, String> {
-    let pattern = Pattern::new(pattern).map_err(|e| format!("Pattern error: {}", e))?;
-    Ok(pattern.matches(name))
+// Check --mime / --exclude-mime against the type sniffed from the start of the file
+fn mime_skip_reason(config: &ScrapeConfig, file_path: &Path) -> Option<String> {
+    let mut head = Vec::with_capacity(8192);
+    if let Err(e) = File::open(file_path).and_then(|file| file.take(8192).read_to_end(&mut head)) {
+        warn!(
+            "Could not read {} to detect its type: {}",
+            file_path.display(),
+            e
+        );
+        return Some("unreadable".to_string());
+    }
+    let mime = detect::sniff_mime(&file_path.to_string_lossy(), &head, config.binary_threshold);
+    let included = config.mime_include.is_empty()
+        || config
+            .mime_include
+            .iter()
+            .any(|pattern| detect::mime_matches(pattern, &mime));
+    let excluded = config
+        .mime_exclude
+        .iter()
+        .any(|pattern| detect::mime_matches(pattern, &mime));
+    if !included || excluded {
+        debug!(
+            "Skipping file '{}' due to MIME type {}",
+            file_path.display(),
+            mime
+        );
+        return Some(format!("MIME type {}", mime));
+    }
+    None
 }
 
 fn _glob_match_alt(pattern: &str, name: &str) -> Result<bool, String> {
@@ -710,122 +3120,680 @@ fn _glob_match_alt(pattern: &str, name: &str) -> Result<bool, String> {
     Ok(false)
 }
 
+// Approximate bytes an entry adds to the output: content plus header and markers
+fn entry_cost(config: &ScrapeConfig, entry_path: &str, content_len: usize) -> u64 {
+    let signature_len = if config.use_signature { 101 } else { 0 };
+    (content_len + entry_path.len() + ENTRY_OVERHEAD_BYTES + signature_len) as u64
+}
+
+// The whole lines of `source` that fit in `max_len` bytes along with the truncation marker, or
+// None if not even one does. They end at the last of the `boundaries` (0-based lines between
+// declarations) that keeps at least half of them, so the last declaration is not cut in two.
+fn truncate_lines(source: &str, max_len: usize, boundaries: &[usize]) -> Option<String> {
+    let opening = format!(
+        "{}truncated to fit the budget]\n",
+        llm_globber_core::RENDERED_MARKER_PREFIX
+    );
+    let keep = max_len.checked_sub(opening.len() + TRUNCATED_MARKER.len())?;
+    let mut end = source.as_bytes()[..keep.min(source.len())]
+        .iter()
+        .rposition(|&byte| byte == b'\n')?
+        + 1;
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    if let Some(cut) = boundaries
+        .iter()
+        .filter_map(|&line| line_starts.get(line).copied())
+        .filter(|&cut| cut <= end && cut * 2 >= end)
+        .max()
+    {
+        end = cut;
+    }
+    Some(format!("{}{}{}", opening, &source[..end], TRUNCATED_MARKER))
+}
+
+// The budget in force, as the user gave it
+fn budget_description(config: &ScrapeConfig) -> String {
+    match config.budget_tokens {
+        Some(tokens) => format!("--budget-tokens budget of {} tokens", tokens),
+        None => format!(
+            "--max-total-size budget of {} MB",
+            config.max_total_size.unwrap_or(0) / (1024 * 1024)
+        ),
+    }
+}
+
+// The option that set the budget, for skip reasons
+fn budget_flag(config: &ScrapeConfig) -> &'static str {
+    match config.budget_tokens {
+        Some(_) => "--budget-tokens",
+        None => "--max-total-size",
+    }
+}
+
+fn has_extension(file_path: impl AsRef<Path>, extension: &str) -> bool {
+    file_path
+        .as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+// Readable replacement for a file's content when a rendering option applies to it, opening with
+// its `[Rendered: ...]` line. Binary files with a rendering (such as SQLite schemas) are then
+// written as rendered entries too.
+fn render_content(
+    config: &ScrapeConfig,
+    file_path: &Path,
+    data: &[u8],
+    is_binary: bool,
+) -> Option<String> {
+    if config.sqlite_schema && sqlite::is_sqlite_file(file_path, data) {
+        match sqlite::dump_schema(file_path) {
+            Ok(schema) => return Some(schema),
+            Err(e) => warn!("Could not dump SQLite schema: {}", e),
+        }
+    }
+    if is_binary {
+        return None;
+    }
+    let source = str::from_utf8(data).ok()?;
+    if let Some(context) = config.trace_context {
+        let lines = canonical_file_path(file_path)
+            .ok()
+            .and_then(|path| config.trace_lines.get(&path));
+        if let Some(excerpt) = lines.and_then(|lines| trace::excerpt(source, lines, context)) {
+            return Some(excerpt);
+        }
+    }
+    if config.render_notebooks && has_extension(file_path, "ipynb") {
+        match notebook::render_notebook(source) {
+            Ok(rendered) => return Some(rendered),
+            Err(e) => warn!(
+                "Including notebook {} as raw JSON: {}",
+                file_path.display(),
+                e
+            ),
+        }
+    }
+    if let (Some(rows), true) = (
+        config.table_sample,
+        table::is_table_file(&file_path.to_string_lossy()),
+    ) {
+        return table::sample_table(source, rows);
+    }
+    if config.outline {
+        return outline::outline(&file_path.to_string_lossy(), source);
+    }
+    None
+}
+
 fn write_file_content(
     config: &mut ScrapeConfig,
-    file_path: &str,
+    file_path: &Path,
     data: &[u8],
-    is_binary: bool,
+    kind: ContentKind,
 ) -> io::Result<()> {
+    let entry_path = header_path(config, file_path);
+    let is_binary = kind != ContentKind::Text;
+
+    // Scan the file as it is on disk so line numbers match the source, not a rendered view
+    let todos = match (config.todos, is_binary, str::from_utf8(data)) {
+        (true, false, Ok(source)) => todos::find_todos(source),
+        _ => Vec::new(),
+    };
+    let calls = match (config.call_graph, is_binary, str::from_utf8(data)) {
+        (true, false, Ok(source)) => callgraph::call_graph(&file_path.to_string_lossy(), source),
+        _ => Vec::new(),
+    };
+
+    // Rendered views replace the content before signing so signatures cover what is written
+    let rendered = render_content(config, file_path, data, is_binary);
+    let is_binary = is_binary && rendered.is_none();
+    let mut is_rendered = rendered.is_some();
+    let data = rendered.as_ref().map_or(data, |text| text.as_bytes());
+
+    // Plugins see the content as it would otherwise be written, so they can redact renders too
+    let mut transformed: Option<Vec<u8>> = None;
+    if !is_binary && !config.plugins.is_empty() {
+        match plugins::apply(&config.plugins, &entry_path, data).map_err(io::Error::other)? {
+            plugins::Verdict::Keep => {}
+            plugins::Verdict::Skip => {
+                record_skip(config, file_path, "skipped by a plugin".to_string());
+                return Ok(());
+            }
+            plugins::Verdict::Transform(content) => transformed = Some(content),
+        }
+    }
+    let data = transformed.as_deref().unwrap_or(data);
+    // Policy redactions come after plugins, so nothing a plugin writes escapes them
+    let redacted = match (&config.policy, is_binary, str::from_utf8(data)) {
+        (Some(policy), false, Ok(text)) => policy.redact(&entry_path, text),
+        _ => None,
+    };
+    let data = redacted.as_deref().map_or(data, str::as_bytes);
+    // Calls are listed even for files the budget leaves out, which is when they help most
+    config.call_graph_lines.extend(
+        calls.into_iter().map(|(caller, callees)| {
+            format!("{}: {} -> {}", entry_path, caller, callees.join(", "))
+        }),
+    );
+
+    // Normalize whitespace last, so signatures and hashes cover the content as written
+    let normalized = match (is_binary, str::from_utf8(data)) {
+        (false, Ok(text)) => config.normalization.apply(text),
+        _ => None,
+    };
+    let data = normalized.as_ref().map_or(data, |text| text.as_bytes());
+    // With --headers-only only the marker is written, whatever the content
+    let written_len = |data: &[u8]| match config.headers_only {
+        true => HEADERS_ONLY_MARKER.len(),
+        false => data.len(),
+    };
+
+    // Enforce --max-total-size before anything for this entry is written
+    let mut budget_outline: Option<String> = None;
+    if let Some(budget) = config.max_total_size {
+        let cost = entry_cost(config, &entry_path, written_len(data));
+        if config.output_bytes + cost > budget {
+            let flag = budget_flag(config);
+            let source = match (is_binary || config.headers_only, str::from_utf8(data)) {
+                (false, Ok(source)) => Some(source),
+                _ => None,
+            };
+            let outlined = match (config.over_budget, source) {
+                (OverBudgetPolicy::Outline | OverBudgetPolicy::Truncate, Some(source)) => {
+                    outline::outline(&file_path.to_string_lossy(), source).filter(|text| {
+                        config.output_bytes + entry_cost(config, &entry_path, text.len()) <= budget
+                    })
+                }
+                _ => None,
+            };
+            let truncated = match (outlined.is_none(), config.over_budget, source) {
+                (true, OverBudgetPolicy::Truncate, Some(source)) => {
+                    let room = budget
+                        .saturating_sub(config.output_bytes + entry_cost(config, &entry_path, 0));
+                    truncate_lines(
+                        source,
+                        room as usize,
+                        &outline::boundaries(&file_path.to_string_lossy(), source),
+                    )
+                }
+                _ => None,
+            };
+            match (outlined, truncated) {
+                (Some(text), _) => {
+                    info!("Outlining {} to stay within {}", file_path.display(), flag);
+                    budget_outline = Some(text);
+                }
+                (None, Some(text)) => {
+                    info!("Truncating {} to stay within {}", file_path.display(), flag);
+                    budget_outline = Some(text);
+                }
+                (None, None) => {
+                    debug!(
+                        "Dropping {} ({} bytes): over {}",
+                        file_path.display(),
+                        cost,
+                        flag
+                    );
+                    record_skip(config, file_path, format!("over {}", flag));
+                    config.budget_dropped.push(entry_path);
+                    return Ok(());
+                }
+            }
+        }
+    }
+    is_rendered |= budget_outline.is_some();
+    let data = budget_outline.as_ref().map_or(data, |text| text.as_bytes());
+    // A plugin may have replaced the line that keeps unglob from writing a view over the file
+    let first_line = data.split(|&byte| byte == b'\n').next().unwrap_or_default();
+    let opens_view = llm_globber_core::is_rendered_marker(&String::from_utf8_lossy(first_line));
+    let reopened = match is_rendered && !opens_view {
+        true => {
+            let opening = format!("{}view]\n", llm_globber_core::RENDERED_MARKER_PREFIX);
+            Some([opening.as_bytes(), data].concat())
+        }
+        false => None,
+    };
+    let data = reopened.as_deref().unwrap_or(data);
+    config.output_bytes += entry_cost(config, &entry_path, written_len(data));
+    let policy = config.policy.as_ref();
+    let todos: Vec<String> = todos
+        .into_iter()
+        .map(|(line, text)| {
+            // The TODO text was taken before redaction
+            let text = policy
+                .and_then(|policy| policy.redact(&entry_path, &text))
+                .unwrap_or(text);
+            format!("{}:{}: {}", entry_path, line, text)
+        })
+        .collect();
+    config.todo_items.extend(todos);
+    if is_binary {
+        let reason = match kind {
+            ContentKind::Minified => "minified (contents omitted)",
+            _ => "binary (contents omitted)",
+        };
+        record_skip(config, file_path, reason.to_string());
+    }
+    config.written_entries.push(entry_path.clone());
+    // Like omitted entries, rendered ones hold none of the file's content
+    if !is_binary && !is_rendered && !config.headers_only {
+        config.written_content_bytes += data.len() as u64;
+    }
+    // Tarballs hold binary files whole; other formats hold only text content
+    let holds_content =
+        (!is_binary && !config.headers_only) || config.output_format == OutputFormat::Tar;
+    let digest = match holds_content && (config.hash_manifest || config.audit.is_some()) {
+        true => Some(sha256_hex(data)),
+        false => None,
+    };
+    if let (true, Some(digest)) = (config.hash_manifest, &digest) {
+        config
+            .content_hashes
+            .push((digest.clone(), entry_path.clone()));
+    }
+    if config.audit.is_some() {
+        config.exported.push(audit::ExportedFile {
+            path: entry_path.clone(),
+            source: canonical_file_path(file_path).unwrap_or_else(|_| file_path.to_path_buf()),
+            sha256: digest,
+        });
+    }
+    let header_path = if config.metadata {
+        llm_globber_core::with_metadata(&entry_path, &entry_metadata(file_path, data, is_binary))
+    } else {
+        entry_path.clone()
+    };
+    // Signatures, hashes and metadata all describe the content before it is collapsed
+    let collapsed = match is_binary || is_rendered {
+        true => None,
+        false => boilerplate::collapse(data, &config.boilerplate),
+    };
+
     let _lock = config
         .output_mutex
         .lock()
         .expect("Output file mutex poisoned"); // Acquire mutex lock
 
     if let Some(output_file) = &mut config.output_file {
-        if config.use_signature && !is_binary {
-            if let Some(keypair) = &config.keypair {
-                // For signing, we need to use the exact same data format that will be used for verification
-                // For signing, use the raw bytes if possible, fallback for non-UTF8 is less ideal for signing
-                // but matches previous behavior. Consider enforcing UTF-8 if signing is critical.
-                let content_bytes = data; // Sign the raw bytes directly
-
-                // Use helper for debug logging
-                log_signature_debug_info("Signing", file_path, content_bytes);
+        // Tarballs keep binary files whole rather than replacing them with a marker
+        if config.output_format == OutputFormat::Tar {
+            let mtime = fs::metadata(file_path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |age| age.as_secs());
+            archives::write_tar_entry(output_file, &entry_path, data, mtime, is_rendered)?;
+            return Ok(());
+        }
 
-                let signature = sign_data(keypair, content_bytes);
-                debug!("Generated signature for {}: {}", file_path, signature);
-                writeln!(
+        if config.output_format == OutputFormat::Chunks {
+            // Binary files have no text to embed, so they produce no records
+            if !is_binary {
+                let content_str = String::from_utf8_lossy(data);
+                let count = chunks::write_chunk_records(
                     output_file,
-                    "'''--- {} --- [SIGNATURE:{}]",
-                    file_path, signature
+                    &entry_path,
+                    &content_str,
+                    config.chunk_tokens,
+                    config.chunk_overlap,
+                    config.bytes_per_token,
                 )?;
-            } else {
-                writeln!(output_file, "'''--- {} ---", file_path)?;
+                debug!("Wrote {} chunk(s) for {}", count, file_path.display());
             }
-        } else {
-            writeln!(output_file, "'''--- {} ---", file_path)?;
+            return Ok(());
         }
 
-        if is_binary {
-            writeln!(output_file, "[Binary file - contents omitted]")?;
-        } else {
-            if !data.is_empty() {
-                let content_str = str::from_utf8(data).unwrap_or("Non-UTF8 content"); //Handle non-utf8
-                output_file.write_all(content_str.as_bytes())?;
+        if config.output_format == OutputFormat::Plain {
+            // Like chunks, plain output has no marker to stand in for binary files
+            if !is_binary {
+                let write_start = Instant::now();
+                plain::write_plain_entry(output_file, &entry_path, data, config.file_comments)?;
+                config.timings.write += write_start.elapsed();
             }
-            writeln!(output_file, "\n'''")?;
-            writeln!(output_file)?; //Extra blank line
+            return Ok(());
+        }
+
+        if let OutputFormat::Interop(layout) = config.output_format {
+            if !is_binary {
+                let content = String::from_utf8_lossy(data);
+                let entry = layout.entry(config.written_entries.len(), &entry_path, &content);
+                let write_start = Instant::now();
+                output_file.write_all(entry.as_bytes())?;
+                config.timings.write += write_start.elapsed();
+            }
+            return Ok(());
+        }
+
+        let start = match config.toc {
+            true => Some(output_file.position()),
+            false => None,
+        };
+        if is_binary || config.headers_only {
+            let marker = match kind {
+                _ if config.headers_only => HEADERS_ONLY_MARKER.to_string(),
+                ContentKind::Minified => MINIFIED_MARKER.to_string(),
+                _ => images::image_placeholder(&file_path.to_string_lossy(), data)
+                    .unwrap_or_else(|| BINARY_MARKER.to_string()),
+            };
+            let entry = match (&config.signer, config.use_signature) {
+                (Some(signer), true) => {
+                    // Sign the marker together with a digest of the content it stands for, so
+                    // neither can be changed without breaking the signature
+                    let digest = sha256_hex(data);
+                    let signed = llm_globber_core::omitted_signed_content(&marker, &digest);
+                    let signature =
+                        sign_data(signer, signed.as_bytes()).map_err(io::Error::other)?;
+                    llm_globber_core::signed_omitted_entry(
+                        &header_path,
+                        &marker,
+                        &digest,
+                        &signature,
+                    )
+                }
+                _ => llm_globber_core::omitted_entry(&header_path, &marker),
+            };
+            let write_start = Instant::now();
+            output_file.write_all(entry.as_bytes())?;
+            config.timings.write += write_start.elapsed();
+        } else if is_rendered {
+            let view = String::from_utf8_lossy(data);
+            let entry = match (&config.signer, config.use_signature) {
+                (Some(signer), true) => {
+                    // Signed like an omitted entry, over the opening line and the view's digest
+                    let digest = sha256_hex(data);
+                    let marker = view.lines().next().unwrap_or_default();
+                    let signed = llm_globber_core::omitted_signed_content(marker, &digest);
+                    let signature =
+                        sign_data(signer, signed.as_bytes()).map_err(io::Error::other)?;
+                    llm_globber_core::rendered_entry(
+                        &header_path,
+                        &view,
+                        Some((&digest, &signature)),
+                    )
+                }
+                _ => llm_globber_core::rendered_entry(&header_path, &view, None),
+            };
+            let write_start = Instant::now();
+            output_file.write_all(entry.as_bytes())?;
+            config.timings.write += write_start.elapsed();
+        } else {
+            let signature = match (&config.signer, config.use_signature) {
+                (Some(signer), true) => {
+                    // Sign the raw bytes; unglob verifies the same bytes rebuilt from the lines
+                    log_signature_debug_info("Signing", &file_path.to_string_lossy(), data);
+                    let signature = sign_data(signer, data).map_err(io::Error::other)?;
+                    debug!(
+                        "Generated signature for {}: {}",
+                        file_path.display(),
+                        signature
+                    );
+                    Some(signature)
+                }
+                _ => None,
+            };
+            let header = llm_globber_core::entry_header(&header_path, signature.as_deref());
+            let data = collapsed.as_deref().unwrap_or(data);
+            // Text entries are UTF-8, as classification and plugins make sure of
+            let content_str = str::from_utf8(data).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is not UTF-8 text: {}", file_path.display(), e),
+                )
+            })?;
+            let content_str = llm_globber_core::escape_content(content_str);
+            // One write per entry rather than one for each part
+            let mut entry = String::with_capacity(
+                header.len() + content_str.len() + llm_globber_core::ENTRY_FOOTER.len(),
+            );
+            entry.push_str(&header);
+            entry.push_str(&content_str);
+            entry.push_str(llm_globber_core::ENTRY_FOOTER);
+            let write_start = Instant::now();
+            output_file.write_all(entry.as_bytes())?;
+            config.timings.write += write_start.elapsed();
+        }
+        if let Some(start) = start {
+            let end = output_file.position();
+            config.toc_entries.push(toc::TocEntry {
+                path: entry_path.clone(),
+                offset: start,
+                length: end - start,
+            });
         }
-        output_file.flush()?;
     }
     Ok(())
 }
 
-fn process_file(config: &mut ScrapeConfig, file_path: &str) -> io::Result<()> {
-    if !is_regular_file(file_path) {
-        warn!("Skipping invalid file path: {}", file_path);
+fn process_file(config: &mut ScrapeConfig, file_path: &Path) -> io::Result<()> {
+    // A file removed since the inputs were walked fails here rather than being skipped
+    let metadata = fs::metadata(file_path)?;
+    if !metadata.is_file() {
+        warn!("Skipping invalid file path: {}", file_path.display());
         return Ok(());
     }
 
-    let file_size = get_file_size(file_path)?;
-    if config.debug_mode {
-        debug!("Processing file {}: size {} bytes", file_path, file_size);
-    }
+    let file_size = metadata.len();
+    info!(
+        "Processing file {}: size {} bytes",
+        file_path.display(),
+        file_size
+    );
 
-    if file_size >= 1024 * 1024 {
+    // Empty files cannot be mapped
+    if file_size > 0
+        && config
+            .mmap_threshold
+            .is_some_and(|threshold| file_size >= threshold)
+    {
         return process_file_mmap(config, file_path, file_size);
     }
 
-    let base_name = Path::new(file_path)
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("");
+    let base_name = file_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
 
     if !should_process_file(config, file_path, base_name) {
         return Ok(());
     }
 
-    let file = File::open(file_path)?;
-    let mut reader = BufReader::new(file);
-    let mut buffer = Vec::new();
-    reader.read_to_end(&mut buffer)?;
+    let buffer_size = config.io_buffer_size;
+    let buffer = match config.prefetched.remove(file_path) {
+        Some(buffer) => buffer,
+        None => read_with_retry(config, file_path, || {
+            let mut reader = BufReader::with_capacity(buffer_size, File::open(file_path)?);
+            let mut buffer = Vec::with_capacity(file_size as usize);
+            reader.read_to_end(&mut buffer)?;
+            Ok(buffer)
+        })?,
+    };
+    config.timings.bytes += buffer.len() as u64;
 
-    let is_binary = is_binary_data(&buffer);
-    write_file_content(config, file_path, &buffer, is_binary)?;
+    let transformed = transform_content(config, file_path, &buffer)?;
+    let raw = transformed.as_deref().unwrap_or(&buffer);
+    let (kind, decoded) = classify_content(config, file_path, raw);
+    let data = decoded.as_ref().map_or(raw, |text| text.as_bytes());
+    write_file_content(config, file_path, data, kind)?;
 
     Ok(())
 }
 
-fn print_progress(config: &ScrapeConfig) {
-    if !config.show_progress || config.quiet {
-        return;
-    }
-
-    let elapsed = config.start_time.elapsed().as_secs_f64();
-    if elapsed < 0.1 {
-        return; // Too soon
+// Stand in for a file that could not be read with an `[ERROR: path: reason]` entry, so the archive
+// says what it is missing. Other formats have nowhere to put one; the failure is only reported.
+fn write_error_entry(config: &mut ScrapeConfig, file_path: &Path, reason: &str) -> io::Result<()> {
+    if config.output_format != OutputFormat::Text {
+        return Ok(());
     }
-
-    let files_per_sec = config.processed_files as f64 / elapsed;
-
-    let processed_str = format!("{}", config.processed_files).green();
+    let entry_path = header_path(config, file_path);
+    let reason = reason.replace(['\n', '\r'], " ");
+    let marker = format!(
+        "{}{}: {}]",
+        llm_globber_core::ERROR_MARKER_PREFIX,
+        entry_path,
+        reason
+    );
+    // Signed like any omitted entry, over the digest of the content it stands for, here none
+    let entry = match (&config.signer, config.use_signature) {
+        (Some(signer), true) => {
+            let digest = sha256_hex(b"");
+            let signed = llm_globber_core::omitted_signed_content(&marker, &digest);
+            let signature = sign_data(signer, signed.as_bytes()).map_err(io::Error::other)?;
+            llm_globber_core::signed_omitted_entry(&entry_path, &marker, &digest, &signature)
+        }
+        _ => llm_globber_core::omitted_entry(&entry_path, &marker),
+    };
+    config.output_bytes += entry.len() as u64;
+    config.written_entries.push(entry_path.clone());
+    let _lock = config
+        .output_mutex
+        .lock()
+        .expect("Output file mutex poisoned");
+    if let Some(output_file) = &mut config.output_file {
+        let start = output_file.position();
+        output_file.write_all(entry.as_bytes())?;
+        if config.toc {
+            let end = output_file.position();
+            config.toc_entries.push(toc::TocEntry {
+                path: entry_path,
+                offset: start,
+                length: end - start,
+            });
+        }
+    }
+    Ok(())
+}
+
+// Run a read, retrying errors that network filesystems such as NFS and SMB report for
+// transient failures up to --retries times, waiting longer before each attempt
+fn read_with_retry<T>(
+    config: &mut ScrapeConfig,
+    file_path: &Path,
+    mut read: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut delay = config.retry_delay;
+    let mut attempt = 0;
+    loop {
+        let read_start = Instant::now();
+        let result = read();
+        config.timings.read += read_start.elapsed();
+        match result {
+            Ok(value) => {
+                if attempt > 0 {
+                    config
+                        .retried_files
+                        .push((file_path.display().to_string(), attempt));
+                }
+                return Ok(value);
+            }
+            Err(e) if attempt < config.retries && is_transient_error(&e) => {
+                attempt += 1;
+                warn!(
+                    "Reading {} failed: {}; retry {}/{} in {} ms",
+                    file_path.display(),
+                    e,
+                    attempt,
+                    config.retries,
+                    delay.as_millis()
+                );
+                std::thread::sleep(delay);
+                delay = next_retry_delay(delay, config.retry_delay);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Twice the last wait, up to MAX_RETRY_DELAY or the first wait if that was longer
+fn next_retry_delay(delay: Duration, first: Duration) -> Duration {
+    delay.saturating_mul(2).min(MAX_RETRY_DELAY.max(first))
+}
+
+// EIO is what a dropped or timed out network mount usually surfaces as
+fn is_transient_error(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    ) || error.raw_os_error() == Some(libc::EIO)
+}
+
+// A progress bar on a terminal, measured in bytes so the percentage, ETA and throughput stay
+// meaningful when a few large files dominate. Returns the bar with the size of each file.
+fn start_progress_bar(
+    config: &ScrapeConfig,
+    file_paths: &[PathBuf],
+) -> Option<(ProgressBar, Vec<u64>)> {
+    if !config.show_progress || config.quiet || !io::stderr().is_terminal() {
+        return None;
+    }
+    let sizes: Vec<u64> = file_paths
+        .iter()
+        .map(|path| fs::metadata(path).map_or(0, |metadata| metadata.len()))
+        .collect();
+    let template = if colored::control::SHOULD_COLORIZE.should_colorize() {
+        "{spinner:.green} [{bar:30.cyan/blue}] {percent:>3}% {binary_bytes_per_sec:>12} ETA {eta:>4} {wide_msg}"
+    } else {
+        "{spinner} [{bar:30}] {percent:>3}% {binary_bytes_per_sec:>12} ETA {eta:>4} {wide_msg}"
+    };
+    let bar = ProgressBar::with_draw_target(Some(sizes.iter().sum()), ProgressDrawTarget::stderr())
+        .with_style(
+            ProgressStyle::with_template(template)
+                .expect("Progress bar template is valid")
+                .progress_chars("=> "),
+        );
+    set_progress_bar(Some(bar.clone()));
+    Some((bar, sizes))
+}
+
+// Files done so far and the file being read, shown after the bar
+fn progress_message(config: &ScrapeConfig, file_path: &Path) -> String {
+    format!(
+        "{}/{} files, {} failed  {}",
+        config.processed_files,
+        config.file_entries.len(),
+        config.failed_files.len(),
+        header_path(config, file_path)
+    )
+}
+
+// Without a terminal there is no bar; log files and CI output get a full line now and then
+fn print_progress(config: &mut ScrapeConfig) {
+    if !config.show_progress || config.quiet {
+        return;
+    }
+
+    let elapsed = config.start_time.elapsed().as_secs_f64();
+    if elapsed < 0.1 {
+        return; // Too soon
+    }
+
+    if config
+        .last_progress
+        .is_some_and(|last| last.elapsed() < PROGRESS_LINE_INTERVAL)
+    {
+        return;
+    }
+    config.last_progress = Some(Instant::now());
+
+    let files_per_sec = config.processed_files as f64 / elapsed;
+
+    let processed_str = format!("{}", config.processed_files).green();
     let total_str = format!("{}", config.file_entries.len()).cyan();
     let files_per_sec_str = format!("{:.1}", files_per_sec).yellow();
-    let failed_str = if config.failed_files > 0 {
-        format!("{}", config.failed_files).red()
+    let failed_str = if !config.failed_files.is_empty() {
+        format!("{}", config.failed_files.len()).red()
     } else {
-        format!("{}", config.failed_files).green()
+        format!("{}", config.failed_files.len()).green()
     };
 
-    eprint!(
-        "\r{} Processed {}/{} files ({} files/sec), {} failed",
+    eprintln!(
+        "{} Processed {}/{} files ({} files/sec), {} failed",
         "🔍".yellow(),
         processed_str,
         total_str,
         files_per_sec_str,
         failed_str
     );
-    io::stderr().flush().unwrap();
 }
 
 fn print_header(msg: &str) {
@@ -850,9 +3818,8 @@ fn print_header(msg: &str) {
     eprintln!();
 }
 
-fn debug_dump_file(filename: &str) -> io::Result<()> {
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
+fn debug_dump_file(filename: &str, identity_files: &[String]) -> io::Result<()> {
+    let reader = compress::open_archive(filename, identity_files)?;
     eprintln!("=== DEBUG DUMP of {} ===", filename);
     for line_result in reader.lines() {
         let line = line_result?;
@@ -865,7 +3832,7 @@ fn debug_dump_file(filename: &str) -> io::Result<()> {
 fn get_git_repo_name(repo_path: &str) -> Result<String, String> {
     // Try to get the remote origin URL first
     let output = Command::new("git")
-        .args(&["config", "--get", "remote.origin.url"])
+        .args(["config", "--get", "remote.origin.url"])
         .current_dir(repo_path)
         .output()
         .map_err(|e| format!("Failed to execute git command: {}", e))?;
@@ -873,7 +3840,7 @@ fn get_git_repo_name(repo_path: &str) -> Result<String, String> {
     if output.status.success() {
         let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
         // Extract repo name from URL (handles both HTTPS and SSH URLs)
-        if let Some(repo_name) = url.split('/').last() {
+        if let Some(repo_name) = url.split('/').next_back() {
             return Ok(repo_name.trim_end_matches(".git").to_string());
         }
     }
@@ -889,7 +3856,7 @@ fn get_git_repo_name(repo_path: &str) -> Result<String, String> {
 
 fn get_git_branch(repo_path: &str) -> Result<String, String> {
     let output = Command::new("git")
-        .args(&["rev-parse", "--abbrev-ref", "HEAD"])
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
         .current_dir(repo_path)
         .output()
         .map_err(|e| format!("Failed to execute git command: {}", e))?;
@@ -907,7 +3874,7 @@ fn get_git_branch(repo_path: &str) -> Result<String, String> {
 
 fn get_git_tracked_files(repo_path: &str) -> Result<Vec<String>, String> {
     let output = Command::new("git")
-        .args(&["ls-files"])
+        .args(["ls-files"])
         .current_dir(repo_path)
         .output()
         .map_err(|e| format!("Failed to execute git command: {}", e))?;
@@ -930,9 +3897,74 @@ fn get_git_tracked_files(repo_path: &str) -> Result<Vec<String>, String> {
     Ok(files)
 }
 
+// Run a git command in the repository and return its non-empty output lines
+fn git_output_lines(repo_path: &str, args: &[&str], action: &str) -> Result<Vec<String>, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("Failed to execute git command: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to {}: {}",
+            action,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+// The most recent commits as `sha | date | author | subject` lines, newest first
+fn get_git_log(repo_path: &str, count: usize) -> Result<Vec<String>, String> {
+    git_output_lines(
+        repo_path,
+        &[
+            "log",
+            "-n",
+            &count.to_string(),
+            "--date=short",
+            "--format=%h | %ad | %an | %s",
+        ],
+        "read git log",
+    )
+}
+
+// Paths (relative to repo_path) changed by the commits `git log` selects with `commit_filters`,
+// e.g. `--author=NAME` or `--max-count=N`
+fn get_git_changed_files(
+    repo_path: &str,
+    commit_filters: &[String],
+) -> Result<HashSet<String>, String> {
+    let mut args = vec!["log", "--relative", "--name-only", "--format="];
+    args.extend(commit_filters.iter().map(String::as_str));
+    Ok(git_output_lines(repo_path, &args, "read git log")?
+        .into_iter()
+        .collect())
+}
+
+// The repository's top-level directory and repo_path's location within it (e.g. `services/api/`)
+fn get_git_root_and_prefix(repo_path: &str) -> Result<(PathBuf, String), String> {
+    let lines = git_output_lines(
+        repo_path,
+        &["rev-parse", "--show-toplevel", "--show-prefix"],
+        "locate repository root",
+    )?;
+    let root = lines
+        .first()
+        .map(PathBuf::from)
+        .ok_or_else(|| "Failed to locate repository root".to_string())?;
+    Ok((root, lines.get(1).cloned().unwrap_or_default()))
+}
+
 fn is_git_repository(path: &str) -> bool {
     let output = Command::new("git")
-        .args(&["rev-parse", "--is-inside-work-tree"])
+        .args(["rev-parse", "--is-inside-work-tree"])
         .current_dir(path)
         .output();
 
@@ -950,17 +3982,52 @@ fn is_git_url(url: &str) -> bool {
         || url.starts_with("git@")
 }
 
-fn clone_git_repository(url: &str) -> Result<String, String> {
-    use std::env;
+fn parse_git_log(matches: &ArgMatches) -> Result<Option<usize>, String> {
+    matches
+        .value_of("git_log")
+        .map(|count| {
+            count
+                .parse::<usize>()
+                .map_err(|e| format!("Invalid --log value '{}': {}", count, e))
+        })
+        .transpose()
+}
+
+fn parse_changed_in(matches: &ArgMatches) -> Result<Option<usize>, String> {
+    matches
+        .value_of("changed_in")
+        .map(|count| {
+            count
+                .parse::<usize>()
+                .ok()
+                .filter(|count| *count > 0)
+                .ok_or_else(|| format!("Invalid --changed-in value '{}'", count))
+        })
+        .transpose()
+}
 
-    // Create a temporary directory for cloning
-    let temp_dir = env::temp_dir().join(format!(
-        "llm_globber_clone_{}",
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos()
-    ));
+// Commits of history a clone needs for the options that read git history, or None for all of it
+fn git_history_depth(matches: &ArgMatches) -> Result<Option<usize>, String> {
+    if matches.is_present("author") {
+        return Ok(None);
+    }
+    let log = parse_git_log(matches)?.unwrap_or(0);
+    // The oldest commit of a shallow clone looks like it adds every file, so fetch one more
+    let changed_in = parse_changed_in(matches)?.map_or(0, |count| count + 1);
+    Ok(Some(log.max(changed_in).max(1)))
+}
+
+fn clone_git_repository(url: &str, depth: Option<usize>) -> Result<String, String> {
+    // Create a temporary directory for cloning. Repositories are passed around by their path as
+    // a string, so one that is not UTF-8 is refused before anything is cloned.
+    let temp_dir = temp::path("clone");
+    let Some(clone_path) = temp_dir.to_str().map(str::to_string) else {
+        return Err(format!(
+            "Cannot clone {} into {}: the temporary directory must be a UTF-8 path (see --tmpdir)",
+            url,
+            temp_dir.display()
+        ));
+    };
 
     info!(
         "Cloning {} to temporary directory: {}",
@@ -969,8 +4036,12 @@ fn clone_git_repository(url: &str) -> Result<String, String> {
     );
 
     // Execute git clone command
+    let depth_arg = depth.map(|depth| format!("--depth={}", depth));
     let output = Command::new("git")
-        .args(&["clone", "--depth", "1", url, temp_dir.to_str().unwrap()])
+        .arg("clone")
+        .args(depth_arg)
+        .arg(url)
+        .arg(&temp_dir)
         .output()
         .map_err(|e| format!("Failed to execute git clone: {}", e))?;
 
@@ -980,21 +4051,300 @@ fn clone_git_repository(url: &str) -> Result<String, String> {
     }
 
     info!("Successfully cloned repository to {}", temp_dir.display());
-    Ok(temp_dir.to_string_lossy().to_string())
+    Ok(clone_path)
+}
+
+// A daemon's clone of a repository, brought up to date with its remote, or a new clone that
+// the daemon keeps for later requests
+fn cached_clone(
+    cache: &daemon::SharedCache,
+    url: &str,
+    depth: Option<usize>,
+) -> Result<String, String> {
+    let mut cache = cache.lock().expect("Daemon cache mutex poisoned");
+    if let Some(path) = cache.clone_path(url, depth) {
+        match update_clone(&path, depth) {
+            Ok(()) => {
+                info!("Reusing clone of {} in {}", url, path);
+                return Ok(path);
+            }
+            Err(e) => {
+                warn!("Cloning {} again: {}", url, e);
+                if let Err(e) = cleanup_temp_directory(&path) {
+                    warn!("{}", e);
+                }
+            }
+        }
+    }
+    let path = clone_git_repository(url, depth)?;
+    cache.add_clone(url, depth, path.clone());
+    Ok(path)
+}
+
+// Fetch a cached clone's branch and move it to the fetched commit
+fn update_clone(path: &str, depth: Option<usize>) -> Result<(), String> {
+    let depth_arg = depth.map(|depth| format!("--depth={}", depth));
+    let fetch: Vec<&str> = ["fetch", "--quiet", "origin"]
+        .into_iter()
+        .chain(depth_arg.as_deref())
+        .collect();
+    for args in [fetch, vec!["reset", "--quiet", "--hard", "@{upstream}"]] {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .args(&args)
+            .output()
+            .map_err(|e| format!("Failed to execute git: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "git {} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Add the tracked files of a repository that pass --author, --changed-in, --owner and the usual filters
+fn collect_git_files(
+    config: &mut ScrapeConfig,
+    matches: &ArgMatches,
+    git_path: &str,
+) -> Result<(), String> {
+    // Get all tracked files in the git repository
+    let git_files = get_git_tracked_files(git_path)?;
+
+    // --author, --changed-in and --owner narrow the tracked files before the usual filters
+    let authors: Vec<String> = matches
+        .values_of("author")
+        .into_iter()
+        .flatten()
+        .map(|author| format!("--author={}", author))
+        .collect();
+    let authored = if authors.is_empty() {
+        None
+    } else {
+        Some(get_git_changed_files(git_path, &authors)?)
+    };
+    let changed_in = parse_changed_in(matches)?;
+    let recently_changed = match changed_in {
+        Some(count) => Some(get_git_changed_files(
+            git_path,
+            &[format!("--max-count={}", count)],
+        )?),
+        None => None,
+    };
+    let wanted_owners: Vec<String> = matches
+        .values_of("owner")
+        .into_iter()
+        .flatten()
+        .map(str::to_string)
+        .collect();
+    let code_owners = if wanted_owners.is_empty() {
+        None
+    } else {
+        let (root, prefix) = get_git_root_and_prefix(git_path)?;
+        Some((codeowners::CodeOwners::load(&root)?, prefix))
+    };
+    let git_files: Vec<(String, Option<String>)> = git_files
+        .into_iter()
+        .map(|file_path| {
+            let relative = Path::new(&file_path)
+                .strip_prefix(git_path)
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_else(|_| file_path.clone());
+            let reason = if authored
+                .as_ref()
+                .is_some_and(|authored| !authored.contains(&relative))
+            {
+                Some("not changed by --author".to_string())
+            } else if recently_changed
+                .as_ref()
+                .is_some_and(|changed| !changed.contains(&relative))
+            {
+                Some(format!(
+                    "not changed in the last {} commits",
+                    changed_in.unwrap_or(0)
+                ))
+            } else if code_owners.as_ref().is_some_and(|(owners, prefix)| {
+                !owners.is_owned_by(&format!("{}{}", prefix, relative), &wanted_owners)
+            }) {
+                Some("not owned by --owner".to_string())
+            } else {
+                None
+            };
+            (file_path, reason)
+        })
+        .collect();
+
+    if git_files.is_empty() {
+        return Err(format!(
+            "Error: No tracked files found in git repository: {}",
+            git_path
+        ));
+    }
+
+    info!("Found {} tracked files in git repository", git_files.len());
+
+    // Add all git tracked files to the file entries
+    config.pattern_roots.push(PathBuf::from(git_path));
+    for (file_path, reason) in git_files {
+        let path = Path::new(&file_path);
+        if let Some(reason) = reason {
+            record_skip(config, path, reason);
+            continue;
+        }
+        if path.is_file() {
+            let base_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+            if should_process_file(config, path, base_name) {
+                add_file_entry(config, path);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Extract a zip or tar input to a temporary directory and glob it like a directory given with
+// -r, with headers prefixed by the archive's file name, e.g. `deps.tar.gz/src/lib.rs`
+fn add_archive_input(config: &mut ScrapeConfig, archive: &Path) -> Result<(), String> {
+    let temp_dir = temp::path("archive");
+    fs::create_dir_all(&temp_dir)
+        .map_err(|e| format!("Failed to create temporary directory: {}", e))?;
+    let temp_path = temp_dir.to_string_lossy().to_string();
+    config.temp_dirs.push(temp_path.clone());
+
+    let count = archives::extract(archive, &temp_dir)?;
+    info!("Extracted {} files from {}", count, archive.display());
+
+    let name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let root = temp_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", temp_dir.display(), e))?;
+    config.source_prefixes.push((root, name));
+
+    let recursive = config.recursive;
+    config.recursive = true;
+    config.pattern_roots.push(temp_dir.clone());
+    let result = process_directory(config, &temp_dir);
+    config.recursive = recursive;
+    result
+}
+
+// Download a single-file URL input to a temporary directory and add it like a file given on
+// the command line, with the URL as its header path
+fn add_url_input(config: &mut ScrapeConfig, url: &str) -> Result<(), String> {
+    let temp_dir = temp::path("fetch");
+    fs::create_dir_all(&temp_dir)
+        .map_err(|e| format!("Failed to create temporary directory: {}", e))?;
+    config
+        .temp_dirs
+        .push(temp_dir.to_string_lossy().to_string());
+
+    // Keep the URL's file name so type filters and language detection still apply
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .unwrap_or("index");
+    let data = remote::fetch(url, config.max_file_size)?;
+    let path = temp_dir.join(name);
+    fs::write(&path, data).map_err(|e| format!("Failed to save {}: {}", url, e))?;
+
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?;
+    config.source_prefixes.push((canonical, url.to_string()));
+    if should_process_file(config, &path, name) {
+        add_file_entry(config, &path);
+    }
+    Ok(())
+}
+
+// A clone of `url` for this run, from the daemon's cache when there is one. How large a new
+// clone will be is unknown until it is done, so less than `min_free` bytes free is only warned
+// about; a daemon usually reuses its clone instead.
+fn clone_for_run(
+    config: &mut ScrapeConfig,
+    url: &str,
+    depth: Option<usize>,
+    min_free: u64,
+) -> Result<String, String> {
+    if config.space_check && config.cache.is_none() {
+        diskspace::warn_if_low(&temp::root(), min_free, "a clone");
+    }
+    match &config.cache {
+        Some(cache) => cached_clone(cache, url, depth),
+        None => {
+            let cloned_path = clone_git_repository(url, depth)?;
+            config.temp_dirs.push(cloned_path.clone());
+            Ok(cloned_path)
+        }
+    }
+}
+
+// Clone or verify one --git input and record it, with a name that is unique within the run
+fn add_git_repo(
+    config: &mut ScrapeConfig,
+    matches: &ArgMatches,
+    git_input: &str,
+) -> Result<(), String> {
+    let actual_git_path = if is_git_url(git_input) {
+        // Clone the repository from URL
+        info!("Detected git URL: {}", git_input);
+        // Shallow clones only need as much history as --log asks for
+        let depth = git_history_depth(matches)?;
+        clone_for_run(config, git_input, depth, diskspace::MIN_CLONE_BYTES)?
+    } else {
+        // Local path - verify this is a git repository
+        if !is_git_repository(git_input) {
+            return Err(format!("Error: {} is not a git repository", git_input));
+        }
+        git_input.to_string()
+    };
+
+    // Get repository name and branch for output filename
+    let repo_name = if is_git_url(git_input) {
+        get_repo_name_from_url(git_input)
+    } else {
+        get_git_repo_name(&actual_git_path)?
+    };
+    let branch_name = get_git_branch(&actual_git_path)?;
+
+    // Two repositories with the same name still need distinct header prefixes
+    let mut name = repo_name.clone();
+    let mut suffix = 2;
+    while config.git_repos.iter().any(|repo| repo.name == name) {
+        name = format!("{}-{}", repo_name, suffix);
+        suffix += 1;
+    }
+
+    info!("Processing git repository: {}", actual_git_path);
+    info!("Repository: {}, Branch: {}", name, branch_name);
+    config.git_repos.push(GitRepo {
+        path: actual_git_path,
+        name,
+        branch: branch_name,
+    });
+    Ok(())
 }
 
 fn get_repo_name_from_url(url: &str) -> String {
     // Handle SSH URLs like git@github.com:user/repo.git
     if let Some(ssh_part) = url.strip_prefix("git@") {
         if let Some(repo_part) = ssh_part.split(':').nth(1) {
-            if let Some(repo_name) = repo_part.split('/').last() {
+            if let Some(repo_name) = repo_part.split('/').next_back() {
                 return repo_name.trim_end_matches(".git").to_string();
             }
         }
     }
 
     // Extract repository name from HTTP/HTTPS URL
-    if let Some(last_part) = url.split('/').last() {
+    if let Some(last_part) = url.split('/').next_back() {
         return last_part.trim_end_matches(".git").to_string();
     }
 
@@ -1002,8 +4352,8 @@ fn get_repo_name_from_url(url: &str) -> String {
 }
 
 fn cleanup_temp_directory(path: &str) -> Result<(), String> {
-    // Only clean up directories in temp directory to be safe
-    if !path.contains("llm_globber_clone_") {
+    // Only clean up directories this tool created in the temp directory, to be safe
+    if !temp::is_ours(Path::new(path)) {
         return Ok(());
     }
 
@@ -1015,7 +4365,7 @@ fn cleanup_temp_directory(path: &str) -> Result<(), String> {
 }
 
 fn cleanup_config_temp_dirs(config: &ScrapeConfig) {
-    if let Some(temp_path) = &config.temp_git_path {
+    for temp_path in &config.temp_dirs {
         if let Err(cleanup_err) = cleanup_temp_directory(temp_path) {
             warn!("Failed to cleanup temporary directory: {}", cleanup_err);
         }
@@ -1033,234 +4383,611 @@ fn unglob_file(config: &ScrapeConfig) -> Result<(), String> {
             config.unglob_input_file
         ));
     }
+    if !config.force && !config.as_patch {
+        check_unglob_target(Path::new(&config.output_path))?;
+    }
 
-    let file = File::open(&config.unglob_input_file).map_err(|e| {
-        format!(
-            "Failed to open input file: {}: {}",
-            config.unglob_input_file, e
-        )
-    })?;
+    let reader = compress::open_archive(&config.unglob_input_file, &config.identity_files)
+        .map_err(|e| {
+            format!(
+                "Failed to open input file: {}: {}",
+                config.unglob_input_file, e
+            )
+        })?;
 
-    let reader = BufReader::new(file);
-    let mut lines = reader.lines();
+    let (head, reader) = compress::peek(reader, TAR_PEEK_SIZE)
+        .map_err(|e| format!("Error reading input file: {}", e))?;
+    if archives::is_tar(&head) {
+        return unglob_tar(config, reader);
+    }
+    // Other tools' layouts can only be told apart by lines further in, past repomix's summary
+    let head = String::from_utf8_lossy(&head);
+    let is_native = head
+        .trim_start()
+        .starts_with(llm_globber_core::CLOSING_MARKER)
+        || head.starts_with(llm_globber_core::FORMAT_HEADER_PREFIX);
+    // A compressed or encrypted archive may expand to no more than the extraction limit, so a
+    // compression bomb is stopped while it is read rather than once it has filled memory
+    let limit = fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+        .max(config.max_extract_size);
+    let mut data = Vec::new();
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Error reading input file: {}", e))?;
+    if data.len() as u64 > limit {
+        return Err(format!(
+            "Archive {} expands to more than the {} MB extraction limit (raise it with --max-extract-size)",
+            config.unglob_input_file,
+            config.max_extract_size / (1024 * 1024)
+        ));
+    }
+    if is_native {
+        return unglob_entries(config, &data);
+    }
+    match str::from_utf8(&data)
+        .ok()
+        .and_then(|text| interop::detect(text).map(|layout| (layout, text)))
+    {
+        Some((layout, text)) => unglob_layout(config, layout, text),
+        None => unglob_entries(config, &data),
+    }
+}
 
-    let mut current_file: Option<String> = None;
-    let mut current_content: Vec<String> = Vec::new();
-    let mut current_signature: Option<String> = None;
-    let mut files_extracted = 0;
-    let mut in_file_content = false;
-    let mut extracted_public_key: Option<PublicKey> = None;
+// Extract the files of an archive written by another tool
+fn unglob_layout(config: &ScrapeConfig, layout: interop::Layout, text: &str) -> Result<(), String> {
+    if config.use_signature {
+        return Err(format!(
+            "{} archives carry no signatures to verify; omit --signature",
+            layout.name()
+        ));
+    }
+    info!("Reading {} as {}", config.unglob_input_file, layout.name());
+    let entries = interop::read(layout, text)?;
+    let output_base = Path::new(&config.output_path);
+    let mut extraction = Extraction::new(config.show_progress && !config.quiet);
+    let result = entries.iter().try_for_each(|entry| {
+        if entry.content().len() as u64 > config.max_extract_size {
+            return Err(format!(
+                "Entry {} is larger than the {} MB extraction limit (raise it with --max-extract-size)",
+                entry.path,
+                config.max_extract_size / (1024 * 1024)
+            ));
+        }
+        extract_entry(config, entry, None, output_base, &mut extraction)
+    });
+    extraction.finish();
+    result
+}
 
-    // Get the base output directory
+// Refuse to extract into a directory holding files that could not be got back if the archive
+// overwrote them: one that is neither empty nor a git working tree without changes
+fn check_unglob_target(output_base: &Path) -> Result<(), String> {
+    match unglob::target_risk(output_base)? {
+        Some(reason) => Err(format!(
+            "Refusing to unglob into {}: the directory is not empty and {} (use --force to extract anyway, or --as-patch to review the changes)",
+            output_base.display(),
+            reason
+        )),
+        None => Ok(()),
+    }
+}
+
+// Extract a tarball written with --format tar, applying --map rules to its entry paths
+fn unglob_tar<R: Read>(config: &ScrapeConfig, reader: R) -> Result<(), String> {
+    if config.use_signature {
+        return Err("Tarballs carry no signatures to verify; omit --signature".to_string());
+    }
+    if config.as_patch {
+        return Err("--as-patch does not support tarballs".to_string());
+    }
     let output_base = Path::new(&config.output_path);
+    let count = archives::unpack_tar(
+        reader,
+        output_base,
+        |path| apply_path_maps(&config.path_maps, path),
+        |target| backup_existing(config, output_base, target),
+        config.max_extract_size,
+    )
+    .map_err(|e| format!("Failed to extract tarball: {}", e))?;
+    info!("Extracted {} files from tarball", count);
+    Ok(())
+}
 
-    while let Some(line_result) = lines.next() {
-        let line = line_result.map_err(|e| format!("Error reading line: {}", e))?;
+// Extract the entries of an archive in this tool's format. The whole archive is read and checked
+// against its footer first, so a truncated or altered copy writes nothing.
+fn unglob_entries(config: &ScrapeConfig, data: &[u8]) -> Result<(), String> {
+    check_entries(config, compress::raw_lines(data))?;
+    let mut extraction = Extraction::new(config.show_progress && !config.quiet);
+    let result = extract_entries(config, compress::raw_lines(data), &mut extraction);
+    extraction.finish();
+    result
+}
 
-        // Check for public key at the start of the file
-        if line.starts_with("'''--- PUBLIC_KEY --- [KEY:") && line.ends_with("]") {
-            let key_start = line.find("[KEY:").unwrap() + 5;
-            let key_end = line.len() - 1;
-            let encoded_key = &line[key_start..key_end];
-
-            // Decode and parse the public key
-            match general_purpose::STANDARD.decode(encoded_key) {
-                Ok(key_bytes) => {
-                    if key_bytes.len() == ed25519_dalek::PUBLIC_KEY_LENGTH {
-                        match PublicKey::from_bytes(&key_bytes) {
-                            Ok(public_key) => {
-                                extracted_public_key = Some(public_key);
-                                info!("Found public key in file: {}", encoded_key);
-
-                                // Skip the closing marker line
-                                if let Some(Ok(next_line)) = lines.next() {
-                                    if next_line != "'''" {
-                                        return Err(
-                                            "Invalid public key format: missing closing marker"
-                                                .to_string(),
-                                        );
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                warn!("Invalid public key format: {}", e);
-                            }
-                        }
-                    } else {
-                        warn!("Invalid public key length: {}", key_bytes.len());
-                    }
-                }
-                Err(e) => {
-                    warn!("Failed to decode public key: {}", e);
-                }
+// Read an archive through without extracting it: stop on an oversized entry, and check the
+// entries against the footer, which archives from format 6 on must have, and any hard links and
+// empty directories against the footer's signature
+fn check_entries<I>(config: &ScrapeConfig, lines: I) -> Result<(), String>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    let mut parser = ArchiveParser::new();
+    let mut public_key = None;
+    let mut footer = None;
+    let mut entry_paths = Vec::new();
+    let mut content_bytes = 0;
+    let mut hard_links = Vec::new();
+    let mut empty_dirs = Vec::new();
+    for line_result in lines {
+        let line = line_result.map_err(|e| format!("Error reading line: {}", e))?;
+        let event = parser.feed(&line)?;
+        // Stop on an oversized entry while reading it, before it fills memory
+        if let Some((path, len)) = parser.current_entry() {
+            if len as u64 > config.max_extract_size {
+                return Err(format!(
+                    "Entry {} is larger than the {} MB extraction limit (raise it with --max-extract-size)",
+                    path,
+                    config.max_extract_size / (1024 * 1024)
+                ));
             }
-            continue;
         }
-
-        // Check for file header (with or without signature)
-        if line.starts_with("'''--- ") {
-            // If we were processing a file, write it out before starting a new one
-            if let Some(file_path) = current_file.take() {
-                if config.use_signature && extracted_public_key.is_some() {
-                    // Create a temporary config with the extracted public key
-                    let temp_config = config.clone_for_verification(extracted_public_key);
-
-                    process_extracted_file(
-                        &temp_config,
-                        &file_path,
-                        &current_content,
-                        current_signature.as_deref(),
-                        output_base,
-                    )?;
-                } else {
-                    process_extracted_file(
-                        config,
-                        &file_path,
-                        &current_content,
-                        current_signature.as_deref(),
-                        output_base,
-                    )?;
-                }
-                files_extracted += 1;
-                current_content.clear();
-                // No need to reset current_signature as it will be overwritten in the next iteration
+        match event {
+            // Whether the key is one to trust is checked once extraction starts, before the
+            // first entry is written
+            Some(Event::PublicKey(encoded_key)) => public_key = decode_public_key(&encoded_key),
+            Some(Event::Omitted(omitted)) => entry_paths.push(omitted.path),
+            Some(Event::Entry(entry)) => {
+                content_bytes += entry.content_len();
+                entry_paths.push(entry.path);
             }
+            Some(Event::HardLinks(links)) => hard_links = links,
+            Some(Event::EmptyDirs(dirs)) => empty_dirs = dirs,
+            Some(Event::Footer(found)) => footer = Some(found),
+            _ => {}
+        }
+    }
+    if let Some(entry) = parser.finish() {
+        content_bytes += entry.content_len();
+        entry_paths.push(entry.path);
+    }
+    if let Some((alias, _)) = hard_links
+        .iter()
+        .find(|(alias, _)| entry_paths.contains(alias))
+    {
+        return Err(format!(
+            "Hard link {} would replace the entry of the same name; nothing was extracted",
+            alias
+        ));
+    }
+    match footer {
+        Some(footer) => footer
+            .verify(&entry_paths, content_bytes, &hard_links, &empty_dirs)
+            .and_then(|_| match hard_links.is_empty() && empty_dirs.is_empty() {
+                true => Ok(()),
+                false => verify_footer_signature(config, &footer, public_key.as_ref()),
+            })
+            .map(|_| debug!("Archive matches its footer: {} files", footer.files))
+            .map_err(|e| format!("{}; nothing was extracted", e)),
+        None if parser.version() >= llm_globber_core::FOOTER_FORMAT_VERSION => Err(format!(
+            "{}; nothing was extracted",
+            llm_globber_core::MISSING_FOOTER
+        )),
+        None => {
+            warn!("Archive has no footer, so it cannot be checked for truncation");
+            Ok(())
+        }
+    }
+}
 
-            // Parse the header line to extract file path and optional signature
-            let (file_path, signature) = parse_file_header(&line)?;
+fn extract_entries<I>(
+    config: &ScrapeConfig,
+    lines: I,
+    extraction: &mut Extraction,
+) -> Result<(), String>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    let mut parser = ArchiveParser::new();
+    let mut extracted_public_key: Option<PublicKey> = None;
+    // The archive bytes read so far, which a timestamp section signs the digest of
+    let mut hasher = Sha256::new();
+    let mut timestamped_digest: Option<String> = None;
+    let mut hard_links = Vec::new();
+    let mut empty_dirs = Vec::new();
 
-            current_file = Some(file_path);
-            current_signature = signature;
-            in_file_content = true;
-            continue;
-        }
+    // Get the base output directory
+    let output_base = Path::new(&config.output_path);
 
-        // Check for end of file marker
-        if line == "'''" && in_file_content {
-            in_file_content = false;
-            continue;
+    for line_result in lines {
+        let line = line_result.map_err(|e| format!("Error reading line: {}", e))?;
+        if llm_globber_core::is_timestamp_header(&line) {
+            timestamped_digest = Some(hex_digest(hasher.clone()));
         }
-
-        // If we're in file content, add the line
-        if in_file_content && current_file.is_some() {
-            // Skip binary file markers
-            if line == "[Binary file - contents omitted]" {
-                current_file = None;
-                in_file_content = false;
-                continue;
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+        let event = parser.feed(&line)?;
+        extraction.escaped_paths =
+            parser.version() >= llm_globber_core::ESCAPED_PATHS_FORMAT_VERSION;
+        match event {
+            Some(Event::PublicKey(encoded_key)) => {
+                extracted_public_key = decode_public_key(&encoded_key);
+                check_signer(config, extracted_public_key.as_ref())?;
             }
-
-            current_content.push(line);
+            Some(Event::Omitted(omitted)) => {
+                if omitted.marker.starts_with(llm_globber_core::ERROR_MARKER_PREFIX) {
+                    warn!("The archive is missing a file: {}", omitted.marker);
+                }
+                if llm_globber_core::is_rendered_marker(&omitted.marker) {
+                    info!(
+                        "Not extracting {}: the archive holds a view of it, not its content",
+                        omitted.path
+                    );
+                }
+                require_signer(config, extracted_public_key.as_ref())?;
+                verify_omitted(config, &omitted, extracted_public_key.as_ref(), extraction)?;
+            }
+            Some(Event::Entry(entry)) => {
+                require_signer(config, extracted_public_key.as_ref())?;
+                extract_entry(config, &entry, extracted_public_key, output_base, extraction)?;
+            }
+            Some(Event::Normalization(steps)) => info!(
+                "Files were normalized when globbed ({}), so their whitespace may differ from the originals",
+                steps.join(", ")
+            ),
+            // The section follows the entries, but the last one is only finished below
+            Some(Event::HardLinks(links)) => hard_links = links,
+            Some(Event::EmptyDirs(dirs)) => empty_dirs = dirs,
+            // Checked against the entries before extraction began
+            Some(Event::Footer(_)) => {}
+            Some(Event::FrontMatter(lines)) => {
+                info!("Archive front matter:\n{}", lines.join("\n"))
+            }
+            Some(Event::Timestamp(timestamp)) => {
+                require_signer(config, extracted_public_key.as_ref())?;
+                verify_timestamp(
+                    config,
+                    &timestamp,
+                    timestamped_digest.as_deref(),
+                    extracted_public_key.as_ref(),
+                    extraction,
+                )?;
+            }
+            None => {}
         }
     }
+    info!("Archive format version: {}", parser.version());
 
     // Handle the last file if any
-    if let Some(file_path) = current_file {
-        if config.use_signature && extracted_public_key.is_some() {
-            // Create a temporary config with the extracted public key
-            let temp_config = config.clone_for_verification(extracted_public_key);
-
-            process_extracted_file(
-                &temp_config,
-                &file_path,
-                &current_content,
-                current_signature.as_deref(),
-                output_base,
-            )?;
-        } else {
-            process_extracted_file(
-                config,
-                &file_path,
-                &current_content,
-                current_signature.as_deref(),
-                output_base,
-            )?;
-        }
-        files_extracted += 1;
+    if let Some(entry) = parser.finish() {
+        require_signer(config, extracted_public_key.as_ref())?;
+        extract_entry(
+            config,
+            &entry,
+            extracted_public_key,
+            output_base,
+            extraction,
+        )?;
     }
 
-    if files_extracted == 0 {
+    if extraction.files == 0 {
         return Err("No files were extracted from the input file".to_string());
     }
 
-    info!("Successfully extracted {} files", files_extracted);
-    Ok(())
-}
-
-// Helper function to parse a file header line
-fn parse_file_header(line: &str) -> Result<(String, Option<String>), String> {
-    let trimmed_line = line.trim();
-
-    // Ensure it starts with '''--- and ends with --- or ]
-    if !trimmed_line.starts_with("'''--- ")
-        || !(trimmed_line.ends_with(" ---") || trimmed_line.ends_with(']'))
-    {
-        return Err(format!("Invalid file header format: {}", line));
+    // A patch has no way to express a hard link or a directory, so these are left as they are
+    if config.as_patch {
+        return Ok(());
     }
-
-    // Strip prefix '''---
-    let content = trimmed_line
-        .strip_prefix("'''--- ")
-        .ok_or_else(|| format!("Failed to strip prefix: {}", line))?;
-
-    // Check for signature: path --- [SIGNATURE:...]
-    if let Some((path_part, sig_part)) = content.rsplit_once(" --- [SIGNATURE:") {
-        if let Some(signature) = sig_part.strip_suffix(']') {
-            let file_path = path_part.trim().to_string();
-            Ok((file_path, Some(signature.to_string())))
-        } else {
-            Err(format!("Invalid signature format in header: {}", line))
+    for dir in &empty_dirs {
+        let dir_path = extracted_path(config, output_base, dir, extraction.escaped_paths)?;
+        let new_directories = dir_path
+            .ancestors()
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+            .count();
+        fs::create_dir_all(&dir_path)
+            .map_err(|e| format!("Failed to create directory {}: {}", dir_path.display(), e))?;
+        extraction.directories += new_directories;
+    }
+    for (alias, target) in &hard_links {
+        let alias_path = extracted_path(config, output_base, alias, extraction.escaped_paths)?;
+        let target_path = extracted_path(config, output_base, target, extraction.escaped_paths)?;
+        // --map rules can send an alias where an entry was written
+        if extraction.wrote(&alias_path) {
+            return Err(format!(
+                "Hard link {} would replace {}, which an entry was extracted to",
+                alias,
+                alias_path.display()
+            ));
         }
+        backup_existing(config, output_base, &alias_path)
+            .and_then(|_| link_extracted_file(&target_path, &alias_path))
+            .map_err(|e| {
+                format!(
+                    "Failed to link {} to {}: {}",
+                    alias_path.display(),
+                    target_path.display(),
+                    e
+                )
+            })?;
     }
-    // Check for simple header: path ---
-    else if let Some(path_part) = content.strip_suffix(" ---") {
-        let file_path = path_part.trim().to_string();
-        Ok((file_path, None))
+    if !hard_links.is_empty() {
+        info!("Restored {} hard link(s)", hard_links.len());
     }
-    // Check for public key header (should not be parsed here ideally)
-    else if content.starts_with("PUBLIC_KEY --- [KEY:") {
-        Err("Public key header should be handled separately".to_string())
+
+    Ok(())
+}
+
+// Hard links and empty directories have no signatures of their own, and the footer's covers them.
+// While signatures are checked, sections it does not cover could link a verified file's name to
+// other content, so they are refused.
+fn verify_footer_signature(
+    config: &ScrapeConfig,
+    footer: &Footer,
+    public_key: Option<&PublicKey>,
+) -> Result<(), String> {
+    if !config.use_signature || (public_key.is_none() && !config.strict_signatures()) {
+        return Ok(());
+    }
+    match (public_key, footer.signature.as_deref()) {
+        (Some(key), Some(signature)) => {
+            verify_signature(key, footer.signed_content().as_bytes(), signature).map_err(|e| {
+                format!(
+                    "Signature verification failed for the hard links and empty directories: {}",
+                    e
+                )
+            })
+        }
+        _ => Err(
+            "The archive's hard links and empty directories are not signed, so they cannot be \
+             trusted while signatures are checked"
+                .to_string(),
+        ),
+    }
+}
+
+// Accept the key an archive declares if it is the --key-file key, or else one the trust store
+// accepts
+fn check_signer(config: &ScrapeConfig, public_key: Option<&PublicKey>) -> Result<(), String> {
+    match (config.verify_key, public_key) {
+        (Some(expected), Some(key)) if expected != *key => Err(format!(
+            "Archive is signed by key {}, not the key given with --key-file",
+            trust::encode_key(key)
+        )),
+        (Some(_), Some(_)) => Ok(()),
+        (None, Some(key)) if config.use_signature => {
+            trust::check_signer(key, &config.unglob_input_file, config.require_trusted_key)
+                .map(|_| ())
+        }
+        _ => Ok(()),
+    }
+}
+
+// With --require-trusted-key or --key-file nothing is extracted from an archive without a
+// valid public key, which comes before the first entry
+fn require_signer(config: &ScrapeConfig, public_key: Option<&PublicKey>) -> Result<(), String> {
+    if config.strict_signatures() && public_key.is_none() {
+        return Err("Archive has no valid public key to verify its signatures with".to_string());
+    }
+    Ok(())
+}
+
+// Omitted entries extract nothing, but their signatures are checked like any other so that a
+// placeholder cannot stand in for a signed file unnoticed
+fn verify_omitted(
+    config: &ScrapeConfig,
+    omitted: &Omitted,
+    public_key: Option<&PublicKey>,
+    extraction: &mut Extraction,
+) -> Result<(), String> {
+    debug!("Skipping omitted entry: {}", omitted.path);
+    let Some(public_key) = public_key.filter(|_| config.use_signature) else {
+        return Ok(());
+    };
+    match (omitted.signed_content(), omitted.signature.as_deref()) {
+        (Some(content), Some(signature)) => {
+            verify_signature(public_key, content.as_bytes(), signature).map_err(|e| {
+                extraction.failed += 1;
+                format!("Signature verification failed for {}: {}", omitted.path, e)
+            })?;
+            extraction.verified += 1;
+            debug!("Signature verified for omitted entry: {}", omitted.path);
+            Ok(())
+        }
+        _ if config.strict_signatures() => Err(format!("File {} has no signature", omitted.path)),
+        _ => {
+            warn!(
+                "File {} has no signature but signature verification is enabled",
+                omitted.path
+            );
+            Ok(())
+        }
+    }
+}
+
+// A timestamp is only accepted if it signs the digest of the archive bytes before it, so it
+// cannot be moved to a different archive by the same signer
+fn verify_timestamp(
+    config: &ScrapeConfig,
+    timestamp: &Timestamp,
+    digest: Option<&str>,
+    public_key: Option<&PublicKey>,
+    extraction: &mut Extraction,
+) -> Result<(), String> {
+    let Some(public_key) = public_key.filter(|_| config.use_signature) else {
+        info!(
+            "Archive claims to be signed at {} (not verified)",
+            timestamp.time
+        );
+        return Ok(());
+    };
+    if digest != Some(timestamp.digest.as_str()) {
+        extraction.failed += 1;
+        return Err(format!(
+            "Timestamp {} does not match the archive content",
+            timestamp.time
+        ));
+    }
+    verify_signature(
+        public_key,
+        timestamp.signed_content().as_bytes(),
+        &timestamp.signature,
+    )
+    .map_err(|e| {
+        extraction.failed += 1;
+        format!("Timestamp signature verification failed: {}", e)
+    })?;
+    extraction.verified += 1;
+    info!("Archive signed at {}", timestamp.time);
+    Ok(())
+}
+
+// The public key embedded in an archive, or None with a warning if it is not a valid key
+fn decode_public_key(encoded_key: &str) -> Option<PublicKey> {
+    let key_bytes = match general_purpose::STANDARD.decode(encoded_key) {
+        Ok(key_bytes) => key_bytes,
+        Err(e) => {
+            warn!("Failed to decode public key: {}", e);
+            return None;
+        }
+    };
+    if key_bytes.len() != ed25519_dalek::PUBLIC_KEY_LENGTH {
+        warn!("Invalid public key length: {}", key_bytes.len());
+        return None;
+    }
+    match PublicKey::from_bytes(&key_bytes) {
+        Ok(public_key) => {
+            info!("Found public key in file: {}", encoded_key);
+            Some(public_key)
+        }
+        Err(e) => {
+            warn!("Invalid public key format: {}", e);
+            None
+        }
+    }
+}
+
+// Verify and write one entry, using the archive's own public key when it has one
+fn extract_entry(
+    config: &ScrapeConfig,
+    entry: &Entry,
+    public_key: Option<PublicKey>,
+    output_base: &Path,
+    extraction: &mut Extraction,
+) -> Result<(), String> {
+    let signature = entry.signature.as_deref();
+    if config.use_signature && public_key.is_some() {
+        let temp_config = config.clone_for_verification(public_key);
+        process_extracted_file(&temp_config, entry, signature, output_base, extraction)
     } else {
-        Err(format!("Unrecognized file header format: {}", line))
+        process_extracted_file(config, entry, signature, output_base, extraction)
+    }
+}
+
+// Parse a `SRC_PREFIX=DEST_PREFIX` rewrite rule for unglob
+fn parse_path_map(rule: &str) -> Result<(String, String), String> {
+    match rule.split_once('=') {
+        Some((src, dest)) if !src.is_empty() => Ok((src.to_string(), dest.to_string())),
+        _ => Err(format!(
+            "Invalid --map rule '{}': expected SRC_PREFIX=DEST_PREFIX",
+            rule
+        )),
     }
 }
 
+// Rewrite an entry path with the first matching --map rule
+fn apply_path_maps(path_maps: &[(String, String)], file_path: &str) -> String {
+    for (src, dest) in path_maps {
+        if let Some(rest) = file_path.strip_prefix(src.as_str()) {
+            let mapped = format!("{}{}", dest, rest);
+            debug!("Mapped {} to {}", file_path, mapped);
+            return mapped;
+        }
+    }
+    file_path.to_string()
+}
+
+// Where unglob writes the file for an entry path, after --map rules. `escaped` paths, from format
+// 5 archives, are turned back into the bytes of the original name once mapped. Paths with a `..`
+// component are refused, as lint and the Python unglob refuse them, since they could reach
+// outside the output directory; the Python unglob resolves paths with the same function.
+fn extracted_path(
+    config: &ScrapeConfig,
+    output_base: &Path,
+    file_path: &str,
+    escaped: bool,
+) -> Result<PathBuf, String> {
+    let mapped_path = apply_path_maps(&config.path_maps, file_path);
+    // Archives from older versions may record absolute paths; those are extracted under the
+    // output base
+    unglob::entry_target(output_base, &mapped_path, escaped)
+        .ok_or_else(|| format!("Entry path escapes the output directory: {}", file_path))
+}
+
+// Copy a file unglob is about to overwrite to its --backup location. `target` is a path under
+// `output_base`; the backup of an earlier run is replaced, so it holds the file as it was just
+// before this run.
+fn backup_existing(config: &ScrapeConfig, output_base: &Path, target: &Path) -> io::Result<()> {
+    let Some(mode) = config.backup else {
+        return Ok(());
+    };
+    if !target.is_file() {
+        return Ok(());
+    }
+    let backup = match (mode, target.strip_prefix(output_base)) {
+        (BackupMode::Tree, Ok(relative)) => output_base.join(BACKUP_DIR).join(relative),
+        _ => {
+            let mut name = target.as_os_str().to_owned();
+            name.push(".orig");
+            PathBuf::from(name)
+        }
+    };
+    if let Some(parent) = backup.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(target, &backup)?;
+    debug!("Backed up {} to {}", target.display(), backup.display());
+    Ok(())
+}
+
+// Recreate a hard link recorded in the archive, copying the file where linking is not possible
+fn link_extracted_file(target: &Path, alias: &Path) -> io::Result<()> {
+    if let Some(parent) = alias.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if alias.exists() {
+        fs::remove_file(alias)?;
+    }
+    fs::hard_link(target, alias).or_else(|_| fs::copy(target, alias).map(|_| ()))
+}
+
 // Helper function to process and write an extracted file
 fn process_extracted_file(
     config: &ScrapeConfig,
-    file_path: &str,
-    content: &[String],
+    entry: &Entry,
     signature: Option<&str>,
     output_base: &Path,
+    extraction: &mut Extraction,
 ) -> Result<(), String> {
-    // Use Path::strip_prefix for safer and more robust path manipulation
-    let relative_path = Path::new(file_path)
-        .strip_prefix("test_files/")
-        .unwrap_or_else(|_| Path::new(file_path)); // Fallback if prefix not found
-
-    let output_file_path = output_base.join(relative_path);
+    let file_path = entry.path.as_str();
+    let content = entry.lines.as_slice();
+    let output_file_path =
+        extracted_path(config, output_base, file_path, extraction.escaped_paths)?;
     let output_file_path_str = output_file_path.to_string_lossy().to_string(); // Keep string version for logging/errors
 
     // Verify signature if needed
-    if config.use_signature && config.public_key.is_some() {
+    if let (true, Some(public_key)) = (config.use_signature, config.public_key.as_ref()) {
         match signature {
             Some(sig) => {
-                // Join content with newlines - this is critical for signature verification
-                // Note: This assumes the original file used '\n' line endings.
+                // Joining the lines with newlines rebuilds the exact bytes that were signed
                 let content_str = content.join("\n");
                 let content_bytes = content_str.as_bytes();
 
                 // Use helper for debug logging
                 log_signature_debug_info("Verifying", file_path, content_bytes);
 
-                if let Err(e) = verify_signature(
-                    config
-                        .public_key
-                        .as_ref()
-                        .expect("Public key missing during verification"), // Use expect here
-                    content_bytes,
-                    sig,
-                ) {
+                if let Err(e) = verify_signature(public_key, content_bytes, sig) {
+                    extraction.failed += 1;
                     if config.verbose {
                         return Err(format!(
                             "Signature verification failed for {}: {}. Signature: {}",
@@ -1273,8 +5000,12 @@ fn process_extracted_file(
                         ));
                     }
                 }
+                extraction.verified += 1;
                 debug!("Signature verified for: {}", file_path);
             }
+            None if config.strict_signatures() => {
+                return Err(format!("File {} has no signature", file_path));
+            }
             None => {
                 warn!(
                     "File {} has no signature but signature verification is enabled",
@@ -1284,38 +5015,160 @@ fn process_extracted_file(
         }
     }
 
+    // A recorded size that matches the content shows it is exact, as a signature does
+    let size = content.iter().map(String::len).sum::<usize>() + content.len().saturating_sub(1);
+    let exact = signature.is_some() || entry.metadata.size == Some(size as u64);
+    if let Some(recorded) = entry.metadata.size.filter(|_| !exact) {
+        warn!(
+            "{} holds {} bytes but its header records {}",
+            file_path, size, recorded
+        );
+    }
+
+    if !extraction.claim(file_path, &output_file_path) {
+        return Ok(());
+    }
+    if config.as_patch {
+        return print_extracted_patch(
+            output_base,
+            &output_file_path,
+            file_path,
+            content,
+            exact,
+            extraction,
+        );
+    }
+    let new_directories = output_file_path.parent().map_or(0, |parent| {
+        parent
+            .ancestors()
+            .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+            .count()
+    });
     debug!("Extracting file: {} to {}", file_path, output_file_path_str);
-    write_extracted_file(&output_file_path, content)
-        .map_err(|e| format!("Failed to write file {}: {}", output_file_path_str, e))
+    let bytes = backup_existing(config, output_base, &output_file_path)
+        .and_then(|_| write_extracted_file(&output_file_path, content, exact))
+        .and_then(|bytes| restore_metadata(&output_file_path, &entry.metadata).map(|_| bytes))
+        .map_err(|e| format!("Failed to write file {}: {}", output_file_path_str, e))?;
+    extraction.record(file_path, bytes, new_directories);
+    Ok(())
 }
 
-// Update function signature to accept Path
-fn write_extracted_file(file_path: &Path, content: &[String]) -> io::Result<()> {
-    // Create directory structure if needed
-    if let Some(parent) = file_path.parent() {
-        fs::create_dir_all(parent)?;
+// Give an extracted file the permissions and modification time its header records
+fn restore_metadata(file_path: &Path, metadata: &llm_globber_core::Metadata) -> io::Result<()> {
+    if let Some(mode) = metadata.mode {
+        fs::set_permissions(file_path, fs::Permissions::from_mode(mode & 0o777))?;
+    }
+    if let Some(mtime) = metadata.mtime {
+        File::options()
+            .write(true)
+            .open(file_path)?
+            .set_modified(UNIX_EPOCH + Duration::from_secs(mtime))?;
     }
+    Ok(())
+}
 
-    let mut file = File::create(file_path)?;
+// With --as-patch, print the diff from the file an entry would overwrite to the entry's content
+fn print_extracted_patch(
+    output_base: &Path,
+    output_file_path: &Path,
+    file_path: &str,
+    content: &[String],
+    exact: bool,
+    extraction: &mut Extraction,
+) -> Result<(), String> {
+    let current = match fs::read(output_file_path) {
+        Ok(bytes) => match String::from_utf8(bytes) {
+            Ok(text) => Some(text),
+            Err(_) => {
+                warn!(
+                    "Skipping {}: {} is not UTF-8 text and cannot be diffed",
+                    file_path,
+                    output_file_path.display()
+                );
+                return Ok(());
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            return Err(format!(
+                "Failed to read file {}: {}",
+                output_file_path.display(),
+                e
+            ))
+        }
+    };
+    let new_content = extracted_content(content, exact);
+    let relative = output_file_path
+        .strip_prefix(output_base)
+        .unwrap_or(output_file_path);
+    if let Some(diff) = patch::file_patch(relative, current.as_deref(), &new_content) {
+        print!("{}", diff);
+    }
+    extraction.record(file_path, new_content.len() as u64, 0);
+    Ok(())
+}
 
-    // Join all lines with a single newline and write at once
-    // This preserves the exact format of the original file
-    if !content.is_empty() {
-        let joined_content = content.join("\n");
-        file.write_all(joined_content.as_bytes())?;
+// The bytes unglob writes for an entry. Joining the lines with newlines restores the file's exact
+// bytes. Entries known to be exact are written as they are; others may have been written by
+// hand, so give them the final newline they are likely missing.
+fn extracted_content(content: &[String], exact: bool) -> String {
+    let mut joined_content = content.join("\n");
+    if !exact && !content.is_empty() && !joined_content.ends_with('\n') {
+        joined_content.push('\n');
+    }
+    joined_content
+}
 
-        // Add a final newline if the content doesn't end with one
-        if !joined_content.ends_with('\n') {
-            file.write_all(b"\n")?;
-        }
+// Write an extracted file, returning how many bytes it holds
+fn write_extracted_file(file_path: &Path, content: &[String], exact: bool) -> io::Result<u64> {
+    // Create directory structure if needed
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)?;
     }
 
-    Ok(())
+    let joined_content = extracted_content(content, exact);
+    File::create(file_path)?.write_all(joined_content.as_bytes())?;
+    Ok(joined_content.len() as u64)
 }
 
 fn main() -> Result<(), String> {
     init_logger().map_err(|e| format!("Failed to initialize logger: {}", e))?;
 
+    let args: Vec<String> = std::env::args().collect();
+    // Everything after --ask-daemon is run by a running daemon instead of this process
+    if args.get(1).map(String::as_str) == Some("--ask-daemon") {
+        return daemon::ask(&args[2..]);
+    }
+    run(args, None).map(|_| ())
+}
+
+// Run one request for a daemon client from its working directory, then restore the daemon's
+// own working directory and logging
+fn run_daemon_request(
+    args: Vec<String>,
+    cwd: &Path,
+    cache: &daemon::SharedCache,
+) -> Result<Option<String>, String> {
+    let level = *GLOBAL_LOGGER
+        .level
+        .lock()
+        .expect("Log level mutex poisoned");
+    let daemon_dir =
+        std::env::current_dir().map_err(|e| format!("Failed to read working directory: {}", e))?;
+    std::env::set_current_dir(cwd)
+        .map_err(|e| format!("Failed to enter {}: {}", cwd.display(), e))?;
+    let result = run(args, Some(Arc::clone(cache)));
+    if let Err(e) = std::env::set_current_dir(&daemon_dir) {
+        warn!("Failed to return to {}: {}", daemon_dir.display(), e);
+    }
+    reset_logger(level);
+    temp::set_root(None);
+    result
+}
+
+// Run one invocation, from the command line or on behalf of a daemon client, and return the
+// path of the output file written, if any. `cache` is the daemon's cache when serving a request.
+fn run(args: Vec<String>, cache: Option<daemon::SharedCache>) -> Result<Option<String>, String> {
     let matches = App::new("llm_globber")
         .version("0.1.0")
         .author("Ken Simpson")
@@ -1325,7 +5178,7 @@ fn main() -> Result<(), String> {
                 .short('o')
                 .long("output")
                 .value_name("PATH")
-                .help("Output directory path")
+                .help("Output directory path, or an s3:// or https:// URL to upload to (needs the remote feature)")
                 .takes_value(true),
         )
         .arg(
@@ -1356,13 +5209,62 @@ fn main() -> Result<(), String> {
                 .long("recursive")
                 .help("Recursively process directories"),
         )
+        .arg(
+            Arg::with_name("bench")
+                .long("bench")
+                .help("Glob into a temporary file that is thrown away and report throughput and the time spent walking, reading, formatting and writing")
+                .conflicts_with_all(&["unglob", "plan", "resume", "output_name"]),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .help("Keep state next to the output so an interrupted run can be resumed by running it again with --resume")
+                .conflicts_with_all(&["unglob", "plan", "git_repo", "from_trace", "toc", "todos", "call_graph", "hash_manifest", "max_total_size", "budget_tokens", "model", "skip_log"]),
+        )
+        .arg(
+            Arg::with_name("keep_empty_dirs")
+                .long("keep-empty-dirs")
+                .help("Record directories with no included files so unglob recreates them"),
+        )
+        .arg(
+            Arg::with_name("skip_empty")
+                .long("skip-empty")
+                .help("Leave out zero-byte files such as empty __init__.py and .gitkeep"),
+        )
+        .arg(
+            Arg::with_name("no_tests")
+                .long("no-tests")
+                .help("Leave out tests and their fixtures: tests/ directories, *_test.go, test_*.py, *.spec.ts and the like"),
+        )
+        .arg(
+            Arg::with_name("only_tests")
+                .long("only-tests")
+                .conflicts_with("no_tests")
+                .help("Include only tests and their fixtures, by the same conventions as --no-tests"),
+        )
+        .arg(
+            Arg::with_name("include_vendored")
+                .long("include-vendored")
+                .help("Keep vendor/ and third_party/ directories and files whose copyright notice names someone else"),
+        )
         .arg(
             Arg::with_name("name_pattern")
                 .long("pattern") // Changed from "name" to "pattern" to avoid conflict
                 .short('N')
                 .value_name("PATTERN")
-                .help("Filter files by name pattern (glob syntax, e.g., '*.c')")
-                .takes_value(true),
+                .help("Filter files by name pattern (glob syntax, e.g., '*.c' or 'src/**/*.rs'; repeatable). Files must match any pattern and none prefixed with '!'. Patterns with a '/' match the path relative to the input")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("pattern_basename")
+                .long("pattern-basename")
+                .help("Match --pattern globs against file names only, even when they contain '/'"),
+        )
+        .arg(
+            Arg::with_name("ignore_case")
+                .long("ignore-case")
+                .help("Match -t extensions and --pattern, --skip-pattern, --priority-pattern and --force-text globs regardless of case"),
         )
         .arg(
             Arg::with_name("skip_pattern")
@@ -1424,13 +5326,52 @@ fn main() -> Result<(), String> {
             Arg::with_name("verbose")
                 .short('v')
                 .long("verbose")
-                .help("Verbose output"),
+                .multiple_occurrences(true)
+                .help("Verbose output: -v lists files, -vv adds debug detail, -vvv traces content"),
         )
         .arg(
             Arg::with_name("debug")
                 .long("debug")
                 .help("Print a DEBUG DUMP of the generated output file to stderr"),
         )
+        .arg(
+            Arg::with_name("log_file")
+                .long("log-file")
+                .value_name("PATH")
+                .help("Also append log messages to PATH, even with -q")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log_format")
+                .long("log-format")
+                .value_name("FORMAT")
+                .help("Log record format: text (default) or json; applies to --log-file when given")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no_color")
+                .long("no-color")
+                .help("Disable colored output (also disabled by NO_COLOR or when stderr is not a terminal)"),
+        )
+        .arg(
+            Arg::with_name("pre_hook")
+                .long("pre-hook")
+                .value_name("CMD")
+                .help("Shell command to run before globbing, with $LLM_GLOBBER_INPUTS set")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("post_hook")
+                .long("post-hook")
+                .value_name("CMD")
+                .help("Shell command to run after the output is written, with $LLM_GLOBBER_OUTPUT set and $LLM_GLOBBER_FILES naming a file that lists the entries written")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("print_path")
+                .long("print-path")
+                .help("Print the absolute path of the output file on stdout, also with -q"),
+        )
         .arg(
             Arg::with_name("quiet")
                 .short('q')
@@ -1446,68 +5387,996 @@ fn main() -> Result<(), String> {
         .arg(Arg::with_name("signature").long("signature").help(
             "Add ed25519 signatures to files when globbing and verify signatures when unglobbing",
         ))
+        .arg(
+            Arg::with_name("key_file")
+                .long("key-file")
+                .value_name("FILE")
+                .help("Sign with this OpenSSH ed25519 private key, or when unglobbing accept only archives signed by this key (implies --signature)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("toc")
+                .long("toc")
+                .help("Insert a table of contents with each entry's byte offset and length after the preamble")
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("trim_trailing_whitespace")
+                .long("trim-trailing-whitespace")
+                .help("Remove spaces and tabs from the end of every line")
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("expand_tabs")
+                .long("expand-tabs")
+                .value_name("N")
+                .help("Replace tabs with spaces, with tab stops every N columns")
+                .takes_value(true)
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("collapse_blank_lines")
+                .long("collapse-blank-lines")
+                .value_name("N")
+                .help("Keep at most N consecutive blank lines in each file")
+                .takes_value(true)
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("collapse_boilerplate")
+                .long("collapse-boilerplate")
+                .help("Write license headers and other blocks that open many files once, referenced from each file")
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("headers_only")
+                .long("headers-only")
+                .help("Write every entry's header with its content left out, as an inventory of the project")
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("metadata")
+                .long("metadata")
+                .help("Record each file's size, modification time, mode and line count in its header")
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("hash_manifest")
+                .long("hash-manifest")
+                .help("Also write the SHA-256 of each included file to OUTPUT.sha256 in sha256sum format")
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("audit_log")
+                .long("audit-log")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("Append a JSON line recording the run, its files and their hashes, and where the output went to PATH")
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("detached_sig")
+                .long("detached-sig")
+                .help("Also write a signature over the output file's exact bytes to OUTPUT.sig")
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("timestamp")
+                .long("timestamp")
+                .help("End the archive with a signed timestamp covering its content (implies --signature)")
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("require_trusted_key")
+                .long("require-trusted-key")
+                .help("When unglobbing, refuse archives not signed by a key in the trust store (implies --signature)"),
+        )
         .arg(
             Arg::with_name("git_repo")
                 .long("git")
                 .value_name("PATH/URL")
-                .help("Process a git repository from local path or clone from URL (auto-configures path, name, and files)")
+                .help("Process a git repository from local path or clone from URL (auto-configures path, name, and files); repeatable")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("name_template")
+                .long("name-template")
+                .value_name("TEMPLATE")
+                .help("Output file name template with {name}, {ts}, {date[:FORMAT]}, {counter[:WIDTH]}, {repo}, {branch}, {commit} and {shortsha} (default: {name}_{ts})")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("git_log")
+                .long("log")
+                .value_name("N")
+                .help("With --git, append the last N commits (SHA, date, author, subject)")
+                .takes_value(true)
+                .requires("git_repo"),
+        )
+        .arg(
+            Arg::with_name("author")
+                .long("author")
+                .value_name("NAME")
+                .help("With --git, include only files changed by commits whose author matches NAME (repeatable)")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .requires("git_repo"),
+        )
+        .arg(
+            Arg::with_name("changed_in")
+                .long("changed-in")
+                .value_name("N")
+                .help("With --git, include only files modified in the last N commits of the current branch")
+                .takes_value(true)
+                .requires("git_repo"),
+        )
+        .arg(
+            Arg::with_name("owner")
+                .long("owner")
+                .value_name("OWNER")
+                .help("With --git, include only files owned by OWNER (e.g. @org/team) according to CODEOWNERS (repeatable)")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .requires("git_repo"),
+        )
+        .arg(
+            Arg::with_name("retries")
+                .long("retries")
+                .value_name("N")
+                .help("Retry reads that fail with a transient error such as EIO up to N times (default: 0)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("retry_delay")
+                .long("retry-delay")
+                .value_name("MS")
+                .help("Milliseconds to wait before the first retry, doubled after each one up to 10 seconds (default: 100)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max_files")
+                .long("max-files")
+                .value_name("N")
+                .help("Maximum number of files to include (default: 100000)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("on_overflow")
+                .long("on-overflow")
+                .value_name("POLICY")
+                .help("When more files match than --max-files: truncate (default) or error")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max_total_size")
+                .long("max-total-size")
+                .value_name("MB")
+                .help("Stop adding files once the output would exceed this many MB")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("budget_tokens")
+                .long("budget-tokens")
+                .value_name("TOKENS")
+                .help("Fit the output in about this many tokens, truncating or leaving out the lowest priority files")
+                .takes_value(true)
+                .conflicts_with("max_total_size"),
+        )
+        .arg(
+            Arg::with_name("model")
+                .long("model")
+                .value_name("MODEL")
+                .help("Take the token budget and token estimate from a model preset, such as claude-sonnet, gpt-4o or llama3-8b")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("over_budget")
+                .long("over-budget")
+                .value_name("POLICY")
+                .help("Files that do not fit in --max-total-size or --budget-tokens: skip (default), outline or truncate")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("priority")
+                .long("priority")
+                .value_name("GLOB=WEIGHT,...")
+                .help("Emit files matching higher-weighted globs first, as in 'src/**=10,tests/**=3'")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("compress")
+                .long("compress")
+                .value_name("FORMAT")
+                .help("Compress the output with zstd or gzip, adding .zst or .gz to its name")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("encrypt")
+                .long("encrypt")
+                .value_name("RECIPIENT")
+                .help("Encrypt the output to an age recipient (age:age1...), adding .age to its name; repeatable")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("encrypt_pass")
+                .long("encrypt-pass")
+                .help("Encrypt the output with a passphrase from LLM_GLOBBER_PASSPHRASE or the terminal")
+                .conflicts_with("encrypt"),
+        )
+        .arg(
+            Arg::with_name("identity")
+                .long("identity")
+                .value_name("FILE")
+                .help("age identity file for reading encrypted archives; repeatable")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("tmpdir")
+                .long("tmpdir")
+                .value_name("PATH")
+                .help("Directory for git clones, extracted archives and other temporary files (default: the system temporary directory)")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("mmap_threshold")
+                .long("mmap-threshold")
+                .value_name("KB")
+                .help("Memory-map files of at least KB kilobytes instead of reading them (default: 1024)")
+                .takes_value(true)
+                .conflicts_with("no_mmap"),
+        )
+        .arg(
+            Arg::with_name("no_mmap")
+                .long("no-mmap")
+                .help("Never memory-map files, for filesystems where mapping is slow or unsafe"),
+        )
+        .arg(
+            Arg::with_name("no_space_check")
+                .long("no-space-check")
+                .help("Start clones and outputs without checking that they fit on disk"),
+        )
+        .arg(
+            Arg::with_name("io_buffer")
+                .long("io-buffer")
+                .value_name("KB")
+                .help("Buffer size in KB for reading files and writing the output (default: 256)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("fsync")
+                .long("fsync")
+                .help("Sync the output to disk when it is finished, and after each file with --resume"),
+        )
+        .arg(
+            Arg::with_name("mime")
+                .long("mime")
+                .value_name("TYPES")
+                .help("Include only files whose sniffed MIME type matches (comma separated, e.g. 'text/*,application/json')")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("exclude_mime")
+                .long("exclude-mime")
+                .value_name("TYPES")
+                .help("Exclude files whose sniffed MIME type matches (comma separated)")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("binary_threshold")
+                .long("binary-threshold")
+                .value_name("PERCENT")
+                .help("Treat files with more than PERCENT control bytes as binary (default: 10)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("force_text")
+                .long("force-text")
+                .value_name("PATTERN")
+                .help("Always include files matching this glob pattern as text (repeatable)")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("plugin")
+                .long("plugin")
+                .value_name("FILE")
+                .help("Run each text file through this WASM filter, which may keep, skip or rewrite it (repeatable)")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("transform")
+                .long("transform")
+                .value_name("PATTERN=CMD")
+                .help("Pipe files matching PATTERN through the shell command CMD and include its output (repeatable)")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("dependencies")
+                .long("dependencies")
+                .help("Start the output with a DEPENDENCIES section summarising Cargo.toml, package.json, pyproject.toml, requirements.txt and go.mod"),
+        )
+        .arg(
+            Arg::with_name("prepend")
+                .long("prepend")
+                .value_name("FILE")
+                .help("Add the contents of FILE, such as a style guide or architecture notes, in a section before the first file (repeatable)")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("append")
+                .long("append")
+                .value_name("FILE")
+                .help("Add the contents of FILE, such as a task description, in a section after the last file (repeatable)")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("todos")
+                .long("todos")
+                .help("Append a TODOS section listing every TODO, FIXME and HACK comment with file:line"),
+        )
+        .arg(
+            Arg::with_name("front_matter")
+                .long("front-matter")
+                .help("Start the output with a FRONT MATTER section recording the version, command line, time, inputs, git commit, filters and file and token counts"),
+        )
+        .arg(
+            Arg::with_name("call_graph")
+                .long("call-graph")
+                .help("Append a CALL GRAPH section listing the functions each function calls, for Rust, Python, JavaScript, TypeScript, C and Go"),
+        )
+        .arg(
+            Arg::with_name("skip_log")
+                .long("skip-log")
+                .help("Append a SKIPPED FILES section to the output listing every excluded file and why"),
+        )
+        .arg(
+            Arg::with_name("no_default_excludes")
+                .long("no-default-excludes")
+                .help("Also walk .git, node_modules, target, dist, build, __pycache__ and .venv directories"),
+        )
+        .arg(
+            Arg::with_name("entry")
+                .long("entry")
+                .value_name("FILE")
+                .help("Entry point file to include (repeatable); see --follow-imports")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("follow_imports")
+                .long("follow-imports")
+                .requires("entry")
+                .help("Include only the transitive imports of the --entry files, dependencies first"),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .value_name("ORDER")
+                .help("Order of files in the output: path (default), size, mtime, ext, or none")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("priority_pattern")
+                .long("priority-pattern")
+                .value_name("PATTERN")
+                .help("Emit files matching this glob pattern first (repeatable, earlier patterns win)")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output format: text (default), chunks (JSONL records for vector databases), tar, plain (file contents with no markers), or another tool's layout: repomix-xml, repomix-markdown, files-to-prompt, files-to-prompt-cxml or files-to-prompt-markdown")
+                .takes_value(true)
+                .conflicts_with_all(&["signature", "timestamp", "metadata", "toc", "headers_only", "collapse_boilerplate"]),
+        )
+        .arg(
+            Arg::with_name("chunk_tokens")
+                .long("chunk-tokens")
+                .value_name("TOKENS")
+                .help("Maximum estimated tokens per chunk with --format chunks (default: 512)")
+                .takes_value(true)
+                .requires("format"),
+        )
+        .arg(
+            Arg::with_name("file_comments")
+                .long("file-comments")
+                .help("With --format plain, open each file with a `file: path` comment in its own comment syntax")
+                .requires("format"),
+        )
+        .arg(
+            Arg::with_name("chunk_overlap")
+                .long("chunk-overlap")
+                .value_name("TOKENS")
+                .help("Estimated tokens shared by consecutive chunks with --format chunks (default: 64)")
+                .takes_value(true)
+                .requires("format"),
+        )
+        .arg(
+            Arg::with_name("sample")
+                .long("sample")
+                .value_name("N")
+                .help("Include a random sample of N of the selected files")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sample_by")
+                .long("sample-by")
+                .value_name("GROUP")
+                .help("Spread --sample evenly across groups of files: dir, ext, or none (default)")
+                .takes_value(true)
+                .requires("sample"),
+        )
+        .arg(
+            Arg::with_name("sample_seed")
+                .long("sample-seed")
+                .value_name("SEED")
+                .help("Seed for --sample, to take the same sample again")
+                .takes_value(true)
+                .requires("sample"),
+        )
+        .arg(
+            Arg::with_name("plan")
+                .long("plan")
+                .value_name("FILE")
+                .help("Write the files that would be included to FILE, one per line, instead of globbing them")
+                .takes_value(true)
+                .conflicts_with("unglob"),
+        )
+        .arg(
+            Arg::with_name("files_from")
+                .long("files-from")
+                .value_name("FILE")
+                .help("Also process the paths listed in FILE, one per line ('-' for stdin)")
+                .takes_value(true)
+                .conflicts_with_all(&["unglob", "from_plan"]),
+        )
+        .arg(
+            Arg::with_name("null")
+                .short('0')
+                .long("null")
+                .help("Paths in the --files-from list are separated by NUL bytes, as find -print0 writes them")
+                .requires("files_from"),
+        )
+        .arg(
+            Arg::with_name("from_plan")
+                .long("from-plan")
+                .value_name("FILE")
+                .help("Include exactly the files listed in FILE, in order, as written by --plan")
+                .takes_value(true)
+                .conflicts_with_all(&["unglob", "input_paths", "git_repo", "entry", "from_trace"]),
+        )
+        .arg(
+            Arg::with_name("from_trace")
+                .long("from-trace")
+                .value_name("FILE")
+                .help("Include only the files referenced by a compiler error, stack trace or test log ('-' for stdin)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trace_context")
+                .long("trace-context")
+                .value_name("N")
+                .help("Include only N lines of context around each line referenced in the --from-trace log")
+                .takes_value(true)
+                .requires("from_trace"),
+        )
+        .arg(
+            Arg::with_name("relevant_to")
+                .long("relevant-to")
+                .value_name("QUERY")
+                .help("Include only the files most relevant to QUERY, best match first: by shared keywords (TF-IDF), or by embedding similarity with --embedding-model")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("embedding_model")
+                .long("embedding-model")
+                .value_name("MODEL")
+                .help("Rank --relevant-to files by the similarity of their embeddings from MODEL, such as text-embedding-3-small (needs the remote feature)")
+                .takes_value(true)
+                .requires("relevant_to"),
+        )
+        .arg(
+            Arg::with_name("embedding_url")
+                .long("embedding-url")
+                .value_name("URL")
+                .help("OpenAI-compatible embeddings endpoint for --embedding-model (default: https://api.openai.com/v1/embeddings)")
+                .takes_value(true)
+                .requires("embedding_model"),
+        )
+        .arg(
+            Arg::with_name("top_k")
+                .long("top-k")
+                .value_name("K")
+                .help("Maximum number of files kept by --relevant-to (default: 20)")
+                .takes_value(true)
+                .requires("relevant_to"),
+        )
+        .arg(
+            Arg::with_name("token_budget")
+                .long("token-budget")
+                .value_name("TOKENS")
+                .help("Approximate token budget for files kept by --relevant-to")
+                .takes_value(true)
+                .requires("relevant_to"),
+        )
+        .arg(
+            Arg::with_name("render_notebooks")
+                .long("render-notebooks")
+                .help("Render Jupyter notebooks as markdown and code cells instead of raw JSON"),
+        )
+        .arg(
+            Arg::with_name("table_sample")
+                .long("table-sample")
+                .value_name("N")
+                .help("For CSV/TSV files, include only the header, the first and last N rows, and a row count")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sqlite_schema")
+                .long("sqlite-schema")
+                .help("Include the schema and row counts of SQLite databases (.db, .sqlite) instead of omitting them"),
+        )
+        .arg(
+            Arg::with_name("outline")
+                .long("outline")
+                .help("Emit only declarations (signatures, types, doc comments) instead of full bodies for supported languages"),
+        )
+        .arg(
+            Arg::with_name("path_map")
+                .long("map")
+                .value_name("SRC_PREFIX=DEST_PREFIX")
+                .help("When unglobbing, extract entries under SRC_PREFIX to DEST_PREFIX instead (repeatable)")
+                .takes_value(true)
+                .multiple_occurrences(true),
+        )
+        .arg(
+            Arg::with_name("backup")
+                .long("backup")
+                .value_name("MODE")
+                .help("When unglobbing, copy files about to be overwritten to FILE.orig, or with --backup=tree to the same path under .llm-globber-backup/")
+                .takes_value(true)
+                .require_equals(true)
+                .min_values(0)
+                .default_missing_value("orig"),
+        )
+        .arg(
+            Arg::with_name("as_patch")
+                .long("as-patch")
+                .help("When unglobbing, print a unified diff from the files on disk to the archive's contents instead of writing them")
+                .requires("unglob"),
+        )
+        .arg(
+            Arg::with_name("max_extract_size")
+                .long("max-extract-size")
+                .value_name("SIZE_MB")
+                .help("When unglobbing, refuse archives with a file larger than SIZE_MB megabytes (default: 1024)")
+                .takes_value(true)
+                .requires("unglob"),
+        )
+        .arg(
+            Arg::with_name("force")
+                .long("force")
+                .help("When unglobbing, extract into a non-empty directory even if it is not a clean git working tree")
+                .requires("unglob"),
+        )
+        .arg(
+            Arg::with_name("relative_to")
+                .long("relative-to")
+                .value_name("DIR")
+                .help("Record header paths relative to DIR (default: common ancestor of the inputs)")
                 .takes_value(true),
         )
         .arg(
             Arg::with_name("input_paths")
                 .value_name("FILES/DIRECTORIES")
-                .help("Files or directories to process")
+                .help("Files, directories or quoted glob patterns such as 'src/**/*.rs' to process, or http(s) URLs of single files")
                 .multiple(true)
-                .required_unless_one(&["git_repo", "help", "unglob"])
+                .required_unless_one(["git_repo", "help", "unglob", "entry", "from_trace", "from_plan", "files_from"])
                 .min_values(1),
         )
-        .get_matches();
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            App::new("lint")
+                .about("Validate a previously generated LLM Globber output file")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Archive to validate")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            App::new("ls")
+                .about("List the entries of a previously generated LLM Globber output file")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Archive to list")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            App::new("cat")
+                .about("Print the content of one entry of a previously generated LLM Globber output file")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Archive to read")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("path")
+                        .value_name("PATH")
+                        .help("Path of the entry, as its header records it")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            App::new("verify")
+                .about("Check a file against a detached signature written with --detached-sig")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("File to verify")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("sig")
+                        .long("sig")
+                        .value_name("SIG_FILE")
+                        .help("Signature file (default: FILE.sig)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("key_file")
+                        .long("key-file")
+                        .value_name("KEY_FILE")
+                        .help("Accept only signatures by this OpenSSH ed25519 key instead of consulting the trust store")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("require_trusted_key")
+                        .long("require-trusted-key")
+                        .help("Refuse signatures by keys missing from the trust store"),
+                ),
+        )
+        .subcommand(
+            App::new("keygen")
+                .about("Create a signing key, or print the public key of one")
+                .arg(
+                    Arg::with_name("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Write a new OpenSSH ed25519 key to FILE (default: the llm-globber signing key)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("print_pubkey")
+                        .long("print-pubkey")
+                        .value_name("KEY_FILE")
+                        .help("Print the public key of an existing OpenSSH ed25519 key")
+                        .takes_value(true)
+                        .conflicts_with("output"),
+                ),
+        )
+        .subcommand(
+            App::new("trust")
+                .about("Add a signer's public key to the trust store")
+                .arg(
+                    Arg::with_name("key")
+                        .value_name("KEY")
+                        .help("Base64 public key, as an archive's PUBLIC_KEY line holds it, or an OpenSSH ed25519 key file")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("comment")
+                        .long("comment")
+                        .value_name("TEXT")
+                        .help("Note kept next to the key, such as whose it is")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("daemon")
+                .about("Serve runs requested with --ask-daemon over a unix socket, keeping caches warm")
+                .arg(
+                    Arg::with_name("socket")
+                        .long("socket")
+                        .value_name("PATH")
+                        .help("Socket to listen on (default: $LLM_GLOBBER_SOCKET or a per-user socket)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("clean-temp")
+                .about("Remove temporary directories left behind by runs that did not finish")
+                .arg(
+                    Arg::with_name("older_than")
+                        .long("older-than")
+                        .value_name("HOURS")
+                        .help("Only remove directories not modified for this many hours (default: 24)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("convert")
+                .about("Rewrite an LLM Globber, repomix or files-to-prompt archive in another of these layouts")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Archive to convert; its layout is detected")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .value_name("FORMAT")
+                        .help("Layout to write: text, repomix-xml, repomix-markdown, files-to-prompt, files-to-prompt-cxml or files-to-prompt-markdown")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Write to FILE instead of standard output")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("grep")
+                .about("Search the contents of a previously generated LLM Globber output file")
+                .arg(
+                    Arg::with_name("pattern")
+                        .value_name("PATTERN")
+                        .help("Regular expression to search for")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Archive to search")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("ignore_case")
+                        .short('i')
+                        .long("ignore-case")
+                        .help("Match case-insensitively"),
+                )
+                .arg(
+                    Arg::with_name("fixed_strings")
+                        .short('F')
+                        .long("fixed-strings")
+                        .help("Treat PATTERN as a literal string"),
+                ),
+        )
+        .try_get_matches_from(&args);
+    let matches = match matches {
+        Ok(matches) => matches,
+        Err(e) if cache.is_none() => e.exit(),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    // A daemon serves globbing runs only; anything that prints or exits is run directly
+    if cache.is_some() && (matches.subcommand().is_some() || matches.is_present("help")) {
+        return Err(
+            "The daemon only runs globbing requests; run this command directly".to_string(),
+        );
+    }
+
+    colored::control::set_override(use_color(matches.is_present("no_color")));
+    if let Some(format) = matches.value_of("log_format") {
+        set_log_format(LogFormat::parse(format)?);
+    }
+    if let Some(path) = matches.value_of("log_file") {
+        set_log_file(path)?;
+    }
 
     if matches.is_present("help") {
         print_usage("llm_globber");
         exit(0);
     }
 
-    let mut config = ScrapeConfig::default();
+    // Keys for reading archives encrypted to age recipients, for unglob, lint and grep alike
+    let identity_files: Vec<String> = matches
+        .values_of("identity")
+        .map(|values| values.map(str::to_string).collect())
+        .unwrap_or_default();
+
+    if let Some(tmpdir) = matches.value_of("tmpdir") {
+        fs::create_dir_all(tmpdir)
+            .map_err(|e| format!("Could not create --tmpdir directory: {}: {}", tmpdir, e))?;
+        let root = sanitize_path(tmpdir)
+            .map_err(|e| format!("Invalid --tmpdir directory: {}: {}", tmpdir, e))?;
+        temp::set_root(Some(PathBuf::from(root)));
+    }
 
-    // Handle git repository option
-    if let Some(git_input) = matches.value_of("git_repo") {
-        let actual_git_path = if is_git_url(git_input) {
-            // Clone the repository from URL
-            info!("Detected git URL: {}", git_input);
-            let cloned_path = clone_git_repository(git_input)?;
-            config.temp_git_path = Some(cloned_path.clone());
-            cloned_path
-        } else {
-            // Local path - verify this is a git repository
-            if !is_git_repository(git_input) {
-                return Err(format!("Error: {} is not a git repository", git_input));
+    if let Some(("clean-temp", clean_matches)) = matches.subcommand() {
+        let hours = match clean_matches.value_of("older_than") {
+            Some(hours) => hours
+                .parse::<f64>()
+                .ok()
+                .filter(|hours| hours.is_finite() && *hours >= 0.0)
+                .ok_or_else(|| format!("Invalid --older-than value '{}'", hours))?,
+            None => 24.0,
+        };
+        return temp::run_clean_temp(Duration::from_secs_f64(hours * 3600.0)).map(|_| None);
+    }
+
+    if let Some(("lint", lint_matches)) = matches.subcommand() {
+        return lint::run_lint(
+            lint_matches.value_of("file").unwrap_or_default(),
+            &identity_files,
+        )
+        .map(|_| None);
+    }
+
+    if let Some(("ls", ls_matches)) = matches.subcommand() {
+        return listing::run_ls(
+            ls_matches.value_of("file").unwrap_or_default(),
+            &identity_files,
+        )
+        .map(|_| None);
+    }
+
+    if let Some(("cat", cat_matches)) = matches.subcommand() {
+        return toc::run_cat(
+            cat_matches.value_of("file").unwrap_or_default(),
+            cat_matches.value_of("path").unwrap_or_default(),
+            &identity_files,
+        )
+        .map(|_| None);
+    }
+
+    if let Some(("verify", verify_matches)) = matches.subcommand() {
+        let file = Path::new(verify_matches.value_of("file").unwrap_or_default());
+        let sig = verify_matches
+            .value_of("sig")
+            .map_or_else(|| sigfile::sig_path(file), PathBuf::from);
+        let key = match verify_matches.value_of("key_file") {
+            Some(path) => Some(sshkey::load_public_key(path)?),
+            None => None,
+        };
+        let (signer, trusted) = sigfile::verify(
+            &sig,
+            file,
+            key,
+            verify_matches.is_present("require_trusted_key"),
+        )?;
+        println!(
+            "{}: good signature by {}{}",
+            file.display(),
+            if trusted { "" } else { "untrusted key " },
+            trust::encode_key(&signer)
+        );
+        // Signed archives say how they were made; other signed files have nothing to add.
+        // Encrypted ones are only read with an identity, rather than asking for a passphrase.
+        let encrypted = File::open(file)
+            .and_then(|stream| compress::peek(stream, 64))
+            .is_ok_and(|(head, _)| encrypt::is_encrypted(&head));
+        if !encrypted || !identity_files.is_empty() {
+            if let Ok(front_matter) =
+                listing::read_front_matter(&file.to_string_lossy(), &identity_files)
+            {
+                listing::print_front_matter(&front_matter);
             }
-            git_input.to_string()
+        }
+        return Ok(None);
+    }
+
+    if let Some(("keygen", keygen_matches)) = matches.subcommand() {
+        let public_key = if let Some(path) = keygen_matches.value_of("print_pubkey") {
+            sshkey::load_public_key(path)?
+        } else if let Some(path) = keygen_matches.value_of("output") {
+            let keypair = sshkey::generate_key_file(path, "llm_globber")?;
+            info!("Created signing key {}", path);
+            // Archives signed with the new key should extract here without a warning
+            trust::TrustStore::load()?.add(&keypair.public, &format!("key file {}", path))?;
+            keypair.public
+        } else {
+            trust::create_signing_key()?.public
         };
+        println!("{}", trust::encode_key(&public_key));
+        return Ok(None);
+    }
+
+    if let Some(("trust", trust_matches)) = matches.subcommand() {
+        let key = trust::parse_key(trust_matches.value_of("key").unwrap_or_default())?;
+        let mut store = trust::TrustStore::load()?;
+        let comment = trust_matches.value_of("comment").map_or_else(
+            || format!("added {}", chrono::Local::now().format("%Y-%m-%d")),
+            str::to_string,
+        );
+        store.add(&key, &comment)?;
+        info!(
+            "Trusting key {}; it is in {}",
+            trust::encode_key(&key),
+            store.path().display()
+        );
+        return Ok(None);
+    }
+
+    if let Some(("daemon", daemon_matches)) = matches.subcommand() {
+        let socket = daemon_matches
+            .value_of("socket")
+            .map_or_else(daemon::socket_path, PathBuf::from);
+        // Passphrases would come from the daemon's environment or terminal, not the client's
+        encrypt::refuse_passphrases();
+        return daemon::serve(&socket, run_daemon_request).map(|_| None);
+    }
+
+    if let Some(("convert", convert_matches)) = matches.subcommand() {
+        return interop::run_convert(
+            convert_matches.value_of("file").unwrap_or_default(),
+            convert_matches.value_of("to").unwrap_or_default(),
+            convert_matches.value_of("output"),
+            &identity_files,
+        )
+        .map(|_| None);
+    }
+
+    if let Some(("grep", grep_matches)) = matches.subcommand() {
+        let pattern = search::build_pattern(
+            grep_matches.value_of("pattern").unwrap_or_default(),
+            grep_matches.is_present("ignore_case"),
+            grep_matches.is_present("fixed_strings"),
+        )?;
+        return search::run_grep(
+            grep_matches.value_of("file").unwrap_or_default(),
+            &pattern,
+            &identity_files,
+        )
+        .map(|_| None);
+    }
+
+    let mut config = ScrapeConfig {
+        identity_files,
+        cache,
+        ..ScrapeConfig::default()
+    };
 
-        // Set git repo path
-        config.git_repo_path = Some(actual_git_path.clone());
+    // Handle git repository option
+    if let Some(git_inputs) = matches.values_of("git_repo") {
+        for git_input in git_inputs {
+            if let Err(e) = add_git_repo(&mut config, &matches, git_input) {
+                cleanup_config_temp_dirs(&config);
+                return Err(e);
+            }
+        }
 
         // Set output path to current directory if not specified
         let output_path = matches.value_of("output_path").unwrap_or(".");
-        config.output_path = sanitize_path(output_path)
-            .map_err(|e| format!("Invalid output path: {}: {}", output_path, e))?;
-
-        // Get repository name and branch for output filename
-        let repo_name = if is_git_url(git_input) {
-            get_repo_name_from_url(git_input)
-        } else {
-            get_git_repo_name(&actual_git_path)?
+        set_output_destination(&mut config, output_path)?;
+
+        // Name the output after the repositories and branches unless -n was given
+        config.output_filename = match matches.value_of("output_name") {
+            Some(output_filename) => output_filename.to_string(),
+            None => config
+                .git_repos
+                .iter()
+                .map(|repo| format!("{}_{}", repo.name, repo.branch))
+                .collect::<Vec<_>>()
+                .join("+"),
         };
-        let branch_name = get_git_branch(&actual_git_path)?;
-        config.output_filename = format!("{}_{}", repo_name, branch_name);
 
         // Enable recursion
         config.recursive = true;
 
-        info!("Processing git repository: {}", actual_git_path);
-        info!("Repository: {}, Branch: {}", repo_name, branch_name);
         info!(
             "Output will be: {}/{}.txt",
             config.output_path, config.output_filename
@@ -1527,6 +6396,28 @@ fn main() -> Result<(), String> {
         if let Some(output_filename) = matches.value_of("output_name") {
             config.output_filename = output_filename.to_string();
         }
+
+        if let Some(rules) = matches.values_of("path_map") {
+            for rule in rules {
+                config.path_maps.push(parse_path_map(rule)?);
+            }
+        }
+        if let Some(mode) = matches.value_of("backup") {
+            config.backup = Some(BackupMode::parse(mode)?);
+        }
+        config.as_patch = matches.is_present("as_patch");
+        config.force = matches.is_present("force");
+        if let Some(size) = matches.value_of("max_extract_size") {
+            let mb_size = size
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid --max-extract-size value '{}': {}", size, e))?;
+            config.max_extract_size = mb_size.saturating_mul(1024 * 1024);
+        }
+    } else if matches.is_present("plan") {
+        // Planning only lists files, so there is no output to name
+    } else if matches.is_present("bench") {
+        // Benchmarks write to a temporary file of their own
+        config.output_filename = "bench".to_string();
     } else {
         // Standard mode - require output path and filename
         let output_path = matches
@@ -1536,8 +6427,7 @@ fn main() -> Result<(), String> {
             .value_of("output_name")
             .ok_or("Error: Output filename (-n) is required when not using --git or --unglob")?;
 
-        config.output_path = sanitize_path(output_path)
-            .map_err(|e| format!("Invalid output path: {}: {}", output_path, e))?;
+        set_output_destination(&mut config, output_path)?;
         config.output_filename = output_filename.to_string();
     }
 
@@ -1550,8 +6440,34 @@ fn main() -> Result<(), String> {
     if matches.is_present("recursive") {
         config.recursive = true;
     }
-    if let Some(name_pattern) = matches.value_of("name_pattern") {
-        config.name_pattern = name_pattern.to_string();
+    config.keep_empty_dirs = matches.is_present("keep_empty_dirs");
+    config.skip_empty = matches.is_present("skip_empty");
+    config.include_vendored = matches.is_present("include_vendored");
+    config.test_files = match (
+        matches.is_present("no_tests"),
+        matches.is_present("only_tests"),
+    ) {
+        (true, _) => TestFiles::Skip,
+        (_, true) => TestFiles::Only,
+        _ => TestFiles::Keep,
+    };
+    config.resume = matches.is_present("resume");
+    config.bench = matches.is_present("bench");
+    config.ignore_case = matches.is_present("ignore_case");
+    config.pattern_basename = matches.is_present("pattern_basename");
+    if let Some(name_patterns) = matches.values_of("name_pattern") {
+        for pattern_str in name_patterns {
+            let (negated, glob) = match pattern_str.strip_prefix('!') {
+                Some(glob) => (true, glob),
+                None => (false, pattern_str),
+            };
+            let pattern = Pattern::new(glob)
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern_str, e))?;
+            match negated {
+                true => config.excluded_name_patterns.push(pattern),
+                false => config.name_patterns.push(pattern),
+            }
+        }
     }
     if let Some(skip_patterns) = matches.values_of("skip_pattern") {
         for pattern_str in skip_patterns {
@@ -1563,6 +6479,216 @@ fn main() -> Result<(), String> {
             }
         }
     }
+    if let Some(sort) = matches.value_of("sort") {
+        config.sort_order = SortOrder::parse(sort)?;
+    }
+    if let Some(priority_patterns) = matches.values_of("priority_pattern") {
+        for pattern_str in priority_patterns {
+            let pattern = Pattern::new(pattern_str)
+                .map_err(|e| format!("Invalid priority pattern '{}': {}", pattern_str, e))?;
+            config.priority_patterns.push(pattern);
+        }
+    }
+    config.outline = matches.is_present("outline");
+    config.render_notebooks = matches.is_present("render_notebooks");
+    config.sqlite_schema = matches.is_present("sqlite_schema");
+    config.default_excludes = !matches.is_present("no_default_excludes");
+    if let Some(max_files) = matches.value_of("max_files") {
+        config.max_files = max_files
+            .parse::<usize>()
+            .ok()
+            .filter(|max_files| *max_files > 0)
+            .ok_or_else(|| format!("Invalid --max-files value '{}'", max_files))?;
+    }
+    if let Some(retries) = matches.value_of("retries") {
+        config.retries = retries
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid --retries value '{}'", retries))?;
+    }
+    if let Some(delay) = matches.value_of("retry_delay") {
+        config.retry_delay = delay
+            .parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|_| format!("Invalid --retry-delay value '{}'", delay))?;
+    }
+    if let Some(policy) = matches.value_of("on_overflow") {
+        config.on_overflow = OverflowPolicy::parse(policy)?;
+    }
+    if let Some(size_str) = matches.value_of("max_total_size") {
+        let mb_size = size_str
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid --max-total-size value '{}': {}", size_str, e))?;
+        config.max_total_size = Some(mb_size * 1024 * 1024);
+    }
+    // A model preset fills in what is not given explicitly
+    let model = matches.value_of("model").map(models::find).transpose()?;
+    if let Some(model) = &model {
+        info!(
+            "Model {}: {} token context window, about {} bytes per token",
+            model.name, model.context_tokens, model.bytes_per_token
+        );
+        config.bytes_per_token = model.bytes_per_token;
+    }
+    let budget_tokens = match matches.value_of("budget_tokens") {
+        Some(tokens) => Some(
+            tokens
+                .parse::<usize>()
+                .ok()
+                .filter(|tokens| *tokens > 0)
+                .ok_or_else(|| format!("Invalid --budget-tokens value '{}'", tokens))?,
+        ),
+        None if config.max_total_size.is_none() => model.as_ref().map(models::Model::budget_tokens),
+        None => None,
+    };
+    if let Some(tokens) = budget_tokens {
+        config.budget_tokens = Some(tokens);
+        config.max_total_size = Some((tokens as f64 * config.bytes_per_token) as u64);
+        config.over_budget = OverBudgetPolicy::Truncate;
+    }
+    if let Some(priority) = matches.value_of("priority") {
+        config.priority_weights = parse_priority_weights(priority)?;
+    }
+    if let Some(recipients) = matches.values_of("encrypt") {
+        config.encrypt = Some(encrypt::Encryption::recipients(
+            &recipients.collect::<Vec<_>>(),
+        )?);
+    }
+    if matches.is_present("encrypt_pass") {
+        config.encrypt = Some(encrypt::Encryption::passphrase()?);
+    }
+    if let Some(compression) = matches.value_of("compress") {
+        config.compress = Some(compress::Compression::parse(compression)?);
+    }
+    if let Some(threshold) = matches.value_of("mmap_threshold") {
+        let kb = threshold
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid --mmap-threshold value '{}': {}", threshold, e))?;
+        config.mmap_threshold = Some(kb * 1024);
+    }
+    if matches.is_present("no_mmap") {
+        config.mmap_threshold = None;
+    }
+    config.space_check = !matches.is_present("no_space_check");
+    if let Some(size) = matches.value_of("io_buffer") {
+        config.io_buffer_size = size
+            .parse::<usize>()
+            .ok()
+            .filter(|kb| *kb > 0)
+            .map(|kb| kb * 1024)
+            .ok_or_else(|| format!("Invalid --io-buffer value '{}'", size))?;
+    }
+    config.fsync = matches.is_present("fsync");
+    config.skip_log = matches.is_present("skip_log");
+    config.todos = matches.is_present("todos");
+    config.call_graph = matches.is_present("call_graph");
+    if matches.is_present("front_matter") {
+        config.front_matter = Some(command_front_matter(&matches));
+    }
+    config.dependencies = matches.is_present("dependencies");
+    config.git_log = parse_git_log(&matches)?;
+    config.prepend = user_sections(&matches, "prepend")?;
+    config.append = user_sections(&matches, "append")?;
+    if let Some(template) = matches.value_of("name_template") {
+        config.name_template = template.to_string();
+    }
+    if let Some(policy) = matches.value_of("over_budget") {
+        if config.max_total_size.is_none() {
+            return Err("--over-budget needs --max-total-size or --budget-tokens".to_string());
+        }
+        config.over_budget = OverBudgetPolicy::parse(policy)?;
+    }
+    if let Some(threshold) = matches.value_of("binary_threshold") {
+        config.binary_threshold = threshold
+            .parse::<usize>()
+            .ok()
+            .filter(|threshold| *threshold <= 100)
+            .ok_or_else(|| format!("Invalid --binary-threshold value '{}'", threshold))?;
+    }
+    for (arg, list) in [
+        ("mime", &mut config.mime_include),
+        ("exclude_mime", &mut config.mime_exclude),
+    ] {
+        if let Some(values) = matches.values_of(arg) {
+            list.extend(
+                values
+                    .flat_map(|value| value.split(','))
+                    .map(str::trim)
+                    .filter(|mime| !mime.is_empty())
+                    .map(str::to_string),
+            );
+        }
+    }
+    if let Some(patterns) = matches.values_of("force_text") {
+        for pattern_str in patterns {
+            let pattern = Pattern::new(pattern_str)
+                .map_err(|e| format!("Invalid force-text pattern '{}': {}", pattern_str, e))?;
+            config.force_text_patterns.push(pattern);
+        }
+    }
+    config.policy = policy::Policy::load()?;
+    if let Some(policy) = &config.policy {
+        for source in &policy.sources {
+            info!("Applying policy {}", source.display());
+        }
+    }
+    let mut audit_logs = config
+        .policy
+        .as_ref()
+        .map_or(Vec::new(), |policy| policy.audit_logs.clone());
+    audit_logs.extend(matches.value_of("audit_log").map(PathBuf::from));
+    if !audit_logs.is_empty() {
+        // A resumed run would only record the files written after the interruption
+        if config.resume {
+            return Err("--resume cannot be used with an audit log".to_string());
+        }
+        config.audit = Some(audit::Audit::new(audit_logs, args.clone())?);
+    }
+    if let Some(paths) = matches.values_of("plugin") {
+        for path in paths {
+            config.plugins.push(plugins::Plugin::load(path)?);
+        }
+    }
+    if let Some(rules) = matches.values_of("transform") {
+        for rule in rules {
+            config.transforms.push(transforms::Transform::parse(rule)?);
+        }
+    }
+    if let Some(rows) = matches.value_of("table_sample") {
+        config.table_sample = Some(
+            rows.parse::<usize>()
+                .map_err(|e| format!("Invalid --table-sample value '{}': {}", rows, e))?,
+        );
+    }
+    if let Some(format) = matches.value_of("format") {
+        config.output_format = OutputFormat::parse(format)?;
+    } else if let Some(model) = &model {
+        config.output_format = model.format;
+    }
+    if config.output_format == OutputFormat::Tar && config.compress.is_none() {
+        config.compress = Some(compress::Compression::Gzip);
+    }
+    if let Some(tokens) = matches.value_of("chunk_tokens") {
+        config.chunk_tokens = tokens
+            .parse::<usize>()
+            .ok()
+            .filter(|tokens| *tokens > 0)
+            .ok_or_else(|| format!("Invalid --chunk-tokens value '{}'", tokens))?;
+    }
+    if let Some(tokens) = matches.value_of("chunk_overlap") {
+        config.chunk_overlap = tokens
+            .parse::<usize>()
+            .map_err(|e| format!("Invalid --chunk-overlap value '{}': {}", tokens, e))?;
+    }
+    config.file_comments = matches.is_present("file_comments");
+    if config.file_comments && config.output_format != OutputFormat::Plain {
+        return Err("--file-comments needs --format plain".to_string());
+    }
+    if config.chunk_overlap >= config.chunk_tokens {
+        return Err(format!(
+            "--chunk-overlap ({}) must be smaller than --chunk-tokens ({})",
+            config.chunk_overlap, config.chunk_tokens
+        ));
+    }
     if matches.is_present("threads") {
         warn!("The -j option is deprecated and has no effect");
     }
@@ -1584,37 +6710,85 @@ fn main() -> Result<(), String> {
         config.quiet = true;
         set_quiet_mode(true);
     }
-    if matches.is_present("verbose") {
+    // -v shows which files are processed, -vv adds debug detail, -vvv adds content dumps
+    let verbosity = matches.occurrences_of("verbose");
+    if verbosity > 0 {
         config.verbose = true;
-        set_log_level(LogLevel::Debug);
+        set_log_level(match verbosity {
+            1 => LogLevel::Info,
+            2 => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        });
     }
     if matches.is_present("debug") {
         config.debug_mode = true;
-        set_log_level(LogLevel::Debug);
+        if verbosity < 2 {
+            set_log_level(LogLevel::Debug);
+        }
     }
     if matches.is_present("abort_on_error") {
         config.abort_on_error = true;
     }
 
-    if matches.is_present("signature") {
-        config.use_signature = true;
-
+    if matches.is_present("require_trusted_key") {
         if !config.unglob_mode {
-            // Generate a new keypair for signing
-            let keypair = generate_keypair();
-            let public_key = keypair.public;
+            return Err("--require-trusted-key applies only when unglobbing".to_string());
+        }
+        config.require_trusted_key = true;
+    }
 
-            info!("Generated ed25519 keypair for signing");
+    let key_file = matches.value_of("key_file");
+    config.detached_sig = matches.is_present("detached_sig");
+    config.hash_manifest = matches.is_present("hash_manifest");
+    config.metadata = matches.is_present("metadata");
+    config.toc = matches.is_present("toc");
+    config.headers_only = matches.is_present("headers_only");
+    config.collapse_boilerplate = matches.is_present("collapse_boilerplate");
+    config.normalization.trim_trailing = matches.is_present("trim_trailing_whitespace");
+    if let Some(width) = matches.value_of("expand_tabs") {
+        config.normalization.tab_width = Some(
+            width
+                .parse::<usize>()
+                .ok()
+                .filter(|width| *width > 0)
+                .ok_or_else(|| format!("Invalid --expand-tabs value '{}'", width))?,
+        );
+    }
+    if let Some(max) = matches.value_of("collapse_blank_lines") {
+        config.normalization.max_blank_lines = Some(
+            max.parse::<usize>()
+                .map_err(|e| format!("Invalid --collapse-blank-lines value '{}': {}", max, e))?,
+        );
+    }
+    config.timestamp = matches.is_present("timestamp");
+    if matches.is_present("signature")
+        || config.require_trusted_key
+        || key_file.is_some()
+        || config.timestamp
+    {
+        config.use_signature = true;
+    }
+    if config.use_signature || config.detached_sig {
+        if !config.unglob_mode {
+            let signer = match key_file {
+                Some(path) => sshkey::load_signer(path)?,
+                // Sign with this user's key, so archives can be checked against a trust store
+                None => sshkey::Signer::Key(trust::signing_key()?),
+            };
+            let public_key = signer.public_key();
+            info!("Loaded ed25519 key for signing");
             info!(
                 "Public key: {}",
                 general_purpose::STANDARD.encode(public_key.to_bytes())
             );
 
-            config.keypair = Some(keypair);
+            config.signer = Some(signer);
             config.public_key = Some(public_key);
         } else {
             // For unglobbing, we'll extract the public key from the file
-            // We don't need to generate a keypair here anymore
+            if let Some(path) = key_file {
+                config.verify_key = Some(sshkey::load_public_key(path)?);
+            }
             info!("Will extract public key from input file for signature verification");
         }
     }
@@ -1623,53 +6797,131 @@ fn main() -> Result<(), String> {
         info!("Output path set to: '{}'", config.output_path);
     }
 
-    let mut found_input = false;
-
-    // Process git repository if specified
-    if let Some(git_path) = &config.git_repo_path {
-        found_input = true;
-
-        // Get all tracked files in the git repository
-        let git_files = get_git_tracked_files(git_path)?;
-
-        if git_files.is_empty() {
-            return Err(format!(
-                "Error: No tracked files found in git repository: {}",
-                git_path
-            ));
+    // Runs before any input is walked, so files a formatter or generator writes are included
+    if let (Some(command), false) = (matches.value_of("pre_hook"), config.unglob_mode) {
+        let inputs: Vec<&str> = matches
+            .values_of("input_paths")
+            .into_iter()
+            .flatten()
+            .collect();
+        if let Err(e) = hooks::run(
+            "pre-hook",
+            command,
+            &[(hooks::INPUTS_ENV, inputs.join("\n"))],
+        ) {
+            cleanup_config_temp_dirs(&config);
+            return Err(e);
         }
+    }
 
-        info!("Found {} tracked files in git repository", git_files.len());
+    // A resumed run takes its files from the interrupted one instead of finding them again
+    if config.resume {
+        let state_path =
+            resume::state_path(Path::new(&config.output_path), &config.output_filename);
+        if let Some(state) = resume::load(&state_path)? {
+            info!(
+                "Resuming {}: {} of {} files already written",
+                state.output.display(),
+                state.offsets.len(),
+                state.files.len()
+            );
+            config.file_entries = state
+                .files
+                .iter()
+                .map(|path| FileEntry { path: path.clone() })
+                .collect();
+            config.hard_links = state.hard_links.clone();
+            config.walked_dirs = state.walked_dirs.clone();
+            config.relative_root = state.relative_root.clone();
+            config.resume_state = Some(state);
+            return finish_run(&mut config, &matches);
+        }
+    }
 
-        // Add all git tracked files to the file entries
-        for file_path in git_files {
-            let path = Path::new(&file_path);
-            if path.is_file() {
-                let base_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-                if should_process_file(&config, &file_path, base_name) {
-                    add_file_entry(&mut config, &file_path);
-                }
+    let walk_start = Instant::now();
+    let mut found_input = false;
+    // Directories the inputs live in, used to pick the default header root
+    let mut input_roots: Vec<PathBuf> = Vec::new();
+
+    // Process git repositories if specified
+    let input_paths: Vec<&str> = matches
+        .values_of("input_paths")
+        .into_iter()
+        .flatten()
+        .collect();
+    // With several sources, each repository's files are prefixed with its name in headers
+    let prefix_repos = config.git_repos.len() + usize::from(!input_paths.is_empty()) > 1;
+    for repo in config.git_repos.clone() {
+        found_input = true;
+        if let Ok(root) = Path::new(&repo.path).canonicalize() {
+            if prefix_repos {
+                config.source_prefixes.push((root, repo.name.clone()));
+            } else {
+                input_roots.push(root);
             }
         }
-    } else if let Some(input_paths) = matches.values_of("input_paths") {
-        // Standard mode - process specified input paths
-        let input_paths: Vec<&str> = input_paths.collect();
+        if let Err(e) = collect_git_files(&mut config, &matches, &repo.path) {
+            cleanup_config_temp_dirs(&config);
+            return Err(e);
+        }
+    }
 
+    if !input_paths.is_empty() {
+        // Standard mode - process specified input paths
         for input_path_str in input_paths {
             found_input = true;
-            let input_path = PathBuf::from(input_path_str);
 
-            if !input_path.exists() {
-                warn!(
-                    "Could not access path {}: Path does not exist",
-                    input_path_str
-                );
+            if remote::is_http_url(input_path_str) {
+                if let Err(e) = add_url_input(&mut config, input_path_str) {
+                    cleanup_config_temp_dirs(&config);
+                    return Err(e);
+                }
+                continue;
+            }
+
+            // Patterns are expanded here rather than by the shell, so they match the same files
+            // in every shell and a large project does not run into the argument length limit
+            if is_glob_input(input_path_str) {
+                match expand_glob_input(&mut config, input_path_str) {
+                    Ok(root) => input_roots.extend(root),
+                    Err(e) => {
+                        cleanup_config_temp_dirs(&config);
+                        return Err(e);
+                    }
+                }
+                continue;
+            }
+
+            // Inputs are resolved the way output paths are, so `./src`, its absolute path and a
+            // symlink to it are walked as the same files and give the same headers
+            let input_path = match sanitize_path(input_path_str) {
+                Ok(path) => PathBuf::from(path),
+                Err(e) => {
+                    warn!("Could not access path {}: {}", input_path_str, e);
+                    continue;
+                }
+            };
+            let input_path_str = &*input_path.to_string_lossy();
+
+            if input_path.is_file() && archives::is_archive(&input_path) {
+                if let Err(e) = add_archive_input(&mut config, &input_path) {
+                    cleanup_config_temp_dirs(&config);
+                    return Err(e);
+                }
                 continue;
             }
 
+            let input_root = if input_path.is_dir() {
+                Some(input_path.clone())
+            } else {
+                input_path.parent().map(Path::to_path_buf)
+            };
+            input_roots.extend(input_root);
+
             if input_path.is_dir() {
                 if config.recursive {
-                    process_directory(&mut config, &input_path_str).map_err(|e| {
+                    config.pattern_roots.push(input_path.clone());
+                    process_directory(&mut config, &input_path).map_err(|e| {
                         format!("Error processing directory {}: {}", input_path_str, e)
                     })?;
                 } else {
@@ -1678,26 +6930,197 @@ fn main() -> Result<(), String> {
                         input_path_str
                     );
                 }
-            } else if input_path.is_file() {
-                if should_process_file(
-                    &config,
-                    &input_path_str,
+            } else if input_path.is_file()
+                && should_process_file(
+                    &mut config,
+                    &input_path,
                     input_path
                         .file_name()
                         .and_then(|s| s.to_str())
                         .unwrap_or(""),
-                ) {
-                    add_file_entry(&mut config, &input_path_str);
-                }
+                )
+            {
+                add_file_entry(&mut config, &input_path);
+            }
+        }
+    }
+
+    if let Some(source) = matches.value_of("files_from") {
+        found_input = true;
+        match add_files_from(&mut config, source, matches.is_present("null")) {
+            Ok(roots) => input_roots.extend(roots),
+            Err(e) => {
+                cleanup_config_temp_dirs(&config);
+                return Err(e);
+            }
+        }
+    }
+
+    if let Some(entry_paths) = matches.values_of("entry") {
+        found_input = true;
+        let mut entries = Vec::new();
+        for entry in entry_paths {
+            let canonical = sanitize_path(entry)
+                .map_err(|e| format!("Invalid entry file: {}: {}", entry, e))?;
+            let canonical = PathBuf::from(canonical);
+            input_roots.extend(canonical.parent().map(Path::to_path_buf));
+            entries.push(canonical);
+        }
+
+        let selected = if matches.is_present("follow_imports") {
+            // When inputs were also given, they bound which files the import walk may pull in
+            let allowed: Option<HashSet<PathBuf>> = if config.file_entries.is_empty() {
+                None
+            } else {
+                Some(
+                    config
+                        .file_entries
+                        .iter()
+                        .filter_map(|entry| canonical_file_path(&entry.path).ok())
+                        .collect(),
+                )
+            };
+            let closure = deps::dependency_closure(&entries, allowed.as_ref());
+            info!(
+                "Following imports from {} entry file(s) selected {} files",
+                entries.len(),
+                closure.len()
+            );
+            config.file_entries.clear();
+            config.seen_files.clear();
+            config.linked_files.clear();
+            config.hard_links.clear();
+            config.walked_dirs.clear();
+            // Keep the dependencies-first order unless the user asked for another one
+            if !matches.is_present("sort") {
+                config.sort_order = SortOrder::None;
+            }
+            closure
+        } else {
+            entries
+        };
+
+        for path in selected {
+            let base_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+            if should_process_file(&mut config, &path, base_name)
+                && !config.file_entries.iter().any(|entry| entry.path == path)
+            {
+                add_file_entry(&mut config, &path);
+            }
+        }
+    }
+
+    if let Some(plan_file) = matches.value_of("from_plan") {
+        found_input = true;
+        let paths = match plan::read(plan_file) {
+            Ok(paths) => paths,
+            Err(e) => {
+                cleanup_config_temp_dirs(&config);
+                return Err(e);
+            }
+        };
+        info!("Read {} file(s) from plan {}", paths.len(), plan_file);
+        // The plan says exactly which files to include and in what order, so the usual filters
+        // and sorting do not apply unless the user asks for an order
+        if !matches.is_present("sort") {
+            config.sort_order = SortOrder::None;
+        }
+        for path in paths {
+            if !is_regular_file(&path) {
+                warn!("Skipping {} from the plan: not a file", path);
+                continue;
+            }
+            input_roots.extend(
+                canonical_file_path(&path)
+                    .ok()
+                    .and_then(|path| path.parent().map(Path::to_path_buf)),
+            );
+            add_file_entry(&mut config, Path::new(&path));
+        }
+    }
+
+    if let Some(trace_file) = matches.value_of("from_trace") {
+        found_input = true;
+        let log = if trace_file == "-" {
+            let mut log = String::new();
+            io::stdin()
+                .read_to_string(&mut log)
+                .map_err(|e| format!("Failed to read trace from stdin: {}", e))?;
+            log
+        } else {
+            let bytes = fs::read(trace_file)
+                .map_err(|e| format!("Failed to read trace file: {}: {}", trace_file, e))?;
+            String::from_utf8_lossy(&bytes).into_owned()
+        };
+        let refs = trace::parse_trace(&log);
+
+        // Paths in the log are tried relative to the current directory and each input directory,
+        // then matched by suffix against the files collected from the inputs
+        let mut roots = vec![PathBuf::from(".")];
+        if let Some(input_paths) = matches.values_of("input_paths") {
+            roots.extend(input_paths.map(PathBuf::from).filter(|path| path.is_dir()));
+        }
+        roots.extend(
+            config
+                .git_repos
+                .iter()
+                .map(|repo| PathBuf::from(&repo.path)),
+        );
+        let candidates: Vec<PathBuf> = config
+            .file_entries
+            .iter()
+            .filter_map(|entry| canonical_file_path(&entry.path).ok())
+            .collect();
+        let resolved = trace::resolve_trace(&refs, &roots, &candidates);
+        if input_roots.is_empty() {
+            input_roots.extend(Path::new(".").canonicalize().ok());
+        }
+        info!(
+            "Found {} file reference(s) in {}, {} of them on disk",
+            refs.len(),
+            trace_file,
+            resolved.len()
+        );
+
+        config.file_entries.clear();
+        config.seen_files.clear();
+        config.walked_dirs.clear();
+        // Keep the order files appear in the log unless the user asked for another one
+        if !matches.is_present("sort") {
+            config.sort_order = SortOrder::None;
+        }
+        if let Some(context) = matches.value_of("trace_context") {
+            config.trace_context = Some(
+                context
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid --trace-context value '{}': {}", context, e))?,
+            );
+        }
+        for (path, lines) in resolved {
+            let base_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+            if should_process_file(&mut config, &path, base_name) {
+                input_roots.extend(path.parent().map(Path::to_path_buf));
+                add_file_entry(&mut config, &path);
+                config.trace_lines.insert(path, lines);
             }
         }
     }
 
+    config.relative_root = match matches.value_of("relative_to") {
+        Some(dir) => Some(PathBuf::from(sanitize_path(dir).map_err(|e| {
+            format!("Invalid --relative-to directory: {}: {}", dir, e)
+        })?)),
+        None => common_ancestor(&input_roots),
+    };
+    if let Some(root) = &config.relative_root {
+        info!("Header paths relative to: {}", root.display());
+    }
+
     // If we're in unglob mode, process the input file
     if config.unglob_mode {
         let result = unglob_file(&config);
         cleanup_config_temp_dirs(&config);
-        return result;
+        return result.map(|_| None);
     }
 
     if !found_input {
@@ -1710,13 +7133,173 @@ fn main() -> Result<(), String> {
         return Err("Error: No files found matching criteria".to_string());
     }
 
-    let result = match run_scraper(&mut config) {
+    if config.overflow_files > 0 {
+        let message = format!(
+            "{} more matching files were not included because the limit of {} files was reached",
+            config.overflow_files, config.max_files
+        );
+        if config.on_overflow == OverflowPolicy::Error {
+            cleanup_config_temp_dirs(&config);
+            return Err(format!(
+                "Error: {}. Raise --max-files or narrow the inputs.",
+                message
+            ));
+        }
+        warn!("{}", message);
+    }
+
+    if let Some(query) = matches.value_of("relevant_to") {
+        let top_k = match matches.value_of("top_k") {
+            Some(k) => k
+                .parse::<usize>()
+                .map_err(|e| format!("Invalid --top-k value '{}': {}", k, e))?,
+            None => DEFAULT_TOP_K,
+        };
+        let token_budget = matches
+            .value_of("token_budget")
+            .map(|tokens| {
+                tokens
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid --token-budget value '{}': {}", tokens, e))
+            })
+            .transpose()?;
+        let paths: Vec<PathBuf> = config
+            .file_entries
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect();
+        let mut cache = config
+            .cache
+            .as_ref()
+            .map(|cache| cache.lock().expect("Daemon cache mutex poisoned"));
+        let terms = cache.as_mut().map(|cache| &mut cache.terms);
+        let embedder = matches
+            .value_of("embedding_model")
+            .map(|model| embeddings::Embedder {
+                url: matches
+                    .value_of("embedding_url")
+                    .unwrap_or(embeddings::DEFAULT_URL)
+                    .to_string(),
+                model: model.to_string(),
+            });
+        let relevant = relevance::select_relevant(
+            &paths,
+            query,
+            top_k,
+            token_budget,
+            config.bytes_per_token,
+            terms,
+            embedder.as_ref(),
+        );
+        drop(cache);
+        let relevant = match relevant {
+            Ok(relevant) => relevant,
+            Err(e) => {
+                cleanup_config_temp_dirs(&config);
+                return Err(e);
+            }
+        };
+        if relevant.is_empty() {
+            cleanup_config_temp_dirs(&config);
+            return Err(format!("Error: No files relevant to '{}'", query));
+        }
+        config.file_entries = relevant
+            .into_iter()
+            .map(|path| FileEntry { path })
+            .collect();
+        // Keep the most relevant files first unless the user asked for another order
+        if !matches.is_present("sort") {
+            config.sort_order = SortOrder::None;
+        }
+    }
+
+    if let Some(count) = matches.value_of("sample") {
+        let count = count
+            .parse::<usize>()
+            .map_err(|e| format!("Invalid --sample value '{}': {}", count, e))?;
+        let strata = sample::Strata::parse(matches.value_of("sample_by").unwrap_or("none"))?;
+        // Log a random seed so that a sample worth keeping can be taken again
+        let seed = match matches.value_of("sample_seed") {
+            Some(seed) => seed
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid --sample-seed value '{}': {}", seed, e))?,
+            None => rand::random(),
+        };
+        let paths: Vec<PathBuf> = config
+            .file_entries
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect();
+        let total = paths.len();
+        config.file_entries = sample::sample(paths, count, strata, seed)
+            .into_iter()
+            .map(|path| FileEntry { path })
+            .collect();
+        info!(
+            "Sampled {} of {} files with --sample-seed {}",
+            config.file_entries.len(),
+            total,
+            seed
+        );
+    }
+
+    sort_file_entries(&mut config);
+    config.timings.walk = walk_start.elapsed();
+
+    if let Some(plan_file) = matches.value_of("plan") {
+        let paths: Vec<String> = config
+            .file_entries
+            .iter()
+            .map(|entry| entry.path.display().to_string())
+            .collect();
+        let result = plan::write(plan_file, &paths);
+        cleanup_config_temp_dirs(&config);
+        result?;
+        info!(
+            "Wrote a plan of {} file(s) to {}; edit it and run with --from-plan {}",
+            paths.len(),
+            plan_file,
+            plan_file
+        );
+        return Ok(None);
+    }
+
+    finish_run(&mut config, &matches)
+}
+
+// Write the output for the files found, then run --post-hook and clean up
+fn finish_run(config: &mut ScrapeConfig, matches: &ArgMatches) -> Result<Option<String>, String> {
+    let result = match run_scraper(config) {
+        // The benchmark's output is only there to be timed
+        Ok(_) if config.bench => Ok(None),
         Ok(output_file) => {
-            if matches.is_present("debug") {
-                debug_dump_file(&output_file).map_err(|e| format!("Debug dump failed: {}", e))?;
+            if matches.is_present("debug") && config.remote_output.is_none() {
+                debug_dump_file(&output_file, &config.identity_files)
+                    .map_err(|e| format!("Debug dump failed: {}", e))?;
             }
             info!("Scraper completed successfully: {}", output_file);
-            Ok(())
+            let path = fs::canonicalize(&output_file)
+                .map_or(output_file, |path| path.display().to_string());
+            // Runs once the output is final: compressed, encrypted and uploaded
+            if let Some(command) = matches.value_of("post_hook") {
+                let result = hooks::FileList::write(&config.written_entries).and_then(|files| {
+                    let env = [
+                        (hooks::OUTPUT_ENV, path.clone()),
+                        (hooks::FILES_ENV, files.path().display().to_string()),
+                    ];
+                    hooks::run("post-hook", command, &env)
+                });
+                if let Err(e) = result {
+                    cleanup_config_temp_dirs(config);
+                    return Err(e);
+                }
+            }
+            // The only line written to stdout, so scripts can capture it even with -q. Daemon
+            // clients always print it themselves.
+            if matches.is_present("print_path") && config.cache.is_none() {
+                println!("{}", path);
+            }
+            Ok(Some(path))
         }
         Err(err) => {
             error!("Scraper failed: {}", err);
@@ -1725,18 +7308,24 @@ fn main() -> Result<(), String> {
     };
 
     // Cleanup temporary git directory if needed
-    cleanup_config_temp_dirs(&config);
+    cleanup_config_temp_dirs(config);
 
     result
 }
-// Generate a new keypair for signing
-fn generate_keypair() -> Keypair {
-    let mut csprng = OsRng {};
-    Keypair::generate(&mut csprng)
+fn sha256_hex(data: &[u8]) -> String {
+    hex_digest(Sha256::new_with_prefix(data))
+}
+
+fn hex_digest(hasher: Sha256) -> String {
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
 }
 
-// Sign data with the keypair
-fn sign_data(keypair: &Keypair, data: &[u8]) -> String {
+// Sign data with the signing key
+fn sign_data(signer: &sshkey::Signer, data: &[u8]) -> Result<String, String> {
     debug!("Signing data of length: {} bytes", data.len());
 
     // Log a sample of the data being signed (first 100 bytes or less)
@@ -1747,10 +7336,10 @@ fn sign_data(keypair: &Keypair, data: &[u8]) -> String {
         sample_len, data_sample
     );
 
-    let signature = keypair.sign(data);
+    let signature = signer.sign(data)?;
     let encoded = general_purpose::STANDARD.encode(signature.to_bytes());
     debug!("Generated signature: {}", encoded);
-    encoded
+    Ok(encoded)
 }
 
 // Verify a signature
@@ -0,0 +1,314 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value as JsonValue;
+use toml::{Table, Value};
+
+// Manifest file names recognised, with the ecosystem each belongs to
+const MANIFESTS: &[(&str, &str)] = &[
+    ("Cargo.toml", "cargo"),
+    ("package.json", "npm"),
+    ("pyproject.toml", "python"),
+    ("requirements.txt", "pip"),
+    ("requirements-dev.txt", "pip"),
+    ("go.mod", "go"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Dependency {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) kind: &'static str, // prod, dev, build, peer, optional or indirect
+}
+
+fn ecosystem(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    MANIFESTS
+        .iter()
+        .find(|(manifest, _)| *manifest == name)
+        .map(|(_, ecosystem)| *ecosystem)
+}
+
+// Manifests among the selected files, plus those in `root` and its ancestors up to the
+// repository root, so the stack is known even when only a subdirectory such as src/ is globbed.
+// Ancestors are listed outermost first, followed by the selected files in their given order.
+pub(crate) fn find_manifests(files: &[PathBuf], root: Option<&Path>) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    if let Some(root) = root {
+        let mut ancestors = Vec::new();
+        for dir in root.ancestors() {
+            ancestors.push(dir);
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        // Without a repository boundary only the root itself is searched
+        if !ancestors
+            .last()
+            .is_some_and(|dir| dir.join(".git").exists())
+        {
+            ancestors.truncate(1);
+        }
+        for dir in ancestors.into_iter().rev() {
+            for (name, _) in MANIFESTS {
+                let path = dir.join(name);
+                if path.is_file() {
+                    found.push(path);
+                }
+            }
+        }
+    }
+    for file in files {
+        if ecosystem(file).is_some() && !found.contains(file) {
+            found.push(file.clone());
+        }
+    }
+    found
+}
+
+// `manifest (ecosystem):` followed by one indented `name version (kind)` line per dependency
+pub(crate) fn summary_lines(manifest: &Path, display: &str) -> Result<Vec<String>, String> {
+    let ecosystem = ecosystem(manifest)
+        .ok_or_else(|| format!("{} is not a known manifest", manifest.display()))?;
+    let source = fs::read_to_string(manifest)
+        .map_err(|e| format!("cannot read {}: {}", manifest.display(), e))?;
+    let file_name = manifest.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    let dependencies = match file_name {
+        "Cargo.toml" => parse_cargo(&source),
+        "package.json" => parse_package_json(&source),
+        "pyproject.toml" => parse_pyproject(&source),
+        "requirements-dev.txt" => Ok(parse_requirements(&source, "dev")),
+        "requirements.txt" => Ok(parse_requirements(&source, "prod")),
+        _ => Ok(parse_go_mod(&source)),
+    }
+    .map_err(|e| format!("cannot parse {}: {}", manifest.display(), e))?;
+
+    let mut lines = vec![format!("{} ({}):", display, ecosystem)];
+    if dependencies.is_empty() {
+        lines.push("  (no dependencies)".to_string());
+    }
+    for dependency in dependencies {
+        lines.push(format!(
+            "  {} {} ({})",
+            dependency.name, dependency.version, dependency.kind
+        ));
+    }
+    Ok(lines)
+}
+
+fn parse_cargo(source: &str) -> Result<Vec<Dependency>, String> {
+    let manifest: Table = source.parse().map_err(|e| format!("{}", e))?;
+    let mut dependencies = Vec::new();
+    let mut collect = |table: &Table| {
+        for (section, kind) in [
+            ("dependencies", "prod"),
+            ("dev-dependencies", "dev"),
+            ("build-dependencies", "build"),
+        ] {
+            if let Some(Value::Table(entries)) = table.get(section) {
+                for (name, spec) in entries {
+                    dependencies.push(Dependency {
+                        name: name.clone(),
+                        version: cargo_version(spec),
+                        kind,
+                    });
+                }
+            }
+        }
+    };
+    collect(&manifest);
+    // Platform-specific dependencies live under [target.'cfg(...)'.dependencies]
+    if let Some(Value::Table(targets)) = manifest.get("target") {
+        for target in targets.values() {
+            if let Value::Table(target) = target {
+                collect(target);
+            }
+        }
+    }
+    if let Some(Value::Table(workspace)) = manifest.get("workspace") {
+        collect(workspace);
+    }
+    Ok(dependencies)
+}
+
+// `"1.0"`, `{ version = "1.0" }`, or where the crate comes from when it has no version
+fn cargo_version(spec: &Value) -> String {
+    match spec {
+        Value::String(version) => version.clone(),
+        Value::Table(table) => {
+            if let Some(Value::String(version)) = table.get("version") {
+                version.clone()
+            } else if let Some(Value::String(path)) = table.get("path") {
+                format!("path {}", path)
+            } else if let Some(Value::String(git)) = table.get("git") {
+                format!("git {}", git)
+            } else if table.get("workspace") == Some(&Value::Boolean(true)) {
+                "workspace".to_string()
+            } else {
+                "*".to_string()
+            }
+        }
+        _ => "*".to_string(),
+    }
+}
+
+fn parse_package_json(source: &str) -> Result<Vec<Dependency>, String> {
+    let package: JsonValue = serde_json::from_str(source).map_err(|e| format!("{}", e))?;
+    let mut dependencies = Vec::new();
+    for (section, kind) in [
+        ("dependencies", "prod"),
+        ("devDependencies", "dev"),
+        ("peerDependencies", "peer"),
+        ("optionalDependencies", "optional"),
+    ] {
+        if let Some(entries) = package.get(section).and_then(JsonValue::as_object) {
+            for (name, version) in entries {
+                dependencies.push(Dependency {
+                    name: name.clone(),
+                    version: version.as_str().unwrap_or("*").to_string(),
+                    kind,
+                });
+            }
+        }
+    }
+    Ok(dependencies)
+}
+
+fn parse_pyproject(source: &str) -> Result<Vec<Dependency>, String> {
+    let manifest: Table = source.parse().map_err(|e| format!("{}", e))?;
+    let mut dependencies = Vec::new();
+    let requirements = |value: Option<&Value>, kind, out: &mut Vec<Dependency>| {
+        if let Some(Value::Array(items)) = value {
+            out.extend(
+                items
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .filter_map(|item| parse_requirement(item, kind)),
+            );
+        }
+    };
+
+    // PEP 621 [project] metadata
+    let project = manifest.get("project");
+    requirements(
+        project.and_then(|p| p.get("dependencies")),
+        "prod",
+        &mut dependencies,
+    );
+    if let Some(Value::Table(groups)) = project.and_then(|p| p.get("optional-dependencies")) {
+        for group in groups.values() {
+            requirements(Some(group), "optional", &mut dependencies);
+        }
+    }
+    // PEP 735 dependency groups are development-only by definition
+    if let Some(Value::Table(groups)) = manifest.get("dependency-groups") {
+        for group in groups.values() {
+            requirements(Some(group), "dev", &mut dependencies);
+        }
+    }
+
+    // Poetry keeps its own tables, with versions as strings or inline tables
+    let poetry = manifest.get("tool").and_then(|tool| tool.get("poetry"));
+    let mut poetry_tables = vec![
+        (poetry.and_then(|p| p.get("dependencies")), "prod"),
+        (poetry.and_then(|p| p.get("dev-dependencies")), "dev"),
+    ];
+    if let Some(Value::Table(groups)) = poetry.and_then(|p| p.get("group")) {
+        for group in groups.values() {
+            poetry_tables.push((group.get("dependencies"), "dev"));
+        }
+    }
+    for (table, kind) in poetry_tables {
+        if let Some(Value::Table(entries)) = table {
+            for (name, spec) in entries.iter().filter(|(name, _)| *name != "python") {
+                let version = match spec {
+                    Value::String(version) => version.clone(),
+                    Value::Table(table) => table
+                        .get("version")
+                        .and_then(Value::as_str)
+                        .unwrap_or("*")
+                        .to_string(),
+                    _ => "*".to_string(),
+                };
+                dependencies.push(Dependency {
+                    name: name.clone(),
+                    version,
+                    kind,
+                });
+            }
+        }
+    }
+    Ok(dependencies)
+}
+
+fn parse_requirements(source: &str, kind: &'static str) -> Vec<Dependency> {
+    source
+        .lines()
+        .map(|line| line.split(" #").next().unwrap_or("").trim())
+        // Options such as -r other.txt or --index-url are not dependencies
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('-'))
+        .filter_map(|line| parse_requirement(line, kind))
+        .collect()
+}
+
+// A PEP 508 requirement such as `requests[socks]>=2.31; python_version > "3.8"`
+fn parse_requirement(requirement: &str, kind: &'static str) -> Option<Dependency> {
+    let requirement = requirement.split(';').next()?.trim();
+    let name_end = requirement
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(requirement.len());
+    let name = &requirement[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+    let mut rest = requirement[name_end..].trim_start();
+    // Extras do not change what is installed from the package itself
+    if rest.starts_with('[') {
+        rest = rest
+            .split_once(']')
+            .map_or("", |(_, after)| after)
+            .trim_start();
+    }
+    let version = rest.trim_start_matches('@').trim();
+    Some(Dependency {
+        name: name.to_string(),
+        version: if version.is_empty() { "*" } else { version }.to_string(),
+        kind,
+    })
+}
+
+fn parse_go_mod(source: &str) -> Vec<Dependency> {
+    let mut dependencies = Vec::new();
+    let mut in_block = false;
+    for line in source.lines() {
+        let line = line.trim();
+        let spec = if in_block {
+            if line == ")" {
+                in_block = false;
+                continue;
+            }
+            line
+        } else if line == "require (" {
+            in_block = true;
+            continue;
+        } else if let Some(spec) = line.strip_prefix("require ") {
+            spec
+        } else {
+            continue;
+        };
+        let (spec, comment) = spec.split_once("//").unwrap_or((spec, ""));
+        let mut parts = spec.split_whitespace();
+        if let (Some(name), Some(version)) = (parts.next(), parts.next()) {
+            dependencies.push(Dependency {
+                name: name.to_string(),
+                version: version.to_string(),
+                kind: if comment.trim() == "indirect" {
+                    "indirect"
+                } else {
+                    "prod"
+                },
+            });
+        }
+    }
+    dependencies
+}
@@ -0,0 +1,156 @@
+// Presets for `--model`, so the token budget, output format and token estimate for a model come
+// from its name. Presets in `models.toml` in the configuration directory add to the built-in ones
+// or change them:
+//
+//   [models.claude-sonnet]
+//   context_tokens = 1000000
+//
+//   [models.qwen-coder]
+//   context_tokens = 32768
+//   format = "plain"
+//   bytes_per_token = 3.8
+use std::fs;
+use std::io;
+
+use crate::config;
+use crate::{OutputFormat, BYTES_PER_TOKEN};
+
+const MODELS_FILE: &str = "models.toml";
+const FIELDS: [&str; 3] = ["context_tokens", "format", "bytes_per_token"];
+
+// Name, context window in tokens, output format and approximate bytes of source text per token
+const BUILTIN_MODELS: &[(&str, usize, &str, f64)] = &[
+    ("claude-sonnet", 200_000, "text", 3.5),
+    ("claude-opus", 200_000, "text", 3.5),
+    ("claude-haiku", 200_000, "text", 3.5),
+    ("gpt-4o", 128_000, "text", 4.0),
+    ("gpt-4o-mini", 128_000, "text", 4.0),
+    ("gpt-4-turbo", 128_000, "text", 4.0),
+    ("llama3-8b", 8_192, "text", 4.0),
+    ("llama3-70b", 8_192, "text", 4.0),
+];
+
+#[derive(Debug, Clone)]
+pub(crate) struct Model {
+    pub(crate) name: String,
+    pub(crate) context_tokens: usize,
+    pub(crate) format: OutputFormat, // Used unless --format is given
+    pub(crate) bytes_per_token: f64, // How tokens are estimated from bytes
+}
+
+impl Model {
+    // What the output may take up: three quarters of the context window, leaving the rest for the
+    // prompt and the answer
+    pub(crate) fn budget_tokens(&self) -> usize {
+        self.context_tokens / 4 * 3
+    }
+}
+
+// The preset called `name`, from models.toml if it is there, else built in
+pub(crate) fn find(name: &str) -> Result<Model, String> {
+    let mut model = BUILTIN_MODELS
+        .iter()
+        .find(|(builtin, ..)| *builtin == name)
+        .map(|(name, context_tokens, format, bytes_per_token)| Model {
+            name: name.to_string(),
+            context_tokens: *context_tokens,
+            format: OutputFormat::parse(format).expect("built-in presets name valid formats"),
+            bytes_per_token: *bytes_per_token,
+        });
+    let user_models = user_models()?;
+    if let Some(table) = user_models.as_ref().and_then(|models| models.get(name)) {
+        model = Some(user_model(name, table, model)?);
+    }
+    model.ok_or_else(|| {
+        let mut names: Vec<String> = BUILTIN_MODELS
+            .iter()
+            .map(|(name, ..)| name.to_string())
+            .collect();
+        names.extend(user_models.iter().flat_map(|models| models.keys().cloned()));
+        names.sort();
+        names.dedup();
+        format!(
+            "Unknown model '{}': expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+// The [models] table of models.toml, if there is one
+fn user_models() -> Result<Option<toml::Table>, String> {
+    let Some(path) = config::dir().map(|dir| dir.join(MODELS_FILE)) else {
+        return Ok(None);
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let invalid = |e: String| format!("Invalid {}: {}", path.display(), e);
+    let mut document = text
+        .parse::<toml::Table>()
+        .map_err(|e| invalid(e.message().to_string()))?;
+    match document.remove("models") {
+        None => Ok(None),
+        Some(toml::Value::Table(models)) => Ok(Some(models)),
+        Some(_) => Err(invalid("[models] is not a table".to_string())),
+    }
+}
+
+// A preset from models.toml, with fields it leaves out taken from the built-in preset it changes
+fn user_model(name: &str, value: &toml::Value, base: Option<Model>) -> Result<Model, String> {
+    let invalid =
+        |field: &str| format!("Invalid {} for model '{}' in {}", field, name, MODELS_FILE);
+    let table = value.as_table().ok_or_else(|| invalid("preset"))?;
+    let context_tokens = match table.get("context_tokens") {
+        Some(value) => value
+            .as_integer()
+            .and_then(|tokens| usize::try_from(tokens).ok())
+            .filter(|tokens| *tokens > 0)
+            .ok_or_else(|| invalid("context_tokens"))?,
+        None => base
+            .as_ref()
+            .map(|model| model.context_tokens)
+            .ok_or_else(|| invalid("context_tokens (missing)"))?,
+    };
+    let format = match table.get("format") {
+        Some(value) => value
+            .as_str()
+            .ok_or_else(|| invalid("format"))
+            .and_then(|format| {
+                OutputFormat::parse(format)
+                    .map_err(|e| format!("{} for model '{}' in {}", e, name, MODELS_FILE))
+            })?,
+        None => base
+            .as_ref()
+            .map_or(OutputFormat::Text, |model| model.format),
+    };
+    let bytes_per_token = match table.get("bytes_per_token") {
+        // TOML integers are not floats, and `bytes_per_token = 4` should work too
+        Some(value) => value
+            .as_float()
+            .or_else(|| value.as_integer().map(|bytes| bytes as f64))
+            .filter(|bytes| bytes.is_finite() && *bytes > 0.0)
+            .ok_or_else(|| invalid("bytes_per_token"))?,
+        None => base
+            .as_ref()
+            .map_or(BYTES_PER_TOKEN, |model| model.bytes_per_token),
+    };
+    // A misspelled field is refused rather than silently left at its default
+    if let Some(field) = table.keys().find(|field| !FIELDS.contains(&field.as_str())) {
+        return Err(format!(
+            "Unknown field '{}' for model '{}' in {}: expected {}",
+            field,
+            name,
+            MODELS_FILE,
+            FIELDS.join(", ")
+        ));
+    }
+    Ok(Model {
+        name: name.to_string(),
+        context_tokens,
+        format,
+        bytes_per_token,
+    })
+}
@@ -0,0 +1,66 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, TimeZone};
+
+// Used for `{date}` when no format is given
+const DEFAULT_DATE_FORMAT: &str = "%Y%m%d";
+
+// Expand `{key}` and `{key:argument}` placeholders, asking `lookup` for each value.
+// Values are made safe for file names, so a branch like `feature/login` becomes `feature-login`,
+// and a name that could still leave the output directory, through a `/` or as `..`, is refused.
+pub(crate) fn expand<F>(template: &str, mut lookup: F) -> Result<String, String>
+where
+    F: FnMut(&str, Option<&str>) -> Result<String, String>,
+{
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| format!("Unclosed placeholder in name template '{}'", template))?;
+        let placeholder = &rest[start + 1..end];
+        let (key, argument) = match placeholder.split_once(':') {
+            Some((key, argument)) => (key, Some(argument)),
+            None => (placeholder, None),
+        };
+        out.push_str(&sanitize(&lookup(key, argument)?));
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    if out.trim().is_empty() {
+        return Err(format!("Name template '{}' expands to nothing", template));
+    }
+    if out.contains(['/', '\0']) || out == "." || out == ".." {
+        return Err(format!(
+            "Name template '{}' expands to '{}', which is not a file name in the output directory",
+            template, out
+        ));
+    }
+    Ok(out)
+}
+
+// Format a unix timestamp in local time with a strftime format, rejecting invalid formats
+// instead of letting chrono panic on them
+pub(crate) fn format_date(timestamp: u64, format: Option<&str>) -> Result<String, String> {
+    let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid date format '{}' in name template", format));
+    }
+    let date = Local
+        .timestamp_opt(timestamp as i64, 0)
+        .single()
+        .ok_or_else(|| format!("Invalid timestamp {}", timestamp))?;
+    Ok(date.format(format).to_string())
+}
+
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '\0' => '-',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
@@ -0,0 +1,110 @@
+use llm_globber_core::RENDERED_MARKER_PREFIX;
+use serde_json::Value;
+
+// Render a Jupyter notebook as interleaved markdown and fenced code cells.
+// Text outputs are kept; images and other rich outputs are replaced by a one-line note.
+pub(crate) fn render_notebook(source: &str) -> Result<String, String> {
+    let notebook: Value =
+        serde_json::from_str(source).map_err(|e| format!("not a valid notebook: {}", e))?;
+    let cells = notebook
+        .get("cells")
+        .and_then(Value::as_array)
+        .ok_or_else(|| "notebook has no cells array".to_string())?;
+    let language = notebook_language(&notebook);
+
+    let mut out = format!(
+        "{}notebook, {} cell(s)]\n",
+        RENDERED_MARKER_PREFIX,
+        cells.len()
+    );
+    for cell in cells {
+        let text = joined_text(cell.get("source"));
+        match cell.get("cell_type").and_then(Value::as_str) {
+            Some("markdown") => {
+                out.push_str(text.trim_end());
+                out.push_str("\n\n");
+            }
+            Some("code") => {
+                push_fenced(&mut out, language, &text);
+                if let Some(outputs) = cell.get("outputs").and_then(Value::as_array) {
+                    for output in outputs {
+                        render_output(&mut out, output);
+                    }
+                }
+            }
+            // Raw cells are passed through as-is
+            _ => {
+                if !text.trim().is_empty() {
+                    push_fenced(&mut out, "", &text);
+                }
+            }
+        }
+    }
+    Ok(out.trim_end().to_string() + "\n")
+}
+
+fn notebook_language(notebook: &Value) -> &str {
+    let metadata = notebook.get("metadata");
+    metadata
+        .and_then(|m| m.get("language_info"))
+        .and_then(|info| info.get("name"))
+        .or_else(|| {
+            metadata
+                .and_then(|m| m.get("kernelspec"))
+                .and_then(|spec| spec.get("language"))
+        })
+        .and_then(Value::as_str)
+        .unwrap_or("python")
+}
+
+fn render_output(out: &mut String, output: &Value) {
+    match output.get("output_type").and_then(Value::as_str) {
+        Some("stream") => push_output_text(out, &joined_text(output.get("text"))),
+        Some("execute_result") | Some("display_data") => {
+            let data = output.get("data");
+            if let Some(text) = data.and_then(|d| d.get("text/plain")) {
+                push_output_text(out, &joined_text(Some(text)));
+            }
+            // Everything else (images, HTML, widgets) is dropped, but its presence is noted
+            if let Some(data) = data.and_then(Value::as_object) {
+                for mime in data.keys().filter(|mime| *mime != "text/plain") {
+                    out.push_str(&format!("[{} output omitted]\n\n", mime));
+                }
+            }
+        }
+        Some("error") => {
+            let name = output
+                .get("ename")
+                .and_then(Value::as_str)
+                .unwrap_or("Error");
+            let value = output.get("evalue").and_then(Value::as_str).unwrap_or("");
+            push_output_text(out, &format!("{}: {}", name, value));
+        }
+        _ => {}
+    }
+}
+
+fn push_output_text(out: &mut String, text: &str) {
+    if text.trim().is_empty() {
+        return;
+    }
+    out.push_str("Output:\n");
+    push_fenced(out, "text", text);
+}
+
+fn push_fenced(out: &mut String, language: &str, text: &str) {
+    out.push_str("```");
+    out.push_str(language);
+    out.push('\n');
+    out.push_str(text.trim_end());
+    out.push_str("\n```\n\n");
+}
+
+// Notebook text fields are either a string or a list of line strings
+fn joined_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(lines)) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,320 @@
+use std::path::Path;
+
+use llm_globber_core::RENDERED_MARKER_PREFIX;
+use log::debug;
+use tree_sitter::{Language, Node, Parser, Tree};
+
+// Node kinds that matter for an outline in one language
+struct LanguageSpec {
+    language: Language,
+    // Emitted up to their body, which is replaced by a placeholder
+    signature_kinds: &'static [&'static str],
+    // Emitted in full (type definitions, constants, prototypes)
+    full_kinds: &'static [&'static str],
+    // Emitted up to their body, then their members are outlined recursively
+    container_kinds: &'static [&'static str],
+    // Wrappers such as `export` or decorators whose inner declaration is outlined
+    wrapper_kinds: &'static [&'static str],
+    // Doc comments and attributes kept when directly above a declaration
+    leading_kinds: &'static [&'static str],
+    // Python bodies are indented blocks rather than braces
+    indented_bodies: bool,
+}
+
+fn language_spec(file_path: &str) -> Option<LanguageSpec> {
+    let extension = Path::new(file_path)
+        .extension()
+        .and_then(|e| e.to_str())?
+        .to_lowercase();
+    let spec = match extension.as_str() {
+        "rs" => LanguageSpec {
+            language: tree_sitter_rust::LANGUAGE.into(),
+            signature_kinds: &["function_item"],
+            full_kinds: &[
+                "struct_item",
+                "enum_item",
+                "union_item",
+                "type_item",
+                "const_item",
+                "static_item",
+                "function_signature_item",
+                "associated_type",
+                "macro_definition",
+            ],
+            container_kinds: &["impl_item", "trait_item", "mod_item"],
+            wrapper_kinds: &[],
+            leading_kinds: &["line_comment", "block_comment", "attribute_item"],
+            indented_bodies: false,
+        },
+        "py" => LanguageSpec {
+            language: tree_sitter_python::LANGUAGE.into(),
+            signature_kinds: &["function_definition"],
+            full_kinds: &[],
+            container_kinds: &["class_definition"],
+            wrapper_kinds: &["decorated_definition"],
+            leading_kinds: &["comment"],
+            indented_bodies: true,
+        },
+        "js" | "jsx" | "mjs" | "cjs" => LanguageSpec {
+            language: tree_sitter_javascript::LANGUAGE.into(),
+            signature_kinds: JS_SIGNATURE_KINDS,
+            full_kinds: &[],
+            container_kinds: &["class_declaration", "class"],
+            wrapper_kinds: &["export_statement"],
+            leading_kinds: &["comment"],
+            indented_bodies: false,
+        },
+        "ts" | "tsx" => LanguageSpec {
+            language: if extension == "tsx" {
+                tree_sitter_typescript::LANGUAGE_TSX.into()
+            } else {
+                tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into()
+            },
+            signature_kinds: JS_SIGNATURE_KINDS,
+            full_kinds: &[
+                "interface_declaration",
+                "type_alias_declaration",
+                "enum_declaration",
+                "abstract_method_signature",
+                "method_signature",
+                "public_field_definition",
+            ],
+            container_kinds: &["class_declaration", "abstract_class_declaration", "class"],
+            wrapper_kinds: &["export_statement"],
+            leading_kinds: &["comment"],
+            indented_bodies: false,
+        },
+        "c" | "h" => LanguageSpec {
+            language: tree_sitter_c::LANGUAGE.into(),
+            signature_kinds: &["function_definition"],
+            full_kinds: &[
+                "declaration",
+                "type_definition",
+                "struct_specifier",
+                "enum_specifier",
+                "union_specifier",
+                "preproc_def",
+                "preproc_function_def",
+            ],
+            container_kinds: &[],
+            wrapper_kinds: &[],
+            leading_kinds: &["comment"],
+            indented_bodies: false,
+        },
+        "go" => LanguageSpec {
+            language: tree_sitter_go::LANGUAGE.into(),
+            signature_kinds: &["function_declaration", "method_declaration"],
+            full_kinds: &["type_declaration", "const_declaration", "var_declaration"],
+            container_kinds: &[],
+            wrapper_kinds: &[],
+            leading_kinds: &["comment"],
+            indented_bodies: false,
+        },
+        _ => return None,
+    };
+    Some(spec)
+}
+
+const JS_SIGNATURE_KINDS: &[&str] = &[
+    "function_declaration",
+    "generator_function_declaration",
+    "method_definition",
+];
+
+// Syntax tree of a source file, or None if its language is not supported
+pub(crate) fn parse(file_path: &str, source: &str) -> Option<Tree> {
+    let spec = language_spec(file_path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&spec.language).ok()?;
+    parser.parse(source, None)
+}
+
+// Declarations-only view of a source file, or None if its language is not supported
+pub(crate) fn outline(file_path: &str, source: &str) -> Option<String> {
+    let spec = language_spec(file_path)?;
+    let tree = parse(file_path, source)?;
+    let mut out = format!("{}outline, declarations only]\n", RENDERED_MARKER_PREFIX);
+    outline_members(tree.root_node(), source, &spec, &mut out);
+    debug!(
+        "Outlined {}: {} bytes -> {} bytes",
+        file_path,
+        source.len(),
+        out.len()
+    );
+    Some(out)
+}
+
+// Lines (0-based) where a source file can be cut without splitting a declaration: where each
+// declaration starts, along with the comments and attributes above it, and the line after it
+// ends. Members of classes, impls and the like count too. Empty if the language is not supported.
+pub(crate) fn boundaries(file_path: &str, source: &str) -> Vec<usize> {
+    let (Some(spec), Some(tree)) = (language_spec(file_path), parse(file_path, source)) else {
+        return Vec::new();
+    };
+    let mut lines = Vec::new();
+    member_boundaries(tree.root_node(), &spec, &mut lines);
+    lines.sort_unstable();
+    lines.dedup();
+    lines
+}
+
+fn member_boundaries(parent: Node, spec: &LanguageSpec, lines: &mut Vec<usize>) {
+    let mut cursor = parent.walk();
+    let children: Vec<Node> = parent.named_children(&mut cursor).collect();
+    for (index, child) in children.iter().enumerate() {
+        if !is_declaration(child, spec) {
+            continue;
+        }
+        let mut first_row = child.start_position().row;
+        for candidate in children[..index].iter().rev() {
+            if !spec.leading_kinds.contains(&candidate.kind())
+                || candidate.end_position().row + 1 < first_row
+            {
+                break;
+            }
+            first_row = candidate.start_position().row;
+        }
+        lines.push(first_row);
+        lines.push(child.end_position().row + 1);
+        // Wrapped containers, such as exported classes, are split between their members too
+        let inner = match spec.wrapper_kinds.contains(&child.kind()) {
+            true => child
+                .child_by_field_name("declaration")
+                .or_else(|| child.child_by_field_name("definition")),
+            false => Some(*child),
+        };
+        if let Some(body) = inner
+            .filter(|node| spec.container_kinds.contains(&node.kind()))
+            .and_then(|node| node.child_by_field_name("body"))
+        {
+            member_boundaries(body, spec, lines);
+        }
+    }
+}
+
+fn outline_members(parent: Node, source: &str, spec: &LanguageSpec, out: &mut String) {
+    let mut cursor = parent.walk();
+    let children: Vec<Node> = parent.named_children(&mut cursor).collect();
+    for (index, child) in children.iter().enumerate() {
+        if is_declaration(child, spec) {
+            emit_leading_items(&children[..index], *child, source, spec, out);
+            emit_declaration(
+                *child,
+                line_start(source, child.start_byte()),
+                source,
+                spec,
+                out,
+            );
+        }
+    }
+}
+
+fn is_declaration(node: &Node, spec: &LanguageSpec) -> bool {
+    let kind = node.kind();
+    spec.signature_kinds.contains(&kind)
+        || spec.full_kinds.contains(&kind)
+        || spec.container_kinds.contains(&kind)
+        || spec.wrapper_kinds.contains(&kind)
+}
+
+// Comments and attributes directly above a declaration, with no blank line in between
+fn emit_leading_items(
+    siblings: &[Node],
+    node: Node,
+    source: &str,
+    spec: &LanguageSpec,
+    out: &mut String,
+) {
+    let mut first = siblings.len();
+    let mut next_row = node.start_position().row;
+    while first > 0 {
+        let candidate = siblings[first - 1];
+        if !spec.leading_kinds.contains(&candidate.kind())
+            || candidate.end_position().row + 1 < next_row
+        {
+            break;
+        }
+        next_row = candidate.start_position().row;
+        first -= 1;
+    }
+    for item in &siblings[first..] {
+        push_line(
+            out,
+            &source[line_start(source, item.start_byte())..item.end_byte()],
+        );
+    }
+}
+
+// `start` lets wrappers (export, decorators) keep their prefix text in front of the declaration
+fn emit_declaration(node: Node, start: usize, source: &str, spec: &LanguageSpec, out: &mut String) {
+    let kind = node.kind();
+
+    if spec.wrapper_kinds.contains(&kind) {
+        let inner = node
+            .child_by_field_name("declaration")
+            .or_else(|| node.child_by_field_name("definition"));
+        match inner {
+            Some(inner) if is_declaration(&inner, spec) => {
+                emit_declaration(inner, start, source, spec, out)
+            }
+            _ => push_line(out, &source[start..node.end_byte()]),
+        }
+        return;
+    }
+
+    let body = node.child_by_field_name("body");
+    if spec.full_kinds.contains(&kind) || body.is_none() {
+        push_line(out, &source[start..node.end_byte()]);
+        return;
+    }
+    let body = body.expect("body checked above");
+    let header = source[start..body.start_byte()].trim_end();
+
+    if spec.container_kinds.contains(&kind) {
+        if spec.indented_bodies {
+            push_line(out, header);
+            emit_docstring(body, source, out);
+            outline_members(body, source, spec, out);
+        } else {
+            push_line(out, &format!("{} {{", header));
+            outline_members(body, source, spec, out);
+            push_line(
+                out,
+                &source[line_start(source, body.end_byte() - 1)..body.end_byte()],
+            );
+        }
+        return;
+    }
+
+    if spec.indented_bodies {
+        push_line(out, header);
+        emit_docstring(body, source, out);
+        let indent = " ".repeat(body.start_position().column);
+        push_line(out, &format!("{}...", indent));
+    } else {
+        push_line(out, &format!("{} {{ ... }}", header));
+    }
+}
+
+// Python docstrings are the first statement of a body
+fn emit_docstring(body: Node, source: &str, out: &mut String) {
+    if let Some(first) = body.named_child(0) {
+        let is_docstring = first.kind() == "expression_statement"
+            && first.named_child(0).is_some_and(|n| n.kind() == "string");
+        if is_docstring {
+            push_line(
+                out,
+                &source[line_start(source, first.start_byte())..first.end_byte()],
+            );
+        }
+    }
+}
+
+fn line_start(source: &str, byte: usize) -> usize {
+    source[..byte].rfind('\n').map_or(0, |index| index + 1)
+}
+
+fn push_line(out: &mut String, text: &str) {
+    out.push_str(text.trim_end());
+    out.push('\n');
+}
@@ -0,0 +1,49 @@
+// The archive being written: a buffered writer that counts the bytes written through it, so the
+// offsets --toc records and resume checkpoints take come from the count instead of
+// stream_position, which would flush the buffer after every entry
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+#[derive(Debug)]
+pub(crate) struct OutputFile<W: Write = File> {
+    writer: BufWriter<W>,
+    position: u64, // Length of the output, buffered bytes included
+}
+
+impl OutputFile {
+    // Write after whatever the file already holds, as when resuming or appending
+    pub(crate) fn at_end(mut file: File, capacity: usize) -> io::Result<Self> {
+        let position = file.seek(SeekFrom::End(0))?;
+        Ok(OutputFile::new(file, capacity, position))
+    }
+}
+
+impl<W: Write> OutputFile<W> {
+    // `position` is the length of what `inner` already holds
+    pub(crate) fn new(inner: W, capacity: usize, position: u64) -> Self {
+        OutputFile {
+            writer: BufWriter::with_capacity(capacity, inner),
+            position,
+        }
+    }
+
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub(crate) fn get_ref(&self) -> &W {
+        self.writer.get_ref()
+    }
+}
+
+impl<W: Write> Write for OutputFile<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
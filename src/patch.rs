@@ -0,0 +1,29 @@
+// Unified diffs printed by `--unglob FILE --as-patch`, which compares an archive with the files
+// it would overwrite instead of writing them, so the changes can be reviewed and applied with
+// `git apply` or `patch -p1`
+use std::path::Path;
+
+use diffy::DiffOptions;
+
+// The diff from a file's current content, or None if it does not exist yet, to `new`, with
+// `a/` and `b/` prefixed paths as git writes them. Files that would not change give None.
+pub(crate) fn file_patch(relative: &Path, current: Option<&str>, new: &str) -> Option<String> {
+    if current == Some(new) {
+        return None;
+    }
+    let path = relative.to_string_lossy();
+    // git apply only accepts /dev/null for a file the header declares as new
+    let (original, new_file) = match current {
+        Some(_) => (format!("a/{}", path), ""),
+        None => ("/dev/null".to_string(), "new file mode 100644\n"),
+    };
+    let patch = DiffOptions::new()
+        .set_original_filename(original)
+        .set_modified_filename(format!("b/{}", path))
+        .create_patch(current.unwrap_or(""), new)
+        .to_string();
+    Some(format!(
+        "diff --git a/{} b/{}\n{}{}",
+        path, path, new_file, patch
+    ))
+}
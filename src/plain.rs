@@ -0,0 +1,57 @@
+// `--format plain`: file contents one after another with no markers, for models and tools that
+// stumble over the archive delimiters. With `--file-comments` each file opens with a comment
+// naming it, written in the file's own comment syntax so the result still reads as code.
+use std::io::{self, Write};
+use std::path::Path;
+
+// Line comment opener, or opener and closer, for an extension
+fn comment_syntax(extension: &str) -> Option<(&'static str, &'static str)> {
+    let syntax = match extension {
+        "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "go" | "java" | "js" | "jsx" | "mjs"
+        | "ts" | "tsx" | "kt" | "swift" | "scala" | "dart" | "proto" | "zig" => ("// ", ""),
+        "py" | "rb" | "sh" | "bash" | "zsh" | "pl" | "r" | "toml" | "yaml" | "yml" | "cfg"
+        | "conf" | "ini" | "mk" | "makefile" | "cmake" | "nix" | "ex" | "exs" | "dockerfile" => {
+            ("# ", "")
+        }
+        "sql" | "lua" | "hs" | "elm" | "ada" => ("-- ", ""),
+        "lisp" | "clj" | "el" | "scm" | "asm" | "s" => ("; ", ""),
+        "vim" => ("\" ", ""),
+        "css" | "scss" | "less" => ("/* ", " */"),
+        "html" | "htm" | "xml" | "svg" | "md" | "markdown" | "vue" | "svelte" => ("<!-- ", " -->"),
+        _ => return None,
+    };
+    Some(syntax)
+}
+
+// `// file: path` in the file's comment syntax, or None where a comment would change what the
+// file means or the syntax is unknown, as in JSON or plain text
+pub(crate) fn file_comment(entry_path: &str) -> Option<String> {
+    let path = Path::new(entry_path);
+    let extension = match path.extension() {
+        Some(extension) => extension.to_string_lossy().to_lowercase(),
+        // Dockerfile, Makefile and the like are named rather than given an extension
+        None => path.file_name()?.to_string_lossy().to_lowercase(),
+    };
+    let (open, close) = comment_syntax(&extension)?;
+    Some(format!("{}file: {}{}", open, entry_path, close))
+}
+
+// Write a file's content, after its comment if asked for, ending in a newline so the next file
+// starts on a line of its own
+pub(crate) fn write_plain_entry<W: Write>(
+    output: &mut W,
+    entry_path: &str,
+    content: &[u8],
+    comment: bool,
+) -> io::Result<()> {
+    let mut entry = Vec::with_capacity(content.len() + entry_path.len() + 16);
+    if let Some(line) = comment.then(|| file_comment(entry_path)).flatten() {
+        entry.extend_from_slice(line.as_bytes());
+        entry.push(b'\n');
+    }
+    entry.extend_from_slice(content);
+    if !content.is_empty() && !content.ends_with(b"\n") {
+        entry.push(b'\n');
+    }
+    output.write_all(&entry)
+}
@@ -0,0 +1,33 @@
+// Plans written with --plan and read back with --from-plan: the files a run would include, one
+// path per line, so the selection can be edited by hand before the archive is built
+use std::fs;
+
+const PLAN_HEADER: &str = "\
+# llm_globber plan: the files to include, one per line, in output order.
+# Delete or comment out lines to leave files out, reorder them to change the order, and
+# build the archive with --from-plan. Lines starting with # are ignored.
+";
+
+pub(crate) fn write(plan_file: &str, paths: &[String]) -> Result<(), String> {
+    let mut text = PLAN_HEADER.to_string();
+    for path in paths {
+        text.push_str(path);
+        text.push('\n');
+    }
+    fs::write(plan_file, text).map_err(|e| format!("Failed to write plan: {}: {}", plan_file, e))
+}
+
+// The paths in a plan, in order, without blank lines, comments or repeats
+pub(crate) fn read(plan_file: &str) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(plan_file)
+        .map_err(|e| format!("Failed to read plan: {}: {}", plan_file, e))?;
+    let mut paths: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let path = line.trim_end_matches('\r');
+        if path.trim().is_empty() || path.starts_with('#') || paths.iter().any(|p| p == path) {
+            continue;
+        }
+        paths.push(path.to_string());
+    }
+    Ok(paths)
+}
@@ -0,0 +1,203 @@
+// WASM filters given with --plugin, which can keep, skip or rewrite each text file before it
+// is written. They need the `plugins` feature; without it --plugin is rejected.
+//
+// A plugin exports its `memory`, `alloc(len: i32) -> i32` and
+// `filter(path_ptr: i32, path_len: i32, content_ptr: i32, content_len: i32) -> i64`, which
+// returns 0 to keep the file, -1 to skip it, or `(ptr << 32) | len` of replacement content
+// it has written to its memory at a non-zero `ptr`.
+
+// What a plugin decided for one file
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(not(feature = "plugins"), allow(dead_code))]
+pub(crate) enum Verdict {
+    Keep,
+    Skip,
+    Transform(Vec<u8>),
+}
+
+// Run content through each plugin in turn, each seeing what the previous one produced, and
+// stop at the first that skips the file
+pub(crate) fn apply(plugins: &[Plugin], path: &str, data: &[u8]) -> Result<Verdict, String> {
+    let mut transformed: Option<Vec<u8>> = None;
+    for plugin in plugins {
+        let content = transformed.as_deref().unwrap_or(data);
+        match plugin.filter(path, content)? {
+            Verdict::Keep => {}
+            Verdict::Skip => {
+                log::debug!("Plugin {} skipped {}", plugin.name(), path);
+                return Ok(Verdict::Skip);
+            }
+            // Rewritten content is written as text, so it has to be text
+            Verdict::Transform(content) if std::str::from_utf8(&content).is_err() => {
+                return Err(format!(
+                    "Plugin {} rewrote {} to content that is not UTF-8",
+                    plugin.name(),
+                    path
+                ));
+            }
+            Verdict::Transform(content) => {
+                log::debug!("Plugin {} rewrote {}", plugin.name(), path);
+                transformed = Some(content);
+            }
+        }
+    }
+    Ok(transformed.map_or(Verdict::Keep, Verdict::Transform))
+}
+
+// Without the feature no plugin can be loaded, so there are no values of this type
+#[cfg(not(feature = "plugins"))]
+#[derive(Debug)]
+pub(crate) enum Plugin {}
+
+#[cfg(not(feature = "plugins"))]
+impl Plugin {
+    pub(crate) fn load(path: &str) -> Result<Self, String> {
+        Err(format!(
+            "Cannot load plugin {}: this build has no plugin support; rebuild with --features plugins",
+            path
+        ))
+    }
+
+    fn name(&self) -> &str {
+        match *self {}
+    }
+
+    fn filter(&self, _path: &str, _data: &[u8]) -> Result<Verdict, String> {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "plugins")]
+pub(crate) use wasm::Plugin;
+
+#[cfg(feature = "plugins")]
+mod wasm {
+    use std::fmt;
+
+    use wasmi::{Config, Engine, Linker, Memory, Module, Store, TypedFunc};
+
+    use super::Verdict;
+
+    // Instructions a plugin may run per file before it is stopped, so a plugin stuck in a loop
+    // fails that file instead of hanging the run
+    const FUEL_PER_FILE: u64 = 10_000_000_000;
+    const KEEP: i64 = 0;
+    const SKIP: i64 = -1;
+
+    pub(crate) struct Plugin {
+        path: String,
+        engine: Engine,
+        module: Module,
+    }
+
+    // A fresh instance of a plugin with the exports the host calls
+    struct Running {
+        store: Store<()>,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        filter: TypedFunc<(i32, i32, i32, i32), i64>,
+    }
+
+    impl fmt::Debug for Plugin {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Plugin({})", self.path)
+        }
+    }
+
+    impl Plugin {
+        pub(crate) fn load(path: &str) -> Result<Self, String> {
+            let wasm = std::fs::read(path)
+                .map_err(|e| format!("Failed to read plugin {}: {}", path, e))?;
+            let mut config = Config::default();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config);
+            let module = Module::new(&engine, &wasm)
+                .map_err(|e| format!("Invalid plugin {}: {}", path, e))?;
+            let plugin = Plugin {
+                path: path.to_string(),
+                engine,
+                module,
+            };
+            // Check the exports now rather than on the first file
+            plugin.instantiate()?;
+            Ok(plugin)
+        }
+
+        pub(super) fn name(&self) -> &str {
+            &self.path
+        }
+
+        // Each file gets a fresh instance, so plugins need no way to free memory and cannot
+        // carry state from one file to the next
+        fn instantiate(&self) -> Result<Running, String> {
+            let mut store = Store::new(&self.engine, ());
+            store
+                .set_fuel(FUEL_PER_FILE)
+                .map_err(|e| format!("Plugin {}: {}", self.path, e))?;
+            let instance = Linker::<()>::new(&self.engine)
+                .instantiate(&mut store, &self.module)
+                .and_then(|pre| pre.start(&mut store))
+                .map_err(|e| format!("Failed to start plugin {}: {}", self.path, e))?;
+            let missing = |name: &str, e: &dyn fmt::Display| {
+                format!(
+                    "Plugin {} has no usable `{}` export: {}",
+                    self.path, name, e
+                )
+            };
+            let memory = instance
+                .get_memory(&store, "memory")
+                .ok_or_else(|| missing("memory", &"not found"))?;
+            let alloc = instance
+                .get_typed_func(&store, "alloc")
+                .map_err(|e| missing("alloc", &e))?;
+            let filter = instance
+                .get_typed_func(&store, "filter")
+                .map_err(|e| missing("filter", &e))?;
+            Ok(Running {
+                store,
+                memory,
+                alloc,
+                filter,
+            })
+        }
+
+        pub(super) fn filter(&self, path: &str, data: &[u8]) -> Result<Verdict, String> {
+            let fail = |e: &dyn fmt::Display| format!("Plugin {} failed: {}", self.path, e);
+            let mut running = self.instantiate()?;
+            let (path_ptr, path_len) = running.copy_in(path.as_bytes()).map_err(|e| fail(&e))?;
+            let (data_ptr, data_len) = running.copy_in(data).map_err(|e| fail(&e))?;
+            let result = running
+                .filter
+                .call(&mut running.store, (path_ptr, path_len, data_ptr, data_len))
+                .map_err(|e| fail(&e))?;
+            match result {
+                KEEP => Ok(Verdict::Keep),
+                SKIP => Ok(Verdict::Skip),
+                packed => {
+                    let ptr = (packed as u64 >> 32) as usize;
+                    let mut content = vec![0u8; (packed as u64 & 0xffff_ffff) as usize];
+                    running
+                        .memory
+                        .read(&running.store, ptr, &mut content)
+                        .map_err(|e| fail(&e))?;
+                    Ok(Verdict::Transform(content))
+                }
+            }
+        }
+    }
+
+    impl Running {
+        // Copy bytes into memory the plugin allocated and return where they are
+        fn copy_in(&mut self, bytes: &[u8]) -> Result<(i32, i32), String> {
+            let len = i32::try_from(bytes.len()).map_err(|_| "input too large".to_string())?;
+            let ptr = self
+                .alloc
+                .call(&mut self.store, len)
+                .map_err(|e| e.to_string())?;
+            self.memory
+                .write(&mut self.store, ptr as u32 as usize, bytes)
+                .map_err(|e| e.to_string())?;
+            Ok((ptr, len))
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::{debug, info, warn};
+
+use crate::embeddings::Embedder;
+use crate::estimate_tokens;
+
+// Width of the hashed term vectors; collisions are rare enough at this size for code vocabularies
+const DIMENSIONS: usize = 1024;
+// Files are scored in chunks so one relevant function is not drowned out by the rest of a large file
+const CHUNK_LINES: usize = 60;
+// Longest chunk sent to an embedding model, well inside the input limits of common models
+const MAX_CHUNK_BYTES: usize = 8000;
+
+// Size and chunk terms of files already tokenized, by path, for a daemon to reuse between
+// requests while a file's modification time and length are unchanged
+#[derive(Debug, Default)]
+pub(crate) struct TermCache {
+    files: HashMap<PathBuf, CachedTerms>,
+}
+
+#[derive(Debug)]
+struct CachedTerms {
+    modified: SystemTime,
+    len: u64,
+    size: usize,
+    chunks: Vec<Vec<String>>,
+}
+
+// Rank files by similarity to `query` and keep the top `top_k` that fit in `token_budget`, with
+// tokens estimated at `bytes_per_token`, best first.
+// With an embedder, similarity is semantic: the cosine of the embeddings of the query and of
+// each chunk. Without one it is lexical: TF-IDF vectors over identifier sub-words, folded into
+// DIMENSIONS by hashing, so files only match words the query shares with them.
+pub(crate) fn select_relevant(
+    paths: &[PathBuf],
+    query: &str,
+    top_k: usize,
+    token_budget: Option<usize>,
+    bytes_per_token: f64,
+    cache: Option<&mut TermCache>,
+    embedder: Option<&Embedder>,
+) -> Result<Vec<PathBuf>, String> {
+    let (scores, sizes) = match embedder {
+        Some(embedder) => embedding_scores(paths, query, embedder)?,
+        None => match keyword_scores(paths, query, cache) {
+            Some(scored) => scored,
+            None => return Ok(Vec::new()),
+        },
+    };
+
+    let mut ranked: Vec<usize> = (0..paths.len()).filter(|i| scores[*i].is_some()).collect();
+    // Stable sort keeps the incoming order for ties
+    ranked.sort_by(|a, b| scores[*b].unwrap().total_cmp(&scores[*a].unwrap()));
+
+    let mut selected = Vec::new();
+    let mut used_tokens = 0;
+    for index in ranked {
+        if selected.len() >= top_k {
+            break;
+        }
+        let tokens = estimate_tokens(sizes[index], bytes_per_token);
+        if let Some(budget) = token_budget {
+            if used_tokens + tokens > budget {
+                debug!(
+                    "Skipping {} ({} tokens): would exceed token budget",
+                    paths[index].display(),
+                    tokens
+                );
+                continue;
+            }
+        }
+        used_tokens += tokens;
+        debug!(
+            "Relevance {:.4}: {}",
+            scores[index].unwrap_or_default(),
+            paths[index].display()
+        );
+        selected.push(paths[index].clone());
+    }
+    info!(
+        "Selected {} of {} files relevant to '{}' (~{} tokens)",
+        selected.len(),
+        paths.len(),
+        query,
+        used_tokens
+    );
+    Ok(selected)
+}
+
+// The best TF-IDF score of any chunk of each file, None for files sharing no terms with the
+// query, and each file's size; None if the query has no terms to match
+fn keyword_scores(
+    paths: &[PathBuf],
+    query: &str,
+    mut cache: Option<&mut TermCache>,
+) -> Option<(Vec<Option<f32>>, Vec<usize>)> {
+    let query_terms = tokenize(query);
+    if query_terms.is_empty() {
+        warn!("Relevance query '{}' contains no searchable terms", query);
+        return None;
+    }
+
+    // (file index, terms) for every chunk of every readable text file
+    let mut chunks: Vec<(usize, Vec<String>)> = Vec::new();
+    let mut sizes = vec![0usize; paths.len()];
+    for (index, path) in paths.iter().enumerate() {
+        let stamp = fs::metadata(path)
+            .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+            .ok();
+        let cached = match (&cache, stamp) {
+            (Some(cache), Some(stamp)) => cache
+                .files
+                .get(path)
+                .filter(|cached| (cached.modified, cached.len) == stamp),
+            _ => None,
+        };
+        let (size, file_chunks) = match cached {
+            Some(cached) => (cached.size, cached.chunks.clone()),
+            None => {
+                let content = match fs::read_to_string(path) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        debug!("Not scoring {} for relevance: {}", path.display(), e);
+                        continue;
+                    }
+                };
+                let file_chunks = chunk_terms(path, &content);
+                if let (Some(cache), Some((modified, len))) = (cache.as_deref_mut(), stamp) {
+                    let cached = CachedTerms {
+                        modified,
+                        len,
+                        size: content.len(),
+                        chunks: file_chunks.clone(),
+                    };
+                    cache.files.insert(path.clone(), cached);
+                }
+                (content.len(), file_chunks)
+            }
+        };
+        sizes[index] = size;
+        chunks.extend(file_chunks.into_iter().map(|terms| (index, terms)));
+    }
+
+    let idf = inverse_document_frequency(&chunks);
+    let query_vector = term_vector(&query_terms, &idf);
+
+    let mut best = vec![None; paths.len()];
+    for (index, terms) in &chunks {
+        let score = dot(&query_vector, &term_vector(terms, &idf));
+        if score > best[*index].unwrap_or(0.0) {
+            best[*index] = Some(score);
+        }
+    }
+    Some((best, sizes))
+}
+
+// The best cosine similarity between the query's embedding and any chunk's, for each readable
+// file, and each file's size
+fn embedding_scores(
+    paths: &[PathBuf],
+    query: &str,
+    embedder: &Embedder,
+) -> Result<(Vec<Option<f32>>, Vec<usize>), String> {
+    // The query is embedded along with the chunks, so short runs need a single request
+    let mut inputs = vec![query.to_string()];
+    let mut owners = Vec::new();
+    let mut sizes = vec![0usize; paths.len()];
+    for (index, path) in paths.iter().enumerate() {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                debug!("Not scoring {} for relevance: {}", path.display(), e);
+                continue;
+            }
+        };
+        sizes[index] = content.len();
+        for chunk in chunk_texts(path, &content) {
+            inputs.push(chunk);
+            owners.push(index);
+        }
+    }
+    info!(
+        "Embedding {} chunks with {} for relevance",
+        owners.len(),
+        embedder.model
+    );
+    let vectors = embedder.embed(&inputs)?;
+    let query_vector = &vectors[0];
+
+    let mut best: Vec<Option<f32>> = vec![None; paths.len()];
+    for (index, vector) in owners.iter().zip(&vectors[1..]) {
+        let score = cosine(query_vector, vector);
+        if best[*index].is_none_or(|best| score > best) {
+            best[*index] = Some(score);
+        }
+    }
+    Ok((best, sizes))
+}
+
+// The text of each CHUNK_LINES-line chunk of a file, opening with its path so names count too,
+// cut at MAX_CHUNK_BYTES so no chunk overflows the model's input
+fn chunk_texts(path: &Path, content: &str) -> Vec<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    lines
+        .chunks(CHUNK_LINES)
+        .map(|chunk| {
+            let mut text = format!("{}\n{}", path.display(), chunk.join("\n"));
+            if text.len() > MAX_CHUNK_BYTES {
+                let mut end = MAX_CHUNK_BYTES;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                text.truncate(end);
+            }
+            text
+        })
+        .collect()
+}
+
+// The terms of each CHUNK_LINES-line chunk of a file
+fn chunk_terms(path: &Path, content: &str) -> Vec<Vec<String>> {
+    let lines: Vec<&str> = content.lines().collect();
+    lines
+        .chunks(CHUNK_LINES)
+        .map(|chunk| {
+            // The file path is part of every chunk so matching names count too
+            let mut terms = tokenize(&path.to_string_lossy());
+            terms.extend(tokenize(&chunk.join("\n")));
+            terms
+        })
+        .collect()
+}
+
+// Lowercased, stemmed words, with snake_case and camelCase identifiers also split into their parts
+fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric() && c != '_') {
+        if word.is_empty() {
+            continue;
+        }
+        let parts = split_identifier(word);
+        if parts.len() > 1 {
+            terms.push(word.to_lowercase());
+        }
+        terms.extend(parts);
+    }
+    terms.retain(|term| term.len() > 1);
+    terms.into_iter().map(stem).collect()
+}
+
+// Strip common English inflections so "verified", "verifies" and "verify" share a term
+fn stem(term: String) -> String {
+    if term.len() <= 4 {
+        return term;
+    }
+    for (suffix, replacement) in [
+        ("ied", "y"),
+        ("ies", "y"),
+        ("ing", ""),
+        ("ed", ""),
+        ("s", ""),
+    ] {
+        if let Some(root) = term.strip_suffix(suffix) {
+            if root.len() >= 3 && !root.ends_with('s') {
+                return format!("{}{}", root, replacement);
+            }
+        }
+    }
+    term
+}
+
+fn split_identifier(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    for piece in word.split('_').filter(|p| !p.is_empty()) {
+        let mut current = String::new();
+        let mut previous_lower = false;
+        for c in piece.chars() {
+            if c.is_uppercase() && previous_lower && !current.is_empty() {
+                parts.push(current.to_lowercase());
+                current.clear();
+            }
+            previous_lower = c.is_lowercase() || c.is_ascii_digit();
+            current.push(c);
+        }
+        if !current.is_empty() {
+            parts.push(current.to_lowercase());
+        }
+    }
+    parts
+}
+
+fn inverse_document_frequency(chunks: &[(usize, Vec<String>)]) -> HashMap<String, f32> {
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for (_, terms) in chunks {
+        let mut seen: Vec<&str> = terms.iter().map(String::as_str).collect();
+        seen.sort_unstable();
+        seen.dedup();
+        for term in seen {
+            *document_frequency.entry(term).or_insert(0) += 1;
+        }
+    }
+    let total = chunks.len() as f32;
+    document_frequency
+        .into_iter()
+        .map(|(term, count)| (term.to_string(), (1.0 + total / count as f32).ln()))
+        .collect()
+}
+
+// Unit-length hashed TF-IDF vector; terms missing from the corpus carry no weight
+fn term_vector(terms: &[String], idf: &HashMap<String, f32>) -> Vec<f32> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for term in terms {
+        *counts.entry(term.as_str()).or_insert(0) += 1;
+    }
+    let mut vector = vec![0f32; DIMENSIONS];
+    for (term, count) in counts {
+        if let Some(weight) = idf.get(term) {
+            vector[fnv1a(term) % DIMENSIONS] += (1.0 + (count as f32).ln()) * weight;
+        }
+    }
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+// Embeddings are not always unit length, so they are normalized here
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let norms = dot(a, a).sqrt() * dot(b, b).sqrt();
+    match norms > 0.0 {
+        true => dot(a, b) / norms,
+        false => 0.0,
+    }
+}
+
+// Stable across runs and platforms, unlike the std hasher
+fn fnv1a(text: &str) -> usize {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash as usize
+}
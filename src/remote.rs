@@ -0,0 +1,270 @@
+// Destinations given to -o that are uploaded to instead of written locally, and URL inputs
+// that are downloaded. Both need the `remote` feature; without it such URLs are rejected.
+
+// Whether an -o value names a remote destination rather than a directory
+pub(crate) fn is_remote(path: &str) -> bool {
+    ["s3://", "http://", "https://"]
+        .iter()
+        .any(|scheme| path.starts_with(scheme))
+}
+
+// Whether an input names a file to download rather than a local path
+pub(crate) fn is_http_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+#[cfg(not(feature = "remote"))]
+pub(crate) fn fetch(url: &str, _limit: u64) -> Result<Vec<u8>, String> {
+    Err(format!(
+        "Cannot fetch {}: this build has no URL inputs; rebuild with --features remote",
+        url
+    ))
+}
+
+#[cfg(not(feature = "remote"))]
+pub(crate) fn validate(url: &str) -> Result<(), String> {
+    Err(format!(
+        "Cannot upload to {}: this build has no remote outputs; rebuild with --features remote",
+        url
+    ))
+}
+
+#[cfg(not(feature = "remote"))]
+pub(crate) fn upload(_path: &std::path::Path, url: &str) -> Result<String, String> {
+    validate(url).map(|_| url.to_string())
+}
+
+#[cfg(feature = "remote")]
+pub(crate) use upload::{fetch, upload, validate};
+
+#[cfg(feature = "remote")]
+mod upload {
+    use std::fs;
+    use std::io::Read;
+    use std::path::Path;
+
+    use chrono::Utc;
+    use hmac::{Hmac, Mac};
+    use log::info;
+    use sha2::{Digest, Sha256};
+
+    // Used when neither AWS_REGION nor AWS_DEFAULT_REGION is set, as the AWS CLI does
+    const DEFAULT_S3_REGION: &str = "us-east-1";
+
+    enum Destination {
+        S3 { bucket: String, key: String },
+        Http(String),
+    }
+
+    struct Credentials {
+        access_key: String,
+        secret_key: String,
+        session_token: Option<String>,
+    }
+
+    fn parse(url: &str) -> Result<Destination, String> {
+        if let Some(rest) = url.strip_prefix("s3://") {
+            let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+            if bucket.is_empty() {
+                return Err(format!("No bucket in S3 destination {}", url));
+            }
+            return Ok(Destination::S3 {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            });
+        }
+        Ok(Destination::Http(url.to_string()))
+    }
+
+    // Check a destination before any work is done, so a typo or missing credentials do not
+    // surface only after the whole tree has been globbed
+    pub(crate) fn validate(url: &str) -> Result<(), String> {
+        match parse(url)? {
+            Destination::S3 { .. } => credentials().map(|_| ()),
+            Destination::Http(_) => Ok(()),
+        }
+    }
+
+    // Upload a finished output file and return the URL it was stored at. Destinations ending
+    // in `/` (or naming just a bucket) get the output's file name appended.
+    pub(crate) fn upload(path: &Path, url: &str) -> Result<String, String> {
+        let body =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let with_name = |target: &str| {
+            if target.is_empty() || target.ends_with('/') {
+                format!("{}{}", target, file_name)
+            } else {
+                target.to_string()
+            }
+        };
+        match parse(url)? {
+            Destination::Http(target) => {
+                let target = with_name(&target);
+                info!("Uploading {} bytes to {}", body.len(), target);
+                send(ureq::put(&target), &body)?;
+                Ok(target)
+            }
+            Destination::S3 { bucket, key } => {
+                let key = with_name(&key);
+                info!("Uploading {} bytes to s3://{}/{}", body.len(), bucket, key);
+                put_s3_object(&bucket, &key, &body)?;
+                Ok(format!("s3://{}/{}", bucket, key))
+            }
+        }
+    }
+
+    // Download a URL input, reading at most one byte more than `limit` so that oversized
+    // files are still caught by the size filter without being held in memory whole
+    pub(crate) fn fetch(url: &str, limit: u64) -> Result<Vec<u8>, String> {
+        info!("Fetching {}", url);
+        let response = match ureq::get(url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(code, _)) => {
+                return Err(format!("Failed to fetch {}: HTTP {}", url, code))
+            }
+            Err(e) => return Err(format!("Failed to fetch {}: {}", url, e)),
+        };
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .take(limit.saturating_add(1))
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+        Ok(data)
+    }
+
+    fn send(request: ureq::Request, body: &[u8]) -> Result<(), String> {
+        match request.send_bytes(body) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(code, response)) => {
+                let detail = response.into_string().unwrap_or_default();
+                Err(format!(
+                    "Upload failed with HTTP {}: {}",
+                    code,
+                    detail.trim()
+                ))
+            }
+            Err(e) => Err(format!("Upload failed: {}", e)),
+        }
+    }
+
+    fn credentials() -> Result<Credentials, String> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key), Some(secret_key)) => Ok(Credentials {
+                access_key,
+                secret_key,
+                session_token: var("AWS_SESSION_TOKEN"),
+            }),
+            _ => Err(
+                "S3 uploads need AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY in the environment"
+                    .to_string(),
+            ),
+        }
+    }
+
+    // PUT an object with a SigV4-signed request. AWS_ENDPOINT_URL_S3 or AWS_ENDPOINT_URL point
+    // at S3-compatible stores such as MinIO or R2, which are addressed path-style.
+    fn put_s3_object(bucket: &str, key: &str, body: &[u8]) -> Result<(), String> {
+        let credentials = credentials()?;
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let region = var("AWS_REGION")
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|| DEFAULT_S3_REGION.to_string());
+        let (base, path) = match var("AWS_ENDPOINT_URL_S3").or_else(|| var("AWS_ENDPOINT_URL")) {
+            Some(endpoint) => (
+                endpoint.trim_end_matches('/').to_string(),
+                format!("/{}/{}", bucket, uri_encode(key)),
+            ),
+            None => (
+                format!("https://{}.s3.{}.amazonaws.com", bucket, region),
+                format!("/{}", uri_encode(key)),
+            ),
+        };
+        let host = base
+            .split_once("://")
+            .map_or(base.as_str(), |(_, rest)| rest)
+            .to_string();
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex(&Sha256::digest(body));
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "PUT\n{}\n\n{}\n{}\n{}",
+            path, canonical_headers, signed_headers, payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let signing_key = [region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac_sha256(
+                format!("AWS4{}", credentials.secret_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+        let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            credentials.access_key, scope, signed_headers, signature
+        );
+
+        // ureq sets Host itself from the URL
+        let mut request =
+            ureq::put(&format!("{}{}", base, path)).set("Authorization", &authorization);
+        for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+            request = request.set(name, value);
+        }
+        send(request, body)
+    }
+
+    fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    // Percent-encode an object key as SigV4 expects, keeping `/` as the path separator
+    fn uri_encode(key: &str) -> String {
+        key.bytes()
+            .map(|byte| match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                    (byte as char).to_string()
+                }
+                other => format!("%{:02X}", other),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,217 @@
+// State for `--resume`, kept next to the output while it is written: the files found for the
+// run and the output's length after each one, so a run that was interrupted, as on a slow
+// network share, carries on from the last file it finished instead of finding and reading every
+// file again
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+use llm_globber_core::{ArchiveParser, Event, HARD_LINK_SEPARATOR};
+
+const STATE_HEADER: &str = "LLM_GLOBBER_RESUME: 4";
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct State {
+    pub(crate) output: PathBuf,
+    pub(crate) relative_root: Option<PathBuf>,
+    pub(crate) start: u64, // Output length once the preamble is written
+    pub(crate) signer: Option<String>, // Public key the entries were signed with, if any
+    pub(crate) options: String, // Digest of the options that shape the entries
+    pub(crate) files: Vec<PathBuf>,
+    pub(crate) hard_links: Vec<(PathBuf, PathBuf)>,
+    pub(crate) walked_dirs: Vec<PathBuf>,
+    pub(crate) offsets: Vec<u64>, // Output length after each finished file, in order
+}
+
+impl State {
+    // Where the output stops being trustworthy: after the last finished file
+    pub(crate) fn end(&self) -> u64 {
+        self.offsets.last().copied().unwrap_or(self.start)
+    }
+
+    // Refuse to carry on with a different signer or options than the interrupted run's, which
+    // would leave an archive that reads as two runs and fails its own verification
+    pub(crate) fn check(&self, signer: Option<&str>, options: &str) -> Result<(), String> {
+        if self.signer.as_deref() != signer {
+            return Err(format!(
+                "Cannot resume {}: it was signed with key {} but this run signs with {}. A one-off key cannot be resumed; create one with `llm_globber keygen` or give --key-file, or run without --resume to start over",
+                self.output.display(),
+                self.signer.as_deref().unwrap_or("none"),
+                signer.unwrap_or("none")
+            ));
+        }
+        if self.options != options {
+            return Err(format!(
+                "Cannot resume {}: the interrupted run was given different output options; run it again with the same options, or without --resume to start over",
+                self.output.display()
+            ));
+        }
+        Ok(())
+    }
+}
+
+// The state file for outputs named `name` in `output_dir`
+pub(crate) fn state_path(output_dir: &Path, name: &str) -> PathBuf {
+    output_dir.join(format!(".{}.llm-globber-resume", name))
+}
+
+// The state an interrupted run left at `path`, if any. State whose output is gone cannot be
+// resumed and is ignored.
+pub(crate) fn load(path: &Path) -> Result<Option<State>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let invalid = |line: &str| format!("Invalid line in {}: {}", path.display(), line);
+    // The last line may have been cut short by the interruption, and then has no newline
+    let complete = text.rfind('\n').map_or("", |end| &text[..end]);
+    let mut lines = complete.lines();
+    match lines.next() {
+        Some(STATE_HEADER) => {}
+        Some(header) if header.starts_with("LLM_GLOBBER_RESUME: ") => {
+            return Err(format!(
+                "{} was left by another version of llm_globber; run without --resume to start over",
+                path.display()
+            ))
+        }
+        _ => return Err(format!("{} is not a resume state file", path.display())),
+    }
+    let mut state = State::default();
+    for line in lines {
+        let (field, value) = line.split_once(' ').unwrap_or((line, ""));
+        match field {
+            "OUTPUT" => state.output = PathBuf::from(value),
+            "ROOT" => state.relative_root = Some(PathBuf::from(value)),
+            "START" => state.start = value.parse().map_err(|_| invalid(line))?,
+            "SIGNER" => state.signer = Some(value.to_string()),
+            "OPTIONS" => state.options = value.to_string(),
+            "FILE" => state.files.push(unescape(value)),
+            "LINK" => {
+                let (alias, target) = value
+                    .split_once(HARD_LINK_SEPARATOR)
+                    .ok_or_else(|| invalid(line))?;
+                state.hard_links.push((unescape(alias), unescape(target)));
+            }
+            "DIR" => state.walked_dirs.push(unescape(value)),
+            "DONE" => state
+                .offsets
+                .push(value.parse().map_err(|_| invalid(line))?),
+            _ => return Err(invalid(line)),
+        }
+    }
+    if !state.output.is_file() {
+        log::warn!(
+            "Ignoring {}: its output {} no longer exists",
+            path.display(),
+            state.output.display()
+        );
+        return Ok(None);
+    }
+    Ok(Some(state))
+}
+
+// Appends each finished file's offset to the state file
+#[derive(Debug)]
+pub(crate) struct Journal {
+    file: File,
+}
+
+impl Journal {
+    // Write `state` to `path` in full, replacing what an earlier run left, and keep it open for
+    // the offsets still to come
+    pub(crate) fn create(path: &Path, state: &State) -> io::Result<Self> {
+        let mut text = format!("{}\nOUTPUT {}\n", STATE_HEADER, state.output.display());
+        if let Some(root) = &state.relative_root {
+            text.push_str(&format!("ROOT {}\n", root.display()));
+        }
+        text.push_str(&format!("START {}\n", state.start));
+        if let Some(signer) = &state.signer {
+            text.push_str(&format!("SIGNER {}\n", signer));
+        }
+        text.push_str(&format!("OPTIONS {}\n", state.options));
+        for file in &state.files {
+            text.push_str(&format!("FILE {}\n", escape(file)));
+        }
+        for (alias, target) in &state.hard_links {
+            text.push_str(&format!(
+                "LINK {}{}{}\n",
+                escape(alias),
+                HARD_LINK_SEPARATOR,
+                escape(target)
+            ));
+        }
+        for dir in &state.walked_dirs {
+            text.push_str(&format!("DIR {}\n", escape(dir)));
+        }
+        for offset in &state.offsets {
+            text.push_str(&format!("DONE {}\n", offset));
+        }
+        let mut file = File::create(path)?;
+        file.write_all(text.as_bytes())?;
+        Ok(Journal { file })
+    }
+
+    pub(crate) fn record(&mut self, offset: u64) -> io::Result<()> {
+        writeln!(self.file, "DONE {}", offset)
+    }
+}
+
+// Open the output of an interrupted run for appending, dropping anything written after the
+// last finished file
+pub(crate) fn reopen(state: &State) -> io::Result<File> {
+    let mut file = File::options().write(true).open(&state.output)?;
+    file.set_len(state.end())?;
+    file.seek(SeekFrom::End(0))?;
+    Ok(file)
+}
+
+// The header paths and content bytes of the entries the interrupted run finished, which the
+// footer has to count along with the rest
+pub(crate) fn written_entries(state: &State) -> io::Result<(Vec<String>, u64)> {
+    // The preamble is read too, for the format version and boilerplate the entries depend on
+    let reader = BufReader::new(File::open(&state.output)?.take(state.end()));
+    let mut parser = ArchiveParser::new();
+    let mut paths = Vec::new();
+    let mut bytes = 0;
+    let mut add = |event: Event| match event {
+        Event::Entry(entry) => {
+            bytes += entry.content_len();
+            paths.push(entry.path);
+        }
+        Event::Omitted(omitted) => paths.push(omitted.path),
+        _ => {}
+    };
+    for line in reader.split(b'\n') {
+        let line = String::from_utf8_lossy(&line?).into_owned();
+        if let Some(event) = parser
+            .feed(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        {
+            add(event);
+        }
+    }
+    if let Some(entry) = parser.finish() {
+        add(Event::Entry(entry));
+    }
+    Ok((paths, bytes))
+}
+
+// Paths are kept escaped as in format 5 headers, so any name fits on its line
+fn escape(path: &Path) -> String {
+    llm_globber_core::escape_path(path.as_os_str().as_bytes())
+}
+
+fn unescape(path: &str) -> PathBuf {
+    PathBuf::from(OsString::from_vec(llm_globber_core::unescape_path(path)))
+}
+
+pub(crate) fn remove(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            log::warn!("Failed to remove {}: {}", path.display(), e);
+        }
+    }
+}
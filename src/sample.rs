@@ -0,0 +1,83 @@
+// Random subsets of the selected files, taken with --sample
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use rand::rngs::StdRng;
+use rand::seq::{index, SliceRandom};
+use rand::SeedableRng;
+
+// How --sample-by groups files so that every group is represented
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Strata {
+    None,
+    Dir,
+    Ext,
+}
+
+impl Strata {
+    pub(crate) fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(Strata::None),
+            "dir" => Ok(Strata::Dir),
+            "ext" => Ok(Strata::Ext),
+            other => Err(format!(
+                "Invalid --sample-by value '{}': expected dir, ext, or none",
+                other
+            )),
+        }
+    }
+
+    fn key(self, path: &Path) -> String {
+        let part = match self {
+            Strata::None => None,
+            Strata::Dir => path.parent().map(|dir| dir.as_os_str()),
+            Strata::Ext => path.extension(),
+        };
+        part.map(|part| part.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+// Keep `count` of the paths, chosen at random but in their original order. Stratified samples
+// take one file from each group in turn, so small directories or rare extensions are not
+// crowded out by large ones. The same seed gives the same sample of the same paths.
+pub(crate) fn sample(paths: Vec<PathBuf>, count: usize, strata: Strata, seed: u64) -> Vec<PathBuf> {
+    if paths.len() <= count {
+        return paths;
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let chosen: HashSet<usize> = match strata {
+        Strata::None => index::sample(&mut rng, paths.len(), count)
+            .into_iter()
+            .collect(),
+        _ => {
+            let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+            for (i, path) in paths.iter().enumerate() {
+                groups.entry(strata.key(path)).or_default().push(i);
+            }
+            let mut groups: Vec<Vec<usize>> = groups.into_values().collect();
+            for group in &mut groups {
+                group.shuffle(&mut rng);
+            }
+            // When there are more groups than files to take, which groups get one is random too
+            groups.shuffle(&mut rng);
+            let mut chosen = HashSet::new();
+            let mut round = 0;
+            while chosen.len() < count {
+                for group in &groups {
+                    if let (Some(&i), true) = (group.get(round), chosen.len() < count) {
+                        chosen.insert(i);
+                    }
+                }
+                round += 1;
+            }
+            chosen
+        }
+    };
+    paths
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| chosen.contains(i))
+        .map(|(_, path)| path)
+        .collect()
+}
@@ -0,0 +1,95 @@
+use std::io::BufRead;
+
+use llm_globber_core::{is_omitted_marker, parse_file_header};
+use regex::{Regex, RegexBuilder};
+
+use crate::compress::open_archive;
+
+// A line inside an archive entry that matched the search pattern
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SearchMatch {
+    pub(crate) path: String,
+    pub(crate) line: usize, // Line number within the entry's original file, 1-based
+    pub(crate) text: String,
+}
+
+pub(crate) fn build_pattern(
+    pattern: &str,
+    ignore_case: bool,
+    fixed_strings: bool,
+) -> Result<Regex, String> {
+    let source = if fixed_strings {
+        regex::escape(pattern)
+    } else {
+        pattern.to_string()
+    };
+    RegexBuilder::new(&source)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| format!("Invalid search pattern '{}': {}", pattern, e))
+}
+
+// Search the contents of every text entry in an archive without extracting it
+pub(crate) fn search_archive(
+    filename: &str,
+    pattern: &Regex,
+    identity_files: &[String],
+) -> Result<Vec<SearchMatch>, String> {
+    let reader = open_archive(filename, identity_files)
+        .map_err(|e| format!("Failed to open input file: {}: {}", filename, e))?;
+
+    let mut matches = Vec::new();
+    // Current entry path and the number of content lines read from it so far
+    let mut current: Option<(String, usize)> = None;
+    for line_result in reader.lines() {
+        let line = line_result.map_err(|e| format!("Error reading line: {}", e))?;
+
+        // Headers start a new entry wherever they appear, as they do for unglob
+        if line.starts_with("'''--- PUBLIC_KEY ---") {
+            current = None;
+            continue;
+        }
+        if line.starts_with("'''--- ") {
+            let (path, _) = parse_file_header(&line)?;
+            current = Some((path, 0));
+            continue;
+        }
+
+        let Some((path, line_no)) = current.as_mut() else {
+            continue;
+        };
+        if line == "'''" || (*line_no == 0 && is_omitted_marker(&line)) {
+            current = None;
+            continue;
+        }
+        *line_no += 1;
+        if pattern.is_match(&line) {
+            matches.push(SearchMatch {
+                path: path.clone(),
+                line: *line_no,
+                text: line,
+            });
+        }
+    }
+    Ok(matches)
+}
+
+// Entry point for `llm_globber grep PATTERN FILE`: prints matches as `entry-path:line: text`
+pub(crate) fn run_grep(
+    filename: &str,
+    pattern: &Regex,
+    identity_files: &[String],
+) -> Result<(), String> {
+    let matches = search_archive(filename, pattern, identity_files)?;
+    for found in &matches {
+        println!("{}:{}: {}", found.path, found.line, found.text);
+    }
+    if matches.is_empty() {
+        return Err(format!(
+            "No matches for '{}' in {}",
+            pattern.as_str(),
+            filename
+        ));
+    }
+    Ok(())
+}
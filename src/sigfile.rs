@@ -0,0 +1,109 @@
+// Detached signatures written with --detached-sig: a small text file next to the output
+// holding the signer's public key and an ed25519 signature over the output's exact bytes,
+// so it can be checked without parsing the archive, with llm_globber verify or any ed25519
+// library
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use base64::{engine::general_purpose, Engine};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use log::info;
+use memmap2::Mmap;
+
+use crate::sshkey::Signer;
+use crate::trust;
+
+const SIG_EXTENSION: &str = "sig";
+const SIG_HEADER: &str = "LLM_GLOBBER_SIGNATURE: 1";
+const KEY_PREFIX: &str = "KEY: ";
+const SIGNATURE_PREFIX: &str = "SIGNATURE: ";
+
+// `output.txt` is signed in `output.txt.sig`
+pub(crate) fn sig_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(SIG_EXTENSION);
+    PathBuf::from(name)
+}
+
+// Sign a finished output file and return the path of its signature file
+pub(crate) fn write(path: &Path, signer: &Signer) -> Result<PathBuf, String> {
+    let signature = with_contents(path, |data| signer.sign(data))?;
+    let sig_path = sig_path(path);
+    let text = format!(
+        "{}\n{}{}\n{}{}\n",
+        SIG_HEADER,
+        KEY_PREFIX,
+        trust::encode_key(&signer.public_key()),
+        SIGNATURE_PREFIX,
+        general_purpose::STANDARD.encode(signature.to_bytes())
+    );
+    fs::write(&sig_path, text)
+        .map_err(|e| format!("Failed to write {}: {}", sig_path.display(), e))?;
+    info!("Wrote detached signature {}", sig_path.display());
+    Ok(sig_path)
+}
+
+// Check a file against its signature file. The signer must be `expected` when given, and
+// otherwise one the trust store accepts. Returns the signer's key and whether it is trusted.
+pub(crate) fn verify(
+    sig_path: &Path,
+    path: &Path,
+    expected: Option<PublicKey>,
+    require_trusted: bool,
+) -> Result<(PublicKey, bool), String> {
+    let (key, signature) = read(sig_path)?;
+    let trusted = match expected {
+        Some(expected) if expected != key => {
+            return Err(format!(
+                "{} is signed by key {}, not the key given with --key-file",
+                path.display(),
+                trust::encode_key(&key)
+            ))
+        }
+        Some(_) => true,
+        None => trust::check_signer(&key, &path.display().to_string(), require_trusted)?,
+    };
+    with_contents(path, |data| {
+        key.verify(data, &signature)
+            .map_err(|_| format!("Signature verification failed for {}", path.display()))
+    })?;
+    Ok((key, trusted))
+}
+
+fn read(sig_path: &Path) -> Result<(PublicKey, Signature), String> {
+    let invalid =
+        |e: &dyn std::fmt::Display| format!("Invalid signature file {}: {}", sig_path.display(), e);
+    let text = fs::read_to_string(sig_path)
+        .map_err(|e| format!("Failed to read {}: {}", sig_path.display(), e))?;
+    let mut lines = text.lines();
+    if lines.next() != Some(SIG_HEADER) {
+        return Err(invalid(&format!("expected `{}` first", SIG_HEADER)));
+    }
+    let mut field = |prefix: &str| {
+        lines
+            .next()
+            .and_then(|line| line.strip_prefix(prefix))
+            .ok_or_else(|| invalid(&format!("missing `{}` line", prefix.trim_end())))
+            .and_then(|value| {
+                general_purpose::STANDARD
+                    .decode(value)
+                    .map_err(|e| invalid(&e))
+            })
+    };
+    let key = PublicKey::from_bytes(&field(KEY_PREFIX)?).map_err(|e| invalid(&e))?;
+    let signature = Signature::from_bytes(&field(SIGNATURE_PREFIX)?).map_err(|e| invalid(&e))?;
+    Ok((key, signature))
+}
+
+// Run `f` on a file's bytes, memory-mapped since ed25519 needs the whole message at once
+fn with_contents<T>(path: &Path, f: impl FnOnce(&[u8]) -> Result<T, String>) -> Result<T, String> {
+    let fail = |e: std::io::Error| format!("Failed to read {}: {}", path.display(), e);
+    let file = File::open(path).map_err(fail)?;
+    // Empty files cannot be mapped
+    if file.metadata().map_err(fail)?.len() == 0 {
+        return f(&[]);
+    }
+    let mmap = unsafe { Mmap::map(&file) }.map_err(fail)?;
+    f(&mmap)
+}
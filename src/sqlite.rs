@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use llm_globber_core::RENDERED_MARKER_PREFIX;
+use rusqlite::{Connection, OpenFlags};
+
+const SQLITE_EXTENSIONS: &[&str] = &["db", "sqlite", "sqlite3"];
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+// Database files are recognised by extension and confirmed by their header
+pub(crate) fn is_sqlite_file(file_path: &Path, data: &[u8]) -> bool {
+    SQLITE_EXTENSIONS
+        .iter()
+        .any(|extension| crate::has_extension(file_path, extension))
+        && data.starts_with(SQLITE_MAGIC)
+}
+
+// Text dump of the tables (with row counts), indexes, views and triggers of a database.
+// The database is opened read-only so it is never modified or journaled.
+pub(crate) fn dump_schema(file_path: &Path) -> Result<String, String> {
+    let mut magic = [0u8; 16];
+    File::open(file_path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map_err(|e| format!("cannot read {}: {}", file_path.display(), e))?;
+    if magic != SQLITE_MAGIC {
+        return Err(format!("{} is not a SQLite database", file_path.display()));
+    }
+
+    let connection = Connection::open_with_flags(
+        file_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| format!("cannot open {}: {}", file_path.display(), e))?;
+
+    let mut statement = connection
+        .prepare(
+            "SELECT type, name, tbl_name, sql FROM sqlite_master \
+             WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%' \
+             ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'index' THEN 1 WHEN 'view' THEN 2 ELSE 3 END, name",
+        )
+        .map_err(|e| format!("cannot read schema of {}: {}", file_path.display(), e))?;
+    let objects: Vec<(String, String, String, String)> = statement
+        .query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| format!("cannot read schema of {}: {}", file_path.display(), e))?;
+
+    let mut out = format!(
+        "{}SQLite schema, {} object(s)]\n",
+        RENDERED_MARKER_PREFIX,
+        objects.len()
+    );
+    for (kind, name, table, sql) in objects {
+        let label = match kind.as_str() {
+            "table" => {
+                let count: Result<i64, _> = connection.query_row(
+                    &format!("SELECT COUNT(*) FROM {}", quote_identifier(&name)),
+                    [],
+                    |row| row.get(0),
+                );
+                match count {
+                    Ok(count) => format!("Table: {} ({} rows)", name, count),
+                    Err(_) => format!("Table: {}", name),
+                }
+            }
+            "index" => format!("Index: {} on {}", name, table),
+            "view" => format!("View: {}", name),
+            "trigger" => format!("Trigger: {} on {}", name, table),
+            _ => format!("{}: {}", kind, name),
+        };
+        out.push_str(&format!("\n-- {}\n{};\n", label, sql.trim_end_matches(';')));
+    }
+    Ok(out)
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
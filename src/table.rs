@@ -0,0 +1,65 @@
+use llm_globber_core::RENDERED_MARKER_PREFIX;
+
+const TABLE_EXTENSIONS: &[&str] = &["csv", "tsv"];
+
+pub(crate) fn is_table_file(file_path: &str) -> bool {
+    TABLE_EXTENSIONS
+        .iter()
+        .any(|extension| crate::has_extension(file_path, extension))
+}
+
+// Header row plus the first and last `rows` data rows, with a note giving the total row count.
+// Returns None when the table is small enough to include in full.
+pub(crate) fn sample_table(source: &str, rows: usize) -> Option<String> {
+    let mut records = records(source).into_iter();
+    let header = records.next()?;
+    let data: Vec<&str> = records.collect();
+    if data.len() <= rows * 2 {
+        return None;
+    }
+
+    let omitted = data.len() - rows * 2;
+    let mut out = format!(
+        "{}table sample, {} data rows, showing the first {} and last {}]\n",
+        RENDERED_MARKER_PREFIX,
+        data.len(),
+        rows,
+        rows
+    );
+    out.push_str(header);
+    out.push('\n');
+    for line in &data[..rows] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(&format!("[... {} rows omitted ...]\n", omitted));
+    for line in &data[data.len() - rows..] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    Some(out)
+}
+
+// The records of a table without their line endings. A quoted field may hold line breaks, so a
+// record ends at the first line ending outside quotes; doubled quotes inside a field toggle twice
+// and cancel out.
+fn records(source: &str) -> Vec<&str> {
+    let mut records = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (index, byte) in source.bytes().enumerate() {
+        match byte {
+            b'"' => quoted = !quoted,
+            b'\n' if !quoted => {
+                let record = &source[start..index];
+                records.push(record.strip_suffix('\r').unwrap_or(record));
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < source.len() {
+        records.push(&source[start..]);
+    }
+    records
+}
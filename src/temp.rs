@@ -0,0 +1,80 @@
+// Temporary directories for git clones, extracted archives, downloads and staged uploads, made
+// under --tmpdir or the system temporary directory, and `llm_globber clean-temp`, which removes
+// the ones left behind by runs that crashed
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{info, warn};
+
+// Every directory made here is named `llm_globber_KIND_NANOS`
+const PREFIX: &str = "llm_globber_";
+
+// --tmpdir for the current run
+static ROOT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub(crate) fn set_root(root: Option<PathBuf>) {
+    *ROOT.lock().expect("Temp root mutex poisoned") = root;
+}
+
+pub(crate) fn root() -> PathBuf {
+    ROOT.lock()
+        .expect("Temp root mutex poisoned")
+        .clone()
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+// A new, not yet created, directory path for one kind of temporary files, such as `clone`
+pub(crate) fn path(kind: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    root().join(format!("{}{}_{}", PREFIX, kind, nanos))
+}
+
+// True for directories this tool made in the temporary directory, the only ones it removes
+pub(crate) fn is_ours(path: &Path) -> bool {
+    path.parent() == Some(root().as_path())
+        && path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with(PREFIX))
+}
+
+// Entry point for `llm_globber clean-temp`: removes our directories in the temporary directory
+// that were last modified more than `older_than` ago, so the ones in use by running jobs stay
+pub(crate) fn run_clean_temp(older_than: Duration) -> Result<(), String> {
+    let root = root();
+    let entries = fs::read_dir(&root)
+        .map_err(|e| format!("Failed to read directory {}: {}", root.display(), e))?;
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        if !metadata.is_dir() || !is_ours(&path) || age.is_none_or(|age| age < older_than) {
+            continue;
+        }
+        match fs::remove_dir_all(&path) {
+            Ok(()) => {
+                info!("Removed {}", path.display());
+                println!("{}", path.display());
+                removed += 1;
+            }
+            Err(e) => warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+    info!(
+        "Removed {} stale temporary director{} from {}",
+        removed,
+        if removed == 1 { "y" } else { "ies" },
+        root.display()
+    );
+    Ok(())
+}
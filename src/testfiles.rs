@@ -0,0 +1,59 @@
+// Which files are tests, for `--no-tests` and `--only-tests`, by the naming conventions of the
+// languages' own test runners rather than anything read from the file
+use std::path::Path;
+
+// Directories whose files are all tests, fixtures included
+const TEST_DIRS: &[&str] = &[
+    "test",
+    "tests",
+    "__tests__",
+    "spec",
+    "specs",
+    "testdata",
+    "test_data",
+    "fixtures",
+];
+
+// Endings of test file names before the extension, for the extension
+fn test_suffixes(extension: &str) -> &'static [&'static str] {
+    match extension {
+        "go" | "c" | "cc" | "cpp" | "exs" | "dart" => &["_test"],
+        "py" | "rs" => &["_test", "_tests"],
+        "rb" => &["_test", "_spec"],
+        "lua" => &["_spec"],
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => &[".test", ".spec"],
+        "java" | "swift" => &["Test", "Tests"],
+        "kt" => &["Test", "Tests", "Spec"],
+        "scala" => &["Test", "Spec"],
+        "cs" => &["Tests"],
+        "php" => &["Test"],
+        "groovy" => &["Spec"],
+        _ => &[],
+    }
+}
+
+// True if a file is a test by its name or a directory it is in. `path` should be relative to the
+// input it was found in, so a checkout that itself lives under a `tests` directory does not make
+// every file a test.
+pub(crate) fn is_test_file(path: &Path) -> bool {
+    let in_test_dir = path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .any(|component| TEST_DIRS.contains(&&*component.as_os_str().to_string_lossy()));
+    in_test_dir || is_test_name(path)
+}
+
+fn is_test_name(path: &Path) -> bool {
+    let (Some(stem), Some(extension)) = (path.file_stem(), path.extension()) else {
+        return false;
+    };
+    let (stem, extension) = (stem.to_string_lossy(), extension.to_string_lossy());
+    // pytest collects `test_*.py` and loads `conftest.py` for them
+    if extension == "py" && (stem.starts_with("test_") || stem == "conftest") {
+        return true;
+    }
+    test_suffixes(&extension)
+        .iter()
+        .any(|suffix| stem.len() > suffix.len() && stem.ends_with(suffix))
+}
@@ -0,0 +1,169 @@
+// Tables of contents written with --toc, and `llm_globber cat`, which uses them to read one
+// entry without scanning the archive up to it
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use llm_globber_core::{
+    section, ArchiveParser, Event, CLOSING_MARKER, ENTRY_PREFIX, FORMAT_HEADER_PREFIX,
+    PUBLIC_KEY_PREFIX, SECTION_PREFIX, TOC_SECTION,
+};
+
+use crate::compress::{open_archive, raw_lines};
+
+// Where one entry sits in the archive
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TocEntry {
+    pub(crate) path: String,
+    pub(crate) offset: u64,
+    pub(crate) length: u64,
+}
+
+// The section listing entries whose offsets were taken before the section was inserted ahead
+// of them. Offsets are moved past the section itself, whose length depends on their digits,
+// so this settles on a length that stays the same once they are added.
+fn toc_section(entries: &[TocEntry]) -> String {
+    let mut shift = 0;
+    loop {
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|entry| format!("{} {} {}", entry.offset + shift, entry.length, entry.path))
+            .collect();
+        let text = section(TOC_SECTION, &lines);
+        if text.len() as u64 == shift {
+            return text;
+        }
+        shift = text.len() as u64;
+    }
+}
+
+// Rewrite a finished archive with its table of contents inserted at byte `at`, the end of its
+// preamble
+pub(crate) fn insert(path: &Path, at: u64, entries: &[TocEntry]) -> io::Result<()> {
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".toc");
+    let temp_path = Path::new(&temp_name);
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut writer = BufWriter::new(File::create(temp_path)?);
+    io::copy(&mut (&mut reader).take(at), &mut writer)?;
+    writer.write_all(toc_section(entries).as_bytes())?;
+    io::copy(&mut reader, &mut writer)?;
+    writer.flush()?;
+    fs::set_permissions(temp_path, fs::metadata(path)?.permissions())?;
+    fs::rename(temp_path, path)
+}
+
+// The table of contents from the start of an archive, if it has one. Reading stops at the
+// first entry, since the table comes before them, and the lines before it are fed to `parser`
+// so it knows the format version and any boilerplate the entries refer to.
+fn read_toc<R: BufRead>(
+    reader: R,
+    parser: &mut ArchiveParser,
+) -> Result<Option<Vec<TocEntry>>, String> {
+    let header = format!("{}{} ===", SECTION_PREFIX, TOC_SECTION);
+    let mut entries: Option<Vec<TocEntry>> = None;
+    let mut in_toc = false;
+    for line_result in raw_lines(reader) {
+        let line = line_result.map_err(|e| format!("Error reading line: {}", e))?;
+        if line.starts_with(ENTRY_PREFIX) && !line.starts_with(PUBLIC_KEY_PREFIX) {
+            break;
+        }
+        parser.feed(&line)?;
+        match (in_toc, &mut entries) {
+            (false, None) if line == header => {
+                entries = Some(Vec::new());
+                in_toc = true;
+            }
+            (true, _) if line == CLOSING_MARKER => in_toc = false,
+            (false, _) | (true, None) => {}
+            (true, Some(entries)) => {
+                let mut fields = line.splitn(3, ' ');
+                let entry = match (fields.next(), fields.next(), fields.next()) {
+                    (Some(offset), Some(length), Some(path)) => offset
+                        .parse()
+                        .ok()
+                        .zip(length.parse().ok())
+                        .map(|(offset, length)| TocEntry {
+                            path: path.to_string(),
+                            offset,
+                            length,
+                        }),
+                    _ => None,
+                };
+                entries.push(
+                    entry.ok_or_else(|| format!("Invalid table of contents line: {}", line))?,
+                );
+            }
+        }
+    }
+    Ok(entries)
+}
+
+// The content of one entry, read straight from its offset when the archive is a plain file
+// with a table of contents, and by scanning it otherwise
+pub(crate) fn read_entry(
+    filename: &str,
+    entry_path: &str,
+    identity_files: &[String],
+) -> Result<String, String> {
+    let open_error = |e: io::Error| format!("Failed to open input file: {}: {}", filename, e);
+    let mut file = File::open(filename).map_err(open_error)?;
+    let mut head = vec![0u8; FORMAT_HEADER_PREFIX.len()];
+    let is_plain = file.read_exact(&mut head).is_ok() && head == FORMAT_HEADER_PREFIX.as_bytes();
+    if is_plain {
+        file.rewind().map_err(open_error)?;
+        let mut parser = ArchiveParser::new();
+        let toc = read_toc(BufReader::new(&file), &mut parser)?;
+        if let Some(found) = toc.and_then(|toc| toc.into_iter().find(|e| e.path == entry_path)) {
+            file.seek(SeekFrom::Start(found.offset))
+                .map_err(open_error)?;
+            let reader = BufReader::new(file.take(found.length));
+            return find_entry(reader, parser, entry_path)?.ok_or_else(|| {
+                format!(
+                    "The table of contents of {} does not match its entries",
+                    filename
+                )
+            });
+        }
+    }
+    let reader = open_archive(filename, identity_files).map_err(open_error)?;
+    find_entry(reader, ArchiveParser::new(), entry_path)?
+        .ok_or_else(|| format!("No entry {} in {}", entry_path, filename))
+}
+
+fn find_entry<R: BufRead>(
+    reader: R,
+    mut parser: ArchiveParser,
+    entry_path: &str,
+) -> Result<Option<String>, String> {
+    for line_result in raw_lines(reader) {
+        let line = line_result.map_err(|e| format!("Error reading line: {}", e))?;
+        match parser.feed(&line)? {
+            Some(Event::Entry(entry)) if entry.path == entry_path => return Ok(Some(entry.text())),
+            Some(Event::Omitted(omitted)) if omitted.path == entry_path => {
+                return Err(format!(
+                    "The content of {} was left out of the archive: {}",
+                    entry_path, omitted.marker
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(parser
+        .finish()
+        .filter(|entry| entry.path == entry_path)
+        .map(|entry| entry.text()))
+}
+
+// Entry point for `llm_globber cat FILE PATH`: prints the entry's content as unglob would
+// write it
+pub(crate) fn run_cat(
+    filename: &str,
+    entry_path: &str,
+    identity_files: &[String],
+) -> Result<(), String> {
+    let content = read_entry(filename, entry_path, identity_files)?;
+    io::stdout()
+        .write_all(content.as_bytes())
+        .map_err(|e| format!("Failed to write output: {}", e))
+}
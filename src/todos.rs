@@ -0,0 +1,22 @@
+use regex::Regex;
+
+// `TODO`, `FIXME` and `HACK` markers inside comments, as (line number, comment text) pairs.
+// A marker only counts when a comment opener (`//`, `#`, `/*`, `*`, `--`, `;`, `<!--`) comes
+// before it on the same line, so identifiers and strings such as "TODO list" are not reported.
+pub(crate) fn find_todos(source: &str) -> Vec<(usize, String)> {
+    let marker =
+        Regex::new(r"(?://|#|/\*|^\s*\*|--|;|<!--).*?\b((?:TODO|FIXME|HACK)\b.*)$").unwrap();
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let captures = marker.captures(line)?;
+            let text = captures[1]
+                .trim_end()
+                .trim_end_matches("*/")
+                .trim_end_matches("-->")
+                .trim_end();
+            Some((index + 1, text.to_string()))
+        })
+        .collect()
+}
@@ -0,0 +1,164 @@
+use std::path::{Component, Path, PathBuf};
+
+use llm_globber_core::RENDERED_MARKER_PREFIX;
+use log::debug;
+use regex::Regex;
+
+// A source file mentioned in an error log, with the line numbers it was referenced at
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TraceRef {
+    pub(crate) path: String,
+    pub(crate) lines: Vec<usize>,
+}
+
+// Extract `path:line` references from compiler errors, stack traces and test failures,
+// in order of first appearance. Recognised forms include `src/main.rs:12:5` (rustc, gcc, go,
+// node, pytest), `File "app.py", line 12` (Python) and `Foo.cs(12,5)` (MSVC, C#).
+pub(crate) fn parse_trace(text: &str) -> Vec<TraceRef> {
+    let python = Regex::new(r#"File "([^"]+)", line (\d+)"#).unwrap();
+    let colon =
+        Regex::new(r"((?:[A-Za-z]:)?[\w.~@+\-/\\]*[\w\-]\.[A-Za-z][A-Za-z0-9]*):(\d+)").unwrap();
+    let paren =
+        Regex::new(r"((?:[A-Za-z]:)?[\w.~@+\-/\\]*[\w\-]\.[A-Za-z][A-Za-z0-9]*)\((\d+)").unwrap();
+
+    let mut refs: Vec<TraceRef> = Vec::new();
+    for line in text.lines() {
+        let mut found: Vec<(usize, String, usize)> = Vec::new();
+        for regex in [&python, &colon, &paren] {
+            for captures in regex.captures_iter(line) {
+                let start = captures.get(0).map_or(0, |m| m.start());
+                if let Ok(number) = captures[2].parse::<usize>() {
+                    found.push((start, captures[1].to_string(), number));
+                }
+            }
+        }
+        found.sort_by_key(|(start, _, _)| *start);
+        for (_, path, number) in found {
+            let path = path.trim_start_matches("file://").to_string();
+            match refs.iter_mut().find(|existing| existing.path == path) {
+                Some(existing) => {
+                    if number > 0 && !existing.lines.contains(&number) {
+                        existing.lines.push(number);
+                    }
+                }
+                None => refs.push(TraceRef {
+                    path,
+                    lines: if number > 0 { vec![number] } else { Vec::new() },
+                }),
+            }
+        }
+    }
+    refs
+}
+
+// Map trace references to files on disk, merging references that land on the same file.
+// A path is tried as given, then relative to each root, and finally matched by its longest
+// trailing components against `candidates`, which resolves both bare file names from Java-style
+// traces and absolute paths from another machine such as a CI runner.
+pub(crate) fn resolve_trace(
+    refs: &[TraceRef],
+    roots: &[PathBuf],
+    candidates: &[PathBuf],
+) -> Vec<(PathBuf, Vec<usize>)> {
+    let mut resolved: Vec<(PathBuf, Vec<usize>)> = Vec::new();
+    for trace_ref in refs {
+        let paths = resolve_path(&trace_ref.path, roots, candidates);
+        if paths.is_empty() {
+            debug!("Trace reference {} matches no file", trace_ref.path);
+        }
+        for path in paths {
+            match resolved.iter_mut().find(|(existing, _)| *existing == path) {
+                Some((_, lines)) => lines.extend(&trace_ref.lines),
+                None => resolved.push((path, trace_ref.lines.clone())),
+            }
+        }
+    }
+    for (_, lines) in &mut resolved {
+        lines.sort_unstable();
+        lines.dedup();
+    }
+    resolved
+}
+
+fn resolve_path(reference: &str, roots: &[PathBuf], candidates: &[PathBuf]) -> Vec<PathBuf> {
+    let path = Path::new(reference);
+    let direct = if path.is_absolute() {
+        vec![path.to_path_buf()]
+    } else {
+        roots.iter().map(|root| root.join(path)).collect()
+    };
+    if let Some(found) = direct
+        .iter()
+        .find(|path| path.is_file())
+        .and_then(|path| path.canonicalize().ok())
+    {
+        return vec![found];
+    }
+
+    let components: Vec<Component> = path
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    for skip in 0..components.len() {
+        let suffix: PathBuf = components[skip..].iter().collect();
+        let matches: Vec<PathBuf> = candidates
+            .iter()
+            .filter(|candidate| candidate.ends_with(&suffix))
+            .cloned()
+            .collect();
+        if !matches.is_empty() {
+            return matches;
+        }
+    }
+    Vec::new()
+}
+
+// The referenced lines with `context` lines around each, numbered so they can be matched
+// against the trace. Referenced lines are marked with `>`. Returns None when no referenced
+// line falls inside the file, in which case it is included in full.
+pub(crate) fn excerpt(source: &str, lines: &[usize], context: usize) -> Option<String> {
+    let all: Vec<&str> = source.lines().collect();
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &line in lines.iter().filter(|&&line| line >= 1 && line <= all.len()) {
+        let start = line.saturating_sub(context).max(1);
+        let end = (line + context).min(all.len());
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end + 1 => *last_end = (*last_end).max(end),
+            _ => ranges.push((start, end)),
+        }
+    }
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let width = all.len().to_string().len();
+    let listed: Vec<String> = lines.iter().map(usize::to_string).collect();
+    let mut out = format!(
+        "{}trace excerpt, line(s) {} with {} line(s) of context]\n",
+        RENDERED_MARKER_PREFIX,
+        listed.join(", "),
+        context
+    );
+    let mut next = 1;
+    for (start, end) in ranges {
+        if start > next {
+            out.push_str(&format!("[... lines {}-{} omitted ...]\n", next, start - 1));
+        }
+        for (number, text) in all.iter().enumerate().take(end).skip(start - 1) {
+            let number = number + 1;
+            let mark = if lines.contains(&number) { '>' } else { ' ' };
+            out.push_str(&format!(
+                "{}{:>width$} | {}\n",
+                mark,
+                number,
+                text,
+                width = width
+            ));
+        }
+        next = end + 1;
+    }
+    if next <= all.len() {
+        out.push_str(&format!("[... lines {}-{} omitted ...]\n", next, all.len()));
+    }
+    Some(out)
+}
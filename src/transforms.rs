@@ -0,0 +1,93 @@
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use glob::Pattern;
+use log::debug;
+
+// Set for transform commands: the path of the file being transformed
+pub(crate) const FILE_ENV: &str = "LLM_GLOBBER_FILE";
+
+// A --transform rule: files matching the pattern are piped through the command, and what it
+// prints is included instead of the file's content
+#[derive(Debug, Clone)]
+pub(crate) struct Transform {
+    pattern: Pattern,
+    command: String,
+}
+
+impl Transform {
+    // `PATTERN=COMMAND`, split at the first `=` so commands may contain their own
+    pub(crate) fn parse(rule: &str) -> Result<Self, String> {
+        let (pattern, command) = rule
+            .split_once('=')
+            .filter(|(pattern, command)| !pattern.is_empty() && !command.trim().is_empty())
+            .ok_or_else(|| format!("Invalid transform '{}': expected PATTERN=COMMAND", rule))?;
+        let pattern = Pattern::new(pattern)
+            .map_err(|e| format!("Invalid transform pattern '{}': {}", pattern, e))?;
+        Ok(Transform {
+            pattern,
+            command: command.to_string(),
+        })
+    }
+
+    // Patterns match the file name or the whole path, like --skip-pattern
+    fn matches(&self, file_path: &Path) -> bool {
+        let name = file_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        self.pattern.matches(name) || self.pattern.matches_path(file_path)
+    }
+
+    // Run the command with `sh -c`, with the content on stdin. Its stderr is passed through.
+    fn run(&self, file_path: &Path, data: &[u8]) -> Result<Vec<u8>, String> {
+        let fail = |e: &dyn std::fmt::Display| {
+            format!(
+                "Transform `{}` failed on {}: {}",
+                self.command,
+                file_path.display(),
+                e
+            )
+        };
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .env(FILE_ENV, file_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| fail(&e))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        // Feed stdin from another thread so a command that writes before it has read
+        // everything cannot fill its stdout pipe and deadlock
+        let output = thread::scope(|scope| {
+            // Commands that ignore their input close stdin early, which is not an error
+            scope.spawn(move || stdin.write_all(data));
+            child.wait_with_output()
+        })
+        .map_err(|e| fail(&e))?;
+        if !output.status.success() {
+            return Err(fail(&output.status));
+        }
+        Ok(output.stdout)
+    }
+}
+
+// Pipe content through every rule that matches the file, in the order given. Returns None
+// when no rule matches.
+pub(crate) fn apply(
+    transforms: &[Transform],
+    file_path: &Path,
+    data: &[u8],
+) -> Result<Option<Vec<u8>>, String> {
+    let mut transformed: Option<Vec<u8>> = None;
+    for transform in transforms.iter().filter(|t| t.matches(file_path)) {
+        debug!(
+            "Transforming {} with `{}`",
+            file_path.display(),
+            transform.command
+        );
+        let content = transformed.as_deref().unwrap_or(data);
+        transformed = Some(transform.run(file_path, content)?);
+    }
+    Ok(transformed)
+}
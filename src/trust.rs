@@ -0,0 +1,171 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use base64::{engine::general_purpose, Engine};
+use ed25519_dalek::{Keypair, PublicKey};
+use log::{info, warn};
+use rand::rngs::OsRng;
+
+use crate::config;
+
+const SIGNING_KEY_FILE: &str = "signing_key";
+const TRUSTED_KEYS_FILE: &str = "trusted_keys";
+
+pub(crate) fn encode_key(key: &PublicKey) -> String {
+    general_purpose::STANDARD.encode(key.to_bytes())
+}
+
+// The key this user signs with, which `keygen` creates so that archives from one signer share
+// a key that others can trust. Until then each run signs with a one-off key, since a private
+// key is never written without being asked for.
+pub(crate) fn signing_key() -> Result<Keypair, String> {
+    let Some(path) = config::dir().map(|dir| dir.join(SIGNING_KEY_FILE)) else {
+        warn!(
+            "No configuration directory (set HOME or XDG_CONFIG_HOME); signing with a one-off key"
+        );
+        return Ok(Keypair::generate(&mut OsRng {}));
+    };
+    if path.exists() {
+        return read_signing_key(&path);
+    }
+    warn!(
+        "No signing key at {}; signing with a one-off key. Run `llm_globber keygen` to create one others can trust",
+        path.display()
+    );
+    Ok(Keypair::generate(&mut OsRng {}))
+}
+
+fn read_signing_key(path: &Path) -> Result<Keypair, String> {
+    let encoded = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read signing key {}: {}", path.display(), e))?;
+    let bytes = general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid signing key {}: {}", path.display(), e))?;
+    Keypair::from_bytes(&bytes)
+        .map_err(|e| format!("Invalid signing key {}: {}", path.display(), e))
+}
+
+// The signing key, created if there is none yet. Its owner trusts it from the start.
+pub(crate) fn create_signing_key() -> Result<Keypair, String> {
+    let dir = config::dir()
+        .ok_or("No configuration directory for the signing key (set HOME or XDG_CONFIG_HOME)")?;
+    let path = dir.join(SIGNING_KEY_FILE);
+    if path.exists() {
+        return read_signing_key(&path);
+    }
+
+    let keypair = Keypair::generate(&mut OsRng {});
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut file| {
+            writeln!(
+                file,
+                "{}",
+                general_purpose::STANDARD.encode(keypair.to_bytes())
+            )
+        })
+        .map_err(|e| format!("Failed to write signing key {}: {}", path.display(), e))?;
+    info!("Created signing key {}", path.display());
+    TrustStore::load()?.add(&keypair.public, "own signing key")?;
+    Ok(keypair)
+}
+
+// Public keys whose archives unglob accepts, one base64 key per line followed by an optional
+// comment. Blank lines and lines starting with `#` are ignored.
+#[derive(Debug)]
+pub(crate) struct TrustStore {
+    path: PathBuf,
+    keys: Vec<String>,
+}
+
+impl TrustStore {
+    pub(crate) fn load() -> Result<Self, String> {
+        let path = config::dir()
+            .ok_or("No configuration directory for the trust store (set HOME or XDG_CONFIG_HOME)")?
+            .join(TRUSTED_KEYS_FILE);
+        let keys = match fs::read_to_string(&path) {
+            Ok(text) => text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .filter_map(|line| line.split_whitespace().next())
+                .map(str::to_string)
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        Ok(TrustStore { path, keys })
+    }
+
+    pub(crate) fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub(crate) fn contains(&self, key: &PublicKey) -> bool {
+        self.keys.contains(&encode_key(key))
+    }
+
+    pub(crate) fn add(&mut self, key: &PublicKey, comment: &str) -> Result<(), String> {
+        if self.contains(key) {
+            return Ok(());
+        }
+        let fail = |e: std::io::Error| format!("Failed to update {}: {}", self.path.display(), e);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(fail)?;
+        }
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .map_err(fail)?;
+        fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600)).map_err(fail)?;
+        writeln!(file, "{} {}", encode_key(key), comment).map_err(fail)?;
+        self.keys.push(encode_key(key));
+        Ok(())
+    }
+}
+
+// Decide whether to accept an archive signed by `key`, returning whether the key is trusted.
+// Unknown keys are accepted with a warning but never recorded, unless `require_trusted` refuses
+// them; only `trust` and `keygen` add keys to the store.
+pub(crate) fn check_signer(
+    key: &PublicKey,
+    archive: &str,
+    require_trusted: bool,
+) -> Result<bool, String> {
+    let store = TrustStore::load()?;
+    let encoded = encode_key(key);
+    if store.contains(key) {
+        info!("Archive is signed by trusted key {}", encoded);
+        return Ok(true);
+    }
+    if require_trusted {
+        return Err(format!(
+            "Archive is signed by untrusted key {}; run `llm_globber trust {}` if you trust its signer",
+            encoded, encoded
+        ));
+    }
+    warn!(
+        "{} is signed by unknown key {}, so its signatures show it is intact but not who made it; run `llm_globber trust {}` if you trust its signer",
+        archive, encoded, encoded
+    );
+    Ok(false)
+}
+
+// Parse a key given to `trust`: the base64 form archives declare, or an OpenSSH key file
+pub(crate) fn parse_key(key: &str) -> Result<PublicKey, String> {
+    if Path::new(key).is_file() {
+        return crate::sshkey::load_public_key(key);
+    }
+    general_purpose::STANDARD
+        .decode(key)
+        .ok()
+        .and_then(|bytes| PublicKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| format!("Not a base64 ed25519 public key or key file: {}", key))
+}
@@ -0,0 +1,183 @@
+// Reading small files in batches with io_uring (the `uring` feature): the reads for a batch of
+// files are submitted together and completed by the kernel in any order, instead of one read
+// call after another, which keeps fast NVMe drives and high latency network filesystems busy.
+// Files that cannot be read this way are left for the normal reads, with their retries.
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::PathBuf;
+
+use io_uring::{opcode, types, IoUring};
+use log::{debug, warn};
+
+// Files read per batch, and the most bytes a batch holds in memory at once
+const BATCH_FILES: usize = 64;
+const BATCH_BYTES: u64 = 64 * 1024 * 1024;
+
+// What batches need from a ring, so that tests can stand in for the kernel
+pub(crate) trait Ring {
+    // Queue a read of `len` bytes into `buffer` from the start of `fd`, tagged with `user_data`.
+    // SAFETY: `buffer` must stay valid until the read's completion is reaped.
+    unsafe fn push_read(&mut self, fd: RawFd, buffer: *mut u8, len: u32, user_data: u64);
+    // Submit the queued reads and wait until `want` have completed
+    fn submit_and_wait(&mut self, want: usize) -> io::Result<usize>;
+    // The completions ready now, as (user data, bytes read or negative errno)
+    fn reap(&mut self) -> Vec<(u64, i32)>;
+}
+
+impl Ring for IoUring {
+    unsafe fn push_read(&mut self, fd: RawFd, buffer: *mut u8, len: u32, user_data: u64) {
+        let read = opcode::Read::new(types::Fd(fd), buffer, len)
+            .offset(0)
+            .build()
+            .user_data(user_data);
+        self.submission()
+            .push(&read)
+            .expect("The ring has room for a whole batch");
+    }
+
+    fn submit_and_wait(&mut self, want: usize) -> io::Result<usize> {
+        IoUring::submit_and_wait(self, want)
+    }
+
+    fn reap(&mut self) -> Vec<(u64, i32)> {
+        self.completion()
+            .map(|completion| (completion.user_data(), completion.result()))
+            .collect()
+    }
+}
+
+pub(crate) struct Reader<R: Ring = IoUring> {
+    ring: Option<R>,
+}
+
+impl Reader {
+    // A reader with its own ring, or one that reads nothing when the kernel has no io_uring or
+    // it is blocked, as in many containers
+    pub(crate) fn new() -> Self {
+        Reader::with_ring(match IoUring::new(BATCH_FILES as u32) {
+            Ok(ring) => Some(ring),
+            Err(e) => {
+                warn!("io_uring is unavailable, reading files one by one: {}", e);
+                None
+            }
+        })
+    }
+}
+
+impl<R: Ring> Reader<R> {
+    pub(crate) fn with_ring(ring: Option<R>) -> Self {
+        Reader { ring }
+    }
+
+    // Read the next batch of files from the front of `paths` whole, leaving out any of
+    // `max_size` bytes or more, which are memory-mapped instead, and any too big for a batch.
+    // Returns the contents read and how many
+    // paths the batch covered.
+    pub(crate) fn read_batch(
+        &mut self,
+        paths: &[PathBuf],
+        max_size: u64,
+    ) -> (HashMap<PathBuf, Vec<u8>>, usize) {
+        let mut contents = HashMap::new();
+        let Some(ring) = &mut self.ring else {
+            return (contents, paths.len());
+        };
+        let mut batch = Vec::new();
+        let mut batch_bytes = 0;
+        let mut covered = 0;
+        for path in paths.iter().take(BATCH_FILES) {
+            let size = fs::metadata(path).map_or(0, |metadata| metadata.len());
+            if batch_bytes + size > BATCH_BYTES && !batch.is_empty() {
+                break;
+            }
+            covered += 1;
+            if size == 0 || size >= max_size.min(BATCH_BYTES) {
+                continue;
+            }
+            let Ok(file) = File::open(path) else {
+                continue;
+            };
+            batch_bytes += size;
+            // One byte more than the file held, to tell when it grew since
+            batch.push((path, file, vec![0u8; size as usize + 1]));
+        }
+        match submit(ring, &mut batch) {
+            Ok(lengths) => {
+                for ((path, _, mut buffer), length) in batch.into_iter().zip(lengths) {
+                    // Short or long reads are left to the normal reads to sort out
+                    if length == Some(buffer.len() - 1) {
+                        buffer.truncate(buffer.len() - 1);
+                        contents.insert(path.clone(), buffer);
+                    }
+                }
+                debug!("Read {} of {} files with io_uring", contents.len(), covered);
+            }
+            Err(e) => {
+                // Reads from the failed batch may still be queued or in flight, so the ring is
+                // not used again
+                warn!("io_uring batch failed, reading files one by one: {}", e);
+                self.ring = None;
+            }
+        }
+        (contents, covered)
+    }
+}
+
+// Submit a read for each file and wait for all of them. Returns the bytes each read, or None
+// for reads that failed.
+fn submit<R: Ring>(
+    ring: &mut R,
+    batch: &mut [(&PathBuf, File, Vec<u8>)],
+) -> io::Result<Vec<Option<usize>>> {
+    for (index, (_, file, buffer)) in batch.iter_mut().enumerate() {
+        // SAFETY: the files and buffers outlive the reads, which are all waited for below
+        unsafe {
+            ring.push_read(
+                file.as_raw_fd(),
+                buffer.as_mut_ptr(),
+                buffer.len() as u32,
+                index as u64,
+            )
+        };
+    }
+    let mut lengths = vec![None; batch.len()];
+    let mut pending = batch.len();
+    while pending > 0 {
+        match ring.submit_and_wait(pending) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                // The buffers cannot be freed while the kernel may still write to them, so if
+                // the reads cannot be waited out they are leaked instead
+                if !drain(ring, pending) {
+                    for (_, _, buffer) in batch.iter_mut() {
+                        std::mem::forget(std::mem::take(buffer));
+                    }
+                }
+                return Err(e);
+            }
+        }
+        for (user_data, result) in ring.reap() {
+            pending -= 1;
+            if result >= 0 {
+                lengths[user_data as usize] = Some(result as usize);
+            }
+        }
+    }
+    Ok(lengths)
+}
+
+// Wait out reads still in flight, returning whether every one completed
+fn drain<R: Ring>(ring: &mut R, mut pending: usize) -> bool {
+    while pending > 0 {
+        match ring.submit_and_wait(1) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return false,
+        }
+        pending -= ring.reap().len();
+    }
+    true
+}
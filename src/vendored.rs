@@ -0,0 +1,114 @@
+// Code copied in from other projects, left out unless --include-vendored: files under the
+// directories package managers and build systems vendor into, and files whose copyright notice
+// names someone other than the project's own LICENSE does
+use std::fs;
+use std::path::Path;
+
+// Directories holding third-party code
+pub(crate) const VENDOR_DIRS: &[&str] = &[
+    "vendor",
+    "vendored",
+    "third_party",
+    "third-party",
+    "thirdparty",
+    "3rdparty",
+];
+
+// Where a project's own license and copyright notice are kept
+const LICENSE_FILES: &[&str] = &[
+    "LICENSE",
+    "LICENSE.md",
+    "LICENSE.txt",
+    "LICENCE",
+    "LICENCE.md",
+    "COPYING",
+];
+
+// How far into a file a copyright notice is looked for
+const NOTICE_LINES: usize = 40;
+// What the lines of a notice start with: comment openers, or the notice itself in a text file
+const NOTICE_STARTS: &[&str] = &[
+    "//",
+    "#",
+    "/*",
+    "*",
+    "--",
+    ";",
+    "%",
+    "<!--",
+    "\"\"\"",
+    "copyright",
+    "spdx",
+];
+
+// True if a path relative to its input goes through a vendor directory
+pub(crate) fn in_vendor_dir(relative: &Path) -> bool {
+    relative
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .any(|component| VENDOR_DIRS.contains(&&*component.as_os_str().to_string_lossy()))
+}
+
+// The copyright holders named in the license of the project `root` belongs to, found in `root`
+// or the nearest directory above it with a license file. Empty if there is none, in which case
+// no file can be told apart by its notice.
+pub(crate) fn project_holders(root: &Path) -> Vec<String> {
+    for dir in root.ancestors() {
+        let holders: Vec<String> = LICENSE_FILES
+            .iter()
+            .filter_map(|name| fs::read_to_string(dir.join(name)).ok())
+            .flat_map(|text| {
+                text.lines()
+                    .filter_map(copyright_holder)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        if !holders.is_empty() || dir.join(".git").exists() {
+            return holders;
+        }
+    }
+    Vec::new()
+}
+
+// The holder named by a copyright notice near the top of a file, if it is none of the
+// project's own `holders`
+pub(crate) fn foreign_holder(head: &str, holders: &[String]) -> Option<String> {
+    head.lines()
+        .take(NOTICE_LINES)
+        .filter(|line| {
+            let line = line.trim_start().to_lowercase();
+            NOTICE_STARTS.iter().any(|start| line.starts_with(start))
+        })
+        .filter_map(copyright_holder)
+        .find(|holder| {
+            !holders
+                .iter()
+                .any(|own| holder.contains(own.as_str()) || own.contains(holder.as_str()))
+        })
+}
+
+// `Copyright (c) 2019-2024 Acme Corp. All rights reserved.` names `acme corp`
+fn copyright_holder(line: &str) -> Option<String> {
+    let lower = line.to_lowercase();
+    let rest = &lower[lower.find("copyright")? + "copyright".len()..];
+    // SPDX-FileCopyrightText: 2024 Acme Corp
+    let rest = rest.strip_prefix("text:").unwrap_or(rest);
+    let rest = rest.split("all rights reserved").next().unwrap_or(rest);
+    let holder = rest
+        .replace("(c)", " ")
+        .replace('©', " ")
+        .split_whitespace()
+        .filter(|word| {
+            !word
+                .chars()
+                .all(|c| c.is_ascii_digit() || ",-.:".contains(c))
+        })
+        .filter(|word| *word != "by" && *word != "*/" && *word != "-->")
+        .collect::<Vec<_>>()
+        .join(" ");
+    let holder = holder.trim_end_matches(['.', ',']).to_string();
+    // `Copyright notice`, `copyright holders` and the like in license text name no one
+    let generic = ["notice", "holder", "holders", "owner", "owners", "and"];
+    (!holder.is_empty() && !generic.iter().any(|word| holder.starts_with(word))).then_some(holder)
+}
@@ -0,0 +1,81 @@
+// Whitespace normalization chosen with --trim-trailing-whitespace, --expand-tabs and
+// --collapse-blank-lines, applied to each text file's content before it is signed or written
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Normalization {
+    pub(crate) trim_trailing: bool,
+    pub(crate) tab_width: Option<usize>,
+    pub(crate) max_blank_lines: Option<usize>,
+}
+
+impl Normalization {
+    pub(crate) fn is_empty(&self) -> bool {
+        *self == Normalization::default()
+    }
+
+    // The steps as the archive's NORMALIZED section records them, in the order they are applied
+    pub(crate) fn steps(&self) -> Vec<String> {
+        let mut steps = Vec::new();
+        if let Some(width) = self.tab_width {
+            steps.push(format!("expand-tabs {}", width));
+        }
+        if self.trim_trailing {
+            steps.push("trim-trailing-whitespace".to_string());
+        }
+        if let Some(max) = self.max_blank_lines {
+            steps.push(format!("collapse-blank-lines {}", max));
+        }
+        steps
+    }
+
+    // The normalized content, or None when nothing changed. Line endings, including `\r\n`,
+    // and a missing final newline are kept.
+    pub(crate) fn apply(&self, text: &str) -> Option<String> {
+        let mut normalized = String::with_capacity(text.len());
+        let mut blank_run = 0;
+        for line in text.split_inclusive('\n') {
+            let (body, ending) = split_ending(line);
+            let mut body = match self.tab_width {
+                Some(width) => expand_tabs(body, width),
+                None => body.to_string(),
+            };
+            if self.trim_trailing {
+                body.truncate(body.trim_end_matches([' ', '\t']).len());
+            }
+            if body.trim().is_empty() {
+                blank_run += 1;
+                if self.max_blank_lines.is_some_and(|max| blank_run > max) {
+                    continue;
+                }
+            } else {
+                blank_run = 0;
+            }
+            normalized.push_str(&body);
+            normalized.push_str(ending);
+        }
+        (normalized != text).then_some(normalized)
+    }
+}
+
+fn split_ending(line: &str) -> (&str, &str) {
+    let body = line
+        .strip_suffix('\n')
+        .map_or(line, |body| body.strip_suffix('\r').unwrap_or(body));
+    (body, &line[body.len()..])
+}
+
+// Tabs replaced by spaces up to the next multiple of `width` columns
+fn expand_tabs(line: &str, width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = width - column % width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
@@ -139,7 +139,7 @@ echo "Checking output file for all test files..."
 all_files_found=true
 
 for file in $TEST_FILES; do
-    if ! grep -q "$file" "$ACTUAL_OUTPUT"; then
+    if ! grep -q "$(basename "$file")" "$ACTUAL_OUTPUT"; then
         echo "FAILED: File $file not found in output"
         all_files_found=false
     fi
@@ -147,8 +147,8 @@ done
 
 if [ "$all_files_found" = true ]; then
     # Check if test1.c appears before test1.h (basic order check)
-    C_LINE=$(grep -n "test_files/test1.c" "$ACTUAL_OUTPUT" | head -1 | cut -d':' -f1)
-    H_LINE=$(grep -n "test_files/test1.h" "$ACTUAL_OUTPUT" | head -1 | cut -d':' -f1)
+    C_LINE=$(grep -n "'''--- test1.c ---" "$ACTUAL_OUTPUT" | head -1 | cut -d':' -f1)
+    H_LINE=$(grep -n "'''--- test1.h ---" "$ACTUAL_OUTPUT" | head -1 | cut -d':' -f1)
     
     if [ -z "$C_LINE" ] || [ -z "$H_LINE" ]; then
         echo "FAILED: Couldn't find line numbers for test files"
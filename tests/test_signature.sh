@@ -44,12 +44,18 @@ fi
 
 echo "Created globbed file with signatures: $GLOBBED_FILE"
 
-# Check that the globbed file starts with a public key
+# Check that the globbed file starts with a public key right after the format header
 # Read the first line of the file
 read -r first_line < "$GLOBBED_FILE"
+key_line=$(sed -n 3p "$GLOBBED_FILE")
 
-# Check if the first line matches the exact header pattern
-if [[ ! "$first_line" =~ ^\'\'\'---\ PUBLIC_KEY\ ---\ \[KEY:[A-Za-z0-9+/]+={0,2}\]$ ]]; then
+if [[ ! "$first_line" =~ ^LLM_GLOBBER_FORMAT:\ [0-9]+$ ]]; then
+  echo "Error: File does not start with a format header." >&2
+  exit 1
+fi
+
+# Check if the key line matches the exact header pattern
+if [[ ! "$key_line" =~ ^\'\'\'---\ PUBLIC_KEY\ ---\ \[KEY:[A-Za-z0-9+/]+={0,2}\]$ ]]; then
   echo "Error: File header does not match expected public key header." >&2
   exit 1
 fi
@@ -74,8 +80,8 @@ fi
 
 # Step 4: Run unglob to extract files with signature verification
 echo "Running unglob to extract files with signature verification..."
-echo "Command: ../target/release/llm_globber -u $GLOBBED_FILE -o test_files -v --signature"
-$LLM_GLOBBER -u "$GLOBBED_FILE" -o test_files -v --signature
+echo "Command: ../target/release/llm_globber -u $GLOBBED_FILE -o test_files -v --force --signature"
+$LLM_GLOBBER -u "$GLOBBED_FILE" -o test_files -v --force --signature
 
 # Step 5: Verify extracted files
 echo "Verifying extracted files..."
@@ -99,6 +105,7 @@ echo "Testing tampering detection..."
 
 # Create a tampered version of the globbed file
 TAMPERED_FILE="${GLOBBED_FILE}.tampered"
+TAMPERED_WORD="TAMPERED "
 cp "$GLOBBED_FILE" "$TAMPERED_FILE"
 
 # Tamper with the content of one file in the globbed file
@@ -107,6 +114,9 @@ if grep -q "This is signature test file 2" "$TAMPERED_FILE"; then
     file_contents=$(<"$TAMPERED_FILE")
     # Replace all occurrences (use single slash for first occurrence only)
     file_contents="${file_contents//This is signature test file 2/This is TAMPERED signature test file 2}"
+    # Fix up the footer's byte count, so only the signature can give the change away
+    total_bytes=$(grep -m1 '^TOTAL_BYTES: ' <<< "$file_contents" | cut -d' ' -f2)
+    file_contents="${file_contents/TOTAL_BYTES: $total_bytes/TOTAL_BYTES: $((total_bytes + ${#TAMPERED_WORD}))}"
     # Write the modified contents back to the file
     printf "%s" "$file_contents" > "$TAMPERED_FILE"
 else
@@ -118,12 +128,16 @@ fi
 rm $FILES_TO_GLOB
 
 # Try to unglob the tampered file
-echo "Command: $LLM_GLOBBER -u $TAMPERED_FILE -o test_files -v --signature"
-if $LLM_GLOBBER -u "$TAMPERED_FILE" -o test_files -v --signature; then
+echo "Command: $LLM_GLOBBER -u $TAMPERED_FILE -o test_files -v --force --signature"
+if TAMPER_OUTPUT=$($LLM_GLOBBER -u "$TAMPERED_FILE" -o test_files -v --force --signature 2>&1); then
     echo "FAILURE: Tampering was not detected!"
     exit 1
-else
+elif grep -q "Signature verification failed for sig_test2.txt" <<< "$TAMPER_OUTPUT"; then
     echo "SUCCESS: Tampering was correctly detected"
+else
+    echo "FAILURE: The tampered file was refused, but not for its signature:"
+    echo "$TAMPER_OUTPUT"
+    exit 1
 fi
 
 echo "All signature tests passed successfully!"
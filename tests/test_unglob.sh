@@ -64,8 +64,8 @@ fi
 
 # Step 4: Run unglob to extract files
 echo "Running unglob to extract files..."
-echo "Command: $LLM_GLOBBER -u $GLOBBED_FILE -o test_files -v"
-$LLM_GLOBBER -u "$GLOBBED_FILE" -o test_files -v
+echo "Command: $LLM_GLOBBER -u $GLOBBED_FILE -o test_files -v --force"
+$LLM_GLOBBER -u "$GLOBBED_FILE" -o test_files -v --force
 
 # Debug: Show the content of the globbed file
 echo "Debug: Content of globbed file:"